@@ -136,6 +136,14 @@ pub fn get_pkg_root(k_file_path: &str) -> Option<String> {
     None
 }
 
+/// Loads the kcl.mod file in the root like `load_mod_file`, but returns the parse error
+/// of the malformed kcl.mod file instead of panicking.
+pub fn try_load_mod_file(root: &str) -> Result<KCLModFile, toml::de::Error> {
+    let k_mod_file_path = std::path::Path::new(root).join(KCL_MOD_FILE);
+    let buffer = fs::read(k_mod_file_path).unwrap_or_default();
+    toml::from_slice(buffer.as_slice())
+}
+
 pub fn load_mod_file(root: &str) -> KCLModFile {
     let k_mod_file_path = std::path::Path::new(root).join(KCL_MOD_FILE);
     if !k_mod_file_path.exists() {
@@ -96,6 +96,7 @@ pub enum ErrorKind {
     CompileError,
     EvaluationError,
     UniqueKeyError,
+    CircularImport,
 }
 
 impl std::fmt::Display for ErrorKind {
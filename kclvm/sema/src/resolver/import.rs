@@ -178,14 +178,14 @@ impl<'ctx> Resolver<'ctx> {
                             self.ctx
                                 .ty_ctx
                                 .add_dependencies(&self.ctx.pkgpath, &import_stmt.path);
-                            if self.ctx.ty_ctx.is_cyclic() {
-                                self.handler.add_compile_error(
-                                    &format!(
-                                        "There is a circular import reference between module {} and {}",
-                                        self.ctx.pkgpath, import_stmt.path,
-                                    ),
-                                    stmt.get_pos(),
-                                );
+                            if let Some(path) = self
+                                .ctx
+                                .ty_ctx
+                                .dependency_path(&import_stmt.path, &self.ctx.pkgpath)
+                            {
+                                let mut cycle = vec![self.ctx.pkgpath.clone()];
+                                cycle.extend(path);
+                                self.add_circular_import_error(&cycle);
                             }
                             // Switch pkgpath context
                             if !self.scope_map.contains_key(&import_stmt.path) {
@@ -201,6 +201,41 @@ impl<'ctx> Resolver<'ctx> {
         }
     }
 
+    /// Add the circular import error on every import statement of the cycle, e.g.,
+    /// `["a", "b", "a"]` for the package `a` importing `b` and `b` importing `a`. The
+    /// message of the import statement closing the cycle comes first.
+    fn add_circular_import_error(&mut self, cycle: &[String]) {
+        let cycle_str = cycle.join(" -> ");
+        let mut msgs = vec![];
+        for edge in cycle.windows(2) {
+            let (from, to) = (&edge[0], &edge[1]);
+            let modules = match self.program.pkgs.get(from) {
+                Some(modules) => modules,
+                None => continue,
+            };
+            for module in modules {
+                for stmt in &module.body {
+                    if let ast::Stmt::Import(import_stmt) = &stmt.node {
+                        if &import_stmt.path == to {
+                            msgs.push(Message {
+                                pos: stmt.get_pos(),
+                                style: Style::LineAndColumn,
+                                message: format!(
+                                    "There is a circular import reference between module {} and {}: {}",
+                                    from, to, cycle_str,
+                                ),
+                                note: None,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        if !msgs.is_empty() {
+            self.handler.add_error(ErrorKind::CircularImport, &msgs);
+        }
+    }
+
     pub(crate) fn change_package_context(&mut self, pkgpath: &str, filename: &str) {
         if pkgpath.is_empty() {
             return;
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
 
 use super::{sup, Type, TypeFlags, TypeKind};
@@ -55,6 +55,34 @@ impl TypeContext {
         is_cyclic_directed(&self.dep_graph)
     }
 
+    /// Return the path of the dependencies from "from" to "to" including both ends,
+    /// or None if "to" is not reachable from "from".
+    pub fn dependency_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let from_idx = *self.node_index_map.get(from)?;
+        let to_idx = *self.node_index_map.get(to)?;
+        let mut prev: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut queue = VecDeque::from([from_idx]);
+        while let Some(idx) = queue.pop_front() {
+            if idx == to_idx {
+                let mut path = vec![self.dep_graph[idx].clone()];
+                let mut cur = idx;
+                while let Some(p) = prev.get(&cur) {
+                    path.push(self.dep_graph[*p].clone());
+                    cur = *p;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.dep_graph.neighbors(idx) {
+                if next != from_idx && !prev.contains_key(&next) {
+                    prev.insert(next, idx);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
     /// Add dependencies between "from" and "to".
     pub fn add_dependencies(&mut self, from: &str, to: &str) {
         let from_idx = self.get_or_insert_node_index(from);
@@ -1,6 +1,42 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
 use crate::db::AnalysisDatabase;
 
+/// The analysis of the files, which maps the path of a compiled file to its db.
 #[derive(Default)]
 pub struct Analysis {
-    _db: AnalysisDatabase,
+    pub(crate) db: Arc<RwLock<HashMap<String, AnalysisDatabase>>>,
+}
+
+impl Analysis {
+    /// Sets the db of the file. The caches of the replaced db, e.g., the goto definition
+    /// results, are dropped with it.
+    pub(crate) fn set_db(&self, file: String, db: AnalysisDatabase) {
+        self.db.write().insert(file, db);
+    }
+
+    /// Drops the cached resolutions of the dbs, which are resolved with the old config.
+    pub(crate) fn clear_resolutions(&self) {
+        for db in self.db.read().values() {
+            db.clear_resolutions();
+        }
+    }
+
+    /// Drops the dbs which depend on the changed files, and returns the files of the
+    /// dropped dbs. The dbs of the files whose dependencies are unchanged are kept.
+    pub(crate) fn invalidate(&self, changed_files: &HashSet<String>) -> Vec<String> {
+        let mut db = self.db.write();
+        let invalidated: Vec<String> = db
+            .iter()
+            .filter(|(_, db)| !db.is_valid(changed_files))
+            .map(|(file, _)| file.clone())
+            .collect();
+        for file in &invalidated {
+            db.remove(file);
+        }
+        invalidated
+    }
 }
@@ -0,0 +1,81 @@
+//! Synthetic documents of the builtin functions, which have no source files. The goto
+//! definition of a builtin function navigates to the document under the `kcl-builtin`
+//! uri scheme, whose content is provided by the `kcl.builtinDocument` command, e.g.,
+//!
+//! ```kcl
+//! len(inval: [any] | {any:any} | str) -> int
+//!
+//! Return the length of a value.
+//! ```
+
+use kclvm_sema::builtin::BUILTIN_FUNCTIONS;
+use kclvm_sema::ty::TypeKind;
+use lsp_types::{Location, Position, Range, Url};
+
+/// The uri scheme of the synthetic documents of the builtin functions.
+pub(crate) const BUILTIN_DOC_SCHEME: &str = "kcl-builtin";
+
+/// Returns the uri of the synthetic document of the builtin function.
+pub(crate) fn builtin_doc_uri(name: &str) -> Option<Url> {
+    Url::parse(&format!("{}:///{}.k", BUILTIN_DOC_SCHEME, name)).ok()
+}
+
+/// Returns the location of the signature in the synthetic document of the builtin
+/// function, or None if the name is not a builtin function.
+pub(crate) fn builtin_doc_location(name: &str) -> Option<Location> {
+    let signature = builtin_signature(name)?;
+    Some(Location {
+        uri: builtin_doc_uri(name)?,
+        range: Range::new(
+            Position::new(0, 0),
+            Position::new(0, signature.chars().count() as u32),
+        ),
+    })
+}
+
+/// Returns the content of the synthetic document of the uri, which contains the signature
+/// and the doc of the builtin function.
+pub(crate) fn builtin_doc(uri: &Url) -> Option<String> {
+    if uri.scheme() != BUILTIN_DOC_SCHEME {
+        return None;
+    }
+    let name = uri.path().trim_start_matches('/').trim_end_matches(".k");
+    let signature = builtin_signature(name)?;
+    let functions = BUILTIN_FUNCTIONS;
+    let doc = match &functions.get(name)?.kind {
+        TypeKind::Function(func) => func.doc.clone(),
+        _ => String::new(),
+    };
+    Some(if doc.is_empty() {
+        format!("{}\n", signature)
+    } else {
+        format!("{}\n\n{}\n", signature, doc)
+    })
+}
+
+/// Returns the signature of the builtin function, e.g., `len(inval: str) -> int`. The
+/// parameters with a default value are rendered as `name: type = ...`.
+fn builtin_signature(name: &str) -> Option<String> {
+    let functions = BUILTIN_FUNCTIONS;
+    match &functions.get(name)?.kind {
+        TypeKind::Function(func) => {
+            let params = func
+                .params
+                .iter()
+                .map(|param| {
+                    let default = if param.has_default { " = ..." } else { "" };
+                    format!("{}: {}{}", param.name, param.ty.ty_str(), default)
+                })
+                .chain(func.is_variadic.then(|| "...".to_string()))
+                .collect::<Vec<String>>()
+                .join(", ");
+            Some(format!(
+                "{}({}) -> {}",
+                name,
+                params,
+                func.return_ty.ty_str()
+            ))
+        }
+        _ => None,
+    }
+}
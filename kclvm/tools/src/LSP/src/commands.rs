@@ -0,0 +1,267 @@
+//! Commands of the KCL language server, which are executed by the
+//! `workspace/executeCommand` request.
+//! + kcl.validate: validate a data file against a schema
+//! + kcl.builtinDocument: get the content of the synthetic document of a builtin function
+//! + kcl.explain: explain a diagnostic code with an example
+//! + kcl.dumpAst: dump the AST of a file for debugging the parser
+//! + kcl.generateExample: generate an example instance of a schema
+//! + kcl.memoryUsage: report the approximate resource usage of the server
+//! + kcl.fixAll: remove the unused imports, sort the imports and format a file in one pass
+//! + kcl.workspaceDiagnostics: list the diagnostics of all the files in the workspace
+//! + kcl.exportWordIndex: export the word index of a workspace folder for external analysis
+//! + kcl.importGraph: compute the import graph of the workspace and the circular imports
+//! + kcl.effectiveConfig: evaluate an entry and return the merged value of a top level variable
+
+use crossbeam_channel::Sender;
+use lsp_types::{
+    ApplyWorkspaceEditParams, ProgressToken, Url, WorkDoneProgress, WorkDoneProgressBegin,
+    WorkDoneProgressEnd, WorkDoneProgressReport,
+};
+
+use crate::builtin_doc::builtin_doc;
+use crate::dump_ast::{dump_ast, DumpAstArgs};
+use crate::effective_config::{effective_config, EffectiveConfigArgs};
+use crate::explain::explain;
+use crate::export_word_index::{export_word_index, ExportWordIndexArgs};
+use crate::fix_all::{fix_all, FixAllArgs};
+use crate::generate_example::{generate_example, GenerateExampleArgs};
+use crate::import_graph::{import_graph, ImportGraphArgs};
+use crate::memory_usage::memory_usage;
+use crate::state::{send_work_done_progress, LanguageServerSnapshot, Task};
+use crate::util::{
+    compile_db, load_file_code_from_vfs, parse_param, parse_param_and_compile, to_json, Param,
+};
+use crate::validate::{validate, ValidateArgs};
+use crate::workspace_diagnostics::{workspace_diagnostics, WorkspaceDiagnosticsArgs};
+
+/// Validates a data file against a schema and returns the violations as diagnostics.
+pub(crate) const KCL_VALIDATE_COMMAND: &str = "kcl.validate";
+
+/// Returns the content of the synthetic document of a builtin function by its uri, which
+/// is the target of the goto definition on the builtin function.
+pub(crate) const KCL_BUILTIN_DOCUMENT_COMMAND: &str = "kcl.builtinDocument";
+
+/// Returns the Markdown explanation of a diagnostic code, e.g., `ImmutableError`.
+pub(crate) const KCL_EXPLAIN_COMMAND: &str = "kcl.explain";
+
+/// Returns the pretty-printed JSON of the AST of a file, which reuses the program in the
+/// analysis db of the file if it has been compiled.
+pub(crate) const KCL_DUMP_AST_COMMAND: &str = "kcl.dumpAst";
+
+/// Returns a config snippet of the schema referenced in a file with the required attrs
+/// filled with the placeholder values, as a starting point of the schema instance.
+pub(crate) const KCL_GENERATE_EXAMPLE_COMMAND: &str = "kcl.generateExample";
+
+/// Returns the counts of the analysis dbs, the word index entries and the opened files,
+/// which approximate the memory usage of the language server.
+pub(crate) const KCL_MEMORY_USAGE_COMMAND: &str = "kcl.memoryUsage";
+
+/// Returns the workspace edit which removes the unused imports, sorts the imports and
+/// formats a file, or null if there is nothing to fix. The edit is applied by the server
+/// with the `workspace/applyEdit` request instead if the `apply` argument is set.
+pub(crate) const KCL_FIX_ALL_COMMAND: &str = "kcl.fixAll";
+
+/// Compiles all the KCL files in the workspace folders and returns the map of the file uri
+/// to the diagnostics of the file, which omits the files without any diagnostics.
+pub(crate) const KCL_WORKSPACE_DIAGNOSTICS_COMMAND: &str = "kcl.workspaceDiagnostics";
+
+/// Returns a page of the cached word index of a workspace folder, i.e., the map of each
+/// word to all of its locations, and the offset of the next page if any.
+pub(crate) const KCL_EXPORT_WORD_INDEX_COMMAND: &str = "kcl.exportWordIndex";
+
+/// Returns the edges from the package of each KCL file in the workspace folders to the
+/// packages imported by the file, and the circular imports between the packages.
+pub(crate) const KCL_IMPORT_GRAPH_COMMAND: &str = "kcl.importGraph";
+
+/// Evaluates the compile unit of a file and returns the value of a top level variable in
+/// the output, i.e., the effective config merged from all the layers of the entry.
+pub(crate) const KCL_EFFECTIVE_CONFIG_COMMAND: &str = "kcl.effectiveConfig";
+
+/// Returns the names of all commands supported by the language server.
+pub(crate) fn commands() -> Vec<String> {
+    vec![
+        KCL_VALIDATE_COMMAND.to_string(),
+        KCL_BUILTIN_DOCUMENT_COMMAND.to_string(),
+        KCL_EXPLAIN_COMMAND.to_string(),
+        KCL_DUMP_AST_COMMAND.to_string(),
+        KCL_GENERATE_EXAMPLE_COMMAND.to_string(),
+        KCL_MEMORY_USAGE_COMMAND.to_string(),
+        KCL_FIX_ALL_COMMAND.to_string(),
+        KCL_WORKSPACE_DIAGNOSTICS_COMMAND.to_string(),
+        KCL_EXPORT_WORD_INDEX_COMMAND.to_string(),
+        KCL_IMPORT_GRAPH_COMMAND.to_string(),
+        KCL_EFFECTIVE_CONFIG_COMMAND.to_string(),
+    ]
+}
+
+/// Executes the command with the arguments and returns the result of the command. The
+/// progress of the long running commands is reported with the work done token if any.
+pub(crate) fn execute_command(
+    snapshot: LanguageServerSnapshot,
+    command: &str,
+    arguments: Vec<serde_json::Value>,
+    work_done_token: Option<ProgressToken>,
+    sender: &Sender<Task>,
+) -> anyhow::Result<Option<serde_json::Value>> {
+    match command {
+        KCL_VALIDATE_COMMAND => {
+            let args: ValidateArgs = parse_first_argument(command, arguments)?;
+            Ok(Some(to_json(validate(args)?)?))
+        }
+        KCL_BUILTIN_DOCUMENT_COMMAND => {
+            let uri: Url = parse_first_argument(command, arguments)?;
+            let doc = builtin_doc(&uri)
+                .ok_or_else(|| anyhow::anyhow!("Unknown builtin document: {}", uri))?;
+            Ok(Some(to_json(doc)?))
+        }
+        KCL_EXPLAIN_COMMAND => {
+            let code: String = parse_first_argument(command, arguments)?;
+            let explanation = explain(&code)
+                .ok_or_else(|| anyhow::anyhow!("No explanation for the code {}", code))?;
+            Ok(Some(to_json(explanation)?))
+        }
+        KCL_DUMP_AST_COMMAND => {
+            let args: DumpAstArgs = parse_first_argument(command, arguments)?;
+            let db = snapshot.db.read().get(&args.file).cloned();
+            let program = match db {
+                Some(db) => db.prog,
+                None => {
+                    parse_param(
+                        Param {
+                            file: args.file.clone(),
+                        },
+                        Some(snapshot.vfs),
+                    )?
+                    .prog
+                }
+            };
+            Ok(Some(to_json(dump_ast(&program, &args)?)?))
+        }
+        KCL_GENERATE_EXAMPLE_COMMAND => {
+            let args: GenerateExampleArgs = parse_first_argument(command, arguments)?;
+            let db = snapshot.db.read().get(&args.file).cloned();
+            let (_, prog_scope, _) = match db {
+                Some(db) => compile_db(&db),
+                None => parse_param_and_compile(
+                    Param {
+                        file: args.file.clone(),
+                    },
+                    Some(snapshot.vfs),
+                )?,
+            };
+            Ok(Some(to_json(generate_example(&prog_scope, &args)?)?))
+        }
+        KCL_MEMORY_USAGE_COMMAND => Ok(Some(to_json(memory_usage(&snapshot))?)),
+        KCL_FIX_ALL_COMMAND => {
+            let args: FixAllArgs = parse_first_argument(command, arguments)?;
+            let uri = Url::from_file_path(&args.file)
+                .map_err(|_| anyhow::anyhow!("Invalid file path: {}", args.file))?;
+            let src = load_file_code_from_vfs(&args.file, snapshot.vfs.clone())?;
+            let db = snapshot.db.read().get(&args.file).cloned();
+            let (program, _, diags) = match db {
+                Some(db) => compile_db(&db),
+                None => parse_param_and_compile(
+                    Param {
+                        file: args.file.clone(),
+                    },
+                    Some(snapshot.vfs),
+                )?,
+            };
+            let edit = fix_all(&program, &diags, &args.file, &uri, &src)?;
+            if !args.apply {
+                return Ok(Some(to_json(edit)?));
+            }
+            if let Some(edit) = edit {
+                sender.send(Task::ApplyEdit(ApplyWorkspaceEditParams {
+                    label: Some("Fix all".to_string()),
+                    edit,
+                }))?;
+            }
+            Ok(None)
+        }
+        KCL_WORKSPACE_DIAGNOSTICS_COMMAND => {
+            // The arguments are optional, and the workspace folders are checked by default.
+            let args: WorkspaceDiagnosticsArgs = if arguments.is_empty() {
+                WorkspaceDiagnosticsArgs::default()
+            } else {
+                parse_first_argument(command, arguments)?
+            };
+            let folders = args
+                .folders
+                .unwrap_or_else(|| snapshot.word_index_map.read().keys().cloned().collect());
+            if let Some(token) = &work_done_token {
+                send_work_done_progress(
+                    token,
+                    WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                        title: "Checking the workspace".to_string(),
+                        percentage: Some(0),
+                        ..Default::default()
+                    }),
+                    sender,
+                )?;
+            }
+            let mut reported_percentage = 0;
+            let res = workspace_diagnostics(&snapshot, &folders, &mut |done, total| {
+                let token = match &work_done_token {
+                    Some(token) => token,
+                    None => return,
+                };
+                let percentage = (done * 100 / total) as u32;
+                if percentage > reported_percentage {
+                    reported_percentage = percentage;
+                    let _ = send_work_done_progress(
+                        token,
+                        WorkDoneProgress::Report(WorkDoneProgressReport {
+                            message: Some(format!("{}/{}", done, total)),
+                            percentage: Some(percentage),
+                            ..Default::default()
+                        }),
+                        sender,
+                    );
+                }
+            });
+            if let Some(token) = &work_done_token {
+                send_work_done_progress(
+                    token,
+                    WorkDoneProgress::End(WorkDoneProgressEnd::default()),
+                    sender,
+                )?;
+            }
+            Ok(Some(to_json(res?)?))
+        }
+        KCL_EXPORT_WORD_INDEX_COMMAND => {
+            let args: ExportWordIndexArgs = parse_first_argument(command, arguments)?;
+            Ok(Some(to_json(export_word_index(&snapshot, &args)?)?))
+        }
+        KCL_IMPORT_GRAPH_COMMAND => {
+            // The arguments are optional, and the workspace folders are analyzed by default.
+            let args: ImportGraphArgs = if arguments.is_empty() {
+                ImportGraphArgs::default()
+            } else {
+                parse_first_argument(command, arguments)?
+            };
+            let folders = args
+                .folders
+                .unwrap_or_else(|| snapshot.word_index_map.read().keys().cloned().collect());
+            Ok(Some(to_json(import_graph(&snapshot, &folders)?)?))
+        }
+        KCL_EFFECTIVE_CONFIG_COMMAND => {
+            let args: EffectiveConfigArgs = parse_first_argument(command, arguments)?;
+            Ok(Some(effective_config(&args, snapshot.vfs)?))
+        }
+        _ => Err(anyhow::anyhow!("Unknown command: {}", command)),
+    }
+}
+
+/// Parses the first argument of the command.
+fn parse_first_argument<T: serde::de::DeserializeOwned>(
+    command: &str,
+    arguments: Vec<serde_json::Value>,
+) -> anyhow::Result<T> {
+    let argument = arguments
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Missing the argument of the command {}", command))?;
+    serde_json::from_value(argument)
+        .map_err(|err| anyhow::anyhow!("Invalid argument of the command {}: {}", command, err))
+}
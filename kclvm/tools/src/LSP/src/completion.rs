@@ -1,18 +1,37 @@
 //! Complete for KCL
 //! Github Issue: https://github.com/kcl-lang/kcl/issues/476
-//! Now supports code completion in treigger mode (triggered when user enters `.`),
+//! Now supports code completion in treigger mode (triggered when user enters `.` or `(`),
 //! and the content of the completion includes:
 //!  + import path
 //!  + schema attr
 //!  + builtin function(str function)
 //!  + defitions in pkg
 //!  + system module functions
+//!  + keyword arguments of the call
+//! and code completion without trigger character, including:
+//!  + schema attr in the schema config expr
+//!  + in-scope variables compatible with the schema attr
+//!  + schema attrs in the schema body
+//!  + validation builtins in the check block
+//!  + keys of the plain dicts in the same file
+//!  + type names at the type annotation
+//!  + schema config snippets at the empty assignment
+//!  + file paths in the arguments of the file builtins
 
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::io;
+use std::time::{Duration, Instant};
 use std::{fs, path::Path};
 
 use indexmap::IndexSet;
-use kclvm_ast::ast::{Expr, ImportStmt, Program, Stmt};
+use kclvm_ast::ast::{
+    CallExpr, ConfigEntryOperation, ConfigExpr, Expr, ImportStmt, Node, Program, SchemaExpr, Stmt,
+    StringLit,
+};
+use kclvm_ast::pos::{ContainsPos, GetPos};
+use kclvm_ast::walker::MutSelfWalker;
+use kclvm_ast::MAIN_PKG;
 use kclvm_compiler::pkgpath_without_prefix;
 use kclvm_config::modfile::KCL_FILE_EXTENSION;
 
@@ -20,32 +39,1332 @@ use kclvm_error::Position as KCLPos;
 use kclvm_sema::builtin::{
     get_system_module_members, STANDARD_SYSTEM_MODULES, STRING_MEMBER_FUNCTIONS,
 };
-use kclvm_sema::resolver::scope::ProgramScope;
-use lsp_types::CompletionItem;
+use kclvm_sema::resolver::scope::{ProgramScope, ScopeKind, ScopeObjectKind};
+use kclvm_sema::ty::{assignable_to, SchemaType, TypeKind};
+use lsp_types::{
+    ClientCapabilities, Command, CompletionItem, CompletionItemKind, CompletionList,
+    CompletionResponse, Documentation, InsertTextFormat, MarkupContent, MarkupKind, TextEdit,
+};
+use serde::{Deserialize, Serialize};
 
-use crate::goto_def::{get_identifier_last_name, resolve_var};
+use crate::config::SchemaCompletionStyle;
+use crate::generate_example::{collect_required_attrs, placeholder, INDENT};
+use crate::goto_def::{find_def, get_identifier_last_name, resolve_var, Definition};
+use crate::hover::{describe_obj, info_to_markdown, schema_attrs, ty_str_with_budget, HoverInfo};
 use crate::util::inner_most_expr_in_stmt;
 use crate::util::{fix_missing_identifier, get_pkg_scope};
 
-/// Computes completions at the given position.
+/// The command which triggers the signature help after accepting a call-like completion.
+const TRIGGER_PARAMETER_HINTS_COMMAND: &str = "editor.action.triggerParameterHints";
+
+/// The builtin types and their snippets offered at the type annotations.
+const BUILTIN_TYPES: &[(&str, &str)] = &[
+    ("str", "str"),
+    ("int", "int"),
+    ("float", "float"),
+    ("bool", "bool"),
+    ("any", "any"),
+    ("[T]", "[${1:T}]"),
+    ("{str:T}", "{${1:str}:${2:T}}"),
+];
+
+/// The quantifier expressions and their snippets offered in the check blocks.
+const VALIDATION_QUANTIFIERS: &[(&str, &str)] = &[
+    ("all", "all ${1:item} in ${2:items} {\n\t$0\n}"),
+    ("any", "any ${1:item} in ${2:items} {\n\t$0\n}"),
+];
+
+/// The builtin functions whose string arguments are file paths, with the module name, the
+/// function name and the index of the path argument.
+const FILE_PATH_ARGUMENTS: &[(&str, &str, usize)] = &[
+    ("file", "read", 0),
+    ("yaml", "dump_to_file", 1),
+    ("json", "dump_to_file", 1),
+    ("testing", "setting_file", 0),
+];
+
+/// The builtin functions for validation offered in the check blocks.
+const VALIDATION_FUNCTIONS: &[&str] = &[
+    "all_true",
+    "any_true",
+    "isunique",
+    "len",
+    "multiplyof",
+    "typeof",
+];
+
+/// The completion item computed by the language server, which is converted into the
+/// lsp completion item.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct KCLCompletionItem {
+    pub label: String,
+    pub kind: Option<KCLCompletionItemKind>,
+}
+
+/// The data of the completion item whose documentation is attached lazily by the
+/// `completionItem/resolve` request, which references a definition of the package or an
+/// attr of the schema, e.g., the schema attr completed in the schema config expr.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CompletionItemData {
+    /// The file where the completion is requested, whose program resolves the item.
+    pub file: String,
+    /// The package of the definition.
+    pub pkgpath: String,
+    /// The name of the definition, e.g., the schema.
+    pub name: String,
+    /// The attr of the schema definition, if the item is a schema attr.
+    #[serde(default)]
+    pub attr: Option<String>,
+}
+
+impl CompletionItemData {
+    fn to_value(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self).ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum KCLCompletionItemKind {
+    /// A call-like schema item, e.g., `Server`, which inserts `Server()` with the cursor
+    /// inside the parentheses and triggers the signature help.
+    Schema,
+}
+
+impl From<String> for KCLCompletionItem {
+    fn from(label: String) -> Self {
+        Self { label, kind: None }
+    }
+}
+
+impl KCLCompletionItem {
+    /// Converts the item into the lsp completion item, where the schema is inserted as
+    /// the call or the config by the insertion.
+    fn into_completion_item(self, insertion: &SchemaInsertion) -> CompletionItem {
+        match self.kind {
+            Some(KCLCompletionItemKind::Schema) => insertion.schema_item(self.label),
+            None => CompletionItem {
+                label: self.label,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// How the schemas completed as the calls are inserted, e.g., `Server` after `pkg.`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SchemaInsertion {
+    /// Whether the client supports the snippets, without which only the name is inserted.
+    pub snippet: bool,
+    /// Whether the schema is inserted as the call or the config.
+    pub style: SchemaCompletionStyle,
+    /// The first character following the cursor in the line except the whitespaces, e.g.,
+    /// `(` of `pkg.Server<cursor>()`, where only the name is inserted if it opens the
+    /// delimiters of the style.
+    pub next_char: Option<char>,
+}
+
+impl Default for SchemaInsertion {
+    fn default() -> Self {
+        Self {
+            snippet: true,
+            style: SchemaCompletionStyle::Call,
+            next_char: None,
+        }
+    }
+}
+
+impl SchemaInsertion {
+    /// Returns the insertion of the schemas at the position of the text.
+    pub(crate) fn new(
+        style: SchemaCompletionStyle,
+        snippet: bool,
+        text: &str,
+        pos: &KCLPos,
+    ) -> Self {
+        let next_char = pos.line.checked_sub(1).and_then(|line| {
+            text.lines()
+                .nth(line as usize)?
+                .chars()
+                .skip(pos.column.unwrap_or_default() as usize)
+                .find(|c| !c.is_whitespace())
+        });
+        Self {
+            snippet,
+            style,
+            next_char,
+        }
+    }
+
+    /// Returns the completion item of the schema, which inserts the matched delimiters with
+    /// the cursor inside them, e.g., `Server($0)` or `Server {$0}`, unless the delimiters
+    /// already follow the cursor or the client does not support the snippets.
+    fn schema_item(&self, label: String) -> CompletionItem {
+        let (open, snippet) = match self.style {
+            SchemaCompletionStyle::Call => ('(', format!("{}($0)", label)),
+            SchemaCompletionStyle::Config => ('{', format!("{} {{$0}}", label)),
+        };
+        if !self.snippet || self.next_char == Some(open) {
+            return CompletionItem {
+                label,
+                ..Default::default()
+            };
+        }
+        let command = (self.style == SchemaCompletionStyle::Call).then(|| Command {
+            title: "Trigger Parameter Hints".to_string(),
+            command: TRIGGER_PARAMETER_HINTS_COMMAND.to_string(),
+            arguments: None,
+        });
+        CompletionItem {
+            label,
+            insert_text: Some(snippet),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            command,
+            ..Default::default()
+        }
+    }
+}
+
+/// The time budget of building the completion items, which is checked before building
+/// each item of the large lists, e.g., the schema attrs and the scope variables.
+pub(crate) struct CompletionBudget {
+    deadline: Option<Instant>,
+    exceeded: Cell<bool>,
+}
+
+impl CompletionBudget {
+    /// Starts the budget of the duration from now, `None` for no budget.
+    pub(crate) fn new(budget: Option<Duration>) -> Self {
+        Self {
+            deadline: budget.map(|budget| Instant::now() + budget),
+            exceeded: Cell::new(false),
+        }
+    }
+
+    /// Whether the budget is exceeded, after which no more items are built.
+    fn is_exceeded(&self) -> bool {
+        if !self.exceeded.get() {
+            if let Some(deadline) = self.deadline {
+                self.exceeded.set(Instant::now() >= deadline);
+            }
+        }
+        self.exceeded.get()
+    }
+}
+
+/// Computes completions at the given position without the time budget.
+#[cfg(test)]
 pub(crate) fn completion(
     trigger_character: Option<char>,
     program: &Program,
     pos: &KCLPos,
     prog_scope: &ProgramScope,
 ) -> Option<lsp_types::CompletionResponse> {
-    if let Some('.') = trigger_character {
-        completion_dot(program, pos, prog_scope)
+    completion_with_budget(
+        trigger_character,
+        program,
+        pos,
+        prog_scope,
+        &CompletionBudget::new(None),
+        &SchemaInsertion::default(),
+    )
+}
+
+/// Computes completions at the given position within the time budget. The items built
+/// before the budget is exceeded are returned as an incomplete list, which the client
+/// re-requests. No code completion is offered inside the string literals except the
+/// interpolations and the path arguments of the file builtins.
+pub(crate) fn completion_with_budget(
+    trigger_character: Option<char>,
+    program: &Program,
+    pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    budget: &CompletionBudget,
+    schema_insertion: &SchemaInsertion,
+) -> Option<lsp_types::CompletionResponse> {
+    if in_string_literal(program, pos) {
+        return completion_file_path(program, pos, budget);
+    }
+    let res = match trigger_character {
+        Some('.') => completion_dot(program, pos, prog_scope, budget, schema_insertion),
+        Some('(') => {
+            completion_call_args(program, pos, prog_scope, budget, schema_insertion.snippet)
+        }
+        // todo: Complete identifiers such as variables, types, etc.
+        _ => completion_attr(program, pos, prog_scope, budget, schema_insertion.snippet)
+            .or_else(|| completion_typed_value(program, pos, prog_scope, budget))
+            .or_else(|| {
+                completion_check_block(program, pos, prog_scope, budget, schema_insertion.snippet)
+            })
+            .or_else(|| completion_schema_self(pos, prog_scope, budget))
+            .or_else(|| {
+                completion_call_args(program, pos, prog_scope, budget, schema_insertion.snippet)
+            }),
+    };
+    if !budget.exceeded.get() {
+        return res;
+    }
+    res.map(|res| {
+        let items = match res {
+            CompletionResponse::Array(items) => items,
+            CompletionResponse::List(list) => list.items,
+        };
+        CompletionResponse::List(CompletionList {
+            is_incomplete: true,
+            items,
+        })
+    })
+}
+
+/// Whether the position is inside a string literal, where the code completion is
+/// suppressed, e.g., `"a.<cursor>"`. The interpolations in the string, e.g.,
+/// `"${a.<cursor>}"`, are not treated as the string.
+fn in_string_literal(program: &Program, pos: &KCLPos) -> bool {
+    let node = match program.pos_to_stmt(pos) {
+        Some(node) => node,
+        None => return false,
+    };
+    match inner_most_expr_in_stmt(&node.node, pos, None).0 {
+        // The innermost expr is the joined string itself only if the position is not in
+        // any interpolation of it.
+        Some(expr) if matches!(expr.node, Expr::StringLit(_) | Expr::JoinedString(_)) => {
+            expr.get_pos().less(pos) && pos.less(&expr.get_end_pos())
+        }
+        _ => false,
+    }
+}
+
+/// Complete schema attrs in the schema config expr. The inserted text contains the
+/// attr name and the separator, which is detected from the sibling config entries, and
+/// the cursor is placed after the separator if the client supports the snippets. The
+/// attrs already set are excluded except the dict attrs, the first required attr not set
+/// yet is preselected, and a free-form key is offered for the schema with an index
+/// signature, e.g., `[str]: str`.
+fn completion_attr(
+    program: &Program,
+    pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    budget: &CompletionBudget,
+    snippet: bool,
+) -> Option<lsp_types::CompletionResponse> {
+    let node = program.pos_to_stmt(pos)?;
+    let (expr, schema_expr) = inner_most_expr_in_stmt(&node.node, pos, None);
+    let config_expr = match expr?.node {
+        Expr::Config(config_expr) => config_expr,
+        _ => return None,
+    };
+    let schema_expr = match schema_expr?.node {
+        Expr::Schema(schema_expr) => schema_expr,
+        _ => return None,
+    };
+    let schema_ty = match find_def(node, &schema_expr.name.get_end_pos(), prog_scope)? {
+        Definition::Object(obj) => match &obj.ty.kind {
+            TypeKind::Schema(schema_ty) => schema_ty.clone(),
+            _ => return None,
+        },
+        Definition::Scope(_) => return None,
+    };
+    let separator = config_entry_separator(&config_expr);
+    let set_attrs = config_set_attrs(&config_expr, pos);
+    let mut items: Vec<CompletionItem> = schema_ty
+        .attrs
+        .iter()
+        .filter(|(attr, _)| *attr != "__settings__")
+        // The dict attrs can be set repeatedly, e.g., the entries are merged by `:`.
+        .filter(|(attr, schema_attr)| {
+            !set_attrs.contains(*attr) || matches!(schema_attr.ty.kind, TypeKind::Dict(_, _))
+        })
+        .take_while(|_| !budget.is_exceeded())
+        .map(|(attr, _)| {
+            let item = CompletionItem {
+                label: attr.clone(),
+                data: CompletionItemData {
+                    file: pos.filename.clone(),
+                    pkgpath: schema_ty.pkgpath.clone(),
+                    name: schema_ty.name.clone(),
+                    attr: Some(attr.clone()),
+                }
+                .to_value(),
+                ..Default::default()
+            };
+            with_insert_snippet(item, format!("{}{}$0", attr, separator), snippet)
+        })
+        .collect();
+    // The first required attr which is not set yet is the most likely one to be set next,
+    // which is preselected by the client.
+    let required = schema_ty.attrs.iter().find(|(attr, schema_attr)| {
+        !schema_attr.is_optional && !schema_attr.has_default && !set_attrs.contains(*attr)
+    });
+    if let Some((attr, _)) = required {
+        if let Some(item) = items.iter_mut().find(|item| &item.label == attr) {
+            item.preselect = Some(true);
+        }
+    }
+    // The schema with an index signature accepts arbitrary keys, which are inserted with
+    // the key placeholder.
+    if let Some(index_signature) = &schema_ty.index_signature {
+        let item = CompletionItem {
+            label: index_signature.ty_str(),
+            detail: Some("index signature".to_string()),
+            ..Default::default()
+        };
+        items.push(with_insert_snippet(
+            item,
+            format!("${{1:key}}{}$0", separator),
+            snippet,
+        ));
+    }
+    Some(items.into())
+}
+
+/// Complete the in-scope variables whose types are assignable to the type of the schema
+/// attr at the value position of the schema config expr, e.g., the `Server` instances for
+/// `App { server = <cursor> }` where `server: Server`.
+fn completion_typed_value(
+    program: &Program,
+    pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    budget: &CompletionBudget,
+) -> Option<lsp_types::CompletionResponse> {
+    let node = program.pos_to_stmt(pos)?;
+    let mut finder = TypedValueFinder { pos, entry: None };
+    finder.walk_stmt(&node.node);
+    let (schema_expr, attr) = finder.entry?;
+    let expected_ty = match find_def(node, &schema_expr.name.get_end_pos(), prog_scope)? {
+        Definition::Object(obj) => match &obj.ty.kind {
+            TypeKind::Schema(schema_ty) => schema_ty.get_type_of_attr(&attr)?,
+            _ => return None,
+        },
+        Definition::Scope(_) => return None,
+    };
+    // Every variable is assignable to `any`, which makes no useful suggestion.
+    if expected_ty.is_any() {
+        return None;
+    }
+    let mut scope = prog_scope
+        .scope_map
+        .values()
+        .find_map(|scope| scope.borrow().inner_most(pos))?;
+    let mut names: IndexSet<String> = IndexSet::new();
+    let mut items = vec![];
+    loop {
+        if matches!(scope.kind, ScopeKind::Builtin) {
+            break;
+        }
+        for (name, obj) in &scope.elems {
+            if budget.is_exceeded() {
+                break;
+            }
+            let obj = obj.borrow();
+            // The inner variables shadow the outer ones with the same name.
+            if !matches!(obj.kind, ScopeObjectKind::Variable) || names.contains(name) {
+                continue;
+            }
+            names.insert(name.clone());
+            if assignable_to(obj.ty.clone(), expected_ty.clone()) {
+                items.push(CompletionItem {
+                    label: name.clone(),
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    detail: Some(obj.ty.ty_str()),
+                    ..Default::default()
+                });
+            }
+        }
+        scope = match scope.get_enclosing_scope() {
+            Some(parent) => parent.borrow().clone(),
+            None => break,
+        };
+    }
+    Some(items.into())
+}
+
+/// Complete the validation builtins in the check block of the schema or the rule, which
+/// are sorted before the schema attrs, e.g., the `all`/`any` quantifiers, `isunique` and
+/// `regex.match` if `regex` is imported. The placeholders of the builtins are inserted as
+/// the plain text if the client doesn't support the snippets.
+fn completion_check_block(
+    program: &Program,
+    pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    budget: &CompletionBudget,
+    snippet: bool,
+) -> Option<lsp_types::CompletionResponse> {
+    let in_check_block = match program.pos_to_stmt(pos)?.node {
+        Stmt::Schema(schema_stmt) => schema_stmt.checks.iter().any(|c| c.contains_pos(pos)),
+        Stmt::Rule(rule_stmt) => rule_stmt.checks.iter().any(|c| c.contains_pos(pos)),
+        _ => false,
+    };
+    if !in_check_block {
+        return None;
+    }
+    let validation_item = |label: &str, insert_text: String, kind: CompletionItemKind| {
+        let item = CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            sort_text: Some(format!("0{}", label)),
+            ..Default::default()
+        };
+        with_insert_snippet(item, insert_text, snippet)
+    };
+    let mut items: Vec<CompletionItem> = VALIDATION_QUANTIFIERS
+        .iter()
+        .map(|&(label, snippet)| {
+            validation_item(label, snippet.to_string(), CompletionItemKind::KEYWORD)
+        })
+        .chain(VALIDATION_FUNCTIONS.iter().map(|&name| {
+            validation_item(name, format!("{}($0)", name), CompletionItemKind::FUNCTION)
+        }))
+        .collect();
+    let imports_regex = program.pkgs.values().flatten().any(|module| {
+        module.filename == pos.filename
+            && module.body.iter().any(
+                |stmt| matches!(&stmt.node, Stmt::Import(import_stmt) if import_stmt.path == "regex"),
+            )
+    });
+    if imports_regex {
+        items.push(validation_item(
+            "regex.match",
+            "regex.match(${1:string}, ${2:pattern})".to_string(),
+            CompletionItemKind::FUNCTION,
+        ));
+    }
+    if let Some(CompletionResponse::Array(attrs)) = completion_schema_self(pos, prog_scope, budget)
+    {
+        items.extend(attrs);
+    }
+    Some(items.into())
+}
+
+/// Complete the attrs of the schema whose body contains the position, e.g., the sibling
+/// attrs in the default value of an attr, the check block and the lambdas of the schema,
+/// including the attrs declared in the base schemas.
+fn completion_schema_self(
+    pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    budget: &CompletionBudget,
+) -> Option<lsp_types::CompletionResponse> {
+    let mut scope = prog_scope
+        .scope_map
+        .values()
+        .find_map(|scope| scope.borrow().inner_most(pos))?;
+    // Find the schema scope enclosing the position, e.g., from the scope of a lambda.
+    let (schema_name, parent) = loop {
+        let parent = scope.get_enclosing_scope()?;
+        if let ScopeKind::Schema(name) = &scope.kind {
+            break (name.clone(), parent);
+        }
+        scope = parent.borrow().clone();
+    };
+    let parent = parent.borrow();
+    let obj = parent.elems.get(&schema_name)?.borrow();
+    let schema_ty = match &obj.ty.kind {
+        TypeKind::Schema(schema_ty) => schema_ty,
+        _ => return None,
+    };
+    let mut items: Vec<CompletionItem> = vec![];
+    let mut current: Option<&SchemaType> = Some(schema_ty);
+    while let Some(schema_ty) = current {
+        for (name, attr) in &schema_ty.attrs {
+            if budget.is_exceeded() {
+                break;
+            }
+            // The attrs overridden by the derived schema are completed only once.
+            if name == "__settings__" || items.iter().any(|item| &item.label == name) {
+                continue;
+            }
+            items.push(CompletionItem {
+                label: name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(attr.ty.ty_str()),
+                ..Default::default()
+            });
+        }
+        current = schema_ty.base.as_deref();
+    }
+    Some(items.into())
+}
+
+/// Complete the keyword arguments of the call at the argument position, e.g., right after
+/// the `(` of `Server(<cursor>)`, excluding the arguments already passed by keywords. The
+/// arguments of a schema call are the parameters of the schema, e.g., `name` and `port`
+/// of `schema Server[name: str, port: int]`, while the schema attrs are set by the config.
+fn completion_call_args(
+    program: &Program,
+    pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    budget: &CompletionBudget,
+    snippet: bool,
+) -> Option<lsp_types::CompletionResponse> {
+    let node = program.pos_to_stmt(pos)?;
+    let (func, keywords) = match inner_most_expr_in_stmt(&node.node, pos, None).0?.node {
+        Expr::Call(call_expr) => (*call_expr.func, call_expr.keywords),
+        Expr::Schema(schema_expr) if pos.less(&schema_expr.config.get_pos()) => {
+            let name = schema_expr.name;
+            (
+                Node::node_with_pos(Expr::Identifier(name.node.clone()), name.pos()),
+                schema_expr.kwargs,
+            )
+        }
+        _ => return None,
+    };
+    // The position is on the callee, e.g., `Ser<cursor>ver()`.
+    if !func.get_end_pos().less(pos) {
+        return None;
+    }
+    let params = match find_def(node, &func.get_end_pos(), prog_scope)? {
+        Definition::Object(obj) => match &obj.ty.kind {
+            TypeKind::Schema(schema_ty) => schema_ty.func.params.clone(),
+            TypeKind::Function(func_ty) => func_ty.params.clone(),
+            _ => return None,
+        },
+        Definition::Scope(_) => return None,
+    };
+    let passed: Vec<String> = keywords
+        .iter()
+        .filter_map(|keyword| keyword.node.arg.node.names.first())
+        .map(|name| name.node.clone())
+        .collect();
+    let items: Vec<CompletionItem> = params
+        .iter()
+        .filter(|param| !passed.contains(&param.name))
+        .take_while(|_| !budget.is_exceeded())
+        .map(|param| {
+            let item = CompletionItem {
+                label: format!("{}=", param.name),
+                kind: Some(CompletionItemKind::VARIABLE),
+                detail: Some(param.ty.ty_str()),
+                ..Default::default()
+            };
+            with_insert_snippet(item, format!("{}=$0", param.name), snippet)
+        })
+        .collect();
+    if items.is_empty() {
+        return None;
+    }
+    Some(items.into())
+}
+
+/// Complete the file paths in the path argument of the file builtins, e.g.,
+/// `file.read("conf/<cursor>")`, with the entries of the directory typed before the cursor,
+/// which is relative to the directory of the current file. The directories are suffixed
+/// with `/` so that their entries are completed next.
+fn completion_file_path(
+    program: &Program,
+    pos: &KCLPos,
+    budget: &CompletionBudget,
+) -> Option<lsp_types::CompletionResponse> {
+    let node = program.pos_to_stmt(pos)?;
+    let mut finder = FilePathArgFinder { pos, typed: None };
+    finder.walk_stmt(&node.node);
+    let typed = finder.typed?;
+    let typed_dir = match typed.rfind('/') {
+        Some(i) => &typed[..=i],
+        None => "",
+    };
+    let dir = Path::new(&pos.filename).parent()?.join(typed_dir);
+    let mut entries = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>();
+    entries.sort();
+    let items: Vec<CompletionItem> = entries
+        .iter()
+        .take_while(|_| !budget.is_exceeded())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            Some(if path.is_dir() {
+                CompletionItem {
+                    label: format!("{}/", name),
+                    kind: Some(CompletionItemKind::FOLDER),
+                    ..Default::default()
+                }
+            } else {
+                CompletionItem {
+                    label: name.to_string(),
+                    kind: Some(CompletionItemKind::FILE),
+                    ..Default::default()
+                }
+            })
+        })
+        .collect();
+    if items.is_empty() {
+        return None;
+    }
+    Some(items.into())
+}
+
+/// Complete the keys used by the other plain dicts in the same file for the dict expr at
+/// the position, excluding the keys already set in the dict, so that the keys are spelled
+/// consistently, e.g., the labels of the resources. Offered only if the
+/// `suggest_existing_keys` option is enabled.
+pub(crate) fn completion_existing_keys(
+    program: &Program,
+    pos: &KCLPos,
+    snippet: bool,
+) -> Option<lsp_types::CompletionResponse> {
+    let node = program.pos_to_stmt(pos)?;
+    let config_expr = match inner_most_expr_in_stmt(&node.node, pos, None).0?.node {
+        Expr::Config(config_expr) => config_expr,
+        _ => return None,
+    };
+    let module = program
+        .pkgs
+        .values()
+        .flatten()
+        .find(|module| module.filename == pos.filename)?;
+    let mut collector = DictKeysCollector::default();
+    collector.walk_module(module);
+    let separator = config_entry_separator(&config_expr);
+    let set_keys = config_set_attrs(&config_expr, pos);
+    let items: Vec<CompletionItem> = collector
+        .keys
+        .iter()
+        .filter(|key| !set_keys.contains(*key))
+        .map(|key| {
+            let key_text = if is_identifier(key) {
+                key.clone()
+            } else {
+                format!("{:?}", key)
+            };
+            let item = CompletionItem {
+                label: key.clone(),
+                kind: Some(CompletionItemKind::PROPERTY),
+                detail: Some("existing key".to_string()),
+                ..Default::default()
+            };
+            with_insert_snippet(item, format!("{}{}$0", key_text, separator), snippet)
+        })
+        .collect();
+    Some(items.into())
+}
+
+/// Complete the type names at the type annotation of a variable or a schema attr, e.g.,
+/// `x: <cursor>`, instead of the variables in scope. The context is detected from the text
+/// of the line because the annotation without a type is not parsed into the AST.
+pub(crate) fn completion_type_annotation(
+    text: &str,
+    program: &Program,
+    pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    snippet: bool,
+) -> Option<lsp_types::CompletionResponse> {
+    let line = text.lines().nth(pos.line.checked_sub(1)? as usize)?;
+    let prefix: String = line.chars().take(pos.column? as usize).collect();
+    if !is_type_annotation_prefix(&prefix) {
+        return None;
+    }
+    // `key: <cursor>` in a dict is a config entry rather than a type annotation.
+    if let Some(node) = program.pos_to_stmt(pos) {
+        if let Some(Node {
+            node: Expr::Config(_),
+            ..
+        }) = inner_most_expr_in_stmt(&node.node, pos, None).0
+        {
+            return None;
+        }
+    }
+    let pkgpath = file_pkgpath(program, &pos.filename)?;
+    let mut items: Vec<CompletionItem> = BUILTIN_TYPES
+        .iter()
+        .map(|&(label, insert_text)| {
+            let item = CompletionItem {
+                label: label.to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                ..Default::default()
+            };
+            with_insert_snippet(item, insert_text.to_string(), snippet)
+        })
+        .collect();
+    if let Some(scope) = prog_scope.scope_map.get(&pkgpath) {
+        for (name, obj) in &scope.borrow().elems {
+            let obj = obj.borrow();
+            let kind = match (&obj.kind, &obj.ty.kind) {
+                (ScopeObjectKind::Definition, TypeKind::Schema(schema_ty))
+                    if !schema_ty.is_mixin && !schema_ty.is_protocol && !schema_ty.is_rule =>
+                {
+                    CompletionItemKind::STRUCT
+                }
+                (ScopeObjectKind::TypeAlias, _) => CompletionItemKind::TYPE_PARAMETER,
+                _ => continue,
+            };
+            items.push(CompletionItem {
+                label: name.clone(),
+                kind: Some(kind),
+                ..Default::default()
+            });
+        }
+    }
+    let local_names: Vec<String> = items.iter().map(|item| item.label.clone()).collect();
+    items.extend(completion_package_schemas(
+        program,
+        pos,
+        &pkgpath,
+        prog_scope,
+        &local_names,
+    ));
+    Some(items.into())
+}
+
+/// Complete the schemas of the other packages in the program, which are inserted with
+/// their package-qualified names, e.g., `models.Server`, and the import of the package is
+/// added if the file does not import it yet. The schemas whose names are ambiguous, i.e.,
+/// declared in several packages or in the current package too, are labeled with their
+/// qualified names to be distinguished, and all the candidates are offered.
+fn completion_package_schemas(
+    program: &Program,
+    pos: &KCLPos,
+    pkgpath: &str,
+    prog_scope: &ProgramScope,
+    local_names: &[String],
+) -> Vec<CompletionItem> {
+    let module = match program
+        .pkgs
+        .get(pkgpath)
+        .and_then(|modules| modules.iter().find(|m| m.filename == pos.filename))
+    {
+        Some(module) => module,
+        None => return vec![],
+    };
+    // The import names of the packages imported by the file, and the line after the last
+    // import where the missing imports are added.
+    let mut imports: HashMap<&str, String> = HashMap::new();
+    let mut import_line = 0;
+    for stmt in &module.body {
+        if let Stmt::Import(import_stmt) = &stmt.node {
+            imports.insert(
+                &import_stmt.path,
+                import_stmt
+                    .asname
+                    .clone()
+                    .unwrap_or_else(|| import_stmt.name.clone()),
+            );
+            import_line = stmt.end_line as u32;
+        }
+    }
+
+    let mut pkgpaths: Vec<&String> = program
+        .pkgs
+        .keys()
+        .filter(|other| other.as_str() != pkgpath && other.as_str() != MAIN_PKG)
+        .collect();
+    pkgpaths.sort();
+    let mut candidates: Vec<(&String, String)> = vec![];
+    for other in pkgpaths {
+        let scope = match prog_scope.scope_map.get(other) {
+            Some(scope) => scope.borrow(),
+            None => continue,
+        };
+        let mut names: Vec<String> = scope
+            .elems
+            .iter()
+            .filter(|(name, obj)| {
+                !name.starts_with('_')
+                    && matches!(
+                        (&obj.borrow().kind, &obj.borrow().ty.kind),
+                        (ScopeObjectKind::Definition, TypeKind::Schema(schema_ty))
+                            if !schema_ty.is_mixin && !schema_ty.is_protocol && !schema_ty.is_rule
+                    )
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        candidates.extend(names.into_iter().map(|name| (other, name)));
+    }
+
+    candidates
+        .iter()
+        .map(|(other, name)| {
+            let ambiguous = local_names.contains(name)
+                || candidates
+                    .iter()
+                    .any(|(pkg, candidate)| pkg != other && candidate == name);
+            let import_name = imports
+                .get(other.as_str())
+                .cloned()
+                .unwrap_or_else(|| other.rsplit('.').next().unwrap_or(other).to_string());
+            let qualified_name = format!("{}.{}", import_name, name);
+            let additional_text_edits = (!imports.contains_key(other.as_str())).then(|| {
+                let start = lsp_types::Position::new(import_line, 0);
+                vec![TextEdit {
+                    range: lsp_types::Range::new(start, start),
+                    new_text: format!("import {}\n", other),
+                }]
+            });
+            CompletionItem {
+                label: if ambiguous {
+                    qualified_name.clone()
+                } else {
+                    name.clone()
+                },
+                kind: Some(CompletionItemKind::STRUCT),
+                detail: Some(other.to_string()),
+                filter_text: Some(name.clone()),
+                insert_text: Some(qualified_name),
+                additional_text_edits,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Complete the snippets scaffolding the configs of the schemas in the package at an empty
+/// top level assignment, e.g., `server = <cursor>` to `Server {` with the required attrs
+/// filled with the placeholders as the tab stops. The context is detected from the text of
+/// the line because the assignment without a value is not parsed into the AST. Offered only
+/// if the `suggest_schema_configs` option is enabled and the client supports the snippets.
+pub(crate) fn completion_schema_config(
+    text: &str,
+    program: &Program,
+    pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> Option<lsp_types::CompletionResponse> {
+    let line = text.lines().nth(pos.line.checked_sub(1)? as usize)?;
+    let prefix: String = line.chars().take(pos.column? as usize).collect();
+    let suffix: String = line.chars().skip(pos.column? as usize).collect();
+    if !is_empty_assignment_prefix(&prefix) || !suffix.trim().is_empty() {
+        return None;
+    }
+    let pkgpath = file_pkgpath(program, &pos.filename)?;
+    let scope = prog_scope.scope_map.get(&pkgpath)?.borrow();
+    let items: Vec<CompletionItem> = scope
+        .elems
+        .iter()
+        .filter_map(
+            |(name, obj)| match (&obj.borrow().kind, &obj.borrow().ty.kind) {
+                (ScopeObjectKind::Definition, TypeKind::Schema(schema_ty))
+                    if !schema_ty.is_mixin && !schema_ty.is_protocol && !schema_ty.is_rule =>
+                {
+                    Some(CompletionItem {
+                        label: format!("{} config", name),
+                        kind: Some(CompletionItemKind::SNIPPET),
+                        detail: Some(format!("Scaffold a config of the schema {}", name)),
+                        insert_text: Some(schema_config_snippet(name, schema_ty)),
+                        insert_text_format: Some(InsertTextFormat::SNIPPET),
+                        ..Default::default()
+                    })
+                }
+                _ => None,
+            },
+        )
+        .collect();
+    Some(items.into())
+}
+
+/// Returns the snippet of the config of the schema, whose required attrs are the tab stops
+/// with the placeholders derived from their types.
+fn schema_config_snippet(name: &str, schema_ty: &SchemaType) -> String {
+    let mut required_attrs = vec![];
+    collect_required_attrs(schema_ty, &mut required_attrs);
+    if required_attrs.is_empty() {
+        return format!("{} {{$0}}", name);
+    }
+    let mut snippet = format!("{} {{\n", name);
+    for (i, (attr, ty)) in required_attrs.iter().enumerate() {
+        snippet.push_str(&format!(
+            "{}{} = ${{{}:{}}}\n",
+            INDENT,
+            attr,
+            i + 1,
+            escape_snippet(&placeholder(ty, 1))
+        ));
+    }
+    snippet.push('}');
+    snippet
+}
+
+/// Sets the insert text of the completion item to the snippet if the client supports the
+/// snippets, otherwise to the plain text of the snippet.
+fn with_insert_snippet(item: CompletionItem, snippet: String, supported: bool) -> CompletionItem {
+    if supported {
+        CompletionItem {
+            insert_text: Some(snippet),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..item
+        }
     } else {
-        // todo: Complete identifiers such as attr, variables, types, etc.
-        None
+        CompletionItem {
+            insert_text: Some(snippet_to_plain_text(&snippet)),
+            insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+            ..item
+        }
+    }
+}
+
+/// Returns the plain text of the snippet, where the tab stops are removed and the
+/// placeholders are replaced with their texts, e.g., `key: ` for `${1:key}: $0`.
+fn snippet_to_plain_text(snippet: &str) -> String {
+    let mut text = String::new();
+    // The number of the placeholders enclosing the current character.
+    let mut depth = 0;
+    let mut chars = snippet.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+                chars.next_if_eq(&':');
+                depth += 1;
+            }
+            '$' if chars.peek().map_or(false, |c| c.is_ascii_digit()) => {
+                while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+            }
+            '}' if depth > 0 => depth -= 1,
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+/// Escapes the characters of the text which have special meanings in the snippet syntax.
+fn escape_snippet(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('$', "\\$")
+        .replace('}', "\\}")
+}
+
+/// Whether the text before the cursor is a top level assignment without the value, e.g.,
+/// `server = ` or `server: Server = `, excluding the comparisons and the augmented
+/// assignments, e.g., `a == ` and `a += `.
+fn is_empty_assignment_prefix(prefix: &str) -> bool {
+    if prefix.starts_with(char::is_whitespace) {
+        return false;
+    }
+    let target = match prefix.trim_end().strip_suffix('=') {
+        Some(target) => target.trim_end(),
+        None => return false,
+    };
+    target
+        .split(':')
+        .next()
+        .map_or(false, |name| is_identifier(name.trim_end()))
+}
+
+/// Returns the path of the package where the file is located.
+fn file_pkgpath(program: &Program, filename: &str) -> Option<String> {
+    program.pkgs.iter().find_map(|(pkgpath, modules)| {
+        modules
+            .iter()
+            .any(|module| module.filename == filename)
+            .then(|| pkgpath.clone())
+    })
+}
+
+/// Whether the client supports the snippets in the completion items.
+pub(crate) fn snippet_supported(client_capabilities: &ClientCapabilities) -> bool {
+    client_capabilities
+        .text_document
+        .as_ref()
+        .and_then(|text_document| text_document.completion.as_ref())
+        .and_then(|completion| completion.completion_item.as_ref())
+        .and_then(|completion_item| completion_item.snippet_support)
+        .unwrap_or(false)
+}
+
+/// Whether the client supports the Markdown documentation of the completion items.
+pub(crate) fn completion_markdown_supported(client_capabilities: &ClientCapabilities) -> bool {
+    client_capabilities
+        .text_document
+        .as_ref()
+        .and_then(|text_document| text_document.completion.as_ref())
+        .and_then(|completion| completion.completion_item.as_ref())
+        .and_then(|completion_item| completion_item.documentation_format.as_ref())
+        .map_or(false, |formats| formats.contains(&MarkupKind::Markdown))
+}
+
+/// Attaches the documentation of the definition or the schema attr referenced by the data
+/// to the completion item. The documentation is the Markdown of the hover with the
+/// signature in a `kcl` code block if `markdown` is true, and the plain signature and
+/// docstring otherwise.
+pub(crate) fn resolve_completion_item(
+    mut item: CompletionItem,
+    data: &CompletionItemData,
+    program: &Program,
+    prog_scope: &ProgramScope,
+    markdown: bool,
+) -> CompletionItem {
+    if let Some(info) = completion_item_info(data, program, prog_scope) {
+        item.documentation = Some(if markdown {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: info_to_markdown(info),
+            })
+        } else {
+            Documentation::String(
+                std::iter::once(info.signature)
+                    .chain(info.doc)
+                    .collect::<Vec<String>>()
+                    .join("\n\n"),
+            )
+        });
     }
+    item
+}
+
+/// Returns the description of the definition or the schema attr referenced by the data.
+fn completion_item_info(
+    data: &CompletionItemData,
+    program: &Program,
+    prog_scope: &ProgramScope,
+) -> Option<HoverInfo> {
+    let obj = prog_scope
+        .scope_map
+        .get(&data.pkgpath)?
+        .borrow()
+        .elems
+        .get(&data.name)?
+        .clone();
+    let obj = obj.borrow();
+    let attr = match &data.attr {
+        Some(attr) => attr,
+        None => return Some(describe_obj(program, &obj, prog_scope)),
+    };
+    let schema_ty = match &obj.ty.kind {
+        TypeKind::Schema(schema_ty) => schema_ty,
+        _ => return None,
+    };
+    let (name, schema_attr) = schema_attrs(schema_ty).find(|(name, _)| name == &attr)?;
+    let ty = ty_str_with_budget(&schema_attr.ty);
+    Some(HoverInfo {
+        name: Some(name.clone()),
+        kind: Some(ScopeObjectKind::Attribute),
+        signature: format!(
+            "{}{}: {}",
+            name,
+            if schema_attr.is_optional { "?" } else { "" },
+            ty
+        ),
+        ty,
+        doc: schema_attr.doc.clone(),
+        attrs: vec![],
+    })
+}
+
+/// Whether the text before the cursor is a type annotation without the type or with a
+/// partial type name, e.g., `x: ` or `    name?: St`.
+fn is_type_annotation_prefix(prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+    let name = match prefix.trim_end().strip_suffix(':') {
+        Some(name) => name.trim_end(),
+        None => return false,
+    };
+    let name = name.strip_suffix('?').unwrap_or(name).trim_start();
+    // The block headers, e.g., `else:` and `check:`, are followed by stmts.
+    is_identifier(name) && !matches!(name, "else" | "check")
+}
+
+/// Whether the key can be written as an identifier without the quotes.
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .map_or(false, |c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Collects the keys of the plain dict exprs in the order of the first occurrences. The
+/// configs of the schema exprs are skipped because their keys are the schema attrs.
+#[derive(Default)]
+struct DictKeysCollector {
+    keys: IndexSet<String>,
+}
+
+impl MutSelfWalker for DictKeysCollector {
+    fn walk_schema_expr(&mut self, schema_expr: &SchemaExpr) {
+        for arg in &schema_expr.args {
+            self.walk_expr(&arg.node);
+        }
+        for kwarg in &schema_expr.kwargs {
+            self.walk_keyword(&kwarg.node);
+        }
+        if let Expr::Config(config_expr) = &schema_expr.config.node {
+            for item in &config_expr.items {
+                self.walk_expr(&item.node.value.node);
+            }
+        }
+    }
+
+    fn walk_config_expr(&mut self, config_expr: &ConfigExpr) {
+        for item in &config_expr.items {
+            if let Some(key) = &item.node.key {
+                match &key.node {
+                    Expr::Identifier(id) => {
+                        if let Some(name) = id.names.first() {
+                            self.keys.insert(name.node.clone());
+                        }
+                    }
+                    Expr::StringLit(string_lit) => {
+                        self.keys.insert(string_lit.value.clone());
+                    }
+                    _ => {}
+                }
+            }
+            self.walk_expr(&item.node.value.node);
+        }
+    }
+}
+
+/// Finds the path argument of the file builtins which contains the position, and records
+/// the text of the argument before the position.
+struct FilePathArgFinder<'a> {
+    pos: &'a KCLPos,
+    typed: Option<String>,
+}
+
+impl MutSelfWalker for FilePathArgFinder<'_> {
+    fn walk_call_expr(&mut self, call_expr: &CallExpr) {
+        if let Expr::Identifier(id) = &call_expr.func.node {
+            let names: Vec<&str> = id.names.iter().map(|name| name.node.as_str()).collect();
+            let arg = FILE_PATH_ARGUMENTS
+                .iter()
+                .find(|(module, func, _)| names == [*module, *func])
+                .and_then(|(_, _, index)| call_expr.args.get(*index));
+            if let Some(arg) = arg {
+                if let Expr::StringLit(string_lit) = &arg.node {
+                    if arg.contains_pos(self.pos) {
+                        self.typed =
+                            typed_in_string_lit(string_lit, arg.line, arg.column, self.pos);
+                    }
+                }
+            }
+        }
+        self.walk_expr(&call_expr.func.node);
+        for arg in &call_expr.args {
+            self.walk_expr(&arg.node);
+        }
+        for keyword in &call_expr.keywords {
+            self.walk_keyword(&keyword.node);
+        }
+    }
+}
+
+/// Returns the text typed before the position in the line of the string literal starting at
+/// `line` and `column`. The opening and the closing quotes are located by the raw value of
+/// the literal, e.g., `conf/` of `r"conf/<cursor>"` or `"""conf/<cursor>"""`.
+fn typed_in_string_lit(
+    string_lit: &StringLit,
+    line: u64,
+    column: u64,
+    pos: &KCLPos,
+) -> Option<String> {
+    let raw = string_lit.raw_value.as_str();
+    // The prefix of the raw strings, e.g., `r` of `r"..."`.
+    let unprefixed = raw.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    let quote = unprefixed.chars().next()?;
+    let quotes = quote
+        .to_string()
+        .repeat(if string_lit.is_long_string { 3 } else { 1 });
+    let content = unprefixed.strip_prefix(quotes.as_str())?;
+    // The string being typed may not be closed yet.
+    let content = content.strip_suffix(quotes.as_str()).unwrap_or(content);
+    let line_index = pos.line.checked_sub(line)?;
+    let content_line = content.split('\n').nth(line_index as usize)?;
+    // The prefix and the quotes are ASCII, whose lengths are the numbers of the chars.
+    let open = raw.len() - unprefixed.len() + quotes.len();
+    let start = if line_index == 0 {
+        column + open as u64
+    } else {
+        0
+    };
+    let offset = pos.column.unwrap_or(0).checked_sub(start)?;
+    Some(content_line.chars().take(offset as usize).collect())
+}
+
+/// Finds the innermost schema config entry whose value contains the position, and records
+/// the schema expr and the attr name of the entry. The entries of the plain dict exprs
+/// clear the record because their values are not typed by the schema attrs.
+struct TypedValueFinder<'a> {
+    pos: &'a KCLPos,
+    entry: Option<(SchemaExpr, String)>,
+}
+
+impl TypedValueFinder<'_> {
+    fn entry_contains_pos(&self, config_expr: &ConfigExpr) -> Option<Option<String>> {
+        config_expr
+            .items
+            .iter()
+            .find(|item| item.node.value.contains_pos(self.pos))
+            .map(|item| {
+                item.node.key.as_ref().and_then(|key| match &key.node {
+                    Expr::Identifier(id) => Some(get_identifier_last_name(id)),
+                    Expr::StringLit(string_lit) => Some(string_lit.value.clone()),
+                    _ => None,
+                })
+            })
+    }
+}
+
+impl MutSelfWalker for TypedValueFinder<'_> {
+    fn walk_schema_expr(&mut self, schema_expr: &SchemaExpr) {
+        for arg in &schema_expr.args {
+            self.walk_expr(&arg.node);
+        }
+        for kwarg in &schema_expr.kwargs {
+            self.walk_keyword(&kwarg.node);
+        }
+        if let Expr::Config(config_expr) = &schema_expr.config.node {
+            if let Some(key) = self.entry_contains_pos(config_expr) {
+                self.entry = key.map(|key| (schema_expr.clone(), key));
+            }
+            // The nested schema exprs are walked after the enclosing one, so the
+            // innermost wins.
+            for item in &config_expr.items {
+                self.walk_expr(&item.node.value.node);
+            }
+        }
+    }
+
+    fn walk_config_expr(&mut self, config_expr: &ConfigExpr) {
+        if self.entry_contains_pos(config_expr).is_some() {
+            self.entry = None;
+        }
+        for item in &config_expr.items {
+            if let Some(key) = &item.node.key {
+                self.walk_expr(&key.node);
+            }
+            self.walk_expr(&item.node.value.node);
+        }
+    }
+}
+
+/// Returns the attrs already set in the config expr, excluding the entry being edited at
+/// the position.
+fn config_set_attrs(config_expr: &ConfigExpr, pos: &KCLPos) -> IndexSet<String> {
+    config_expr
+        .items
+        .iter()
+        .filter(|item| !item.contains_pos(pos))
+        .filter_map(|item| match &item.node.key.as_ref()?.node {
+            Expr::Identifier(id) => id.names.first().map(|name| name.node.clone()),
+            Expr::StringLit(string_lit) => Some(string_lit.value.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Detects the separator style used by the entries of the config expr, e.g., ` = ` for
+/// `attr = value` and `: ` for `attr: value`. Defaults to ` = `.
+fn config_entry_separator(config_expr: &ConfigExpr) -> &'static str {
+    for item in config_expr
+        .items
+        .iter()
+        .filter(|item| item.node.key.is_some())
+    {
+        match item.node.operation {
+            ConfigEntryOperation::Union => return ": ",
+            ConfigEntryOperation::Override => return " = ",
+            ConfigEntryOperation::Insert => continue,
+        }
+    }
+    " = "
 }
 
 fn completion_dot(
     program: &Program,
     pos: &KCLPos,
     prog_scope: &ProgramScope,
+    budget: &CompletionBudget,
+    schema_insertion: &SchemaInsertion,
 ) -> Option<lsp_types::CompletionResponse> {
     // Get the position of trigger_character '.'
     let pos = &KCLPos {
@@ -54,24 +1373,67 @@ fn completion_dot(
         column: pos.column.map(|c| c - 1),
     };
 
-    match program.pos_to_stmt(pos) {
-        Some(node) => match node.node {
-            Stmt::Import(stmt) => completion_for_import(&stmt, pos, prog_scope, program),
-            _ => {
-                let expr = inner_most_expr_in_stmt(&node.node, pos, None).0;
-                match expr {
-                    Some(node) => {
-                        let items = get_completion_items(&node.node, prog_scope);
-                        Some(into_completion_items(&items).into())
-                    }
-                    None => None,
-                }
-            }
-        },
-        None => None,
+    let node = match program.pos_to_stmt(pos) {
+        Some(node) => node,
+        None => last_stmt_in_line(program, pos)?,
+    };
+    match node.node {
+        Stmt::Import(stmt) => completion_for_import(&stmt, pos, prog_scope, program),
+        _ => {
+            // The expr recovered by the parser may end before the trailing dot at the end
+            // of the file, e.g., `p.` without any following token, then the dot follows the
+            // innermost expr at the end of the operand before the dot.
+            let expr = inner_most_expr_in_stmt(&node.node, pos, None)
+                .0
+                .or_else(|| {
+                    inner_most_expr_in_stmt(&node.node, &operand_end_pos(&node, pos), None).0
+                })?;
+            let items = get_completion_items(&expr.node, prog_scope, budget);
+            Some(
+                items
+                    .into_iter()
+                    .map(|item| item.into_completion_item(schema_insertion))
+                    .collect::<Vec<CompletionItem>>()
+                    .into(),
+            )
+        }
     }
 }
 
+/// Returns the end position of the operand of the stmt before the position. The operands
+/// of the assert stmt are distinct, e.g., the dot of `assert p., "msg"` follows the
+/// condition `p` rather than the message string, and the other stmts end at their ends.
+fn operand_end_pos(node: &Node<Stmt>, pos: &KCLPos) -> KCLPos {
+    match &node.node {
+        Stmt::Assert(assert_stmt) => [
+            Some(&assert_stmt.test),
+            assert_stmt.if_cond.as_ref(),
+            assert_stmt.msg.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|expr| expr.get_pos().less(pos))
+        .last()
+        .map_or_else(|| node.get_end_pos(), |expr| expr.get_end_pos()),
+        _ => node.get_end_pos(),
+    }
+}
+
+/// Returns the last stmt which ends before the position in the same line, e.g., the stmt
+/// `p` followed by the dot at the end of the file.
+fn last_stmt_in_line(program: &Program, pos: &KCLPos) -> Option<Node<Stmt>> {
+    program
+        .pkgs
+        .values()
+        .flatten()
+        .find(|module| module.filename == pos.filename)?
+        .body
+        .iter()
+        .rev()
+        .find(|stmt| stmt.end_line == pos.line && stmt.get_end_pos().less_equal(pos))
+        .map(|stmt| *stmt.clone())
+}
+
 fn completion_for_import(
     stmt: &ImportStmt,
     _pos: &KCLPos,
@@ -113,7 +1475,11 @@ fn completion_for_import(
     Some(into_completion_items(&items).into())
 }
 
-fn get_completion_items(expr: &Expr, prog_scope: &ProgramScope) -> IndexSet<String> {
+fn get_completion_items(
+    expr: &Expr,
+    prog_scope: &ProgramScope,
+    budget: &CompletionBudget,
+) -> IndexSet<KCLCompletionItem> {
     let mut items = IndexSet::new();
     match expr {
         Expr::Identifier(id) => {
@@ -124,7 +1490,7 @@ fn get_completion_items(expr: &Expr, prog_scope: &ProgramScope) -> IndexSet<Stri
                     items.extend(
                         get_system_module_members(name.as_str())
                             .iter()
-                            .map(|s| s.to_string()),
+                            .map(|s| s.to_string().into()),
                     )
                 }
                 // user module
@@ -134,10 +1500,23 @@ fn get_completion_items(expr: &Expr, prog_scope: &ProgramScope) -> IndexSet<Stri
                 {
                     let scope = scope.borrow();
                     for (name, obj) in &scope.elems {
-                        if obj.borrow().ty.is_module() {
+                        if budget.is_exceeded() {
+                            break;
+                        }
+                        let obj = obj.borrow();
+                        if obj.ty.is_module() {
                             continue;
                         }
-                        items.insert(name.clone());
+                        let kind = match (&obj.kind, &obj.ty.kind) {
+                            (ScopeObjectKind::Definition, TypeKind::Schema(_)) => {
+                                Some(KCLCompletionItemKind::Schema)
+                            }
+                            _ => None,
+                        };
+                        items.insert(KCLCompletionItem {
+                            label: name.clone(),
+                            kind,
+                        });
                     }
                 }
                 return items;
@@ -157,14 +1536,17 @@ fn get_completion_items(expr: &Expr, prog_scope: &ProgramScope) -> IndexSet<Stri
                             kclvm_sema::ty::TypeKind::Str => {
                                 let binding = STRING_MEMBER_FUNCTIONS;
                                 for k in binding.keys() {
-                                    items.insert(format!("{}{}", k, "()"));
+                                    items.insert(format!("{}{}", k, "()").into());
                                 }
                             }
                             // schema attrs
                             kclvm_sema::ty::TypeKind::Schema(schema) => {
                                 for k in schema.attrs.keys() {
+                                    if budget.is_exceeded() {
+                                        break;
+                                    }
                                     if k != "__settings__" {
-                                        items.insert(k.clone());
+                                        items.insert(k.clone().into());
                                     }
                                 }
                             }
@@ -178,13 +1560,13 @@ fn get_completion_items(expr: &Expr, prog_scope: &ProgramScope) -> IndexSet<Stri
             }
         }
         Expr::Selector(select_expr) => {
-            let res = get_completion_items(&select_expr.value.node, prog_scope);
+            let res = get_completion_items(&select_expr.value.node, prog_scope, budget);
             items.extend(res);
         }
         Expr::StringLit(_) => {
             let binding = STRING_MEMBER_FUNCTIONS;
             for k in binding.keys() {
-                items.insert(format!("{}{}", k, "()"));
+                items.insert(format!("{}{}", k, "()").into());
             }
         }
         _ => {}
@@ -1,3 +1,316 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use kclvm_config::modfile::{KCL_FILE_SUFFIX, KCL_MOD_FILE};
+use lsp_types::DiagnosticSeverity;
+use serde::{Deserialize, Serialize};
+
+/// The default max size in bytes of the files to be analyzed, which is 10 MiB.
+pub const DEFAULT_MAX_FILE_SIZE: usize = 10 * 1024 * 1024;
+
+/// The default interval in milliseconds of polling the workspace folders watched by the
+/// server, which is 1 second.
+pub const DEFAULT_WATCHER_POLL_INTERVAL_MS: u64 = 1000;
+
 /// The configuration used by the language server.
-#[derive(Debug, Clone, Default)]
-pub struct Config {}
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// The file extensions of the files treated as KCL files, default is `[".k"]`.
+    pub kcl_extensions: Vec<String>,
+    /// The max size in bytes of the files to be analyzed. The files above the limit are
+    /// not analyzed, but can still be formatted.
+    pub max_file_size: usize,
+    /// The format of the messages logged to the client.
+    pub log_format: LogFormat,
+    /// The entry files compiled eagerly on startup, so that the first request on them does
+    /// not pay the compile latency. The relative paths are relative to the first workspace
+    /// folder.
+    pub warmup_entries: Vec<String>,
+    /// Whether to publish the diagnostics of the files which are not opened but imported
+    /// by the changed files, default is false.
+    pub publish_diagnostics_for_dependencies: bool,
+    /// How the diagnostics are delivered to the client, default is `auto`, which pulls the
+    /// diagnostics if the client supports the `textDocument/diagnostic` request.
+    pub diagnostics_mode: DiagnosticsMode,
+    /// Whether to complete the keys used by the other plain dicts in the same file for a
+    /// plain dict, e.g., `{str:str}`, which has no schema attrs to complete, default is
+    /// false.
+    pub suggest_existing_keys: bool,
+    /// Whether to complete the snippets scaffolding the configs of the schemas with their
+    /// required attrs at an empty top level assignment, e.g., `server = `, if the client
+    /// supports the snippets, default is false.
+    pub suggest_schema_configs: bool,
+    /// Whether to compute and send the diagnostics inline when handling each event instead
+    /// of in the thread pool, which makes the delivery deterministic for the tests and the
+    /// CI, default is false.
+    pub synchronous_diagnostics: bool,
+    /// Whether to include the symbols of the test files, i.e., `*_test.k`, and the hidden
+    /// files prefixed by `_` in the document symbols and the workspace symbols, default is
+    /// true.
+    pub include_test_symbols: bool,
+    /// The severities of the diagnostics overriding the default ones, keyed by the codes
+    /// of the diagnostics, e.g., `{"UnusedImportWarning": 1}` reports the unused imports
+    /// as errors.
+    pub severity_overrides: HashMap<String, DiagnosticSeverity>,
+    /// The glob patterns of the files watched by the client, which are registered on
+    /// initialized, e.g., `**/*.yaml` for the data inputs. Default is empty, which watches
+    /// the files of `kcl_extensions` and `kcl.mod`, i.e., `**/*.k` and `**/kcl.mod`.
+    pub watcher_globs: Vec<String>,
+    /// The time budget in milliseconds of building the completion items, after which the
+    /// items built so far are returned as an incomplete list and the client re-requests
+    /// the completion. Default is none, which builds all the items.
+    pub completion_budget_ms: Option<u64>,
+    /// Whether to warn about the lines indented with the mixed tabs and spaces, which
+    /// can be fixed by the quick fix, default is false.
+    pub lint_indentation: bool,
+    /// The timeout in milliseconds of the requests handled in the thread pool, after which
+    /// the request fails and the late response of its task is dropped. Default is none,
+    /// which waits for the tasks to finish.
+    pub request_timeout_ms: Option<u64>,
+    /// Whether to append the number of the references to the hovers of the schemas and
+    /// the schema attrs, which is counted from the cached word index of the workspace
+    /// folders for the clients without the code lens, default is false.
+    pub hover_reference_count: bool,
+    /// Whether to handle the requests and compute the diagnostics inline on the main loop
+    /// instead of in the thread pool, for the embedders which need the deterministic and
+    /// single-threaded operation, default is false. It implies `synchronous_diagnostics`.
+    pub single_threaded: bool,
+    /// The idle time in milliseconds of the event loop after which the files whose dbs are
+    /// invalidated by the changed watched files are recompiled in the background, so that
+    /// the later requests on them are instant. Default is none, which recompiles them when
+    /// they are requested.
+    pub idle_flush_ms: Option<u64>,
+    /// Whether to relate the diagnostics in the files generated from the templates to their
+    /// locations in the templates by the `# sourcemap: <template>:<line>` comments, default
+    /// is false.
+    pub sourcemap_diagnostics: bool,
+    /// Whether to append the direct subtypes to the hovers of the schemas, i.e., the
+    /// schemas inheriting from them in the compiled programs, default is false.
+    pub hover_show_subtypes: bool,
+    /// Whether to rebuild the word index of the workspace folders from the files
+    /// periodically and log the words whose locations diverge from the incrementally
+    /// updated index, for debugging the incremental updates, default is false.
+    pub verify_word_index: bool,
+    /// How the schemas completed as the calls are inserted with the delimiters closed and
+    /// the cursor inside them if the client supports the snippets, default is `call`.
+    pub schema_completion_style: SchemaCompletionStyle,
+    /// The interval in milliseconds of polling the workspace folders watched by the server
+    /// for the clients which can't watch the files, default is 1000. The polling backs off
+    /// up to 8 times the interval while the files are unchanged.
+    pub watcher_poll_interval_ms: u64,
+}
+
+/// The format of the messages logged to the client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Plain text messages.
+    #[default]
+    Text,
+    /// Structured JSON records including the timestamp, level, method and duration,
+    /// which can be ingested by the observability tooling.
+    Json,
+}
+
+/// How the diagnostics are delivered to the client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticsMode {
+    /// The server publishes the diagnostics by the `textDocument/publishDiagnostics`
+    /// notifications.
+    Push,
+    /// The client pulls the diagnostics by the `textDocument/diagnostic` requests.
+    Pull,
+    /// Pull if the client supports the pull diagnostics, otherwise push. It is resolved
+    /// with the client capabilities on initialize, and falls back to push if unresolved.
+    #[default]
+    Auto,
+}
+
+/// How the schemas completed as the calls are inserted, e.g., `Server` after `pkg.`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaCompletionStyle {
+    /// The call with the parentheses, e.g., `Server()`, which triggers the signature help.
+    #[default]
+    Call,
+    /// The config with the braces, e.g., `Server {}`.
+    Config,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            kcl_extensions: vec![KCL_FILE_SUFFIX.to_string()],
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            log_format: LogFormat::default(),
+            warmup_entries: vec![],
+            publish_diagnostics_for_dependencies: false,
+            diagnostics_mode: DiagnosticsMode::default(),
+            suggest_existing_keys: false,
+            suggest_schema_configs: false,
+            synchronous_diagnostics: false,
+            include_test_symbols: true,
+            severity_overrides: HashMap::new(),
+            watcher_globs: vec![],
+            completion_budget_ms: None,
+            lint_indentation: false,
+            request_timeout_ms: None,
+            hover_reference_count: false,
+            single_threaded: false,
+            idle_flush_ms: None,
+            sourcemap_diagnostics: false,
+            hover_show_subtypes: false,
+            verify_word_index: false,
+            schema_completion_style: SchemaCompletionStyle::default(),
+            watcher_poll_interval_ms: DEFAULT_WATCHER_POLL_INTERVAL_MS,
+        }
+    }
+}
+
+impl Config {
+    /// Deserializes the config from the initialization options of the client. The invalid
+    /// fields, e.g., a string for `max_file_size`, fall back to their defaults, the unknown
+    /// fields are ignored, and the errors describing them are returned to be reported to
+    /// the client.
+    pub fn from_initialization_options(options: serde_json::Value) -> (Config, Vec<String>) {
+        let (valid_fields, errors) = valid_fields(options);
+        let config =
+            serde_json::from_value(serde_json::Value::Object(valid_fields)).unwrap_or_default();
+        (config, errors)
+    }
+
+    /// Merges the fields present in the options into the config, e.g., the config pulled
+    /// by the `workspace/configuration` request, where the absent fields keep their values.
+    /// The invalid fields keep their values too, the unknown fields are ignored, and the
+    /// errors describing them are returned to be reported to the client.
+    pub fn merge(&mut self, options: serde_json::Value) -> Vec<String> {
+        let (valid_fields, mut errors) = valid_fields(options);
+        let mut fields = match serde_json::to_value(&*self) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => return errors,
+        };
+        fields.extend(valid_fields);
+        match serde_json::from_value(serde_json::Value::Object(fields)) {
+            Ok(config) => *self = config,
+            Err(err) => errors.push(err.to_string()),
+        }
+        errors
+    }
+
+    /// Whether the diagnostics are computed and sent inline when handling each event.
+    pub fn synchronous_diagnostics(&self) -> bool {
+        self.synchronous_diagnostics || self.single_threaded
+    }
+
+    /// Whether the file should be analyzed as a KCL file.
+    pub fn is_kcl_file(&self, file: &str) -> bool {
+        self.kcl_extensions
+            .iter()
+            .any(|ext| file.ends_with(ext.as_str()))
+    }
+
+    /// Whether the file is a test file, e.g., `main_test.k`, or a hidden file, e.g.,
+    /// `_main.k`, which are skipped by the compiler when loading the imported packages.
+    pub fn is_test_file(&self, file: &str) -> bool {
+        let name = match Path::new(file).file_name().and_then(|name| name.to_str()) {
+            Some(name) => name,
+            None => return false,
+        };
+        name.starts_with('_')
+            || self.kcl_extensions.iter().any(|ext| {
+                name.strip_suffix(ext.as_str())
+                    .map_or(false, |stem| stem.ends_with("_test"))
+            })
+    }
+
+    /// Whether the symbols of the file are returned by the document symbols and the
+    /// workspace symbols.
+    pub fn includes_symbols_of(&self, file: &str) -> bool {
+        self.include_test_symbols || !self.is_test_file(file)
+    }
+
+    /// Returns the glob patterns of the KCL files by the extensions, e.g., `**/*.k`.
+    pub fn kcl_file_globs(&self) -> Vec<String> {
+        self.kcl_extensions
+            .iter()
+            .map(|ext| format!("**/*{}", ext))
+            .collect()
+    }
+
+    /// Returns the glob patterns of the files watched by the client.
+    pub fn file_watcher_globs(&self) -> Vec<String> {
+        if !self.watcher_globs.is_empty() {
+            return self.watcher_globs.clone();
+        }
+        let mut globs = self.kcl_file_globs();
+        globs.push(format!("**/{}", KCL_MOD_FILE));
+        globs
+    }
+
+    /// Whether the file of the size is too large to be analyzed.
+    pub fn exceeds_max_file_size(&self, size: usize) -> bool {
+        size > self.max_file_size
+    }
+
+    /// Resolves the `auto` diagnostics mode with whether the client supports the pull
+    /// diagnostics.
+    pub fn resolve_diagnostics_mode(&mut self, pull_supported: bool) {
+        if self.diagnostics_mode == DiagnosticsMode::Auto {
+            self.diagnostics_mode = if pull_supported {
+                DiagnosticsMode::Pull
+            } else {
+                DiagnosticsMode::Push
+            };
+        }
+    }
+
+    /// Whether the client pulls the diagnostics, and the diagnostics are not published.
+    pub fn pull_diagnostics(&self) -> bool {
+        self.diagnostics_mode == DiagnosticsMode::Pull
+    }
+}
+
+/// Returns the fields of the options which are valid config fields, and the errors
+/// describing the invalid ones and the unknown ones, e.g., the misspelled `maxFileSize`.
+fn valid_fields(
+    options: serde_json::Value,
+) -> (serde_json::Map<String, serde_json::Value>, Vec<String>) {
+    let fields = match options {
+        serde_json::Value::Object(fields) => fields,
+        serde_json::Value::Null => return (serde_json::Map::new(), vec![]),
+        other => {
+            return (
+                serde_json::Map::new(),
+                vec![format!("expected an object of the config, got `{}`", other)],
+            )
+        }
+    };
+    let known_fields = match serde_json::to_value(Config::default()) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    };
+    let mut errors = vec![];
+    let valid_fields = fields
+        .into_iter()
+        .filter(|(name, value)| {
+            if !known_fields.contains_key(name) {
+                errors.push(format!("unknown `{}`", name));
+                return false;
+            }
+            let field = serde_json::Value::Object(serde_json::Map::from_iter([(
+                name.clone(),
+                value.clone(),
+            )]));
+            match serde_json::from_value::<Config>(field) {
+                Ok(_) => true,
+                Err(err) => {
+                    errors.push(format!("invalid `{}`: {}", name, err));
+                    false
+                }
+            }
+        })
+        .collect();
+    (valid_fields, errors)
+}
@@ -1,3 +1,155 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use indexmap::{IndexMap, IndexSet};
+use kclvm_ast::ast::Program;
+use kclvm_config::modfile::KCL_MOD_FILE;
+use kclvm_config::settings::DEFAULT_SETTING_FILE;
+use kclvm_error::Diagnostic;
+use kclvm_error::Position as KCLPos;
+use lsp_types::{CompletionResponse, GotoDefinitionResponse, SymbolInformation};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+use crate::hover::HoverInfo;
+
+/// The max number of the positions whose resolutions are cached in a db. The resolution of
+/// the least recently requested position is dropped when the limit is reached.
+pub(crate) const MAX_RESOLVED_POSITIONS: usize = 64;
+
+/// The results of resolving the program at a position, each of which is computed on the
+/// first request of its kind and shared by the later requests at the same position, e.g.,
+/// the goto definition following a hover when the cursor stops on a symbol.
+#[derive(Debug, Default)]
+pub(crate) struct Resolution {
+    /// The goto definition result.
+    pub definition: OnceCell<Option<GotoDefinitionResponse>>,
+    /// The descriptions of the element for the hover.
+    pub hover: OnceCell<Vec<HoverInfo>>,
+    /// The name and the declaration position of the schema or the schema attr whose
+    /// references are counted in the hover.
+    pub reference_target: OnceCell<Option<(String, KCLPos)>>,
+    /// The completion result without a trigger character.
+    pub completion: OnceCell<Option<CompletionResponse>>,
+}
+
 /// Holds the result of the compile
-#[derive(Default, Clone)]
-pub(crate) struct AnalysisDatabase {}
+#[derive(Debug, Clone)]
+pub(crate) struct AnalysisDatabase {
+    /// The loaded program before resolving. The program scope is not stored because it
+    /// can't be shared between the request threads, see `util::compile_db`.
+    pub prog: Program,
+    /// The diagnostics reported when loading the program.
+    pub diags: IndexSet<Diagnostic>,
+    /// The files which the program is compiled from, i.e., the file itself, the files of
+    /// its package and the files of the imported packages.
+    pub deps: HashSet<String>,
+    /// The resolutions keyed by the positions in the order of the requests, which are shared
+    /// by the clones of the db and dropped with the db when it is replaced by a new compile,
+    /// or cleared when the config, e.g., the completion style, is changed.
+    resolution_cache: Arc<Mutex<IndexMap<KCLPos, Arc<Resolution>>>>,
+    /// The number of the resolutions served from the cache.
+    resolution_cache_hits: Arc<AtomicUsize>,
+    /// The top level symbols of the packages in the program, which are resolved once and
+    /// matched with the queries of the workspace symbols.
+    symbols: Arc<OnceCell<Vec<SymbolInformation>>>,
+}
+
+impl AnalysisDatabase {
+    pub(crate) fn new(prog: Program, diags: IndexSet<Diagnostic>) -> Self {
+        let deps = prog
+            .pkgs
+            .values()
+            .flatten()
+            .map(|module| module.filename.clone())
+            .collect();
+        Self {
+            prog,
+            diags,
+            deps,
+            resolution_cache: Arc::default(),
+            resolution_cache_hits: Arc::default(),
+            symbols: Arc::default(),
+        }
+    }
+
+    /// Returns the result of the kind selected by `kind` at the position, which is computed
+    /// by `resolve` on the first request and served from the cache later. The concurrent
+    /// requests of the same kind wait for the first one rather than resolving again.
+    pub(crate) fn resolve<T: Clone>(
+        &self,
+        pos: &KCLPos,
+        kind: fn(&Resolution) -> &OnceCell<T>,
+        resolve: impl FnOnce() -> T,
+    ) -> T {
+        let res = {
+            let mut cache = self.resolution_cache.lock();
+            // The position is moved to the end as the most recently requested one.
+            let res = cache.shift_remove(pos).unwrap_or_default();
+            cache.insert(pos.clone(), res.clone());
+            if cache.len() > MAX_RESOLVED_POSITIONS {
+                cache.shift_remove_index(0);
+            }
+            res
+        };
+        let cell = kind(&res);
+        if let Some(value) = cell.get() {
+            self.resolution_cache_hits.fetch_add(1, Ordering::Relaxed);
+            return value.clone();
+        }
+        cell.get_or_init(resolve).clone()
+    }
+
+    /// Drops the cached resolutions, e.g., the completion items built with the old config.
+    pub(crate) fn clear_resolutions(&self) {
+        self.resolution_cache.lock().clear();
+    }
+
+    /// Returns the top level symbols of the packages in the program, which are resolved by
+    /// `resolve` on the first request.
+    pub(crate) fn symbols(
+        &self,
+        resolve: impl FnOnce() -> Vec<SymbolInformation>,
+    ) -> &[SymbolInformation] {
+        self.symbols.get_or_init(resolve)
+    }
+
+    /// Returns the resolution at the position, if any.
+    #[cfg(test)]
+    pub(crate) fn resolution(&self, pos: &KCLPos) -> Option<Arc<Resolution>> {
+        self.resolution_cache.lock().get(pos).cloned()
+    }
+
+    /// Returns the number of the resolutions served from the cache.
+    #[cfg(test)]
+    pub(crate) fn resolution_cache_hits(&self) -> usize {
+        self.resolution_cache_hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of the positions resolved with the db.
+    #[cfg(test)]
+    pub(crate) fn resolved_positions(&self) -> usize {
+        self.resolution_cache.lock().len()
+    }
+
+    /// Whether the db is still valid after the files changed, i.e., none of the changed
+    /// files is a dependency, a new file in the package directory of a dependency or a
+    /// compile settings file, e.g., `kcl.mod`, in an ancestor directory of a dependency.
+    pub(crate) fn is_valid(&self, changed_files: &HashSet<String>) -> bool {
+        !changed_files.iter().any(|changed| {
+            let changed = Path::new(changed);
+            let is_settings_file = changed.file_name().map_or(false, |name| {
+                name == KCL_MOD_FILE || name == DEFAULT_SETTING_FILE
+            });
+            self.deps.iter().any(|dep| {
+                let dep = Path::new(dep);
+                match changed.parent() {
+                    Some(dir) if is_settings_file => dep.starts_with(dir),
+                    dir => dep == changed || dep.parent() == dir,
+                }
+            })
+        })
+    }
+}
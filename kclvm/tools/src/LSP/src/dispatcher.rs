@@ -96,7 +96,8 @@ impl<'a> RequestDispatcher<'a> {
         Ok(self)
     }
 
-    /// Try to dispatch the event as the given Request type on the thread pool.
+    /// Try to dispatch the event as the given Request type on the thread pool, or on the
+    /// current thread if the server is single-threaded.
     pub fn on<R>(
         &mut self,
         compute_response_fn: fn(
@@ -114,6 +115,16 @@ impl<'a> RequestDispatcher<'a> {
             Some(it) => it,
             None => return Ok(self),
         };
+        if self.state.config.single_threaded {
+            let result = compute_response_fn(
+                self.state.snapshot(),
+                params,
+                self.state.task_sender.clone(),
+            );
+            let _result = self.state.respond(result_to_response::<R>(id, result));
+            return Ok(self);
+        }
+        self.state.register_deadline(&id);
 
         self.state.thread_pool.execute({
             let snapshot = self.state.snapshot();
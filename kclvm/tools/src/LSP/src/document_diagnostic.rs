@@ -0,0 +1,79 @@
+//! The pull diagnostics, i.e., the `textDocument/diagnostic` request of LSP 3.17, which
+//! is not available in the version of `lsp_types`, so the request and its types are
+//! defined here.
+
+use std::collections::HashMap;
+
+use kclvm_error::Diagnostic as KCLDiagnostic;
+use lsp_types::{Diagnostic, DiagnosticSeverity, TextDocumentIdentifier};
+use serde::{Deserialize, Serialize};
+
+use crate::sourcemap::SourceMap;
+use crate::suppression::drop_suppressed;
+use crate::to_lsp::kcl_diag_to_lsp_diags;
+
+/// The `textDocument/diagnostic` request sent by the clients which pull the diagnostics.
+pub(crate) enum DocumentDiagnosticRequest {}
+
+impl lsp_types::request::Request for DocumentDiagnosticRequest {
+    type Params = DocumentDiagnosticParams;
+    type Result = FullDocumentDiagnosticReport;
+    const METHOD: &'static str = "textDocument/diagnostic";
+}
+
+/// The parameters of the `textDocument/diagnostic` request.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DocumentDiagnosticParams {
+    /// The text document.
+    pub text_document: TextDocumentIdentifier,
+    /// The additional identifier provided during registration.
+    pub identifier: Option<String>,
+    /// The result id of a previous response if provided.
+    pub previous_result_id: Option<String>,
+}
+
+/// The full diagnostic report of a document, the only kind of the reports returned by
+/// the server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FullDocumentDiagnosticReport {
+    /// Always `full`.
+    pub kind: String,
+    /// The diagnostics of the document.
+    pub items: Vec<Diagnostic>,
+}
+
+/// Returns the full diagnostic report of the file with the diagnostics of its program,
+/// excluding the ones suppressed by the comments in the source of the file.
+pub(crate) fn document_diagnostic<'a>(
+    file: &str,
+    source: &str,
+    diags: impl Iterator<Item = &'a KCLDiagnostic>,
+    severity_overrides: &HashMap<String, DiagnosticSeverity>,
+    sourcemap: Option<&SourceMap>,
+) -> FullDocumentDiagnosticReport {
+    let items = diags
+        .flat_map(|diag| kcl_diag_to_lsp_diags(diag, file, severity_overrides, sourcemap))
+        .collect();
+    FullDocumentDiagnosticReport {
+        kind: "full".to_string(),
+        items: drop_suppressed(items, source),
+    }
+}
+
+/// The `diagnosticProvider` server capability advertised when the diagnostics are pulled.
+pub(crate) fn diagnostic_provider() -> serde_json::Value {
+    serde_json::json!({
+        "interFileDependencies": true,
+        "workspaceDiagnostics": false,
+    })
+}
+
+/// Whether the client supports the pull diagnostics, i.e., the initialize params declare
+/// the `textDocument.diagnostic` client capability.
+pub(crate) fn pull_diagnostics_supported(initialize_params: &serde_json::Value) -> bool {
+    initialize_params
+        .pointer("/capabilities/textDocument/diagnostic")
+        .map_or(false, |capability| !capability.is_null())
+}
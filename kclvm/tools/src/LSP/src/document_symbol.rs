@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use std::path::Path;
 
-use kclvm_ast::ast::Program;
+use kclvm_ast::ast::{ConfigEntry, Expr, Node, Program, SchemaStmt, Stmt};
+use kclvm_ast::pos::GetPos;
 use kclvm_ast::MAIN_PKG;
 use kclvm_sema::resolver::scope::ProgramScope;
 use kclvm_sema::resolver::scope::Scope;
@@ -10,49 +12,232 @@ use kclvm_sema::resolver::scope::ScopeObjectKind;
 use lsp_types::Range;
 use lsp_types::{DocumentSymbol, DocumentSymbolResponse, SymbolKind};
 
+use crate::from_lsp::canonicalize;
 use crate::to_lsp::lsp_pos;
+use crate::util::{parse_param_and_compile, Param};
+
+/// The max depth of the nested keys of the dict and list data in the outline.
+const MAX_DATA_SYMBOL_DEPTH: usize = 8;
+
+/// Returns the document symbols of the file without a language server, e.g., for the
+/// external outline generators.
+pub fn document_symbols(file: &str) -> anyhow::Result<Option<DocumentSymbolResponse>> {
+    let (program, prog_scope, _) = parse_param_and_compile(
+        Param {
+            file: file.to_string(),
+        },
+        None,
+    )?;
+    Ok(document_symbol(file, &program, &prog_scope))
+}
 
 pub(crate) fn document_symbol(
     file: &str,
-    _program: &Program,
+    program: &Program,
     prog_scope: &ProgramScope,
 ) -> Option<lsp_types::DocumentSymbolResponse> {
     let mut documentsymbols: Vec<DocumentSymbol> = vec![];
     let scope = prog_scope.scope_map.get(MAIN_PKG).unwrap().borrow();
+    // The file and the files of the scope objects are canonicalized once and compared, the
+    // latter of which are the files of the package.
+    let file = canonicalize(Path::new(file));
+    let mut in_file: HashMap<String, bool> = HashMap::new();
+    let mut is_in_file = |filename: &str| {
+        *in_file
+            .entry(filename.to_string())
+            .or_insert_with(|| canonicalize(Path::new(filename)) == file)
+    };
     // Get variable in scope
     for obj in scope.elems.values().filter(|obj| {
-        if let Ok(canonicalized_path) = Path::new(&obj.borrow().start.filename).canonicalize() {
-            // skip schema definition
-            canonicalized_path.eq(Path::new(file))
-                && obj.borrow().kind != ScopeObjectKind::Definition
-        } else {
-            false
-        }
+        // skip schema definition
+        is_in_file(&obj.borrow().start.filename) && obj.borrow().kind != ScopeObjectKind::Definition
     }) {
-        documentsymbols.push(scope_obj_to_document_symbol(obj.borrow().clone()));
+        let mut symbol = scope_obj_to_document_symbol(obj.borrow().clone());
+        // The keys of the dict and list data are nested in the variable, so that the data
+        // files without schemas can be navigated, and so are the declarations of the lambda.
+        if let Some(Stmt::Assign(assign_stmt)) = program
+            .pos_to_stmt(&obj.borrow().start)
+            .map(|stmt| stmt.node)
+        {
+            symbol.children = data_symbols(&assign_stmt.value, MAX_DATA_SYMBOL_DEPTH)
+                .or_else(|| lambda_symbols(&assign_stmt.value, &scope));
+        }
+        documentsymbols.push(symbol);
     }
     // Get schema definition in scope
-    for child in scope.children.iter().filter(|child| {
-        if let Ok(canonicalized_path) = Path::new(&child.borrow().start.filename).canonicalize() {
-            canonicalized_path.eq(Path::new(file))
-        } else {
-            false
-        }
-    }) {
-        if let Some(symbol) = schema_scope_to_document_symbol(child.borrow().clone()) {
+    for child in scope
+        .children
+        .iter()
+        .filter(|child| is_in_file(&child.borrow().start.filename))
+    {
+        if let Some(symbol) = schema_scope_to_document_symbol(child.borrow().clone(), program) {
             documentsymbols.push(symbol)
         }
     }
     Some(DocumentSymbolResponse::Nested(documentsymbols))
 }
 
+/// Returns the symbols of the keys of the dict data, and the elements of the list data
+/// which contain keys, e.g., `[0]` for the first element.
+fn data_symbols(value: &Node<Expr>, depth: usize) -> Option<Vec<DocumentSymbol>> {
+    if depth == 0 {
+        return None;
+    }
+    let symbols: Vec<DocumentSymbol> = match &value.node {
+        Expr::Config(config_expr) => config_expr
+            .items
+            .iter()
+            .filter_map(|entry| config_entry_symbol(entry, depth))
+            .collect(),
+        Expr::List(list_expr) => list_expr
+            .elts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, elt)| {
+                let children = data_symbols(elt, depth - 1)?;
+                Some(data_symbol(
+                    format!("[{}]", i),
+                    SymbolKind::OBJECT,
+                    &**elt,
+                    &**elt,
+                    Some(children),
+                ))
+            })
+            .collect(),
+        _ => return None,
+    };
+    if symbols.is_empty() {
+        None
+    } else {
+        Some(symbols)
+    }
+}
+
+/// Returns the symbol of the config entry whose key is an identifier or a string.
+fn config_entry_symbol(entry: &Node<ConfigEntry>, depth: usize) -> Option<DocumentSymbol> {
+    let key = entry.node.key.as_ref()?;
+    let name = match &key.node {
+        Expr::Identifier(id) => id
+            .names
+            .iter()
+            .map(|name| name.node.clone())
+            .collect::<Vec<String>>()
+            .join("."),
+        Expr::StringLit(string_lit) => string_lit.value.clone(),
+        _ => return None,
+    };
+    let value = &entry.node.value;
+    let kind = match &value.node {
+        Expr::Config(_) => SymbolKind::OBJECT,
+        Expr::List(_) => SymbolKind::ARRAY,
+        _ => SymbolKind::KEY,
+    };
+    let children = data_symbols(value, depth - 1);
+    Some(data_symbol(name, kind, &**key, &**value, children))
+}
+
+#[allow(deprecated)]
+fn data_symbol<K, V>(
+    name: String,
+    kind: SymbolKind,
+    key: &Node<K>,
+    value: &Node<V>,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        kind,
+        range: Range {
+            start: lsp_pos(&key.get_pos()),
+            end: lsp_pos(&value.get_end_pos()),
+        },
+        selection_range: Range {
+            start: lsp_pos(&key.get_pos()),
+            end: lsp_pos(&key.get_end_pos()),
+        },
+        detail: None,
+        tags: None,
+        children,
+        deprecated: None,
+    }
+}
+
+/// Returns the symbols of the local variables in the body of the lambda value, where the
+/// lambdas nested in the body are nested recursively, e.g., `join` and its locals in
+/// `make = lambda { join = lambda { sep = "-" } }`. The scope is the enclosing scope of
+/// the lambda, whose child lambda scope has the types of the local variables.
+fn lambda_symbols(value: &Node<Expr>, scope: &Scope) -> Option<Vec<DocumentSymbol>> {
+    let lambda_expr = match &value.node {
+        Expr::Lambda(lambda_expr) => lambda_expr,
+        _ => return None,
+    };
+    let pos = value.get_pos();
+    let lambda_scope = scope.children.iter().find(|child| {
+        let child = child.borrow();
+        matches!(child.kind, ScopeKind::Lambda)
+            && child.start.less_equal(&pos)
+            && pos.less_equal(&child.end)
+    });
+    let mut symbols = vec![];
+    for stmt in &lambda_expr.body {
+        if let Stmt::Assign(assign_stmt) = &stmt.node {
+            for target in &assign_stmt.targets {
+                if target.node.names.len() != 1 {
+                    continue;
+                }
+                let name = target.node.names[0].node.clone();
+                let ty = lambda_scope.and_then(|lambda_scope| {
+                    let lambda_scope = lambda_scope.borrow();
+                    let obj = lambda_scope.elems.get(&name)?;
+                    let ty = obj.borrow().ty.ty_str();
+                    Some(ty)
+                });
+                let children = lambda_scope.and_then(|lambda_scope| {
+                    lambda_symbols(&assign_stmt.value, &lambda_scope.borrow())
+                });
+                let mut symbol =
+                    data_symbol(name, SymbolKind::VARIABLE, &**target, &**target, children);
+                symbol.detail = ty;
+                symbols.push(symbol);
+            }
+        }
+    }
+    if symbols.is_empty() {
+        None
+    } else {
+        Some(symbols)
+    }
+}
+
+/// Returns the value of the schema attr declared in the schema stmt by the name.
+fn schema_attr_value<'a>(schema_stmt: &'a SchemaStmt, name: &str) -> Option<&'a Node<Expr>> {
+    schema_stmt.body.iter().find_map(|stmt| match &stmt.node {
+        Stmt::SchemaAttr(schema_attr) if schema_attr.name.node == name => {
+            schema_attr.value.as_deref()
+        }
+        Stmt::Assign(assign_stmt)
+            if assign_stmt
+                .targets
+                .iter()
+                .any(|target| target.node.get_name() == name) =>
+        {
+            Some(&*assign_stmt.value)
+        }
+        _ => None,
+    })
+}
+
 #[allow(deprecated)]
-fn schema_scope_to_document_symbol(scope: Scope) -> Option<DocumentSymbol> {
+fn schema_scope_to_document_symbol(scope: Scope, program: &Program) -> Option<DocumentSymbol> {
     if let ScopeKind::Schema(schema_name) = &scope.kind {
         let range = Range {
             start: lsp_pos(&scope.start),
             end: lsp_pos(&scope.end),
         };
+        let schema_stmt = match program.pos_to_stmt(&scope.start).map(|stmt| stmt.node) {
+            Some(Stmt::Schema(schema_stmt)) => Some(schema_stmt),
+            _ => None,
+        };
         Some(DocumentSymbol {
             name: schema_name.clone(),
             kind: SymbolKind::STRUCT,
@@ -62,7 +247,15 @@ fn schema_scope_to_document_symbol(scope: Scope) -> Option<DocumentSymbol> {
                 scope
                     .elems
                     .iter()
-                    .map(|(_, obj)| scope_obj_to_document_symbol(obj.borrow().clone()))
+                    .map(|(name, obj)| {
+                        let mut symbol = scope_obj_to_document_symbol(obj.borrow().clone());
+                        // The declarations of the lambda attrs are nested in the attrs.
+                        symbol.children = schema_stmt
+                            .as_ref()
+                            .and_then(|schema_stmt| schema_attr_value(schema_stmt, name))
+                            .and_then(|value| lambda_symbols(value, &scope));
+                        symbol
+                    })
                     .collect(),
             ),
             detail: Some("schema".to_string()),
@@ -93,7 +286,7 @@ fn scope_obj_to_document_symbol(obj: ScopeObject) -> DocumentSymbol {
     }
 }
 
-fn scope_obj_kind_to_document_symbol_kind(kind: ScopeObjectKind) -> SymbolKind {
+pub(crate) fn scope_obj_kind_to_document_symbol_kind(kind: ScopeObjectKind) -> SymbolKind {
     match kind {
         ScopeObjectKind::Variable => SymbolKind::VARIABLE,
         ScopeObjectKind::Attribute => SymbolKind::PROPERTY,
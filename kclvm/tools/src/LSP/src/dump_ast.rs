@@ -0,0 +1,61 @@
+//! Dump the AST of a file which the language server sees, i.e., the module compiled with
+//! the file contents in the vfs, as the pretty-printed JSON for debugging the parser.
+
+use kclvm_ast::ast::Program;
+use serde::Deserialize;
+
+/// The default max size in bytes of the dumped AST.
+const DEFAULT_MAX_DUMP_SIZE: usize = 64 * 1024;
+
+/// The arguments of the `kcl.dumpAst` command.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct DumpAstArgs {
+    /// The path of the KCL file.
+    pub file: String,
+    /// The JSON pointer of the dumped node in the module of the file, e.g., `/body/0` for
+    /// the first statement. Default is the whole module.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// The max size in bytes of the output, and the output beyond the size is truncated.
+    #[serde(default)]
+    pub max_size: Option<usize>,
+}
+
+/// Dumps the AST node at the path in the module of the file in the program.
+pub(crate) fn dump_ast(program: &Program, args: &DumpAstArgs) -> anyhow::Result<String> {
+    let module = program
+        .pkgs
+        .values()
+        .flatten()
+        .find(|module| module.filename == args.file)
+        .ok_or_else(|| anyhow::anyhow!("The file {} is not compiled", args.file))?;
+    let value = serde_json::to_value(module)?;
+    let node = match &args.path {
+        Some(path) => value
+            .pointer(path)
+            .ok_or_else(|| anyhow::anyhow!("No AST node at the path {}", path))?,
+        None => &value,
+    };
+    let dump = serde_json::to_string_pretty(node)?;
+    Ok(truncate(
+        dump,
+        args.max_size.unwrap_or(DEFAULT_MAX_DUMP_SIZE),
+    ))
+}
+
+/// Truncates the dump to at most `max_size` bytes on a char boundary, and notes the size
+/// of the whole dump at the end.
+fn truncate(dump: String, max_size: usize) -> String {
+    if dump.len() <= max_size {
+        return dump;
+    }
+    let end = (0..=max_size)
+        .rev()
+        .find(|i| dump.is_char_boundary(*i))
+        .unwrap_or(0);
+    format!(
+        "{}\n... (truncated, {} bytes in total)",
+        &dump[..end],
+        dump.len()
+    )
+}
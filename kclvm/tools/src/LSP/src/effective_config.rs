@@ -0,0 +1,74 @@
+//! The effective config of a top level variable, i.e., the `kcl.effectiveConfig` command,
+//! which evaluates the entry of a file and returns the merged value of the variable, e.g.,
+//! the config unified from the base and the environment specific layers of a konfig stack,
+//! without rendering the other variables.
+//!
+//! The entry is evaluated by the runner of the KCL programs in the same way as `kcl run`
+//! with the path selector `-S <variable>`, and the opened files are evaluated with their
+//! contents in the vfs.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use kclvm_ast::ast::CmdExternalPkgSpec;
+use kclvm_driver::lookup_compile_unit;
+use kclvm_parser::ParseSession;
+use kclvm_runner::{exec_program, ExecProgramArgs};
+use parking_lot::RwLock;
+use ra_ap_vfs::Vfs;
+use serde::Deserialize;
+
+use crate::util::load_files_code_from_vfs;
+
+/// The arguments of the `kcl.effectiveConfig` command.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct EffectiveConfigArgs {
+    /// The path of the KCL file whose compile unit is evaluated.
+    pub file: String,
+    /// The name of the top level variable whose value is returned.
+    pub variable: String,
+}
+
+/// Evaluates the compile unit of the file and returns the JSON value of the variable in
+/// the output. An error is returned if the evaluation fails or the variable is not in the
+/// output, e.g., a private variable prefixed by `_`.
+pub(crate) fn effective_config(
+    args: &EffectiveConfigArgs,
+    vfs: Arc<RwLock<Vfs>>,
+) -> anyhow::Result<serde_json::Value> {
+    let (files, opts) = lookup_compile_unit(&args.file, true);
+    let opts = opts.unwrap_or_default();
+    let work_dir = Path::new(&args.file)
+        .parent()
+        .map(|dir| dir.to_string_lossy().to_string());
+    let k_code_list = load_files_code_from_vfs(
+        &files
+            .iter()
+            .map(|file| file.as_str())
+            .collect::<Vec<&str>>(),
+        vfs,
+    )?;
+    let exec_args = ExecProgramArgs {
+        work_dir,
+        k_filename_list: files,
+        k_code_list,
+        external_pkgs: opts
+            .package_maps
+            .into_iter()
+            .map(|(pkg_name, pkg_path)| CmdExternalPkgSpec { pkg_name, pkg_path })
+            .collect(),
+        path_selector: vec![args.variable.clone()],
+        disable_yaml_result: true,
+        ..Default::default()
+    };
+    let res = exec_program(Arc::new(ParseSession::default()), &exec_args)
+        .map_err(|err| anyhow::anyhow!("Failed to evaluate {}: {}", args.file, err))?;
+    if res.json_result.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The variable {} is not in the output of {}",
+            args.variable,
+            args.file
+        ));
+    }
+    Ok(serde_json::from_str(&res.json_result)?)
+}
@@ -0,0 +1,345 @@
+//! Explanations of the diagnostic codes, which are the names of the KCL error and warning
+//! kinds set to the `code` of the lsp diagnostics, e.g., `ImmutableError`.
+
+/// The Markdown explanations with examples keyed by the diagnostic codes.
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "InvalidSyntax",
+        "The code does not follow the KCL grammar, e.g., a missing expression or an unclosed bracket.
+
+```kcl
+a = # error: expected an expression
+b = [1, 2 # error: the list is not closed
+```",
+    ),
+    (
+        "TabError",
+        "The indentation mixes tabs and spaces inconsistently. Use 4 spaces for each indentation level.",
+    ),
+    (
+        "Indentation",
+        "The indentation of the line does not match any outer indentation level.
+
+```kcl
+schema Person:
+    name: str
+  age: int # error: unindent does not match any outer indentation level
+```",
+    ),
+    (
+        "CannotFindModule",
+        "The imported module can not be found in the program root, the external packages or the system modules.
+
+```kcl
+import abc # error: cannot find the module abc
+```
+
+Check the import path, or add the package to the dependencies in `kcl.mod`.",
+    ),
+    (
+        "RecursiveLoad",
+        "The packages import each other recursively, which is not allowed.
+
+```kcl
+# pkg_a/a.k
+import pkg_b
+# pkg_b/b.k
+import pkg_a # error: recursively loading
+```",
+    ),
+    (
+        "FloatOverflow",
+        "The float value is too large to be represented.",
+    ),
+    (
+        "FloatUnderflow",
+        "The float value is too small to be represented.",
+    ),
+    (
+        "IntOverflow",
+        "The integer value exceeds the 64-bit range.
+
+```kcl
+a = 9223372036854775807 + 1 # error: integer overflow
+```",
+    ),
+    (
+        "InvalidDocstring",
+        "The docstring of the schema is not well-formed.",
+    ),
+    (
+        "Deprecated",
+        "The attribute or the schema is decorated with `@deprecated` and should not be used.
+
+```kcl
+schema Person:
+    @deprecated
+    fullName?: str
+
+p = Person {
+    fullName = \"Alice\" # error: fullName was deprecated
+}
+```",
+    ),
+    (
+        "UnKnownDecorator",
+        "The decorator is not a builtin decorator. KCL supports the builtin decorators only, e.g., `@deprecated` and `@info`.",
+    ),
+    (
+        "InvalidDecoratorTarget",
+        "The decorator can not be applied to the target, e.g., a decorator of schema attributes applied to a schema.",
+    ),
+    (
+        "InvalidFormatSpec",
+        "The format spec of the string interpolation is invalid.
+
+```kcl
+a = 1
+b = \"${a: #json}\" # error: invalid format spec
+```",
+    ),
+    (
+        "SchemaCheckFailure",
+        "The check block of the schema is not satisfied by the config.
+
+```kcl
+schema Person:
+    age: int
+
+    check:
+        age > 0
+
+p = Person {
+    age = -1 # error: check failed
+}
+```",
+    ),
+    (
+        "IndexSignatureError",
+        "The index signature of the schema is invalid or conflicts with the attributes.
+
+```kcl
+schema Data:
+    name: int
+    [str]: str # error: the type of name int does not match the index signature str
+```",
+    ),
+    (
+        "TypeError",
+        "The type of the value does not match the expected type.
+
+```kcl
+schema Person:
+    name: str
+
+p = Person {
+    name = 1 # error: expected str, got int(1)
+}
+```",
+    ),
+    (
+        "NameError",
+        "The name is not defined in the scope.
+
+```kcl
+a = b # error: name 'b' is not defined
+```",
+    ),
+    (
+        "ValueError",
+        "The value is not valid for the operation, e.g., an invalid argument of a builtin function.
+
+```kcl
+a = int(\"abc\") # error: invalid literal for int()
+```",
+    ),
+    (
+        "KeyError",
+        "The key is not found in the dict or the schema.
+
+```kcl
+data = {key = 1}
+value = data[\"unknown\"] # error when the key does not exist
+```",
+    ),
+    (
+        "AttributeError",
+        "The attribute is not found in the value, or the schema config contains an undeclared attribute.
+
+```kcl
+schema Person:
+    name: str
+
+p = Person {
+    age = 1 # error: Cannot add member 'age' to schema 'Person'
+}
+```",
+    ),
+    (
+        "AssertionError",
+        "The condition of the `assert` statement is false.
+
+```kcl
+a = 1
+assert a > 1, \"a should be greater than 1\"
+```",
+    ),
+    (
+        "ImmutableError",
+        "The variable is immutable and can not be reassigned. The global variables in KCL are immutable once defined, use a different name or make the variable mutable with a private name prefixed by `_`.
+
+```kcl
+a = 1
+a = 2 # error: Immutable variable 'a' is modified
+
+_b = 1
+_b = 2 # ok: private variables are mutable
+```",
+    ),
+    (
+        "MultiInheritError",
+        "A schema can inherit from only one base schema. Use mixins to reuse the logic of several schemas.
+
+```kcl
+schema A:
+    a: int
+schema B:
+    b: int
+schema C(A, B): # error: multiple inheritance is not allowed
+    c: int
+```",
+    ),
+    (
+        "CycleInheritError",
+        "The schemas inherit from each other cyclically.
+
+```kcl
+schema A(B):
+    a: int
+schema B(A): # error: A and B inherit from each other
+    b: int
+```",
+    ),
+    (
+        "IllegalInheritError",
+        "The base of the schema is not a schema, e.g., a mixin, a protocol or a builtin type.
+
+```kcl
+schema PersonMixin:
+    name: str
+schema Person(PersonMixin): # error: a schema can not inherit from a mixin
+    age: int
+```",
+    ),
+    (
+        "IllegalAttributeError",
+        "The attribute or the key is illegal, e.g., a dict key of a type which can not be a key.",
+    ),
+    (
+        "IllegalParameterError",
+        "The parameters of the schema, the lambda or the function call are illegal, e.g., a missing argument or an unexpected keyword argument.
+
+```kcl
+f = lambda x: int {
+    x
+}
+a = f(y=1) # error: unexpected keyword argument 'y'
+```",
+    ),
+    (
+        "RecursionError",
+        "The evaluation exceeds the maximum recursion depth, e.g., a schema which references itself without an end.",
+    ),
+    (
+        "PlanError",
+        "The result of the program can not be planned into YAML or JSON, e.g., a lambda value in the output.",
+    ),
+    (
+        "CannotAddMembers",
+        "The schema is closed and the config adds members which are not declared in the schema.
+
+```kcl
+schema Person:
+    name: str
+
+p = Person {
+    age = 1 # error: Cannot add member 'age' to schema 'Person'
+}
+```",
+    ),
+    (
+        "CompileError",
+        "The program can not be compiled, e.g., an invalid statement in a lambda body or a schema.",
+    ),
+    (
+        "EvaluationError",
+        "An error occurs when evaluating the program, e.g., a division by zero.
+
+```kcl
+a = 1 / 0 # error: division by zero
+```",
+    ),
+    (
+        "UniqueKeyError",
+        "The key is defined more than once where it should be unique, e.g., the same schema attribute declared twice.
+
+```kcl
+schema Person:
+    name: str
+    name: str # error: the attribute name is declared twice
+```",
+    ),
+    (
+        "CircularImport",
+        "The packages import each other in a cycle, which is reported on every import statement of the cycle with the other statements related.
+
+```kcl
+# a/a.k
+import b # error: circular import a -> b -> a
+# b/b.k
+import a # error: circular import a -> b -> a
+```
+
+Move the shared definitions into another package imported by both.",
+    ),
+    (
+        "CompilerWarning",
+        "A general warning reported by the compiler.",
+    ),
+    (
+        "UnusedImportWarning",
+        "The module is imported but not used. Remove the import statement.
+
+```kcl
+import math # warning: Module 'math' imported but unused
+
+a = 1
+```",
+    ),
+    (
+        "ReimportWarning",
+        "The module is imported more than once in the same file. Remove the duplicated import statement.
+
+```kcl
+import math
+import math # warning: the module math is reimported
+```",
+    ),
+    (
+        "ImportPositionWarning",
+        "The import statement should be placed at the top of the file.
+
+```kcl
+a = 1
+import math # warning: the import statement should be at the top of the file
+```",
+    ),
+];
+
+/// Returns the Markdown explanation of the diagnostic code.
+pub(crate) fn explain(code: &str) -> Option<String> {
+    EXPLANATIONS
+        .iter()
+        .find(|(name, _)| *name == code)
+        .map(|(name, explanation)| format!("# {}\n\n{}", name, explanation))
+}
@@ -0,0 +1,76 @@
+//! Export the word index of a workspace folder, i.e., the map of each word to all of its
+//! locations, by the `kcl.exportWordIndex` command for the external analysis of the
+//! symbol occurrences. The cached index is exported page by page in the order of the words.
+
+use std::collections::BTreeMap;
+
+use lsp_types::{Location, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::state::LanguageServerSnapshot;
+
+/// The default number of the words in a page of the export.
+const DEFAULT_PAGE_SIZE: usize = 1000;
+
+/// The max number of the words in a page of the export.
+const MAX_PAGE_SIZE: usize = 10000;
+
+/// The arguments of the `kcl.exportWordIndex` command.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportWordIndexArgs {
+    /// The workspace folder whose word index is exported.
+    pub folder: Url,
+    /// The number of the words skipped in the order of the words, which is the
+    /// `nextOffset` of the previous page. Default is 0.
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// The max number of the words in the page, which is bounded by `MAX_PAGE_SIZE`.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// A page of the exported word index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportedWordIndex {
+    /// The words in the page and their locations.
+    pub words: BTreeMap<String, Vec<Location>>,
+    /// The number of the words in the whole index.
+    pub total: usize,
+    /// The offset of the next page, which is none for the last page.
+    pub next_offset: Option<usize>,
+}
+
+/// Exports a page of the word index of the folder. The stale index is refreshed with the
+/// changed files before it is exported.
+pub(crate) fn export_word_index(
+    snapshot: &LanguageServerSnapshot,
+    args: &ExportWordIndexArgs,
+) -> anyhow::Result<ExportedWordIndex> {
+    snapshot
+        .word_index_generations
+        .refresh(&snapshot.word_index_map, snapshot.vfs.clone());
+    let word_index_map = snapshot.word_index_map.read();
+    let index = word_index_map
+        .get(&args.folder)
+        .ok_or_else(|| anyhow::anyhow!("The folder {} is not indexed", args.folder))?;
+    let mut words: Vec<&String> = index.keys().collect();
+    words.sort();
+    let offset = args.offset.unwrap_or_default();
+    let limit = args
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let end = offset.saturating_add(limit).min(words.len());
+    Ok(ExportedWordIndex {
+        words: words
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|word| ((*word).clone(), index[*word].clone()))
+            .collect(),
+        total: words.len(),
+        next_offset: (end < words.len()).then_some(end),
+    })
+}
@@ -15,9 +15,9 @@ mod tests;
 // start position, end position and the word itself.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct LineWord {
-    startpos: u64,
-    endpos: u64,
-    word: String,
+    pub startpos: u64,
+    pub endpos: u64,
+    pub word: String,
 }
 
 // Get the word of the position.
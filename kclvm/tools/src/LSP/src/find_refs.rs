@@ -0,0 +1,247 @@
+//! Find references for KCL
+//! The candidates of the references are the occurrences of the word in the
+//! workspace word index, and a candidate is kept only if it has the same
+//! definition as the symbol at the position.
+//! + schema attr: the candidates are restricted to the files loaded together with
+//!   the declaring schema and verified with the already resolved program, which avoids
+//!   recompiling every file containing the same word and over-matching the attrs with
+//!   the same name in other schemas.
+//! + others: the files of the candidates are compiled and the candidates are verified
+//!   by the goto definition result. If the definition is in a file opened with the
+//!   unsaved changes, the positions of the definition differ between the programs which
+//!   load the file from the vfs and from the disk, e.g., the imported packages are loaded
+//!   from the disk, so the definitions in the file are compared by the names and kinds.
+//! The occurrences in the comments and the strings have no definition, so they are
+//! never returned as references. The declaration itself is returned only if
+//! `include_declaration` is true.
+//! The files of the definitions are compared by their canonicalized paths, and the
+//! references are sorted by the locations without the duplicates, e.g., the same
+//! occurrence indexed in the overlapped workspace folders, so that the result does not
+//! depend on the order of the word indexes.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use kclvm_ast::ast::Program;
+use kclvm_error::Position as KCLPos;
+use kclvm_sema::resolver::scope::{ProgramScope, ScopeObject, ScopeObjectKind};
+use lsp_types::{Location, Url};
+use parking_lot::RwLock;
+use ra_ap_vfs::Vfs;
+
+use crate::config::Config;
+use crate::from_lsp::{canonicalize, file_path_from_url};
+use crate::goto_def::{find_def, Definition};
+use crate::to_lsp::lsp_pos;
+use crate::util::{build_word_index, load_file_code_from_vfs, parse_param_and_compile, Param};
+
+/// Returns the locations of all references of the symbol at the position in the file
+/// without a language server, e.g., for the external refactoring tools. The candidates
+/// are the words indexed in the root of the program, i.e., the directory of `kcl.mod`,
+/// or the directory of the file if there is no `kcl.mod`.
+pub fn references(file: &str, pos: KCLPos, include_decl: bool) -> anyhow::Result<Vec<Location>> {
+    let (program, prog_scope, _) = parse_param_and_compile(
+        Param {
+            file: file.to_string(),
+        },
+        None,
+    )?;
+    let root = if program.root.is_empty() {
+        Path::new(file)
+            .parent()
+            .and_then(|dir| dir.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid file path: {}", file))?
+            .to_string()
+    } else {
+        program.root.clone()
+    };
+    let root_url = Url::from_file_path(&root)
+        .map_err(|_| anyhow::anyhow!("can't convert path to url: {}", root))?;
+    let mut word_index_map = HashMap::new();
+    word_index_map.insert(
+        root_url,
+        build_word_index(root, &Config::default().kcl_extensions)?,
+    );
+    Ok(find_refs(
+        &program,
+        &pos,
+        &prog_scope,
+        &word_index_map,
+        None,
+        include_decl,
+    )
+    .unwrap_or_default())
+}
+
+/// Returns the locations of all references of the symbol at the position.
+pub(crate) fn find_refs(
+    program: &Program,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    word_index_map: &HashMap<Url, HashMap<String, Vec<Location>>>,
+    vfs: Option<Arc<RwLock<Vfs>>>,
+    include_declaration: bool,
+) -> Option<Vec<Location>> {
+    find_refs_with_progress(
+        program,
+        kcl_pos,
+        prog_scope,
+        word_index_map,
+        vfs,
+        include_declaration,
+        &mut |_, _| {},
+    )
+}
+
+/// Returns the locations of all references of the symbol at the position like `find_refs`,
+/// and calls `progress` with the number of the verified candidates and the number of all
+/// candidates after each candidate is verified, which may take long in a large workspace.
+pub(crate) fn find_refs_with_progress(
+    program: &Program,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    word_index_map: &HashMap<Url, HashMap<String, Vec<Location>>>,
+    vfs: Option<Arc<RwLock<Vfs>>>,
+    include_declaration: bool,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Option<Vec<Location>> {
+    let node = program.pos_to_stmt(kcl_pos)?;
+    let obj = match find_def(node, kcl_pos, prog_scope)? {
+        Definition::Object(obj) => obj,
+        Definition::Scope(_) => return None,
+    };
+    let candidates: Vec<&Location> = word_index_map
+        .values()
+        .filter_map(|index| index.get(&obj.name))
+        .flatten()
+        .filter(|loc| include_declaration || !is_declaration(loc, &obj))
+        .collect();
+    let total = candidates.len();
+    let mut refs: Vec<Location> = match obj.kind {
+        ScopeObjectKind::Attribute => {
+            let files: HashSet<&str> = program
+                .pkgs
+                .values()
+                .flatten()
+                .map(|module| module.filename.as_str())
+                .collect();
+            candidates
+                .into_iter()
+                .enumerate()
+                .filter(|(i, loc)| {
+                    let is_ref = match file_path_from_url(&loc.uri) {
+                        Ok(file) => {
+                            files.contains(file.as_str())
+                                && has_same_def(program, prog_scope, &file, loc, &obj, false)
+                        }
+                        Err(_) => false,
+                    };
+                    progress(i + 1, total);
+                    is_ref
+                })
+                .map(|(_, loc)| loc.clone())
+                .collect()
+        }
+        _ => {
+            let unsaved = has_unsaved_changes(&obj.start.filename, &vfs);
+            // Compile each file of the candidates only once.
+            let mut compiled: HashMap<String, Option<(Program, ProgramScope)>> = HashMap::new();
+            candidates
+                .into_iter()
+                .enumerate()
+                .filter(|(i, loc)| {
+                    let is_ref = match file_path_from_url(&loc.uri) {
+                        Ok(file) => {
+                            let entry = compiled.entry(file.clone()).or_insert_with(|| {
+                                parse_param_and_compile(Param { file: file.clone() }, vfs.clone())
+                                    .ok()
+                                    .map(|(program, prog_scope, _)| (program, prog_scope))
+                            });
+                            match entry {
+                                Some((program, prog_scope)) => {
+                                    has_same_def(program, prog_scope, &file, loc, &obj, unsaved)
+                                }
+                                None => false,
+                            }
+                        }
+                        Err(_) => false,
+                    };
+                    progress(i + 1, total);
+                    is_ref
+                })
+                .map(|(_, loc)| loc.clone())
+                .collect()
+        }
+    };
+    refs.sort_by(|a, b| {
+        (a.uri.as_str(), a.range.start, a.range.end).cmp(&(
+            b.uri.as_str(),
+            b.range.start,
+            b.range.end,
+        ))
+    });
+    refs.dedup();
+    Some(refs)
+}
+
+/// Whether the location is the declaration of `obj`.
+fn is_declaration(loc: &Location, obj: &ScopeObject) -> bool {
+    loc.range.start == lsp_pos(&obj.start)
+        && file_path_from_url(&loc.uri).map_or(false, |file| same_file(&file, &obj.start.filename))
+}
+
+/// Whether the paths are the same file, which are compared by the canonicalized paths if
+/// they differ, e.g., a path through a symlink or with a different drive letter case.
+fn same_file(a: &str, b: &str) -> bool {
+    a == b || canonicalize(Path::new(a)) == canonicalize(Path::new(b))
+}
+
+/// Whether the positions are the same in the same file.
+fn same_pos(a: &KCLPos, b: &KCLPos) -> bool {
+    a.line == b.line && a.column == b.column && same_file(&a.filename, &b.filename)
+}
+
+/// Whether the file is opened in the vfs with the changes unsaved to the disk.
+fn has_unsaved_changes(file: &str, vfs: &Option<Arc<RwLock<Vfs>>>) -> bool {
+    let vfs = match vfs {
+        Some(vfs) => vfs.clone(),
+        None => return false,
+    };
+    match load_file_code_from_vfs(file, vfs) {
+        Ok(code) => std::fs::read_to_string(file).map_or(true, |saved| saved != code),
+        Err(_) => false,
+    }
+}
+
+/// Whether the word at the location is defined by `obj`. If `by_name` is true, the
+/// definition is compared by the file, the name and the kind rather than the position,
+/// which may be changed by the unsaved changes of the file.
+fn has_same_def(
+    program: &Program,
+    prog_scope: &ProgramScope,
+    file: &str,
+    loc: &Location,
+    obj: &ScopeObject,
+    by_name: bool,
+) -> bool {
+    let pos = KCLPos {
+        filename: file.to_string(),
+        line: (loc.range.start.line + 1) as u64,
+        column: Some(loc.range.start.character as u64),
+    };
+    match program
+        .pos_to_stmt(&pos)
+        .and_then(|node| find_def(node, &pos, prog_scope))
+    {
+        Some(Definition::Object(def)) if by_name => {
+            def.name == obj.name
+                && def.kind == obj.kind
+                && same_file(&def.start.filename, &obj.start.filename)
+        }
+        Some(Definition::Object(def)) => {
+            same_pos(&def.start, &obj.start) && same_pos(&def.end, &obj.end)
+        }
+        _ => false,
+    }
+}
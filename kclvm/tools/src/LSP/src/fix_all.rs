@@ -0,0 +1,139 @@
+//! Fix all the issues of a file which can be fixed automatically in one pass, i.e., the
+//! `source.fixAll` code action and the `kcl.fixAll` command.
+//! + remove the unused and the reimported imports
+//! + sort the remaining imports by their paths at the position of the first import
+//! + format the file
+
+use std::collections::{HashMap, HashSet};
+
+use indexmap::IndexSet;
+use kclvm_ast::ast::{Program, Stmt};
+use kclvm_error::{Diagnostic, DiagnosticId, WarningKind};
+use lsp_types::{CodeAction, CodeActionKind, Position, Range, TextEdit, Url, WorkspaceEdit};
+use serde::Deserialize;
+
+use crate::formatting::format_source;
+
+/// The arguments of the `kcl.fixAll` command.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct FixAllArgs {
+    /// The path of the KCL file.
+    pub file: String,
+    /// Whether the edit is applied by the server with the `workspace/applyEdit` request
+    /// instead of being returned, e.g., for the clients which ignore the command result.
+    #[serde(default)]
+    pub apply: bool,
+}
+
+/// Returns the edit which fixes all the issues of the file, or `None` if there is
+/// nothing to fix.
+pub(crate) fn fix_all(
+    program: &Program,
+    diags: &IndexSet<Diagnostic>,
+    file: &str,
+    uri: &Url,
+    src: &str,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    let fixed = format_source(&organize_imports(program, diags, file, src))
+        .map_err(|err| anyhow::anyhow!("Fixing all failed for {}: {}", file, err))?;
+    if fixed == src {
+        return Ok(None);
+    }
+    let edit = TextEdit {
+        range: Range::new(Position::new(0, 0), Position::new(u32::MAX, u32::MAX)),
+        new_text: fixed,
+    };
+    Ok(Some(WorkspaceEdit {
+        changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+        ..Default::default()
+    }))
+}
+
+/// Returns the `source.fixAll` code action of the file, or `None` if there is nothing
+/// to fix.
+pub(crate) fn fix_all_action(
+    program: &Program,
+    diags: &IndexSet<Diagnostic>,
+    file: &str,
+    uri: &Url,
+    src: &str,
+) -> anyhow::Result<Option<CodeAction>> {
+    Ok(
+        fix_all(program, diags, file, uri, src)?.map(|edit| CodeAction {
+            title: "Fix all auto-fixable problems".to_string(),
+            kind: Some(CodeActionKind::SOURCE_FIX_ALL),
+            edit: Some(edit),
+            ..Default::default()
+        }),
+    )
+}
+
+/// Removes the unused and the reimported imports of the file, and sorts the remaining
+/// ones by their paths at the position of the first import.
+fn organize_imports(
+    program: &Program,
+    diags: &IndexSet<Diagnostic>,
+    file: &str,
+    src: &str,
+) -> String {
+    let module = match program
+        .pkgs
+        .values()
+        .flatten()
+        .find(|module| module.filename == file)
+    {
+        Some(module) => module,
+        None => return src.to_string(),
+    };
+    // The lines of the unused and the reimported imports.
+    let removed_lines: HashSet<u64> = diags
+        .iter()
+        .filter(|diag| {
+            matches!(
+                diag.code,
+                Some(DiagnosticId::Warning(
+                    WarningKind::UnusedImportWarning | WarningKind::ReimportWarning
+                ))
+            )
+        })
+        .flat_map(|diag| &diag.messages)
+        .filter(|msg| msg.pos.filename == file)
+        .map(|msg| msg.pos.line)
+        .collect();
+    let lines: Vec<&str> = src.lines().collect();
+    let mut import_lines: HashSet<usize> = HashSet::new();
+    let mut imports: Vec<(String, String)> = vec![];
+    for stmt in &module.body {
+        if let Stmt::Import(import_stmt) = &stmt.node {
+            let (start, end) = (stmt.line as usize - 1, stmt.end_line as usize);
+            if end > lines.len() {
+                return src.to_string();
+            }
+            import_lines.extend(start..end);
+            if !removed_lines.contains(&stmt.line) {
+                imports.push((import_stmt.path.clone(), lines[start..end].join("\n")));
+            }
+        }
+    }
+    let first_import = match import_lines.iter().min() {
+        Some(line) => *line,
+        None => return src.to_string(),
+    };
+    imports.sort();
+    imports.dedup();
+
+    let mut fixed: Vec<&str> = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        if i == first_import {
+            fixed.extend(imports.iter().map(|(_, import)| import.as_str()));
+        }
+        if !import_lines.contains(&i) {
+            fixed.push(line);
+        }
+    }
+    let mut fixed = fixed.join("\n");
+    if src.ends_with('\n') {
+        fixed.push('\n');
+    }
+    fixed
+}
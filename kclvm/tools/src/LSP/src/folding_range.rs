@@ -0,0 +1,101 @@
+//! Folding ranges of a KCL file, including:
+//!  + the group of the consecutive import stmts, of the `imports` kind
+//!  + the multi-line stmts, e.g., the schema stmts and the configs, including the
+//!    multi-line stmts in the schema and the if bodies
+//!  + the custom regions between the `#region` and `#endregion` comments, of the `region`
+//!    kind, which can be nested
+//! The file is parsed only without being compiled, and the syntax errors are tolerated.
+
+use std::sync::Arc;
+
+use kclvm_ast::ast::{Comment, Module, Node, NodeRef, Stmt};
+use kclvm_parser::{parse_file_with_global_session, ParseSession};
+use lsp_types::{FoldingRange, FoldingRangeKind};
+
+/// The comment marking the start of a custom region, e.g., `# region servers`.
+const REGION_START_MARKER: &str = "region";
+/// The comment marking the end of the innermost custom region.
+const REGION_END_MARKER: &str = "endregion";
+
+/// Parses the source of the file and returns its folding ranges.
+pub(crate) fn folding_range(file: &str, source: String) -> anyhow::Result<Vec<FoldingRange>> {
+    let module =
+        parse_file_with_global_session(Arc::new(ParseSession::default()), file, Some(source))
+            .map_err(|err| anyhow::anyhow!(err))?;
+    Ok(module_folding_ranges(&module))
+}
+
+/// Returns the folding ranges of the module sorted by the start lines.
+fn module_folding_ranges(module: &Module) -> Vec<FoldingRange> {
+    let mut ranges = vec![];
+    import_ranges(&module.body, &mut ranges);
+    stmt_ranges(&module.body, &mut ranges);
+    region_ranges(&module.comments, &mut ranges);
+    ranges.sort_by_key(|range| (range.start_line, range.end_line));
+    ranges
+}
+
+/// Folds each group of the consecutive import stmts spanning multiple lines.
+fn import_ranges(body: &[NodeRef<Stmt>], ranges: &mut Vec<FoldingRange>) {
+    let mut group: Option<(u64, u64)> = None;
+    for stmt in body {
+        match (&stmt.node, group) {
+            (Stmt::Import(_), Some((start, _))) => group = Some((start, stmt.end_line)),
+            (Stmt::Import(_), None) => group = Some((stmt.line, stmt.end_line)),
+            (_, Some((start, end))) => {
+                ranges.extend(folding(start, end, Some(FoldingRangeKind::Imports)));
+                group = None;
+            }
+            (_, None) => {}
+        }
+    }
+    if let Some((start, end)) = group {
+        ranges.extend(folding(start, end, Some(FoldingRangeKind::Imports)));
+    }
+}
+
+/// Folds the multi-line stmts except the imports, and the stmts in their bodies.
+fn stmt_ranges(body: &[NodeRef<Stmt>], ranges: &mut Vec<FoldingRange>) {
+    for stmt in body {
+        match &stmt.node {
+            Stmt::Import(_) => continue,
+            Stmt::Schema(schema_stmt) => stmt_ranges(&schema_stmt.body, ranges),
+            Stmt::If(if_stmt) => {
+                stmt_ranges(&if_stmt.body, ranges);
+                stmt_ranges(&if_stmt.orelse, ranges);
+            }
+            _ => {}
+        }
+        ranges.extend(folding(stmt.line, stmt.end_line, None));
+    }
+}
+
+/// Folds the custom regions between the region marker comments. The unbalanced markers
+/// are ignored.
+fn region_ranges(comments: &[NodeRef<Comment>], ranges: &mut Vec<FoldingRange>) {
+    let mut starts: Vec<&Node<Comment>> = vec![];
+    for comment in comments {
+        let text = comment.node.text.trim_start_matches('#').trim_start();
+        if text.starts_with(REGION_END_MARKER) {
+            if let Some(start) = starts.pop() {
+                ranges.extend(folding(
+                    start.line,
+                    comment.end_line,
+                    Some(FoldingRangeKind::Region),
+                ));
+            }
+        } else if text.starts_with(REGION_START_MARKER) {
+            starts.push(comment);
+        }
+    }
+}
+
+/// Returns the folding range between the 1-based lines if it spans multiple lines.
+fn folding(start_line: u64, end_line: u64, kind: Option<FoldingRangeKind>) -> Option<FoldingRange> {
+    (end_line > start_line).then(|| FoldingRange {
+        start_line: (start_line - 1) as u32,
+        end_line: (end_line - 1) as u32,
+        kind,
+        ..Default::default()
+    })
+}
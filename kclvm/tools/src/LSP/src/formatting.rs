@@ -0,0 +1,23 @@
+use lsp_types::{Position, Range, TextEdit};
+
+/// Formats a KCL source string and returns the formatted text. This does not
+/// require the source to be backed by a file, so it can be reused outside the
+/// language server.
+pub fn format_source(src: &str) -> anyhow::Result<String> {
+    let (source, _) = kclvm_tools::format::format_source(src)?;
+    Ok(source)
+}
+
+/// Formats the source of `file` and returns the edits which replace the whole document
+/// with the formatted text. No edits are returned if the source is already formatted.
+pub(crate) fn format(file: String, src: String) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let source = format_source(&src)
+        .map_err(|err| anyhow::anyhow!("Formatting failed for {}: {}", file, err))?;
+    if source == src {
+        return Ok(Some(vec![]));
+    }
+    Ok(Some(vec![TextEdit {
+        range: Range::new(Position::new(0, 0), Position::new(u32::MAX, u32::MAX)),
+        new_text: source,
+    }]))
+}
@@ -1,61 +1,151 @@
+use std::collections::HashMap;
 use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use kclvm_error::Position as KCLPos;
+use kclvm_utils::path::PathPrefix;
 use lsp_types::{Position, Url};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 use ra_ap_vfs::AbsPathBuf;
 
 /// Converts the specified `uri` to an absolute path. Returns an error if the url could not be
-/// converted to an absolute path.
+/// converted to an absolute path. The path is the same as the one of `file_path_from_url`,
+/// so that the files in the vfs are keyed consistently with the requests.
 pub(crate) fn abs_path(uri: &Url) -> anyhow::Result<AbsPathBuf> {
-    uri.to_file_path()
+    file_path_from_url(uri)
         .ok()
-        .and_then(|path| AbsPathBuf::try_from(path).ok())
+        .and_then(|path| AbsPathBuf::try_from(std::path::PathBuf::from(path)).ok())
         .ok_or_else(|| anyhow::anyhow!("invalid uri: {}", uri))
 }
 
-// Convert pos format
-// The position in lsp protocol is different with position in ast node whose line number is 1 based.
-pub(crate) fn kcl_pos(file: &str, pos: Position) -> KCLPos {
+/// Converts the lsp position to the KCL position of the token under the cursor. When the
+/// cursor is at a token boundary or on the whitespace right after a token, e.g., `name|`,
+/// `name| = 1` or `name|.attr`, the token immediately to the left is preferred like most
+/// editors do, so that hover, goto definition and completion resolve the same node.
+/// The character of the lsp position is the UTF-16 code units, which is converted to the
+/// char index of the line, e.g., `a` of `b = "😀" + a` is at the column 11 and the char 10.
+pub(crate) fn kcl_token_pos(file: &str, pos: Position, text: &str) -> KCLPos {
+    let mut column = pos.character as u64;
+    if let Some(line) = text.lines().nth(pos.line as usize) {
+        let chars: Vec<char> = line.chars().collect();
+        let index = char_index(line, pos.character);
+        column = index as u64;
+        let is_id_char = |i: usize| {
+            chars
+                .get(i)
+                .map_or(false, |c| rustc_lexer::is_id_continue(*c))
+        };
+        if index > 0 && !is_id_char(index) && is_id_char(index - 1) {
+            column -= 1;
+        }
+    }
     KCLPos {
         filename: file.to_string(),
         line: (pos.line + 1) as u64,
-        column: if pos.character == 0 {
-            None
-        } else {
-            Some(pos.character as u64)
-        },
+        column: Some(column),
     }
 }
 
-/// Converts the given lsp range to `Range`
-pub(crate) fn text_range(text: &str, range: lsp_types::Range) -> Range<usize> {
-    let mut lines_length = vec![];
-    let lines_text: Vec<&str> = text.split('\n').collect();
-    let mut pre_total_length = 0;
-    // range line base-zeror
-    for i in 0..range.end.line + 1 {
-        let i = i as usize;
-        if i < lines_text.len() {
-            let line = lines_text.get(i).unwrap();
-            lines_length.push(pre_total_length);
-            pre_total_length += line.len() + "\n".len();
-        } else {
-            lines_length.push(pre_total_length);
+/// Returns the char index of the UTF-16 code unit offset in the line, which is clamped to
+/// the number of the chars of the line.
+fn char_index(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (index, ch) in line.chars().enumerate() {
+        if units >= character {
+            return index;
         }
+        units += ch.len_utf16() as u32;
     }
+    line.chars().count()
+}
 
-    let start =
-        lines_length.get(range.start.line as usize).unwrap() + range.start.character as usize;
-    let end = lines_length.get(range.end.line as usize).unwrap() + range.end.character as usize;
-
-    Range { start, end }
+/// Converts the given lsp range to `Range` of the offsets in the text. The lines are
+/// terminated by either `\n` or `\r\n`, and the character beyond the end of a line is
+/// clamped to the end of the line before its terminator, so that the positions in a CRLF
+/// text never split the `\r\n`.
+pub(crate) fn text_range(text: &str, range: lsp_types::Range) -> Range<usize> {
+    let mut lines = vec![];
+    let mut line_start = 0;
+    for line in text.split('\n') {
+        let content = line.strip_suffix('\r').unwrap_or(line);
+        lines.push((line_start, content.len()));
+        line_start += line.len() + "\n".len();
+    }
+    let offset = |pos: Position| match lines.get(pos.line as usize) {
+        Some((start, len)) => start + (pos.character as usize).min(*len),
+        None => text.len(),
+    };
+    Range {
+        start: offset(range.start),
+        end: offset(range.end),
+    }
 }
 
 /// Converts the specified `url` to a utf8 encoded file path string. Returns an error if the url could not be
 /// converted to a valid utf8 encoded file path string.
+/// On Windows, the url with a host is converted to the UNC path, e.g., `file://server/share/a.k`
+/// to `\\server\share\a.k`, and the drive letter is uppercased like the paths given by the OS
+/// and recorded by the compiler, e.g., both `file:///C:/a.k` and `file:///c%3A/a.k` to `C:\a.k`,
+/// so that the same file is keyed by the same path.
+/// The symlinks are resolved like the package root of the compiler, so that the files of
+/// the symlinked packages are keyed by the same paths as their modules. The path of an
+/// existing file is canonicalized once and cached, see `forget_file_path`.
 pub(crate) fn file_path_from_url(url: &Url) -> anyhow::Result<String> {
-    url.to_file_path()
-        .ok()
-        .and_then(|path| path.to_str().map(|p| p.to_string()))
-        .ok_or_else(|| anyhow::anyhow!("can't convert url to file path: {}", url))
+    let path = url
+        .to_file_path()
+        .map_err(|_| anyhow::anyhow!("can't convert url to file path: {}", url))?;
+    if let Some(file) = FILE_PATHS.lock().get(&path) {
+        return Ok(file.clone());
+    }
+    let file = canonicalize(&path)
+        .to_str()
+        .map(uppercase_drive_letter)
+        .ok_or_else(|| anyhow::anyhow!("can't convert url to file path: {}", url))?;
+    // The path which does not exist yet is not cached, e.g., an unsaved file, which may be
+    // created as a symlink later.
+    if path.exists() {
+        FILE_PATHS.lock().insert(path, file.clone());
+    }
+    Ok(file)
+}
+
+/// The canonicalized file paths of the existing files keyed by the paths of the urls.
+static FILE_PATHS: Lazy<Mutex<HashMap<PathBuf, String>>> = Lazy::new(Default::default);
+
+/// Drops the cached file path of the url, e.g., when the file is created or deleted.
+pub(crate) fn forget_file_path(url: &Url) {
+    if let Ok(path) = url.to_file_path() {
+        FILE_PATHS.lock().remove(&path);
+    }
+}
+
+/// Canonicalizes the path, whose parent is canonicalized if the path does not exist yet,
+/// e.g., the new path of a renamed directory or an unsaved file. The `\\?\` prefix of the
+/// canonicalized path on Windows is removed.
+pub(crate) fn canonicalize(path: &Path) -> PathBuf {
+    let canonicalized = path.canonicalize().ok().or_else(|| {
+        match (
+            path.parent().and_then(|p| p.canonicalize().ok()),
+            path.file_name(),
+        ) {
+            (Some(parent), Some(name)) => Some(parent.join(name)),
+            _ => None,
+        }
+    });
+    match canonicalized {
+        Some(path) => PathBuf::from(path.adjust_canonicalization()),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Uppercases the drive letter of the Windows path, e.g., `c:\a.k` to `C:\a.k`.
+fn uppercase_drive_letter(path: &str) -> String {
+    let mut chars = path.chars();
+    match (chars.next(), chars.next()) {
+        (Some(drive), Some(':')) if drive.is_ascii_alphabetic() => {
+            format!("{}{}", drive.to_ascii_uppercase(), &path[1..])
+        }
+        _ => path.to_string(),
+    }
 }
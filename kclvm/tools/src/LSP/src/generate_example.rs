@@ -0,0 +1,117 @@
+//! Generate an example instance of a schema as a starting point, i.e., a config snippet
+//! with the required attrs of the schema and its base schemas filled with the placeholder
+//! values derived from their types.
+
+use std::rc::Rc;
+
+use kclvm_ast::ast::Node;
+use kclvm_sema::resolver::scope::{ProgramScope, ScopeKind};
+use kclvm_sema::ty::{SchemaType, Type, TypeKind};
+use serde::Deserialize;
+
+use crate::goto_def::{resolve_var, Definition};
+use crate::hover::schema_attrs;
+
+/// The max depth of the nested schema examples, which stops the recursive schemas.
+const MAX_EXAMPLE_DEPTH: usize = 8;
+
+/// The indentation of the attrs in the example.
+pub(crate) const INDENT: &str = "    ";
+
+/// The arguments of the `kcl.generateExample` command.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct GenerateExampleArgs {
+    /// The path of the KCL file where the schema is referenced.
+    pub file: String,
+    /// The schema referenced in the file, e.g., `Server` or `pkg.Server`.
+    pub schema: String,
+}
+
+/// Generates the example instance of the schema referenced in the file.
+pub(crate) fn generate_example(
+    prog_scope: &ProgramScope,
+    args: &GenerateExampleArgs,
+) -> anyhow::Result<String> {
+    let scope = prog_scope
+        .scope_map
+        .values()
+        .find(|scope| match &scope.borrow().kind {
+            ScopeKind::Package(files) => files.contains(&args.file),
+            _ => false,
+        })
+        .ok_or_else(|| anyhow::anyhow!("The file {} is not compiled", args.file))?;
+    let names: Vec<Node<String>> = args
+        .schema
+        .split('.')
+        .map(|name| Node::dummy_node(name.to_string()))
+        .collect();
+    let schema_ty = match resolve_var(&names, &scope.borrow(), &prog_scope.scope_map) {
+        Some(Definition::Object(obj)) => match &obj.ty.kind {
+            TypeKind::Schema(schema_ty) => schema_ty.clone(),
+            _ => return Err(anyhow::anyhow!("{} is not a schema", args.schema)),
+        },
+        _ => return Err(anyhow::anyhow!("Cannot find the schema {}", args.schema)),
+    };
+    Ok(schema_example(&args.schema, &schema_ty, 0))
+}
+
+/// Returns the example config of the schema with its required attrs, i.e., the attrs
+/// which are neither optional nor with default values.
+fn schema_example(name: &str, schema_ty: &SchemaType, depth: usize) -> String {
+    let mut required_attrs = vec![];
+    collect_required_attrs(schema_ty, &mut required_attrs);
+    if required_attrs.is_empty() || depth >= MAX_EXAMPLE_DEPTH {
+        return format!("{} {{}}", name);
+    }
+    let indent = INDENT.repeat(depth + 1);
+    let mut example = format!("{} {{\n", name);
+    for (attr, ty) in required_attrs {
+        example.push_str(&format!(
+            "{}{} = {}\n",
+            indent,
+            attr,
+            placeholder(&ty, depth + 1)
+        ));
+    }
+    example.push_str(&INDENT.repeat(depth));
+    example.push('}');
+    example
+}
+
+/// Collects the required attrs declared in the schema and its base schemas, where the
+/// attrs of the base schemas come first.
+pub(crate) fn collect_required_attrs(schema_ty: &SchemaType, attrs: &mut Vec<(String, Rc<Type>)>) {
+    if let Some(base) = &schema_ty.base {
+        collect_required_attrs(base, attrs);
+    }
+    for (name, attr) in schema_attrs(schema_ty) {
+        if attr.is_optional || attr.has_default {
+            continue;
+        }
+        match attrs.iter_mut().find(|(attr_name, _)| attr_name == name) {
+            Some((_, ty)) => *ty = attr.ty.clone(),
+            None => attrs.push((name.clone(), attr.ty.clone())),
+        }
+    }
+}
+
+/// Returns the placeholder value of the type.
+pub(crate) fn placeholder(ty: &Type, depth: usize) -> String {
+    match &ty.kind {
+        TypeKind::Bool => "False".to_string(),
+        TypeKind::Int => "0".to_string(),
+        TypeKind::Float => "0.0".to_string(),
+        TypeKind::Str => "\"\"".to_string(),
+        TypeKind::BoolLit(v) => if *v { "True" } else { "False" }.to_string(),
+        TypeKind::IntLit(v) => v.to_string(),
+        TypeKind::FloatLit(v) => format!("{:?}", v),
+        TypeKind::StrLit(v) => format!("{:?}", v),
+        TypeKind::List(_) => "[]".to_string(),
+        TypeKind::Dict(_, _) => "{}".to_string(),
+        TypeKind::Union(types) => types
+            .first()
+            .map_or("None".to_string(), |ty| placeholder(ty, depth)),
+        TypeKind::Schema(schema_ty) => schema_example(&schema_ty.name, schema_ty, depth),
+        _ => "None".to_string(),
+    }
+}
@@ -5,23 +5,42 @@
 //! + schema definition
 //! + mixin definition
 //! + schema attr
+//! + schema attr declared in the base schema, protocol and mixins
 //! + attr type
+//! + builtin function, which navigates to its synthetic document
+//! + lambda parameter and the local variable in the lambda body
+//! + import, which navigates to the imported schema if the module is named after it
+//!   or declares a single schema, e.g., `import .models.server` to `schema Server`
+//! + attr of a dict config, including the attrs merged by the spread operator, e.g.,
+//!   `config.name` to the key `name` in `base` of `config = {**base}`
+//! + attr accessed on the value of a selector expr, including the ones in the string
+//!   interpolations, e.g., `name` in `"${servers[0].name}"` to the schema attr
+//! + named constant of another module used as a member of the union type in the value
+//!   position, e.g., `consts.PROD` of `env = consts.PROD` for `env: "prod" | "dev"`
+//! + schema re-exported by another package, which navigates to the declaration of the
+//!   schema through the re-export chain, e.g., `Server = base.Server` to `schema Server`
 
 use indexmap::{IndexMap, IndexSet};
 use kclvm_ast::pos::{ContainsPos, GetPos};
+use kclvm_ast::walker::MutSelfWalker;
 
-use kclvm_ast::ast::{Expr, Identifier, ImportStmt, Node, Program, Stmt};
+use kclvm_ast::ast::{
+    BinOp, BinOrCmpOp, ConfigEntry, Expr, Identifier, ImportStmt, LambdaExpr, NameConstant, Node,
+    NodeRef, NumberLitValue, Program, SelectorExpr, Stmt, UnaryOp,
+};
 use kclvm_compiler::pkgpath_without_prefix;
 use kclvm_error::Position as KCLPos;
 
-use kclvm_sema::resolver::scope::{ProgramScope, Scope, ScopeObject};
-use kclvm_sema::ty::SchemaType;
+use kclvm_sema::resolver::scope::{ProgramScope, Scope, ScopeKind, ScopeObject, ScopeObjectKind};
+use kclvm_sema::ty::{sup, SchemaType, Type, TypeKind};
 use lsp_types::{GotoDefinitionResponse, Url};
 use lsp_types::{Location, Range};
 use std::cell::RefCell;
 use std::path::Path;
 use std::rc::Rc;
 
+use crate::builtin_doc::builtin_doc_location;
+use crate::from_lsp::canonicalize;
 use crate::to_lsp::lsp_pos;
 use crate::util::{
     get_pkg_scope, get_pos_from_real_path, get_real_path_from_external, inner_most_expr_in_stmt,
@@ -36,9 +55,25 @@ pub(crate) fn goto_definition(
     match program.pos_to_stmt(kcl_pos) {
         Some(node) => match node.node {
             Stmt::Import(stmt) => goto_def_for_import(&stmt, kcl_pos, prog_scope, program),
-            _ => match find_def(node.clone(), kcl_pos, prog_scope) {
+            // The attr of the selector expr is resolved first, otherwise it may be resolved
+            // to a variable with the same name.
+            _ => match find_selector_attr_def(&node, kcl_pos, prog_scope)
+                .or_else(|| find_def(node.clone(), kcl_pos, prog_scope))
+            {
+                // The builtin functions have no source files, so navigate to their
+                // synthetic documents instead.
+                Some(Definition::Object(obj)) if obj.start.filename.is_empty() => {
+                    builtin_doc_location(&obj.name).map(GotoDefinitionResponse::Scalar)
+                }
+                Some(Definition::Object(obj)) => positions_to_goto_def_resp(
+                    &Definition::Object(follow_reexport(obj, prog_scope)).get_positions(),
+                ),
                 Some(def) => positions_to_goto_def_resp(&def.get_positions()),
-                None => None,
+                None => find_config_key_def(program, &node, kcl_pos, prog_scope).and_then(|key| {
+                    let mut positions = IndexSet::new();
+                    positions.insert(key);
+                    positions_to_goto_def_resp(&positions)
+                }),
             },
         },
         None => None,
@@ -160,7 +195,17 @@ pub(crate) fn find_def(
                 None => {
                     for (_, scope) in &prog_scope.scope_map {
                         match scope.borrow().inner_most(kcl_pos) {
-                            Some(s) => return resolve_var(&id.names, &s, &prog_scope.scope_map),
+                            Some(s) => {
+                                return resolve_var(&id.names, &s, &prog_scope.scope_map)
+                                    .or_else(|| {
+                                        find_attr_in_enclosing_schema(
+                                            &id.names,
+                                            &s,
+                                            &prog_scope.scope_map,
+                                        )
+                                    })
+                                    .map(|def| locate_lambda_param(def, &node.node, kcl_pos))
+                            }
                             None => continue,
                         }
                     }
@@ -171,6 +216,217 @@ pub(crate) fn find_def(
     None
 }
 
+/// The max depth of the spread sources to be followed, which avoids the infinite recursion
+/// on the configs spreading each other.
+const MAX_SPREAD_DEPTH: usize = 16;
+
+/// Finds the key of the attr of a dict config variable, e.g., `name` of `config.name`. The
+/// keys merged by the spread operator, e.g., `config = {**base}`, are resolved to the keys
+/// in the spread sources, and the keys of a schema config fall back to the schema attrs.
+fn find_config_key_def(
+    program: &Program,
+    node: &Node<Stmt>,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> Option<(KCLPos, KCLPos)> {
+    let (expr, _) = inner_most_expr_in_stmt(&node.node, kcl_pos, None);
+    let id = match expr?.node {
+        Expr::Identifier(id) => id,
+        _ => return None,
+    };
+    let index = id
+        .names
+        .iter()
+        .position(|name| name.contains_pos(kcl_pos))?;
+    if index == 0 {
+        return None;
+    }
+    let names: Vec<String> = id.names[..=index]
+        .iter()
+        .map(|name| name.node.clone())
+        .collect();
+    let value = find_var_value(program, &names[0], kcl_pos, prog_scope)?;
+    find_key_in_value(program, &value, &names[1..], prog_scope, MAX_SPREAD_DEPTH)
+}
+
+/// Finds the value expr assigned to the variable visible at the position.
+fn find_var_value(
+    program: &Program,
+    name: &str,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> Option<NodeRef<Expr>> {
+    let scope = prog_scope
+        .scope_map
+        .values()
+        .find_map(|scope| scope.borrow().inner_most(kcl_pos))?;
+    let obj = scope.lookup(name)?;
+    let obj = obj.borrow();
+    if !matches!(obj.kind, ScopeObjectKind::Variable) {
+        return None;
+    }
+    match program.pos_to_stmt(&obj.start)?.node {
+        Stmt::Assign(assign_stmt) => Some(assign_stmt.value),
+        _ => None,
+    }
+}
+
+/// Finds the key `names` in the config value, following the spread sources.
+fn find_key_in_value(
+    program: &Program,
+    value: &Node<Expr>,
+    names: &[String],
+    prog_scope: &ProgramScope,
+    depth: usize,
+) -> Option<(KCLPos, KCLPos)> {
+    if depth == 0 || names.is_empty() {
+        return None;
+    }
+    match &value.node {
+        Expr::Config(config_expr) => {
+            find_key_in_entries(program, &config_expr.items, names, prog_scope, depth)
+        }
+        Expr::Schema(schema_expr) => {
+            find_key_in_value(program, &schema_expr.config, names, prog_scope, depth)
+                .or_else(|| find_schema_attr_def(&schema_expr.name, names, prog_scope))
+        }
+        Expr::Identifier(id) if id.names.len() == 1 => {
+            let value = find_var_value(program, &id.names[0].node, &value.get_pos(), prog_scope)?;
+            find_key_in_value(program, &value, names, prog_scope, depth - 1)
+        }
+        _ => None,
+    }
+}
+
+/// Finds the attr `names` declared in the schema named `schema_name`.
+fn find_schema_attr_def(
+    schema_name: &Node<Identifier>,
+    names: &[String],
+    prog_scope: &ProgramScope,
+) -> Option<(KCLPos, KCLPos)> {
+    let scope = prog_scope
+        .scope_map
+        .values()
+        .find_map(|scope| scope.borrow().inner_most(&schema_name.get_pos()))?;
+    let schema_type = match resolve_var(&schema_name.node.names, &scope, &prog_scope.scope_map)? {
+        Definition::Object(obj) => match &obj.ty.kind {
+            TypeKind::Schema(schema_type) => schema_type.clone(),
+            _ => return None,
+        },
+        Definition::Scope(_) => return None,
+    };
+    let names: Vec<Node<String>> = names
+        .iter()
+        .map(|name| Node::dummy_node(name.clone()))
+        .collect();
+    match find_declared_attr_in_schema(&schema_type, &names, &prog_scope.scope_map)? {
+        Definition::Object(attr) => Some((attr.start, attr.end)),
+        Definition::Scope(_) => None,
+    }
+}
+
+/// Finds the key `names` in the config entries. The later entries override the earlier
+/// ones, so the entries are searched backwards.
+fn find_key_in_entries(
+    program: &Program,
+    entries: &[NodeRef<ConfigEntry>],
+    names: &[String],
+    prog_scope: &ProgramScope,
+    depth: usize,
+) -> Option<(KCLPos, KCLPos)> {
+    entries
+        .iter()
+        .rev()
+        .find_map(|entry| match &entry.node.key {
+            Some(key) => {
+                let key_pos = match &key.node {
+                    Expr::Identifier(id) => id
+                        .names
+                        .first()
+                        .filter(|name| name.node == names[0])
+                        .map(|name| (name.get_pos(), name.get_end_pos())),
+                    Expr::StringLit(string_lit) if string_lit.value == names[0] => {
+                        Some((key.get_pos(), key.get_end_pos()))
+                    }
+                    _ => None,
+                }?;
+                if names.len() == 1 {
+                    return Some(key_pos);
+                }
+                find_key_in_value(program, &entry.node.value, &names[1..], prog_scope, depth)
+            }
+            // The spread entries `**base` and the if entries have no keys.
+            None => match &entry.node.value.node {
+                Expr::ConfigIfEntry(if_entry) => {
+                    find_key_in_entries(program, &if_entry.items, names, prog_scope, depth).or_else(
+                        || {
+                            if_entry.orelse.as_ref().and_then(|orelse| {
+                                find_key_in_value(program, orelse, names, prog_scope, depth)
+                            })
+                        },
+                    )
+                }
+                _ => find_key_in_value(program, &entry.node.value, names, prog_scope, depth),
+            },
+        })
+}
+
+/// The lambda parameters are defined with the position of the whole lambda expr in the
+/// scope, so locate the parameter in the innermost lambda whose body contains the position.
+fn locate_lambda_param(def: Definition, stmt: &Stmt, pos: &KCLPos) -> Definition {
+    match def {
+        Definition::Object(mut obj) if matches!(obj.kind, ScopeObjectKind::Parameter) => {
+            let mut collector = LambdaParamCollector {
+                name: obj.name.clone(),
+                pos,
+                param: None,
+            };
+            collector.walk_stmt(stmt);
+            if let Some((start, end)) = collector.param {
+                obj.start = start;
+                obj.end = end;
+            }
+            Definition::Object(obj)
+        }
+        def => def,
+    }
+}
+
+/// Finds the position of the lambda parameter named `name` in the innermost lambda whose
+/// body contains the position `pos`.
+struct LambdaParamCollector<'a> {
+    name: String,
+    pos: &'a KCLPos,
+    param: Option<(KCLPos, KCLPos)>,
+}
+
+impl MutSelfWalker for LambdaParamCollector<'_> {
+    fn walk_lambda_expr(&mut self, lambda_expr: &LambdaExpr) {
+        let body_contains_pos = match (lambda_expr.body.first(), lambda_expr.body.last()) {
+            (Some(first), Some(last)) => {
+                first.get_pos().less_equal(self.pos) && self.pos.less_equal(&last.get_end_pos())
+            }
+            _ => false,
+        };
+        if body_contains_pos {
+            if let Some(args) = &lambda_expr.args {
+                if let Some(arg) = args
+                    .node
+                    .args
+                    .iter()
+                    .find(|arg| arg.node.get_name() == self.name)
+                {
+                    self.param = Some((arg.get_pos(), arg.get_end_pos()));
+                }
+            }
+        }
+        // The nested lambdas are walked after the enclosing one, so the innermost wins.
+        for stmt in &lambda_expr.body {
+            self.walk_stmt(&stmt.node);
+        }
+    }
+}
+
 /// Similar to vars.rs/resolver_var, find a ScopeObj corresponding to the definition of identifier
 pub(crate) fn resolve_var(
     node_names: &[Node<String>],
@@ -222,6 +478,17 @@ pub(crate) fn resolve_var(
                             // Todo: find key def in dict
                             None
                         }
+                        // The inferred type of the variable may be a union, e.g.,
+                        // `Server {} if cond else None`, and the attr is found in the
+                        // first schema declaring it.
+                        kclvm_sema::ty::TypeKind::Union(types) => {
+                            types.iter().find_map(|ty| match &ty.kind {
+                                kclvm_sema::ty::TypeKind::Schema(schema_type) => {
+                                    find_attr_in_schema(schema_type, &node_names[1..], scope_map)
+                                }
+                                _ => None,
+                            })
+                        }
                         _ => None,
                     }
                 }
@@ -231,30 +498,288 @@ pub(crate) fn resolve_var(
     }
 }
 
+/// Finds the definition of the attr accessed on the value of a selector expr, e.g.,
+/// `image` in `servers[0].image`, where the schema of the element is resolved from the
+/// type of the subscripted value. The selector exprs in the string interpolations are
+/// found as well, e.g., `"${servers[0].image}"`.
+pub(crate) fn find_selector_attr_def(
+    node: &Node<Stmt>,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> Option<Definition> {
+    let mut finder = SelectorAttrFinder {
+        pos: kcl_pos,
+        selector: None,
+    };
+    finder.walk_stmt(&node.node);
+    let selector = finder.selector?;
+    let scope = prog_scope
+        .scope_map
+        .values()
+        .find_map(|scope| scope.borrow().inner_most(kcl_pos))?;
+    let value_ty = expr_ty(&selector.value.node, &scope, prog_scope)?;
+    match &value_ty.kind {
+        TypeKind::Schema(schema_ty) => {
+            find_attr_in_schema(schema_ty, &selector.attr.node.names, &prog_scope.scope_map)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the inferred type of the expr, e.g., the element type `Server` of `servers[0]`
+/// for `servers: [Server]`, or `float` of `a + 1.0` for `a: int`. The literal types of
+/// the operands are widened, e.g., `int` of `1 + 2` rather than `int(3)`.
+pub(crate) fn expr_ty(expr: &Expr, scope: &Scope, prog_scope: &ProgramScope) -> Option<Rc<Type>> {
+    match expr {
+        Expr::Identifier(id) => match resolve_var(&id.names, scope, &prog_scope.scope_map)? {
+            Definition::Object(obj) => Some(obj.ty),
+            Definition::Scope(_) => None,
+        },
+        Expr::Subscript(subscript) => {
+            let ty = expr_ty(&subscript.value.node, scope, prog_scope)?;
+            // The slice has the same type as the subscripted value.
+            if subscript.index.is_none() {
+                return Some(ty);
+            }
+            match &ty.kind {
+                TypeKind::List(item_ty) => Some(item_ty.clone()),
+                TypeKind::Dict(_, val_ty) => Some(val_ty.clone()),
+                TypeKind::Str | TypeKind::StrLit(_) => Some(ty),
+                _ => None,
+            }
+        }
+        Expr::Selector(selector) => match &expr_ty(&selector.value.node, scope, prog_scope)?.kind {
+            TypeKind::Schema(schema_ty) => {
+                schema_ty.get_type_of_attr(&get_identifier_last_name(&selector.attr.node))
+            }
+            _ => None,
+        },
+        Expr::Paren(paren_expr) => expr_ty(&paren_expr.expr.node, scope, prog_scope),
+        Expr::NumberLit(number_lit) => match (&number_lit.binary_suffix, &number_lit.value) {
+            (Some(_), _) => Some(Rc::new(Type::number_multiplier_non_lit_ty())),
+            (None, NumberLitValue::Int(_)) => Some(Rc::new(Type::INT)),
+            (None, NumberLitValue::Float(_)) => Some(Rc::new(Type::FLOAT)),
+        },
+        Expr::StringLit(_) | Expr::JoinedString(_) => Some(Rc::new(Type::STR)),
+        Expr::NameConstantLit(name_constant) => match name_constant.value {
+            NameConstant::True | NameConstant::False => Some(Rc::new(Type::BOOL)),
+            NameConstant::None | NameConstant::Undefined => Some(Rc::new(Type::NONE)),
+        },
+        Expr::Compare(_) => Some(Rc::new(Type::BOOL)),
+        Expr::Unary(unary_expr) => match unary_expr.op {
+            UnaryOp::Not => Some(Rc::new(Type::BOOL)),
+            _ => expr_ty(&unary_expr.operand.node, scope, prog_scope).map(widen_literal_ty),
+        },
+        Expr::Binary(binary_expr) => {
+            let op = match &binary_expr.op {
+                BinOrCmpOp::Bin(op) => op,
+                BinOrCmpOp::Cmp(_) => return Some(Rc::new(Type::BOOL)),
+            };
+            if let BinOp::And = op {
+                return Some(Rc::new(Type::BOOL));
+            }
+            let left = widen_literal_ty(expr_ty(&binary_expr.left.node, scope, prog_scope)?);
+            let right = widen_literal_ty(expr_ty(&binary_expr.right.node, scope, prog_scope)?);
+            binary_ty(op, left, right)
+        }
+        Expr::If(if_expr) => {
+            let body = widen_literal_ty(expr_ty(&if_expr.body.node, scope, prog_scope)?);
+            let orelse = widen_literal_ty(expr_ty(&if_expr.orelse.node, scope, prog_scope)?);
+            Some(sup(&[body, orelse]))
+        }
+        Expr::Call(call_expr) => {
+            let func_ty = expr_ty(&call_expr.func.node, scope, prog_scope)?;
+            match &func_ty.kind {
+                TypeKind::Function(func_ty) => Some(func_ty.return_ty.clone()),
+                // The schema is called to instantiate it, e.g., `Server()`.
+                TypeKind::Schema(_) => Some(func_ty),
+                _ => None,
+            }
+        }
+        Expr::Schema(schema_expr) => {
+            let ty = expr_ty(
+                &Expr::Identifier(schema_expr.name.node.clone()),
+                scope,
+                prog_scope,
+            )?;
+            ty.is_schema().then_some(ty)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the type of the result of the binary operation on the operands of the types,
+/// following the binary operator calculation table of the resolver.
+fn binary_ty(op: &BinOp, left: Rc<Type>, right: Rc<Type>) -> Option<Rc<Type>> {
+    if left.is_any() || right.is_any() {
+        return Some(Rc::new(Type::ANY));
+    }
+    let number = || {
+        if left.is_float() || right.is_float() {
+            Rc::new(Type::FLOAT)
+        } else {
+            Rc::new(Type::INT)
+        }
+    };
+    match op {
+        BinOp::Add if left.is_number() && right.is_number() => Some(number()),
+        BinOp::Add if left.is_str() && right.is_str() => Some(Rc::new(Type::STR)),
+        BinOp::Add if left.is_list() && right.is_list() => Some(Type::list_ref(sup(&[
+            left.list_item_ty(),
+            right.list_item_ty(),
+        ]))),
+        BinOp::Sub | BinOp::Pow | BinOp::Div | BinOp::FloorDiv
+            if left.is_number() && right.is_number() =>
+        {
+            Some(number())
+        }
+        BinOp::Mul if left.is_number() && right.is_number() => Some(number()),
+        // The repetition of the string or the list, e.g., `"a" * 2`.
+        BinOp::Mul if left.is_int() && (right.is_str() || right.is_list()) => Some(right),
+        BinOp::Mul if (left.is_str() || left.is_list()) && right.is_int() => Some(left),
+        BinOp::Mod | BinOp::LShift | BinOp::RShift | BinOp::BitXor | BinOp::BitAnd
+            if left.is_number() && right.is_number() =>
+        {
+            Some(Rc::new(Type::INT))
+        }
+        BinOp::BitOr if left.is_int() && right.is_int() => Some(Rc::new(Type::INT)),
+        BinOp::BitOr if left.is_schema() => Some(left),
+        BinOp::BitOr | BinOp::Or => Some(sup(&[left, right])),
+        _ => None,
+    }
+}
+
+/// Widens the literal type to its variable type, e.g., `int(1)` to `int`.
+fn widen_literal_ty(ty: Rc<Type>) -> Rc<Type> {
+    match &ty.kind {
+        TypeKind::BoolLit(_) => Rc::new(Type::BOOL),
+        TypeKind::IntLit(_) => Rc::new(Type::INT),
+        TypeKind::FloatLit(_) => Rc::new(Type::FLOAT),
+        TypeKind::StrLit(_) => Rc::new(Type::STR),
+        _ => ty,
+    }
+}
+
+/// Finds the selector expr whose attr contains the position, e.g., `servers[0].image`.
+struct SelectorAttrFinder<'a> {
+    pos: &'a KCLPos,
+    selector: Option<SelectorExpr>,
+}
+
+impl MutSelfWalker for SelectorAttrFinder<'_> {
+    fn walk_selector_expr(&mut self, selector_expr: &SelectorExpr) {
+        if selector_expr.attr.contains_pos(self.pos) {
+            self.selector = Some(selector_expr.clone());
+        }
+        self.walk_expr(&selector_expr.value.node);
+    }
+}
+
 pub fn find_attr_in_schema(
     schema_type: &SchemaType,
     names: &[Node<String>],
     scope_map: &IndexMap<String, Rc<RefCell<Scope>>>,
 ) -> Option<Definition> {
-    let schema_pkg_scope = get_pkg_scope(&schema_type.pkgpath, scope_map);
     let names = if schema_type.pkgpath.is_empty() {
         &names[1..]
     } else {
         names
     };
-    for child in &schema_pkg_scope.children {
-        let child_scope = child.borrow();
-        if let kclvm_sema::resolver::scope::ScopeKind::Schema(schema_name) = &child_scope.kind {
-            if schema_name == &schema_type.name {
-                return resolve_var(&names, &child_scope, scope_map);
+    match find_declared_attr_in_schema(schema_type, names, scope_map) {
+        Some(def) => Some(def),
+        None => get_schema_scope(schema_type, scope_map)
+            .and_then(|schema_scope| resolve_var(names, &schema_scope.borrow(), scope_map)),
+    }
+}
+
+/// Find the attr declared in the schema or in its base schema, protocol and mixins.
+fn find_declared_attr_in_schema(
+    schema_type: &SchemaType,
+    names: &[Node<String>],
+    scope_map: &IndexMap<String, Rc<RefCell<Scope>>>,
+) -> Option<Definition> {
+    let name = &names.first()?.node;
+    if let Some(schema_scope) = get_schema_scope(schema_type, scope_map) {
+        let schema_scope = schema_scope.borrow();
+        if schema_scope.elems.contains_key(name) {
+            return resolve_var(names, &schema_scope, scope_map);
+        }
+    }
+    schema_type
+        .base
+        .iter()
+        .chain(schema_type.protocol.iter())
+        .map(|ty| ty.as_ref())
+        .chain(schema_type.mixins.iter())
+        .find_map(|ty| find_declared_attr_in_schema(ty, names, scope_map))
+}
+
+/// Find the attr in the schema which encloses the scope, e.g., the protocol attr
+/// referenced in the body of a mixin or a rule.
+fn find_attr_in_enclosing_schema(
+    names: &[Node<String>],
+    scope: &Scope,
+    scope_map: &IndexMap<String, Rc<RefCell<Scope>>>,
+) -> Option<Definition> {
+    match &scope.kind {
+        ScopeKind::Schema(schema_name) => {
+            let ty = scope.lookup(schema_name)?.borrow().ty.clone();
+            match &ty.kind {
+                TypeKind::Schema(schema_type) => {
+                    find_declared_attr_in_schema(schema_type, names, scope_map)
+                }
+                _ => None,
             }
         }
+        ScopeKind::Package(_) | ScopeKind::Builtin => None,
+        _ => {
+            find_attr_in_enclosing_schema(names, &scope.get_enclosing_scope()?.borrow(), scope_map)
+        }
     }
-    None
+}
+
+/// Follows the variable re-exporting a schema to the declaration of the schema, e.g.,
+/// `Server` declared by `Server = base.Server` to `schema Server` in `base`. The type of
+/// the variable is the schema itself, so the chains of any length end at the declaration.
+fn follow_reexport(obj: ScopeObject, prog_scope: &ProgramScope) -> ScopeObject {
+    let schema_ty = match (&obj.kind, &obj.ty.kind) {
+        (ScopeObjectKind::Variable, TypeKind::Schema(schema_ty)) if !schema_ty.is_instance => {
+            schema_ty
+        }
+        _ => return obj,
+    };
+    let decl = prog_scope
+        .scope_map
+        .get(&pkgpath_without_prefix!(schema_ty.pkgpath))
+        .and_then(|scope| {
+            scope
+                .borrow()
+                .elems
+                .get(&schema_ty.name)
+                .map(|decl| decl.borrow().clone())
+        })
+        .filter(|decl| matches!(decl.kind, ScopeObjectKind::Definition));
+    decl.unwrap_or(obj)
+}
+
+/// Get the scope of the schema in the package where the schema is defined.
+fn get_schema_scope(
+    schema_type: &SchemaType,
+    scope_map: &IndexMap<String, Rc<RefCell<Scope>>>,
+) -> Option<Rc<RefCell<Scope>>> {
+    get_pkg_scope(&schema_type.pkgpath, scope_map)
+        .children
+        .iter()
+        .find(|child| match &child.borrow().kind {
+            ScopeKind::Schema(schema_name) => schema_name == &schema_type.name,
+            _ => false,
+        })
+        .cloned()
 }
 
 // Convert kcl position to GotoDefinitionResponse. This function will convert to
-// None, Scalar or Array according to the number of positions
+// None, Scalar or Array according to the number of positions. The symlinks in the paths
+// are resolved, so that the same file is located by the same uri through any link.
 fn positions_to_goto_def_resp(
     positions: &IndexSet<(KCLPos, KCLPos)>,
 ) -> Option<GotoDefinitionResponse> {
@@ -263,7 +788,7 @@ fn positions_to_goto_def_resp(
         1 => {
             let (start, end) = positions.iter().next().unwrap().clone();
             Some(lsp_types::GotoDefinitionResponse::Scalar(Location {
-                uri: Url::from_file_path(start.filename.clone()).unwrap(),
+                uri: Url::from_file_path(canonicalize(Path::new(&start.filename))).unwrap(),
                 range: Range {
                     start: lsp_pos(&start),
                     end: lsp_pos(&end),
@@ -274,7 +799,7 @@ fn positions_to_goto_def_resp(
             let mut res = vec![];
             for (start, end) in positions {
                 res.push(Location {
-                    uri: Url::from_file_path(start.filename.clone()).unwrap(),
+                    uri: Url::from_file_path(canonicalize(Path::new(&start.filename))).unwrap(),
                     range: Range {
                         start: lsp_pos(start),
                         end: lsp_pos(end),
@@ -289,9 +814,14 @@ fn positions_to_goto_def_resp(
 fn goto_def_for_import(
     stmt: &ImportStmt,
     _kcl_pos: &KCLPos,
-    _prog_scope: &ProgramScope,
+    prog_scope: &ProgramScope,
     program: &Program,
 ) -> Option<GotoDefinitionResponse> {
+    if let Some(obj) = find_imported_schema(stmt, prog_scope) {
+        let mut positions = IndexSet::new();
+        positions.insert((obj.start, obj.end));
+        return positions_to_goto_def_resp(&positions);
+    }
     let pkgpath = &stmt.path;
     let mut real_path =
         Path::new(&program.root).join(pkgpath.replace('.', &std::path::MAIN_SEPARATOR.to_string()));
@@ -307,6 +837,26 @@ fn goto_def_for_import(
     positions_to_goto_def_resp(&positions)
 }
 
+/// Finds the only schema declared in the imported module, e.g., `Server` of
+/// `import .models.server` whose module declares the schema `Server` only. The modules
+/// declaring several schemas or none are navigated to their files.
+fn find_imported_schema(stmt: &ImportStmt, prog_scope: &ProgramScope) -> Option<ScopeObject> {
+    let scope = prog_scope.scope_map.get(&stmt.path)?.borrow();
+    let mut schemas = scope
+        .elems
+        .values()
+        .map(|obj| obj.borrow().clone())
+        .filter(|obj| {
+            matches!(obj.kind, ScopeObjectKind::Definition)
+                && matches!(obj.ty.kind, TypeKind::Schema(_))
+                && !obj.start.filename.is_empty()
+        });
+    match (schemas.next(), schemas.next()) {
+        (Some(schema), None) => Some(schema),
+        _ => None,
+    }
+}
+
 pub(crate) fn get_identifier_last_name(id: &Identifier) -> String {
     match id.names.len() {
         0 => "".to_string(),
@@ -1,41 +1,589 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
 use indexmap::IndexSet;
-use kclvm_ast::ast::Program;
+use kclvm_ast::ast::{self, Node, Program, Stmt};
+use kclvm_ast::pos::ContainsPos;
+use kclvm_ast_pretty::{print_ast_node, ASTNode};
 use kclvm_error::Position as KCLPos;
-use kclvm_sema::resolver::scope::{ProgramScope, ScopeObjectKind};
-use lsp_types::{Hover, HoverContents, MarkedString};
+use kclvm_sema::resolver::scope::{ProgramScope, ScopeKind, ScopeObject, ScopeObjectKind};
+use kclvm_sema::ty::{SchemaAttr, SchemaType, Type, TypeKind};
+use lsp_types::{
+    ClientCapabilities, Hover, HoverContents, Location, MarkedString, MarkupContent, MarkupKind,
+    Position, Range, Url,
+};
+
+use crate::from_lsp::file_path_from_url;
+use crate::goto_def::{expr_ty, find_def, find_selector_attr_def, Definition};
+use crate::util::{inner_most_expr_in_stmt, parse_param_and_compile, utf16_columns, Param};
+
+/// The separator between the sections of the Markdown hover content.
+const MARKDOWN_SECTION_SEPARATOR: &str = "\n\n---\n\n";
+
+/// The max length of a type string in the hover content. The type string of a deeply
+/// nested type beyond the length is truncated with an ellipsis.
+const MAX_TYPE_STR_LEN: usize = 256;
+
+/// The max number of the schema attributes in the hover content.
+const MAX_HOVER_ATTRS: usize = 64;
+
+/// The max number of the subtypes in the hover content of a schema.
+const MAX_HOVER_SUBTYPES: usize = 10;
+
+/// The ellipsis appended to the truncated hover content.
+const ELLIPSIS: &str = "...";
 
-use crate::goto_def::find_def;
+/// The structured description of a symbol or an expr at a position, from which the hover
+/// content is formatted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HoverInfo {
+    /// The name of the symbol, or `None` if the position is not on a name.
+    pub name: Option<String>,
+    /// The kind of the symbol, or `None` if the position is not on a name.
+    pub kind: Option<ScopeObjectKind>,
+    /// The type string of the symbol or the expr.
+    pub ty: String,
+    /// The signature shown as the code block, e.g., the type string of a variable or
+    /// `type Alias = int` of a type alias.
+    pub signature: String,
+    /// The docstring of the schema.
+    pub doc: Option<String>,
+    /// The attributes of the schema, e.g., `name: str`.
+    pub attrs: Vec<String>,
+}
+
+/// Returns the structured description of the element at the position in the file without
+/// a language server, e.g., for the external documentation tools.
+pub fn hover_info(file: &str, pos: KCLPos) -> anyhow::Result<Option<HoverInfo>> {
+    let (program, prog_scope, _) = parse_param_and_compile(
+        Param {
+            file: file.to_string(),
+        },
+        None,
+    )?;
+    Ok(hover_infos(&program, &pos, &prog_scope).into_iter().next())
+}
 
 /// Returns a short text describing element at position.
 /// Specifically, the doc for schema and schema attr(todo)
+/// When `markdown` is true, the signature, docstring and attributes are
+/// rendered as Markdown sections separated by horizontal rules.
+/// The range of the hovered name is read from the file on the disk, and the requests use
+/// `hover_range` with the document text in the vfs instead.
+#[cfg(test)]
 pub(crate) fn hover(
     program: &Program,
     kcl_pos: &KCLPos,
     prog_scope: &ProgramScope,
+    markdown: bool,
 ) -> Option<lsp_types::Hover> {
-    match program.pos_to_stmt(kcl_pos) {
-        Some(node) => {
-            let mut docs: IndexSet<String> = IndexSet::new();
-            if let Some(def) = find_def(node, kcl_pos, prog_scope) {
-                if let crate::goto_def::Definition::Object(obj) = def {
-                    match obj.kind {
-                        ScopeObjectKind::Definition => {
-                            docs.insert(obj.ty.ty_str());
-                            let doc = obj.ty.into_schema_type().doc.clone();
-                            if !doc.is_empty() {
-                                docs.insert(doc);
-                            }
-                        }
-                        _ => {
-                            docs.insert(obj.ty.ty_str());
-                        }
-                    }
+    let infos = hover_infos(program, kcl_pos, prog_scope);
+    let text = std::fs::read_to_string(&kcl_pos.filename).unwrap_or_default();
+    let range = hover_range(&infos, &text, kcl_pos);
+    infos_to_hover(infos, markdown).map(|hover| Hover { range, ..hover })
+}
+
+/// Returns the range of the hovered name, which the client highlights, or `None` if the
+/// hover describes the type of an expr rather than a name, e.g., on an operator.
+pub(crate) fn hover_range(infos: &[HoverInfo], text: &str, kcl_pos: &KCLPos) -> Option<Range> {
+    infos.first()?.name.as_ref()?;
+    token_range(text, kcl_pos)
+}
+
+/// Returns the range of the identifier token at the position in the text. The columns of
+/// the KCL position are the char indexes of the line, and the columns of the range are the
+/// UTF-16 code units, e.g., `a` of `b = "😀" + a` is at the char 10 and the column 11.
+pub(crate) fn token_range(text: &str, kcl_pos: &KCLPos) -> Option<Range> {
+    let line = kcl_pos.line.checked_sub(1)?;
+    let content = text.lines().nth(line as usize)?;
+    let chars: Vec<char> = content.chars().collect();
+    let is_id_char = |i: usize| {
+        chars
+            .get(i)
+            .map_or(false, |c| rustc_lexer::is_id_continue(*c))
+    };
+    let index = kcl_pos.column? as usize;
+    if !is_id_char(index) {
+        return None;
+    }
+    let mut start = index;
+    while start > 0 && is_id_char(start - 1) {
+        start -= 1;
+    }
+    let mut end = index + 1;
+    while is_id_char(end) {
+        end += 1;
+    }
+    let columns = utf16_columns(content);
+    Some(Range::new(
+        Position::new(line as u32, columns[start]),
+        Position::new(line as u32, columns[end]),
+    ))
+}
+
+/// Formats the descriptions of the element as the hover content.
+pub(crate) fn infos_to_hover(infos: Vec<HoverInfo>, markdown: bool) -> Option<lsp_types::Hover> {
+    let infos = infos.into_iter();
+    if markdown {
+        let sections: Vec<String> = infos.map(info_to_markdown).collect();
+        if sections.is_empty() {
+            return None;
+        }
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: sections.join(MARKDOWN_SECTION_SEPARATOR),
+            }),
+            range: None,
+        })
+    } else {
+        docs_to_hover(
+            infos
+                .flat_map(|info| std::iter::once(info.signature).chain(info.doc))
+                .collect(),
+        )
+    }
+}
+
+/// Returns the descriptions of the element at the position.
+/// If the name at the position is ambiguous, e.g., a schema attr shadowing a schema with
+/// the same name, all the interpretations are described, from the innermost one.
+/// If the position is not on a name, the inferred type of the expr is described.
+pub(crate) fn hover_infos(
+    program: &Program,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> Vec<HoverInfo> {
+    let node = match program.pos_to_stmt(kcl_pos) {
+        Some(node) => node,
+        None => return vec![],
+    };
+    if let Some(info) = attr_decl_info(&node, kcl_pos, true) {
+        return vec![info];
+    }
+    let objs = hover_objs(&node, kcl_pos, prog_scope);
+    if objs.is_empty() {
+        if let Some(info) = attr_decl_info(&node, kcl_pos, false) {
+            return vec![info];
+        }
+        // Not on a named symbol, e.g., on the operator of `a + b`, then the inferred type
+        // of the innermost expr is described.
+        return enclosing_expr_ty(&node, kcl_pos, prog_scope)
+            .map(|ty| {
+                let ty = ty_str_with_budget(&ty);
+                HoverInfo {
+                    name: None,
+                    kind: None,
+                    signature: ty.clone(),
+                    ty,
+                    doc: None,
+                    attrs: vec![],
+                }
+            })
+            .into_iter()
+            .collect();
+    }
+    objs.iter()
+        .map(|obj| describe_obj(program, obj, prog_scope))
+        .collect()
+}
+
+/// Returns the description of the schema attr declaration whose `?` or default value is at
+/// the position, e.g., `port?: int = 80`, which shows the optionality and the default
+/// value. Only the `?` is matched if `optional_mark` is true, otherwise the default value.
+fn attr_decl_info(node: &Node<Stmt>, kcl_pos: &KCLPos, optional_mark: bool) -> Option<HoverInfo> {
+    let schema_stmt = match &node.node {
+        Stmt::Schema(schema_stmt) => schema_stmt,
+        _ => return None,
+    };
+    let attr = schema_stmt
+        .body
+        .iter()
+        .filter(|stmt| stmt.contains_pos(kcl_pos))
+        .find_map(|stmt| match &stmt.node {
+            Stmt::SchemaAttr(attr) => Some(attr),
+            _ => None,
+        })?;
+    let matched = if optional_mark {
+        attr.is_optional
+            && kcl_pos.line == attr.name.end_line
+            && kcl_pos.column == Some(attr.name.end_column)
+    } else {
+        attr.value
+            .as_ref()
+            .map_or(false, |value| value.contains_pos(kcl_pos))
+    };
+    if !matched {
+        return None;
+    }
+    let ty = attr.ty.node.to_string();
+    let default = attr
+        .value
+        .as_ref()
+        .map(|value| print_ast_node(ASTNode::Expr(value)));
+    let mut signature = format!(
+        "{}{}: {}",
+        attr.name.node,
+        if attr.is_optional { "?" } else { "" },
+        ty
+    );
+    let mut doc = vec![format!(
+        "The attribute is {}",
+        if attr.is_optional {
+            "optional"
+        } else {
+            "required"
+        }
+    )];
+    if let Some(default) = &default {
+        signature.push_str(&format!(" = {}", default));
+        doc.push(format!("the default value is `{}`", default));
+    }
+    Some(HoverInfo {
+        name: Some(attr.name.node.clone()),
+        kind: Some(ScopeObjectKind::Attribute),
+        ty,
+        signature,
+        doc: Some(doc.join(", ")),
+        attrs: vec![],
+    })
+}
+
+/// Returns the objects described by the hover at the position, from the innermost one.
+fn hover_objs(node: &Node<Stmt>, kcl_pos: &KCLPos, prog_scope: &ProgramScope) -> Vec<ScopeObject> {
+    let shadowed = shadowed_objs(node, kcl_pos, prog_scope);
+    if shadowed.len() > 1 {
+        return shadowed;
+    }
+    // The attr of the subscripted element is resolved first, otherwise it may be
+    // resolved to a variable with the same name.
+    let def = find_selector_attr_def(node, kcl_pos, prog_scope)
+        .or_else(|| find_def(node.clone(), kcl_pos, prog_scope));
+    match def {
+        Some(Definition::Object(obj)) => vec![obj],
+        _ => vec![],
+    }
+}
+
+/// Returns the name and the declaration position of the schema or the schema attr at the
+/// position, whose references are counted by `count_references`.
+pub(crate) fn reference_target(
+    program: &Program,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> Option<(String, KCLPos)> {
+    let node = program.pos_to_stmt(kcl_pos)?;
+    let obj = hover_objs(&node, kcl_pos, prog_scope).into_iter().next()?;
+    let counted = match obj.kind {
+        ScopeObjectKind::Definition => matches!(obj.ty.kind, TypeKind::Schema(_)),
+        ScopeObjectKind::Attribute => true,
+        _ => false,
+    };
+    counted.then(|| (obj.name.clone(), obj.start.clone()))
+}
+
+/// Returns the number of the references of the name declared at the position, which is the
+/// number of the occurrences of the name in the cached word index excluding the
+/// declaration. The index is not refreshed, so the count is approximate.
+pub(crate) fn count_references(
+    name: &str,
+    decl: &KCLPos,
+    word_index_map: &HashMap<Url, HashMap<String, Vec<Location>>>,
+) -> usize {
+    let decl_line = decl.line.saturating_sub(1) as u32;
+    word_index_map
+        .values()
+        .filter_map(|index| index.get(name))
+        .flatten()
+        .filter(|loc| {
+            loc.range.start.line != decl_line
+                || file_path_from_url(&loc.uri).map_or(true, |file| file != decl.filename)
+        })
+        .count()
+}
+
+/// Appends the line of the reference count to the hover content, as the last section of
+/// the Markdown content or the last marked string.
+pub(crate) fn with_reference_count(hover: Hover, count: usize) -> Hover {
+    let line = match count {
+        1 => "1 reference".to_string(),
+        _ => format!("{} references", count),
+    };
+    with_section(hover, line)
+}
+
+/// Returns the names of the direct subtypes of the schema, i.e., the schemas in the
+/// programs whose base schemas are named after it, sorted by their names. The base schemas
+/// are matched by their names without resolving, so the subtypes are approximate like the
+/// reference count.
+pub(crate) fn direct_subtypes<'a>(
+    name: &str,
+    programs: impl Iterator<Item = &'a Program>,
+) -> Vec<String> {
+    // The modules shared by the programs are scanned once.
+    let mut scanned = HashSet::new();
+    let mut subtypes = vec![];
+    for module in programs.flat_map(|program| program.pkgs.values().flatten()) {
+        if !scanned.insert(&module.filename) {
+            continue;
+        }
+        for stmt in &module.body {
+            if let Stmt::Schema(schema_stmt) = &stmt.node {
+                let base = schema_stmt
+                    .parent_name
+                    .as_ref()
+                    .and_then(|parent| parent.node.names.last());
+                if base.map_or(false, |base| base.node == name) {
+                    subtypes.push(schema_stmt.name.node.clone());
                 }
             }
-            docs_to_hover(docs)
         }
-        None => None,
     }
+    subtypes.sort();
+    subtypes
+}
+
+/// Appends the line of the direct subtypes to the hover content, which lists the first
+/// `MAX_HOVER_SUBTYPES` subtypes and the number of the others.
+pub(crate) fn with_subtypes(hover: Hover, subtypes: &[String]) -> Hover {
+    let mut names: Vec<String> = subtypes.iter().take(MAX_HOVER_SUBTYPES).cloned().collect();
+    if subtypes.len() > MAX_HOVER_SUBTYPES {
+        names.push(format!("+{} more", subtypes.len() - MAX_HOVER_SUBTYPES));
+    }
+    with_section(hover, format!("Subtypes: {}", names.join(", ")))
+}
+
+/// Appends the line to the hover content, as the last section of the Markdown content or
+/// the last marked string.
+fn with_section(hover: Hover, line: String) -> Hover {
+    let contents = match hover.contents {
+        HoverContents::Markup(mut content) => {
+            content.value.push_str(MARKDOWN_SECTION_SEPARATOR);
+            content.value.push_str(&line);
+            HoverContents::Markup(content)
+        }
+        HoverContents::Scalar(doc) => HoverContents::Array(vec![doc, MarkedString::String(line)]),
+        HoverContents::Array(mut docs) => {
+            docs.push(MarkedString::String(line));
+            HoverContents::Array(docs)
+        }
+    };
+    Hover { contents, ..hover }
+}
+
+/// Returns the inferred type of the innermost expr at the position which is not a name,
+/// e.g., `int` of `a + 1` for `a: int`.
+fn enclosing_expr_ty(
+    node: &Node<Stmt>,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> Option<Rc<Type>> {
+    let expr = inner_most_expr_in_stmt(&node.node, kcl_pos, None).0?;
+    if matches!(expr.node, ast::Expr::Identifier(_)) {
+        return None;
+    }
+    let scope = prog_scope
+        .scope_map
+        .values()
+        .find_map(|scope| scope.borrow().inner_most(kcl_pos))?;
+    expr_ty(&expr.node, &scope, prog_scope)
+}
+
+/// Returns the description of the object.
+pub(crate) fn describe_obj(
+    program: &Program,
+    obj: &ScopeObject,
+    prog_scope: &ProgramScope,
+) -> HoverInfo {
+    let ty = ty_str_with_budget(&obj.ty);
+    let mut info = HoverInfo {
+        name: Some(obj.name.clone()),
+        kind: Some(obj.kind.clone()),
+        signature: ty.clone(),
+        ty,
+        doc: None,
+        attrs: vec![],
+    };
+    match obj.kind {
+        ScopeObjectKind::Definition => {
+            if let TypeKind::Schema(schema_ty) = &obj.ty.kind {
+                if !schema_ty.doc.is_empty() {
+                    info.doc = Some(schema_ty.doc.clone());
+                }
+                let mut schema_attrs = schema_attrs(schema_ty);
+                info.attrs = schema_attrs
+                    .by_ref()
+                    .take(MAX_HOVER_ATTRS)
+                    .map(|(name, attr)| format!("{}: {}", name, ty_str_with_budget(&attr.ty)))
+                    .collect();
+                if schema_attrs.next().is_some() {
+                    info.attrs.push(ELLIPSIS.to_string());
+                }
+            }
+        }
+        ScopeObjectKind::TypeAlias => {
+            info.signature = format!("type {} = {}", obj.name, info.ty);
+        }
+        _ => {
+            if let Some(alias) = annotated_type_alias(program, obj, prog_scope) {
+                info.signature = format!("{} (= {})", alias, info.ty);
+            }
+        }
+    }
+    info
+}
+
+/// Returns the objects with the name of the single-name identifier at the position in the
+/// enclosing scopes from the innermost one, e.g., the schema attr `Name` and the schema
+/// `Name` shadowed by it.
+fn shadowed_objs(
+    node: &Node<Stmt>,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> Vec<ScopeObject> {
+    let name = match inner_most_expr_in_stmt(&node.node, kcl_pos, None) {
+        (Some(expr), None) => match expr.node {
+            ast::Expr::Identifier(id) if id.names.len() == 1 && id.pkgpath.is_empty() => {
+                id.names[0].node.clone()
+            }
+            _ => return vec![],
+        },
+        _ => return vec![],
+    };
+    let mut scope = match prog_scope
+        .scope_map
+        .values()
+        .find_map(|scope| scope.borrow().inner_most(kcl_pos))
+    {
+        Some(scope) => scope,
+        None => return vec![],
+    };
+    let mut objs: Vec<ScopeObject> = vec![];
+    loop {
+        if matches!(scope.kind, ScopeKind::Builtin) {
+            break;
+        }
+        if let Some(obj) = scope.elems.get(&name) {
+            let obj = obj.borrow();
+            if !objs.iter().any(|other| other.start == obj.start) {
+                objs.push(obj.clone());
+            }
+        }
+        scope = match scope.get_enclosing_scope() {
+            Some(parent) => parent.borrow().clone(),
+            None => break,
+        };
+    }
+    objs
+}
+
+/// Returns the name of the type alias annotated on the declaration of the variable or the
+/// schema attr, e.g., `Alias` for `a: Alias = 1`. The type of the object is the expansion
+/// of the alias, and the annotation in the AST keeps the alias name.
+fn annotated_type_alias(
+    program: &Program,
+    obj: &ScopeObject,
+    prog_scope: &ProgramScope,
+) -> Option<String> {
+    let stmt = program.pos_to_stmt(&obj.start)?;
+    let ty = match &stmt.node {
+        Stmt::Assign(assign_stmt) => assign_stmt.ty.as_ref()?.node.clone(),
+        Stmt::Schema(schema_stmt) => schema_stmt
+            .body
+            .iter()
+            .filter(|stmt| stmt.contains_pos(&obj.start))
+            .find_map(|stmt| match &stmt.node {
+                Stmt::SchemaAttr(schema_attr) => Some(schema_attr.ty.node.clone()),
+                _ => None,
+            })?,
+        _ => return None,
+    };
+    let alias = match ty {
+        ast::Type::Named(identifier) => identifier.get_name(),
+        _ => return None,
+    };
+    let name = alias.rsplit('.').next()?;
+    prog_scope
+        .scope_map
+        .values()
+        .any(|scope| {
+            scope.borrow().elems.get(name).map_or(false, |obj| {
+                matches!(obj.borrow().kind, ScopeObjectKind::TypeAlias)
+            })
+        })
+        .then_some(alias)
+}
+
+/// Returns the attrs declared in the schema, excluding the internal `__settings__` attr.
+pub(crate) fn schema_attrs(schema_ty: &SchemaType) -> impl Iterator<Item = (&String, &SchemaAttr)> {
+    schema_ty
+        .attrs
+        .iter()
+        .filter(|(name, _)| name.as_str() != "__settings__")
+}
+
+/// Returns the type string of the type, which is truncated with an ellipsis once it
+/// exceeds `MAX_TYPE_STR_LEN`. The nested types are not stringified beyond the budget,
+/// so that the hover on a pathological type returns promptly.
+pub(crate) fn ty_str_with_budget(ty: &Type) -> String {
+    let mut ty_str = String::new();
+    if write_ty_str(ty, &mut ty_str, MAX_TYPE_STR_LEN) {
+        return ty_str;
+    }
+    let end = (0..=MAX_TYPE_STR_LEN.min(ty_str.len()))
+        .rev()
+        .find(|i| ty_str.is_char_boundary(*i))
+        .unwrap_or(0);
+    ty_str.truncate(end);
+    ty_str.push_str(ELLIPSIS);
+    ty_str
+}
+
+/// Writes the type string of the type into `out` as `Type::ty_str` does, and returns
+/// false once the length of `out` exceeds `max_len`.
+fn write_ty_str(ty: &Type, out: &mut String, max_len: usize) -> bool {
+    match &ty.kind {
+        TypeKind::List(item_ty) => {
+            out.push('[');
+            if !write_ty_str(item_ty, out, max_len) {
+                return false;
+            }
+            out.push(']');
+        }
+        TypeKind::Dict(key_ty, val_ty) => {
+            out.push('{');
+            if !write_ty_str(key_ty, out, max_len) {
+                return false;
+            }
+            out.push(':');
+            if !write_ty_str(val_ty, out, max_len) {
+                return false;
+            }
+            out.push('}');
+        }
+        TypeKind::Union(types) => {
+            for (i, ty) in types.iter().enumerate() {
+                if i > 0 {
+                    out.push('|');
+                }
+                if !write_ty_str(ty, out, max_len) {
+                    return false;
+                }
+            }
+        }
+        _ => out.push_str(&ty.ty_str()),
+    }
+    out.len() <= max_len
+}
+
+/// Whether the client supports the Markdown hover content.
+pub(crate) fn markdown_supported(client_capabilities: &ClientCapabilities) -> bool {
+    client_capabilities
+        .text_document
+        .as_ref()
+        .and_then(|text_document| text_document.hover.as_ref())
+        .and_then(|hover| hover.content_format.as_ref())
+        .map_or(false, |formats| formats.contains(&MarkupKind::Markdown))
 }
 
 // Convert docs to Hover. This function will convert to
@@ -57,3 +605,20 @@ fn docs_to_hover(docs: IndexSet<String>) -> Option<lsp_types::Hover> {
         }),
     }
 }
+
+// Convert the description to the Markdown content. The signature, docstring
+// and attributes sections are joined with `---` horizontal rules.
+pub(crate) fn info_to_markdown(info: HoverInfo) -> String {
+    let mut sections: Vec<String> = vec![format!("```kcl\n{}\n```", info.signature)];
+    sections.extend(info.doc);
+    if !info.attrs.is_empty() {
+        sections.push(
+            info.attrs
+                .iter()
+                .map(|attr| format!("- `{}`", attr))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        );
+    }
+    sections.join(MARKDOWN_SECTION_SEPARATOR)
+}
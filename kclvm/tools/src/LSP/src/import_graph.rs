@@ -0,0 +1,177 @@
+//! The import graph of the workspace folders, i.e., the `kcl.importGraph` command, which
+//! returns the import statements of the KCL files as the edges from the package of each
+//! file to the imported packages for the documentation and the dependency analysis.
+//!
+//! The programs in the analysis db are reused for the compiled files, and the other files
+//! are parsed unless they have been covered by the program of a previous entry. The
+//! circular imports between the packages are reported along with the edges.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use kclvm_ast::ast::{Program, Stmt};
+use kclvm_ast::MAIN_PKG;
+use lsp_types::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::from_lsp::file_path_from_url;
+use crate::state::LanguageServerSnapshot;
+use crate::util::{get_kcl_files_with_extensions, parse_param, Param};
+
+/// The arguments of the `kcl.importGraph` command.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct ImportGraphArgs {
+    /// The folders whose import graph is computed, which are the workspace folders if omitted.
+    pub folders: Option<Vec<Url>>,
+}
+
+/// An import statement in a file, from the package of the file to the imported package.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub(crate) struct ImportEdge {
+    /// The file of the import statement.
+    pub file: Url,
+    /// The package path of the file, which is `__main__` for the files in the root.
+    pub from: String,
+    /// The package path of the imported package.
+    pub to: String,
+}
+
+/// The import graph of the workspace folders.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ImportGraph {
+    /// The edges ordered by the files and the packages.
+    pub edges: Vec<ImportEdge>,
+    /// The circular imports, each of which is the packages on the cycle starting from the
+    /// least one, e.g., `["a", "b"]` for `a` importing `b` and `b` importing `a`.
+    pub cycles: Vec<Vec<String>>,
+}
+
+/// Computes the import graph of the KCL files in the folders, which include the imports of
+/// the packages out of the folders reachable from the files in the folders.
+pub(crate) fn import_graph(
+    snapshot: &LanguageServerSnapshot,
+    folders: &[Url],
+) -> anyhow::Result<ImportGraph> {
+    let roots = folders
+        .iter()
+        .map(|folder| file_path_from_url(folder).map(PathBuf::from))
+        .collect::<anyhow::Result<Vec<PathBuf>>>()?;
+    let mut files = vec![];
+    for root in &roots {
+        files.extend(get_kcl_files_with_extensions(
+            root,
+            &snapshot.config.kcl_extensions,
+        )?);
+    }
+    files.sort();
+    files.dedup();
+
+    let mut covered: HashSet<String> = HashSet::new();
+    let mut edges = BTreeSet::new();
+    for file in &files {
+        if covered.contains(file) {
+            continue;
+        }
+        let db = snapshot.db.read().get(file).cloned();
+        let program = match db {
+            Some(db) => db.prog,
+            None => parse_param(Param { file: file.clone() }, Some(snapshot.vfs.clone()))?.prog,
+        };
+        for module in program.pkgs.values().flatten() {
+            covered.insert(module.filename.clone());
+        }
+        edges.extend(program_edges(&program));
+    }
+    let cycles = import_cycles(&edges);
+    Ok(ImportGraph {
+        edges: edges.into_iter().collect(),
+        cycles,
+    })
+}
+
+/// Returns the import edges of all the modules in the program. The package path of the
+/// main package is derived from the directory of its files relative to the program root, so
+/// that a file is in the same package whichever entry it is compiled from.
+fn program_edges(program: &Program) -> Vec<ImportEdge> {
+    let mut edges = vec![];
+    for (pkgpath, modules) in &program.pkgs {
+        for module in modules {
+            let uri = match Url::from_file_path(&module.filename) {
+                Ok(uri) => uri,
+                Err(_) => continue,
+            };
+            let from = if pkgpath == MAIN_PKG {
+                main_pkgpath(&program.root, &module.filename)
+            } else {
+                pkgpath.clone()
+            };
+            for stmt in &module.body {
+                if let Stmt::Import(import_stmt) = &stmt.node {
+                    edges.push(ImportEdge {
+                        file: uri.clone(),
+                        from: from.clone(),
+                        to: import_stmt.path.clone(),
+                    });
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// Returns the package path of the main file relative to the program root, e.g., `a.b` for
+/// `<root>/a/b/main.k`, and `__main__` for the files in the root.
+fn main_pkgpath(root: &str, file: &str) -> String {
+    let dir = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+    match dir.strip_prefix(root) {
+        Ok(rel) if rel.components().next().is_some() => rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("."),
+        _ => MAIN_PKG.to_string(),
+    }
+}
+
+/// Returns the circular imports in the package graph of the edges. Each cycle is found by a
+/// back edge of the depth-first search and is rotated to start from its least package.
+fn import_cycles(edges: &BTreeSet<ImportEdge>) -> Vec<Vec<String>> {
+    let mut graph: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for edge in edges {
+        graph
+            .entry(edge.from.as_str())
+            .or_default()
+            .insert(edge.to.as_str());
+    }
+    let mut cycles = BTreeSet::new();
+    let mut visited = HashSet::new();
+    for pkg in graph.keys() {
+        let mut stack = vec![];
+        visit(pkg, &graph, &mut stack, &mut visited, &mut cycles);
+    }
+    cycles.into_iter().collect()
+}
+
+fn visit<'a>(
+    pkg: &'a str,
+    graph: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+    stack: &mut Vec<&'a str>,
+    visited: &mut HashSet<&'a str>,
+    cycles: &mut BTreeSet<Vec<String>>,
+) {
+    if let Some(start) = stack.iter().position(|p| *p == pkg) {
+        let mut cycle: Vec<String> = stack[start..].iter().map(|p| p.to_string()).collect();
+        let least = (0..cycle.len()).min_by_key(|i| &cycle[*i]).unwrap_or(0);
+        cycle.rotate_left(least);
+        cycles.insert(cycle);
+        return;
+    }
+    if !visited.insert(pkg) {
+        return;
+    }
+    stack.push(pkg);
+    for dep in graph.get(pkg).into_iter().flatten() {
+        visit(dep, graph, stack, visited, cycles);
+    }
+    stack.pop();
+}
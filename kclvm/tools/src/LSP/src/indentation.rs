@@ -0,0 +1,59 @@
+//! The optional lint of the indentation, which warns about the lines indented with the
+//! mixed tabs and spaces instead of reformatting the file silently.
+//!
+//! The indentation of a line is mixed if it contains both tabs and spaces, or it contains
+//! tabs while the other lines of the file are indented with spaces.
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+
+/// The code of the mixed indentation diagnostic.
+pub(crate) const MIXED_INDENTATION_CODE: &str = "MixedIndentation";
+
+/// The number of the columns of a tab stop when the tabs are converted to spaces.
+const TAB_WIDTH: usize = 4;
+
+/// Returns the warnings on the lines of the source with the mixed indentation, whose
+/// ranges cover the indentation.
+pub(crate) fn indentation_diagnostics(source: &str) -> Vec<Diagnostic> {
+    let indents: Vec<&str> = source.lines().map(indentation).collect();
+    let has_space_indents = indents
+        .iter()
+        .any(|indent| !indent.is_empty() && !indent.contains('\t'));
+    indents
+        .iter()
+        .enumerate()
+        .filter(|(_, indent)| indent.contains('\t') && (indent.contains(' ') || has_space_indents))
+        .map(|(line, indent)| Diagnostic {
+            range: Range::new(
+                Position::new(line as u32, 0),
+                Position::new(line as u32, indent.len() as u32),
+            ),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(MIXED_INDENTATION_CODE.to_string())),
+            message: "Mixed tabs and spaces in the indentation".to_string(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Returns the indentation of the line converted to spaces, where a tab advances to the
+/// next tab stop.
+pub(crate) fn normalize_indentation(indent: &str) -> String {
+    let mut width = 0;
+    for c in indent.chars() {
+        width = match c {
+            '\t' => (width / TAB_WIDTH + 1) * TAB_WIDTH,
+            _ => width + 1,
+        };
+    }
+    " ".repeat(width)
+}
+
+/// Returns the leading tabs and spaces of the line, which is empty for the blank lines.
+pub(crate) fn indentation(line: &str) -> &str {
+    let content = line.trim_start_matches([' ', '\t']);
+    if content.is_empty() {
+        return "";
+    }
+    &line[..line.len() - content.len()]
+}
@@ -1,16 +1,124 @@
+use config::Config;
+use lsp_server::Connection;
+use state::LanguageServerState;
+pub use transport::Transport;
+
 mod analysis;
+mod builtin_doc;
+mod capabilities;
+mod commands;
 mod completion;
 mod config;
 mod db;
 mod dispatcher;
+mod document_diagnostic;
+pub mod document_symbol;
+mod dump_ast;
+mod effective_config;
+mod explain;
+mod export_word_index;
 mod find_ref;
+pub mod find_refs;
+mod fix_all;
+mod folding_range;
+pub mod formatting;
 mod from_lsp;
+mod generate_example;
+mod goto_def;
+pub mod hover;
+mod import_graph;
+mod indentation;
+mod memory_usage;
+mod moniker;
 mod notification;
+mod quick_fix;
+mod rename;
+mod request;
+mod sourcemap;
 mod state;
+mod suppression;
 mod to_lsp;
+mod transport;
 mod util;
+mod validate;
+mod watcher;
+mod word_index;
+mod workspace_diagnostics;
+mod workspace_symbol;
 
-mod document_symbol;
-mod goto_def;
-mod hover;
-mod request;
+#[cfg(test)]
+mod tests;
+
+/// Runs the main loop of the language server. This will receive requests and handle them.
+pub fn main_loop(
+    connection: Connection,
+    config: Config,
+    config_errors: &[String],
+    initialize_params: lsp_types::InitializeParams,
+) -> anyhow::Result<()> {
+    let mut state = LanguageServerState::new(
+        connection.sender,
+        config,
+        initialize_params.capabilities.clone(),
+    );
+    state.warn_invalid_config(config_errors);
+    let workspace_folders = workspace_folders(&initialize_params);
+    state.build_word_index_map(&workspace_folders);
+    state.warm_up(&workspace_folders);
+    state.watch_folders(&workspace_folders);
+    state.run(connection.receiver)
+}
+
+/// Returns the workspace folders of the client, falls back to the root uri if the
+/// client does not support the workspace folders.
+fn workspace_folders(initialize_params: &lsp_types::InitializeParams) -> Vec<lsp_types::Url> {
+    match &initialize_params.workspace_folders {
+        Some(folders) if !folders.is_empty() => {
+            folders.iter().map(|folder| folder.uri.clone()).collect()
+        }
+        _ => initialize_params.root_uri.iter().cloned().collect(),
+    }
+}
+
+/// Main entry point for the language server
+pub fn run_server(transport: &Transport) -> anyhow::Result<()> {
+    // Setup IO connections
+    let (connection, io_threads) = transport.connect()?;
+    // Wait for a client to connect
+    let (initialize_id, initialize_params) = connection.initialize_start()?;
+    let pull_diagnostics_supported =
+        document_diagnostic::pull_diagnostics_supported(&initialize_params);
+
+    let initialize_params =
+        util::from_json::<lsp_types::InitializeParams>("InitializeParams", initialize_params)?;
+
+    let (mut config, config_errors) = initialize_params
+        .initialization_options
+        .clone()
+        .map(Config::from_initialization_options)
+        .unwrap_or_default();
+    config.resolve_diagnostics_mode(pull_diagnostics_supported);
+
+    let server_capabilities = capabilities::server_capabilities(&initialize_params.capabilities);
+
+    let initialize_result = lsp_types::InitializeResult {
+        capabilities: server_capabilities,
+        server_info: Some(lsp_types::ServerInfo {
+            name: String::from("kcl-language-server"),
+            version: None,
+        }),
+    };
+
+    let mut initialize_result = serde_json::to_value(initialize_result)
+        .map_err(|_| anyhow::anyhow!("Initialize result error"))?;
+    if config.pull_diagnostics() {
+        initialize_result["capabilities"]["diagnosticProvider"] =
+            document_diagnostic::diagnostic_provider();
+    }
+
+    connection.initialize_finish(initialize_id, initialize_result)?;
+
+    main_loop(connection, config, &config_errors, initialize_params)?;
+    io_threads.join()?;
+    Ok(())
+}
@@ -0,0 +1,35 @@
+//! The approximate resource usage of the language server, which is reported by the
+//! `kcl.memoryUsage` command for debugging the leaks in the long sessions.
+
+use serde::Serialize;
+
+use crate::state::LanguageServerSnapshot;
+
+/// The counts of the entries held by the language server.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MemoryUsage {
+    /// The number of the analysis dbs, i.e., the compiled files.
+    pub db_entries: usize,
+    /// The number of the words in the word indexes of all workspace folders.
+    pub word_index_entries: usize,
+    /// The number of the locations of the words in the word indexes.
+    pub word_index_locations: usize,
+    /// The number of the files opened in the client, i.e., held by the vfs.
+    pub opened_files: usize,
+}
+
+/// Counts the entries held by the snapshot of the language server.
+pub(crate) fn memory_usage(snapshot: &LanguageServerSnapshot) -> MemoryUsage {
+    let word_index_map = snapshot.word_index_map.read();
+    MemoryUsage {
+        db_entries: snapshot.db.read().len(),
+        word_index_entries: word_index_map.values().map(|index| index.len()).sum(),
+        word_index_locations: word_index_map
+            .values()
+            .flat_map(|index| index.values())
+            .map(|locations| locations.len())
+            .sum(),
+        opened_files: snapshot.vfs.read().iter().count(),
+    }
+}
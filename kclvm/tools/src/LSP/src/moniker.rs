@@ -0,0 +1,58 @@
+//! Moniker for KCL
+//! A moniker gives a symbol a stable identity across files and repositories,
+//! which is built from the package path and the name of the symbol definition.
+
+use kclvm_ast::ast::Program;
+use kclvm_compiler::pkgpath_without_prefix;
+use kclvm_error::Position as KCLPos;
+use kclvm_sema::resolver::scope::{ProgramScope, ScopeKind, ScopeObject};
+use kclvm_sema::ty::TypeKind;
+use lsp_types::{Moniker, MonikerKind, UniquenessLevel};
+
+use crate::goto_def::{find_def, Definition};
+
+/// The moniker scheme of KCL symbols.
+pub(crate) const KCL_MONIKER_SCHEME: &str = "kcl";
+
+/// Returns the monikers of the symbol at the position.
+pub(crate) fn moniker(
+    program: &Program,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> Option<Vec<Moniker>> {
+    let node = program.pos_to_stmt(kcl_pos)?;
+    match find_def(node, kcl_pos, prog_scope)? {
+        Definition::Object(obj) => {
+            let pkgpath = obj_pkgpath(&obj, prog_scope)?;
+            let kind = if obj.start.filename == kcl_pos.filename {
+                MonikerKind::Export
+            } else {
+                MonikerKind::Import
+            };
+            Some(vec![Moniker {
+                scheme: KCL_MONIKER_SCHEME.to_string(),
+                identifier: format!("{}.{}", pkgpath, obj.name),
+                unique: UniquenessLevel::Scheme,
+                kind: Some(kind),
+            }])
+        }
+        Definition::Scope(_) => None,
+    }
+}
+
+/// Get the package path where the scope object is defined.
+fn obj_pkgpath(obj: &ScopeObject, prog_scope: &ProgramScope) -> Option<String> {
+    if let TypeKind::Schema(schema_ty) = &obj.ty.kind {
+        if schema_ty.name == obj.name && !schema_ty.pkgpath.is_empty() {
+            return Some(pkgpath_without_prefix!(schema_ty.pkgpath));
+        }
+    }
+    prog_scope
+        .scope_map
+        .iter()
+        .find(|(_, scope)| match &scope.borrow().kind {
+            ScopeKind::Package(files) => files.contains(&obj.start.filename),
+            _ => false,
+        })
+        .map(|(pkgpath, _)| pkgpath.clone())
+}
@@ -1,4 +1,15 @@
-use lsp_types::notification::{DidChangeTextDocument, DidOpenTextDocument, DidSaveTextDocument};
+use std::collections::HashSet;
+
+use lsp_types::notification::{
+    DidChangeTextDocument, DidChangeWatchedFiles, DidCloseTextDocument, DidOpenTextDocument,
+    DidSaveTextDocument, Initialized, Notification,
+};
+use lsp_types::request::{RegisterCapability, WorkspaceConfiguration};
+use lsp_types::{
+    ConfigurationItem, ConfigurationParams, DidChangeWatchedFilesRegistrationOptions,
+    DocumentFilter, FileChangeType, FileSystemWatcher, Registration, RegistrationParams,
+    TextDocumentRegistrationOptions, TextDocumentSaveRegistrationOptions,
+};
 
 use crate::{
     dispatcher::NotificationDispatcher, from_lsp, state::LanguageServerState,
@@ -11,16 +22,148 @@ impl LanguageServerState {
         notification: lsp_server::Notification,
     ) -> anyhow::Result<()> {
         self.log_message(format!("on notification {:?}", notification));
+        // After the shutdown request, the notifications are dropped except the `exit`
+        // notification, which is handled by the main loop.
+        if self.shutdown_requested {
+            return Ok(());
+        }
         NotificationDispatcher::new(self, notification)
+            .on::<Initialized>(LanguageServerState::on_initialized)?
             .on::<DidOpenTextDocument>(LanguageServerState::on_did_open_text_document)?
             .on::<DidChangeTextDocument>(LanguageServerState::on_did_change_text_document)?
             .on::<DidSaveTextDocument>(LanguageServerState::on_did_save_text_document)?
-            // .on::<DidCloseTextDocument>(LanguageServerState::on_did_close_text_document)?
-            // .on::<DidChangeWatchedFiles>(LanguageServerState::on_did_change_watched_files)?
+            .on::<DidCloseTextDocument>(LanguageServerState::on_did_close_text_document)?
+            .on::<DidChangeWatchedFiles>(LanguageServerState::on_did_change_watched_files)?
             .finish();
         Ok(())
     }
 
+    /// Called when the `Initialized` notification was received. This is the point to register
+    /// the dynamic capabilities and pull the workspace configuration from the client.
+    fn on_initialized(&mut self, _params: lsp_types::InitializedParams) -> anyhow::Result<()> {
+        let workspace_caps = self
+            .client_capabilities
+            .workspace
+            .clone()
+            .unwrap_or_default();
+        if self.client_watches_files() {
+            let registration_options = DidChangeWatchedFilesRegistrationOptions {
+                watchers: self
+                    .config
+                    .file_watcher_globs()
+                    .into_iter()
+                    .map(|glob| FileSystemWatcher {
+                        glob_pattern: glob,
+                        kind: None,
+                    })
+                    .collect(),
+            };
+            let registration = Registration {
+                id: DidChangeWatchedFiles::METHOD.to_string(),
+                method: DidChangeWatchedFiles::METHOD.to_string(),
+                register_options: Some(serde_json::to_value(registration_options)?),
+            };
+            self.send_request::<RegisterCapability>(
+                RegistrationParams {
+                    registrations: vec![registration],
+                },
+                |_, _| (),
+            )?;
+        }
+
+        let sync_dynamic_registration = self
+            .client_capabilities
+            .text_document
+            .as_ref()
+            .and_then(|caps| caps.synchronization.as_ref())
+            .and_then(|caps| caps.dynamic_registration)
+            .unwrap_or(false);
+        if sync_dynamic_registration {
+            // The saves are notified for the documents selected by the KCL extensions of
+            // the config, which may differ from the files of the KCL language of the client.
+            let registration_options = TextDocumentSaveRegistrationOptions {
+                include_text: Some(false),
+                text_document_registration_options: TextDocumentRegistrationOptions {
+                    document_selector: Some(
+                        self.config
+                            .kcl_file_globs()
+                            .into_iter()
+                            .map(|glob| DocumentFilter {
+                                language: None,
+                                scheme: Some("file".to_string()),
+                                pattern: Some(glob),
+                            })
+                            .collect(),
+                    ),
+                },
+            };
+            let registration = Registration {
+                id: DidSaveTextDocument::METHOD.to_string(),
+                method: DidSaveTextDocument::METHOD.to_string(),
+                register_options: Some(serde_json::to_value(registration_options)?),
+            };
+            self.send_request::<RegisterCapability>(
+                RegistrationParams {
+                    registrations: vec![registration],
+                },
+                |_, _| (),
+            )?;
+        }
+
+        if workspace_caps.configuration.unwrap_or(false) {
+            self.send_request::<WorkspaceConfiguration>(
+                ConfigurationParams {
+                    items: vec![ConfigurationItem {
+                        scope_uri: None,
+                        section: Some("kcl".to_string()),
+                    }],
+                },
+                |state, response| {
+                    let lsp_server::Response { error, result, .. } = response;
+                    if let Some(err) = error {
+                        state.log_message(format!("failed to fetch the configuration: {:?}", err));
+                        return;
+                    }
+                    let options = result
+                        .and_then(|value| {
+                            serde_json::from_value::<Vec<serde_json::Value>>(value).ok()
+                        })
+                        .and_then(|mut configs| configs.pop());
+                    if let Some(options) = options {
+                        // The pulled config overrides the fields it sets, while the
+                        // diagnostics mode is kept as resolved and advertised on initialize,
+                        // which can't be changed after the initialization.
+                        let diagnostics_mode = state.config.diagnostics_mode;
+                        let errors = state.config.merge(options);
+                        state.config.diagnostics_mode = diagnostics_mode;
+                        state.analysis.clear_resolutions();
+                        for error in errors {
+                            state.show_message(
+                                lsp_types::MessageType::WARNING,
+                                format!(
+                                    "Invalid workspace configuration, {}, the current value is kept",
+                                    error
+                                ),
+                            );
+                        }
+                    }
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Whether the client supports the dynamic registration of the watched files, otherwise
+    /// the workspace folders are watched by the server.
+    pub(crate) fn client_watches_files(&self) -> bool {
+        self.client_capabilities
+            .workspace
+            .as_ref()
+            .and_then(|caps| caps.did_change_watched_files)
+            .and_then(|caps| caps.dynamic_registration)
+            .unwrap_or(false)
+    }
+
     /// Called when a `DidOpenTextDocument` notification was received.
     fn on_did_open_text_document(
         &mut self,
@@ -28,24 +171,107 @@ impl LanguageServerState {
     ) -> anyhow::Result<()> {
         let path = from_lsp::abs_path(&params.text_document.uri)?;
         self.log_message(format!("on did open file: {:?}", path));
-        self.vfs
-            .write()
-            .set_file_contents(path.into(), Some(params.text_document.text.into_bytes()));
+        self.document_versions.write().insert(
+            params.text_document.uri.clone(),
+            params.text_document.version,
+        );
+        // Some clients reopen the opened documents, e.g., on reload. The document with the
+        // same contents is not changed, so it is not recompiled.
+        {
+            let vfs = self.vfs.read();
+            if let Some(file_id) = vfs.file_id(&path.clone().into()) {
+                if vfs.file_contents(file_id) == params.text_document.text.as_bytes() {
+                    return Ok(());
+                }
+            }
+        }
+        let size = params.text_document.text.len();
+        // The dbs are compiled with the file contents on the disk, which are stale if the
+        // opened document differs from them.
+        if std::fs::read_to_string(&path).ok().as_ref() != Some(&params.text_document.text) {
+            let file = from_lsp::file_path_from_url(&params.text_document.uri)?;
+            self.analysis.invalidate(&HashSet::from([file.clone()]));
+            self.mark_word_index_changed(&file);
+        }
+        self.vfs.write().set_file_contents(
+            path.clone().into(),
+            Some(params.text_document.text.into_bytes()),
+        );
+        if self.config.exceeds_max_file_size(size) {
+            self.show_message(
+                lsp_types::MessageType::INFO,
+                format!(
+                    "Skip analyzing {:?} whose size {} bytes exceeds the max file size {} bytes",
+                    path, size, self.config.max_file_size
+                ),
+            );
+        }
         Ok(())
     }
 
-    /// Called when a `DidChangeTextDocument` notification was received.
+    /// Called when a `DidSaveTextDocument` notification was received.
     fn on_did_save_text_document(
         &mut self,
         params: lsp_types::DidSaveTextDocumentParams,
     ) -> anyhow::Result<()> {
         let lsp_types::DidSaveTextDocumentParams {
             text_document,
-            text: _,
+            text,
         } = params;
 
         let path = from_lsp::abs_path(&text_document.uri)?;
         self.log_message(format!("on did save file: {:?}", path));
+        // The saved contents may differ from the vfs, e.g., the file is formatted by an
+        // external formatter on save, then the vfs and the word index are reconciled with
+        // the saved contents.
+        let saved = match text.or_else(|| std::fs::read_to_string(&path).ok()) {
+            Some(saved) => saved,
+            None => return Ok(()),
+        };
+        let changed = {
+            let vfs = self.vfs.read();
+            match vfs.file_id(&path.clone().into()) {
+                Some(file_id) => vfs.file_contents(file_id) != saved.as_bytes(),
+                None => false,
+            }
+        };
+        if changed {
+            let file = from_lsp::file_path_from_url(&text_document.uri)?;
+            self.analysis.invalidate(&HashSet::from([file.clone()]));
+            self.mark_word_index_changed(&file);
+            self.vfs
+                .write()
+                .set_file_contents(path.into(), Some(saved.into_bytes()));
+        }
+        Ok(())
+    }
+
+    /// Called when a `DidCloseTextDocument` notification was received. The closed document
+    /// is owned by the file system again, so the unsaved changes are dropped from the vfs.
+    fn on_did_close_text_document(
+        &mut self,
+        params: lsp_types::DidCloseTextDocumentParams,
+    ) -> anyhow::Result<()> {
+        let path = from_lsp::abs_path(&params.text_document.uri)?;
+        self.log_message(format!("on did close file: {:?}", path));
+        self.document_versions
+            .write()
+            .remove(&params.text_document.uri);
+
+        let contents = std::fs::read(&path).ok();
+        let changed = {
+            let vfs = self.vfs.read();
+            match vfs.file_id(&path.clone().into()) {
+                Some(file_id) => Some(vfs.file_contents(file_id)) != contents.as_deref(),
+                None => false,
+            }
+        };
+        if changed {
+            let file = from_lsp::file_path_from_url(&params.text_document.uri)?;
+            self.analysis.invalidate(&HashSet::from([file.clone()]));
+            self.mark_word_index_changed(&file);
+            self.vfs.write().set_file_contents(path.into(), contents);
+        }
         Ok(())
     }
 
@@ -61,16 +287,67 @@ impl LanguageServerState {
 
         let path = from_lsp::abs_path(&text_document.uri)?;
         self.log_message(format!("on did_change file: {:?}", path));
+        self.document_versions
+            .write()
+            .insert(text_document.uri.clone(), text_document.version);
+
+        {
+            let vfs = &mut *self.vfs.write();
+            let file_id = vfs
+                .file_id(&path.clone().into())
+                .ok_or(anyhow::anyhow!("Already checked that the file_id exists!"))?;
 
-        let vfs = &mut *self.vfs.write();
-        let file_id = vfs
-            .file_id(&path.clone().into())
-            .ok_or(anyhow::anyhow!("Already checked that the file_id exists!"))?;
+            let mut text = String::from_utf8(vfs.file_contents(file_id).to_vec())?;
+            apply_document_changes(&mut text, content_changes);
+            vfs.set_file_contents(path.into(), Some(text.into_bytes()));
+        }
+        let file = from_lsp::file_path_from_url(&text_document.uri)?;
+        self.analysis.invalidate(&HashSet::from([file.clone()]));
+        self.mark_word_index_changed(&file);
 
-        let mut text = String::from_utf8(vfs.file_contents(file_id).to_vec())?;
-        apply_document_changes(&mut text, content_changes);
-        vfs.set_file_contents(path.into(), Some(text.into_bytes()));
+        Ok(())
+    }
 
+    /// Called when a `DidChangeWatchedFiles` notification was received, or the files in the
+    /// workspace folders watched by the server are changed. The documents opened in the
+    /// client are owned by the client, so their contents on the disk are ignored.
+    pub(crate) fn on_did_change_watched_files(
+        &mut self,
+        params: lsp_types::DidChangeWatchedFilesParams,
+    ) -> anyhow::Result<()> {
+        let mut changed_files = HashSet::new();
+        for change in params.changes {
+            if self.document_versions.read().contains_key(&change.uri) {
+                continue;
+            }
+            if change.typ != FileChangeType::CHANGED {
+                from_lsp::forget_file_path(&change.uri);
+            }
+            // The invalid uri is skipped rather than dropping the remaining changes.
+            let (path, file) = match (
+                from_lsp::abs_path(&change.uri),
+                from_lsp::file_path_from_url(&change.uri),
+            ) {
+                (Ok(path), Ok(file)) => (path, file),
+                (Err(err), _) | (_, Err(err)) => {
+                    self.log_message(format!("skip the watched file change: {}", err));
+                    continue;
+                }
+            };
+            self.log_message(format!("on did change watched file: {:?}", path));
+            let contents = if change.typ == FileChangeType::DELETED {
+                None
+            } else {
+                std::fs::read(&path).ok()
+            };
+            self.vfs.write().set_file_contents(path.into(), contents);
+            self.mark_word_index_changed(&file);
+            changed_files.insert(file);
+        }
+        let invalidated = self.analysis.invalidate(&changed_files);
+        if self.config.idle_flush_ms.is_some() {
+            self.pending_recompiles.extend(invalidated);
+        }
         Ok(())
     }
 }
@@ -0,0 +1,252 @@
+//! Code actions for KCL
+//! + convert the config entry separator style, i.e., `attr: value` to `attr = value`
+//!   and vice versa. The entries whose values are config or schema exprs are skipped,
+//!   because `:` (union) and `=` (override) have different semantics for them.
+//! + add the required attrs missing from the schema config at the end of the config
+//!   block, whose values are the placeholders derived from their types.
+//! + convert the mixed indentation of the lines reported by the indentation lint to
+//!   spaces.
+
+use std::collections::HashMap;
+
+use kclvm_ast::ast::{
+    ConfigEntry, ConfigEntryOperation, ConfigExpr, Expr, Node, Program, SchemaExpr,
+};
+use kclvm_ast::pos::GetPos;
+use kclvm_ast::walker::MutSelfWalker;
+use kclvm_sema::resolver::scope::ProgramScope;
+use kclvm_sema::ty::TypeKind;
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, NumberOrString, Position, Range,
+    TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::generate_example::{collect_required_attrs, placeholder, INDENT};
+use crate::goto_def::{find_def, Definition};
+use crate::indentation::{indentation, normalize_indentation, MIXED_INDENTATION_CODE};
+
+/// Returns the code actions available on the lines of the range in the file.
+pub(crate) fn quick_fix(
+    program: &Program,
+    prog_scope: &ProgramScope,
+    file: &str,
+    uri: &Url,
+    range: Range,
+) -> Vec<CodeActionOrCommand> {
+    let mut collector = ConfigEntryCollector {
+        start_line: range.start.line as u64 + 1,
+        end_line: range.end.line as u64 + 1,
+        entries: vec![],
+        schema_exprs: vec![],
+    };
+    for module in program.pkgs.values().flatten() {
+        if module.filename == file {
+            collector.walk_module(module);
+        }
+    }
+    let mut actions: Vec<CodeActionOrCommand> = collector
+        .entries
+        .iter()
+        .filter_map(|entry| convert_config_entry_separator(entry, uri))
+        .map(CodeActionOrCommand::CodeAction)
+        .collect();
+    // The innermost schema config on the line, which starts the last.
+    if let Some(schema_expr) = collector
+        .schema_exprs
+        .iter()
+        .max_by_key(|schema_expr| (schema_expr.name.line, schema_expr.name.column))
+    {
+        actions.extend(
+            add_missing_attrs(program, prog_scope, schema_expr, uri)
+                .into_iter()
+                .map(CodeActionOrCommand::CodeAction),
+        );
+    }
+    actions
+}
+
+/// Returns the code actions which add the required attrs missing from the config of the
+/// schema expr, one for each attr.
+fn add_missing_attrs(
+    program: &Program,
+    prog_scope: &ProgramScope,
+    schema_expr: &SchemaExpr,
+    uri: &Url,
+) -> Vec<CodeAction> {
+    let config = &schema_expr.config;
+    let config_expr = match &config.node {
+        Expr::Config(config_expr) => config_expr,
+        _ => return vec![],
+    };
+    let name_end = schema_expr.name.get_end_pos();
+    let schema_ty = match program
+        .pos_to_stmt(&name_end)
+        .and_then(|node| find_def(node, &name_end, prog_scope))
+    {
+        Some(Definition::Object(obj)) => match &obj.ty.kind {
+            TypeKind::Schema(schema_ty) => schema_ty.clone(),
+            _ => return vec![],
+        },
+        _ => return vec![],
+    };
+    let set_attrs: Vec<String> = config_expr
+        .items
+        .iter()
+        .filter_map(|item| match &item.node.key.as_ref()?.node {
+            Expr::Identifier(id) => id.names.first().map(|name| name.node.clone()),
+            Expr::StringLit(string_lit) => Some(string_lit.value.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut required_attrs = vec![];
+    collect_required_attrs(&schema_ty, &mut required_attrs);
+    let last_item = config_expr.items.last();
+    // The closing brace is on its own line, then the attr is inserted as a new line before
+    // it, otherwise the attr is appended inline before the closing brace.
+    let on_new_line = last_item.map_or(config.end_line > config.line, |item| {
+        config.end_line > item.end_line
+    });
+    let closing_brace = Position::new(config.end_line as u32 - 1, config.end_column as u32 - 1);
+    required_attrs
+        .into_iter()
+        .filter(|(attr, _)| !set_attrs.contains(attr))
+        .map(|(attr, ty)| {
+            let edit = if on_new_line {
+                // Align with the other entries, or indent from the closing brace.
+                let indent = match last_item {
+                    Some(item) => " ".repeat(item.column as usize),
+                    None => format!("{}{}", " ".repeat(closing_brace.character as usize), INDENT),
+                };
+                let depth = indent.len() / INDENT.len();
+                TextEdit {
+                    range: Range::new(
+                        Position::new(closing_brace.line, 0),
+                        Position::new(closing_brace.line, 0),
+                    ),
+                    new_text: format!("{}{} = {}\n", indent, attr, placeholder(&ty, depth)),
+                }
+            } else {
+                let separator = if last_item.is_some() { ", " } else { "" };
+                TextEdit {
+                    range: Range::new(closing_brace, closing_brace),
+                    new_text: format!("{}{} = {}", separator, attr, placeholder(&ty, 0)),
+                }
+            };
+            CodeAction {
+                title: format!("Add the missing attribute `{}`", attr),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Returns the code actions which convert the mixed indentation of the lines of the
+/// diagnostics to spaces, one for each line.
+pub(crate) fn fix_mixed_indentation(
+    diagnostics: &[Diagnostic],
+    source: &str,
+    uri: &Url,
+) -> Vec<CodeActionOrCommand> {
+    let lines: Vec<&str> = source.lines().collect();
+    diagnostics
+        .iter()
+        .filter(|diag| {
+            diag.code == Some(NumberOrString::String(MIXED_INDENTATION_CODE.to_string()))
+        })
+        .filter_map(|diag| {
+            let line = diag.range.start.line;
+            let indent = indentation(lines.get(line as usize)?);
+            let edit = TextEdit {
+                range: Range::new(
+                    Position::new(line, 0),
+                    Position::new(line, indent.len() as u32),
+                ),
+                new_text: normalize_indentation(indent),
+            };
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Convert the indentation to spaces".to_string(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diag.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(true),
+                ..Default::default()
+            }))
+        })
+        .collect()
+}
+
+/// Returns the code action which converts the separator of the config entry.
+fn convert_config_entry_separator(entry: &Node<ConfigEntry>, uri: &Url) -> Option<CodeAction> {
+    let key = entry.node.key.as_ref()?;
+    let value = &entry.node.value;
+    if key.end_line != value.line || matches!(value.node, Expr::Config(_) | Expr::Schema(_)) {
+        return None;
+    }
+    let (title, separator) = match entry.node.operation {
+        ConfigEntryOperation::Union => ("Convert `:` to `=`", " = "),
+        ConfigEntryOperation::Override => ("Convert `=` to `:`", ": "),
+        ConfigEntryOperation::Insert => return None,
+    };
+    let edit = TextEdit {
+        range: Range::new(
+            Position::new(key.end_line as u32 - 1, key.end_column as u32),
+            Position::new(value.line as u32 - 1, value.column as u32),
+        ),
+        new_text: separator.to_string(),
+    };
+    Some(CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Collects the config entries whose keys start on the lines in `[start_line, end_line]`,
+/// and the schema exprs spanning the start line.
+struct ConfigEntryCollector {
+    start_line: u64,
+    end_line: u64,
+    entries: Vec<Node<ConfigEntry>>,
+    schema_exprs: Vec<SchemaExpr>,
+}
+
+impl MutSelfWalker for ConfigEntryCollector {
+    fn walk_schema_expr(&mut self, schema_expr: &SchemaExpr) {
+        if schema_expr.name.line <= self.start_line
+            && self.start_line <= schema_expr.config.end_line
+        {
+            self.schema_exprs.push(schema_expr.clone());
+        }
+        for arg in &schema_expr.args {
+            self.walk_expr(&arg.node);
+        }
+        for kwarg in &schema_expr.kwargs {
+            self.walk_keyword(&kwarg.node);
+        }
+        self.walk_expr(&schema_expr.config.node);
+    }
+
+    fn walk_config_expr(&mut self, config_expr: &ConfigExpr) {
+        for config_entry in &config_expr.items {
+            if let Some(key) = &config_entry.node.key {
+                if key.line >= self.start_line && key.line <= self.end_line {
+                    self.entries.push(*config_entry.clone());
+                }
+                self.walk_expr(&key.node);
+            }
+            self.walk_expr(&config_entry.node.value.node);
+        }
+    }
+}
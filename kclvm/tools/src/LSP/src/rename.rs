@@ -0,0 +1,390 @@
+//! Rename for KCL
+//! Now supports renaming a package directory, which rewrites the import paths of the
+//! package and its nested packages in the files of the project, e.g., renaming the
+//! directory `pkg` to `base` rewrites `import pkg.sub` to `import base.sub`. The relative
+//! imports are kept relative, e.g., `import ..pkg.sub` to `import ..base.sub`.
+//! And supports renaming a symbol and its references, where the symbols defined outside
+//! the program root, e.g., in the external packages of the package cache, the builtin
+//! and the system modules, are read-only and rejected by the prepare rename.
+//! The edits are returned as the document changes versioned by the opened documents if the
+//! client supports them, so that the client detects the edits on the stale documents.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use kclvm_ast::ast::{Expr, Program, Stmt};
+use kclvm_ast::pos::{ContainsPos, GetPos};
+use kclvm_config::modfile::get_pkg_root;
+use kclvm_config::vfs::fix_import_path;
+use kclvm_error::Position as KCLPos;
+use kclvm_parser::parse_file;
+use kclvm_sema::resolver::scope::ProgramScope;
+use lsp_types::{
+    ClientCapabilities, DocumentChanges, FileRename, Location, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, PrepareRenameResponse, Range,
+    TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
+use parking_lot::RwLock;
+use ra_ap_vfs::Vfs;
+
+use crate::find_refs::find_refs;
+use crate::from_lsp::file_path_from_url;
+use crate::goto_def::{find_def, Definition};
+use crate::to_lsp::lsp_pos;
+use crate::util::{
+    get_kcl_files_with_extensions, inner_most_expr_in_stmt, load_file_code_from_vfs,
+};
+
+/// Returns the range of the symbol at the position with its current name as the placeholder
+/// of the new name if it can be renamed, or an error if the symbol is read-only.
+pub(crate) fn prepare_rename(
+    program: &Program,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> anyhow::Result<Option<PrepareRenameResponse>> {
+    Ok(
+        rename_target(program, kcl_pos, prog_scope)?.map(|(range, placeholder)| {
+            PrepareRenameResponse::RangeWithPlaceholder { range, placeholder }
+        }),
+    )
+}
+
+/// Returns the edits which rename the symbol at the position and all of its references.
+pub(crate) fn rename_symbol(
+    program: &Program,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    new_name: &str,
+    word_index_map: &HashMap<Url, HashMap<String, Vec<Location>>>,
+    vfs: Arc<RwLock<Vfs>>,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    if !is_identifier(new_name) {
+        return Err(anyhow::anyhow!(
+            "The new name {} is not a valid identifier",
+            new_name
+        ));
+    }
+    let name = match rename_target(program, kcl_pos, prog_scope)? {
+        Some((_, name)) => name,
+        None => return Ok(None),
+    };
+    let refs = find_refs(
+        program,
+        kcl_pos,
+        prog_scope,
+        word_index_map,
+        Some(vfs),
+        true,
+    )
+    .ok_or_else(|| anyhow::anyhow!("Failed to find the references of {}", name))?;
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for loc in refs {
+        changes.entry(loc.uri).or_default().push(TextEdit {
+            range: loc.range,
+            new_text: new_name.to_string(),
+        });
+    }
+    if changes.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }))
+    }
+}
+
+/// The keywords of KCL, which can't be the names of the symbols.
+const KEYWORDS: [&str; 27] = [
+    "as",
+    "import",
+    "rule",
+    "schema",
+    "mixin",
+    "protocol",
+    "check",
+    "for",
+    "assert",
+    "if",
+    "elif",
+    "else",
+    "or",
+    "and",
+    "not",
+    "in",
+    "is",
+    "lambda",
+    "all",
+    "any",
+    "filter",
+    "map",
+    "type",
+    "True",
+    "False",
+    "None",
+    "Undefined",
+];
+
+/// Whether the name is a valid identifier of KCL rather than a keyword.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().map_or(false, rustc_lexer::is_id_start)
+        && chars.all(rustc_lexer::is_id_continue)
+        && !KEYWORDS.contains(&name)
+}
+
+/// Returns the range and the text of the identifier name at the position, or None if there
+/// is no symbol at the position. The symbols defined outside the program root are rejected.
+fn rename_target(
+    program: &Program,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+) -> anyhow::Result<Option<(Range, String)>> {
+    let node = match program.pos_to_stmt(kcl_pos) {
+        Some(node) => node,
+        None => return Ok(None),
+    };
+    let name = match inner_most_expr_in_stmt(&node.node, kcl_pos, None).0 {
+        Some(expr) => match expr.node {
+            Expr::Identifier(id) => id.names.into_iter().find(|name| name.contains_pos(kcl_pos)),
+            _ => None,
+        },
+        None => None,
+    };
+    let name = match name {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    match find_def(node, kcl_pos, prog_scope) {
+        Some(Definition::Object(obj)) => {
+            if obj.start.filename.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Cannot rename the builtin symbol {}",
+                    obj.name
+                ));
+            }
+            if !Path::new(&obj.start.filename).starts_with(&program.root) {
+                return Err(anyhow::anyhow!(
+                    "Cannot rename the symbol {} defined outside the workspace in {}",
+                    obj.name,
+                    obj.start.filename
+                ));
+            }
+            Ok(Some((
+                Range::new(lsp_pos(&name.get_pos()), lsp_pos(&name.get_end_pos())),
+                name.node,
+            )))
+        }
+        Some(Definition::Scope(_)) => Err(anyhow::anyhow!(
+            "Cannot rename the module {}, rename the package directory instead",
+            name.node
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Whether the client supports the document changes in the workspace edits.
+pub(crate) fn document_changes_supported(client_capabilities: &ClientCapabilities) -> bool {
+    client_capabilities
+        .workspace
+        .as_ref()
+        .and_then(|workspace| workspace.workspace_edit.as_ref())
+        .and_then(|workspace_edit| workspace_edit.document_changes)
+        .unwrap_or(false)
+}
+
+/// Converts the changes of the workspace edit to the document changes, where the documents
+/// opened in the client are identified with their versions, and the others, i.e., the files
+/// on the disk, with null versions. The documents are sorted by their uris.
+pub(crate) fn with_document_changes(
+    edit: WorkspaceEdit,
+    versions: &HashMap<Url, i32>,
+) -> WorkspaceEdit {
+    let mut changes: Vec<(Url, Vec<TextEdit>)> =
+        edit.changes.unwrap_or_default().into_iter().collect();
+    changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let edits = changes
+        .into_iter()
+        .map(|(uri, edits)| TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                version: versions.get(&uri).copied(),
+                uri,
+            },
+            edits: edits.into_iter().map(OneOf::Left).collect(),
+        })
+        .collect();
+    WorkspaceEdit {
+        changes: None,
+        document_changes: Some(DocumentChanges::Edits(edits)),
+        ..edit
+    }
+}
+
+/// Returns the edits which rewrite the import paths of the renamed package directories.
+/// The renames of files are skipped.
+pub(crate) fn rename_packages(
+    renames: &[FileRename],
+    extensions: &[String],
+    vfs: Arc<RwLock<Vfs>>,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for rename in renames {
+        let old_dir = PathBuf::from(file_path_from_url(&Url::parse(&rename.old_uri)?)?);
+        let new_dir = PathBuf::from(file_path_from_url(&Url::parse(&rename.new_uri)?)?);
+        if !old_dir.is_dir() {
+            continue;
+        }
+        // The package root is the directory of the `kcl.mod` file, or the parent
+        // directory of the package if the project has no `kcl.mod` file.
+        let root = match get_pkg_root(&old_dir.to_string_lossy()) {
+            Some(root) => PathBuf::from(root),
+            None => match old_dir.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => continue,
+            },
+        };
+        let (old_pkgpath, new_pkgpath) = match (pkgpath(&old_dir, &root), pkgpath(&new_dir, &root))
+        {
+            (Some(old_pkgpath), Some(new_pkgpath)) if !old_pkgpath.is_empty() => {
+                (old_pkgpath, new_pkgpath)
+            }
+            _ => continue,
+        };
+        for file in get_kcl_files_with_extensions(&root, extensions)? {
+            let edits = rename_imports_in_file(&file, &root, &old_pkgpath, &new_pkgpath, &vfs);
+            if !edits.is_empty() {
+                if let Ok(uri) = Url::from_file_path(&file) {
+                    changes.entry(uri).or_default().extend(edits);
+                }
+            }
+        }
+    }
+    if changes.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }))
+    }
+}
+
+/// Returns the edits which rewrite the imports of the package `old_pkgpath` and its nested
+/// packages to `new_pkgpath` in the file.
+fn rename_imports_in_file(
+    file: &str,
+    root: &Path,
+    old_pkgpath: &str,
+    new_pkgpath: &str,
+    vfs: &Arc<RwLock<Vfs>>,
+) -> Vec<TextEdit> {
+    let code = match load_file_code_from_vfs(file, vfs.clone()) {
+        Ok(code) => code,
+        Err(_) => return vec![],
+    };
+    let module = match parse_file(file, Some(code.clone())) {
+        Ok(module) => module,
+        Err(_) => return vec![],
+    };
+    let root_str = root.to_string_lossy();
+    // The package of the file after renaming, which is the base of its relative imports.
+    let file_pkgpath = Path::new(file)
+        .parent()
+        .and_then(|dir| pkgpath(dir, root))
+        .map(|pkgpath| rename_pkgpath(&pkgpath, old_pkgpath, new_pkgpath).unwrap_or(pkgpath))
+        .unwrap_or_default();
+    let mut edits = vec![];
+    for stmt in &module.body {
+        let import_stmt = match &stmt.node {
+            Stmt::Import(import_stmt) => import_stmt,
+            _ => continue,
+        };
+        let abs_pkgpath = fix_import_path(&root_str, file, &import_stmt.path);
+        let new_abs_pkgpath = match rename_pkgpath(&abs_pkgpath, old_pkgpath, new_pkgpath) {
+            Some(pkgpath) => pkgpath,
+            None => continue,
+        };
+        let new_path = if import_stmt.rawpath.starts_with('.') {
+            relative_import_path(&import_stmt.rawpath, &file_pkgpath, &new_abs_pkgpath)
+        } else {
+            new_abs_pkgpath
+        };
+        if new_path == import_stmt.rawpath {
+            continue;
+        }
+        // Locate the import path in the line of the import statement.
+        let line = match code.lines().nth(stmt.line as usize - 1) {
+            Some(line) => line,
+            None => continue,
+        };
+        // The columns of the edit are the UTF-16 code units of the line.
+        let column = line
+            .char_indices()
+            .nth(stmt.column as usize)
+            .map_or(0, |(i, _)| i);
+        let start = match line[column..].find(&import_stmt.rawpath) {
+            Some(start) => line[..column + start].encode_utf16().count() as u32,
+            None => continue,
+        };
+        let end = start + import_stmt.rawpath.encode_utf16().count() as u32;
+        edits.push(TextEdit {
+            range: Range::new(
+                Position::new(stmt.line as u32 - 1, start),
+                Position::new(stmt.line as u32 - 1, end),
+            ),
+            new_text: new_path,
+        });
+    }
+    edits
+}
+
+/// Rewrites the pkgpath if it is the package `old_pkgpath` or its nested package, e.g.,
+/// `pkg.sub` to `base.sub` when renaming `pkg` to `base`.
+fn rename_pkgpath(pkgpath: &str, old_pkgpath: &str, new_pkgpath: &str) -> Option<String> {
+    if pkgpath == old_pkgpath {
+        Some(new_pkgpath.to_string())
+    } else {
+        pkgpath
+            .strip_prefix(old_pkgpath)
+            .filter(|rest| rest.starts_with('.'))
+            .map(|rest| format!("{}{}", new_pkgpath, rest))
+    }
+}
+
+/// Converts the absolute pkgpath to the import path relative to the package of the file
+/// with the same leading dots as the `rawpath`. Falls back to the absolute pkgpath if the
+/// package can't be reached with the leading dots.
+fn relative_import_path(rawpath: &str, file_pkgpath: &str, abs_pkgpath: &str) -> String {
+    let dots = rawpath.chars().take_while(|c| *c == '.').count();
+    let mut base: Vec<&str> = file_pkgpath.split('.').filter(|s| !s.is_empty()).collect();
+    if dots - 1 > base.len() {
+        return abs_pkgpath.to_string();
+    }
+    base.truncate(base.len() - (dots - 1));
+    let base = base.join(".");
+    let rest = if base.is_empty() {
+        Some(abs_pkgpath)
+    } else {
+        abs_pkgpath
+            .strip_prefix(&base)
+            .and_then(|rest| rest.strip_prefix('.'))
+    };
+    match rest {
+        Some(rest) => format!("{}{}", ".".repeat(dots), rest),
+        None => abs_pkgpath.to_string(),
+    }
+}
+
+/// Returns the pkgpath of the directory relative to the root, e.g., `pkg.sub` for
+/// `<root>/pkg/sub`, or None if the directory is not under the root.
+fn pkgpath(dir: &Path, root: &Path) -> Option<String> {
+    let relative = dir.strip_prefix(root).ok()?;
+    Some(
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("."),
+    )
+}
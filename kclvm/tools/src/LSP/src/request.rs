@@ -1,17 +1,58 @@
-use std::time::Instant;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use anyhow::Ok;
 use crossbeam_channel::Sender;
+use indexmap::{IndexMap, IndexSet};
+use kclvm_ast::ast::Program;
+use kclvm_ast::MAIN_PKG;
+use kclvm_error::Diagnostic;
+use kclvm_error::Position as KCLPos;
+use kclvm_sema::resolver::scope::{ProgramScope, ScopeObjectKind};
+use lsp_types::{
+    CodeActionKind, CodeActionOrCommand, Url, WorkDoneProgress, WorkDoneProgressBegin,
+    WorkDoneProgressEnd, WorkDoneProgressReport,
+};
 
 use crate::{
-    completion::completion,
+    commands::execute_command,
+    completion::{
+        completion_existing_keys, completion_markdown_supported, completion_schema_config,
+        completion_type_annotation, completion_with_budget, resolve_completion_item,
+        snippet_supported, CompletionBudget, CompletionItemData, SchemaInsertion,
+    },
+    db::AnalysisDatabase,
     dispatcher::RequestDispatcher,
+    document_diagnostic::{
+        document_diagnostic, DocumentDiagnosticParams, DocumentDiagnosticRequest,
+        FullDocumentDiagnosticReport,
+    },
     document_symbol::document_symbol,
-    from_lsp::{file_path_from_url, kcl_pos},
+    find_refs::find_refs_with_progress,
+    fix_all::fix_all_action,
+    folding_range::folding_range,
+    formatting::format,
+    from_lsp::{file_path_from_url, kcl_token_pos},
     goto_def::goto_definition,
     hover,
-    state::{log_message, LanguageServerSnapshot, LanguageServerState, Task},
-    util::{parse_param_and_compile, Param},
+    indentation::indentation_diagnostics,
+    moniker::moniker,
+    quick_fix::{fix_mixed_indentation, quick_fix},
+    rename::{
+        document_changes_supported, prepare_rename, rename_packages, rename_symbol,
+        with_document_changes,
+    },
+    sourcemap::SourceMap,
+    state::{
+        log_message, send_work_done_progress, LanguageServerSnapshot, LanguageServerState, Task,
+    },
+    suppression::drop_suppressed,
+    util::{
+        compile_db, get_kcl_files_with_extensions, load_file_code_from_vfs, parse_param,
+        parse_param_and_compile, Param,
+    },
+    workspace_symbol::{matches_query, workspace_symbols},
 };
 
 impl LanguageServerState {
@@ -41,9 +82,21 @@ impl LanguageServerState {
                 Ok(())
             })?
             .on::<lsp_types::request::GotoDefinition>(handle_goto_definition)?
+            .on::<lsp_types::request::References>(handle_reference)?
             .on::<lsp_types::request::Completion>(handle_completion)?
+            .on::<lsp_types::request::ResolveCompletionItem>(handle_completion_resolve)?
             .on::<lsp_types::request::HoverRequest>(handle_hover)?
             .on::<lsp_types::request::DocumentSymbolRequest>(handle_document_symbol)?
+            .on::<lsp_types::request::FoldingRangeRequest>(handle_folding_range)?
+            .on::<lsp_types::request::MonikerRequest>(handle_moniker)?
+            .on::<lsp_types::request::Formatting>(handle_formatting)?
+            .on::<lsp_types::request::CodeActionRequest>(handle_code_action)?
+            .on::<lsp_types::request::ExecuteCommand>(handle_execute_command)?
+            .on::<lsp_types::request::WillRenameFiles>(handle_will_rename_files)?
+            .on::<lsp_types::request::PrepareRenameRequest>(handle_prepare_rename)?
+            .on::<lsp_types::request::Rename>(handle_rename)?
+            .on::<lsp_types::request::WorkspaceSymbol>(handle_workspace_symbol)?
+            .on::<DocumentDiagnosticRequest>(handle_document_diagnostic)?
             .finish();
 
         Ok(())
@@ -57,21 +110,99 @@ pub(crate) fn handle_goto_definition(
     sender: Sender<Task>,
 ) -> anyhow::Result<Option<lsp_types::GotoDefinitionResponse>> {
     let file = file_path_from_url(&params.text_document_position_params.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(None);
+    }
 
-    let (program, prog_scope, _) = parse_param_and_compile(
-        Param {
-            file: file.to_string(),
+    let text = load_file_code_from_vfs(&file, snapshot.vfs.clone())?;
+    let kcl_pos = kcl_token_pos(&file, params.text_document_position_params.position, &text);
+    let db = db_with_cache(&snapshot, &file, &text)?;
+    let res = db.resolve(
+        &kcl_pos,
+        |res| &res.definition,
+        || {
+            let (program, prog_scope, _) = compile_db(&db);
+            goto_definition(&program, &kcl_pos, &prog_scope)
         },
-        Some(snapshot.vfs),
-    )?;
-    let kcl_pos = kcl_pos(&file, params.text_document_position_params.position);
-    let res = goto_definition(&program, &kcl_pos, &prog_scope);
+    );
     if res.is_none() {
         log_message("Definition not found".to_string(), &sender)?;
     }
     Ok(res)
 }
 
+/// Called when a `References` request was received.
+pub(crate) fn handle_reference(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::ReferenceParams,
+    sender: Sender<Task>,
+) -> anyhow::Result<Option<Vec<lsp_types::Location>>> {
+    let file = file_path_from_url(&params.text_document_position.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(None);
+    }
+
+    let text = load_file_code_from_vfs(&file, snapshot.vfs.clone())?;
+    let (program, prog_scope, _) = compile_with_db(&snapshot, &file)?;
+    let kcl_pos = kcl_token_pos(&file, params.text_document_position.position, &text);
+    snapshot
+        .word_index_generations
+        .refresh(&snapshot.word_index_map, snapshot.vfs.clone());
+    // Report the progress of the verification of the candidates with the work done token,
+    // which keeps the request of a large search alive in the client.
+    let token = params.work_done_progress_params.work_done_token;
+    if let Some(token) = &token {
+        send_work_done_progress(
+            token,
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: "Finding references".to_string(),
+                percentage: Some(0),
+                ..Default::default()
+            }),
+            &sender,
+        )?;
+    }
+    let mut reported_percentage = 0;
+    let res = find_refs_with_progress(
+        &program,
+        &kcl_pos,
+        &prog_scope,
+        &snapshot.word_index_map.read(),
+        Some(snapshot.vfs),
+        params.context.include_declaration,
+        &mut |done, total| {
+            let token = match &token {
+                Some(token) => token,
+                None => return,
+            };
+            let percentage = (done * 100 / total) as u32;
+            if percentage > reported_percentage {
+                reported_percentage = percentage;
+                let _ = send_work_done_progress(
+                    token,
+                    WorkDoneProgress::Report(WorkDoneProgressReport {
+                        message: Some(format!("{}/{}", done, total)),
+                        percentage: Some(percentage),
+                        ..Default::default()
+                    }),
+                    &sender,
+                );
+            }
+        },
+    );
+    if let Some(token) = &token {
+        send_work_done_progress(
+            token,
+            WorkDoneProgress::End(WorkDoneProgressEnd::default()),
+            &sender,
+        )?;
+    }
+    if res.is_none() {
+        log_message("References not found".to_string(), &sender)?;
+    }
+    Ok(res)
+}
+
 /// Called when a `Completion` request was received.
 pub(crate) fn handle_completion(
     snapshot: LanguageServerSnapshot,
@@ -79,14 +210,12 @@ pub(crate) fn handle_completion(
     sender: Sender<Task>,
 ) -> anyhow::Result<Option<lsp_types::CompletionResponse>> {
     let file = file_path_from_url(&params.text_document_position.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(None);
+    }
 
-    let (program, prog_scope, _) = parse_param_and_compile(
-        Param {
-            file: file.to_string(),
-        },
-        Some(snapshot.vfs),
-    )?;
-    let kcl_pos = kcl_pos(&file, params.text_document_position.position);
+    let text = load_file_code_from_vfs(&file, snapshot.vfs.clone())?;
+    let kcl_pos = kcl_token_pos(&file, params.text_document_position.position, &text);
     log_message(
         format!(
             "handle_completion {:?}",
@@ -99,13 +228,121 @@ pub(crate) fn handle_completion(
         .and_then(|ctx| ctx.trigger_character)
         .and_then(|s| s.chars().next());
 
-    let res = completion(
-        completion_trigger_character,
+    // The completion without a trigger character is shared with the hover and the goto
+    // definition at the same position.
+    if completion_trigger_character.is_none() {
+        let db = db_with_cache(&snapshot, &file, &text)?;
+        return Ok(db.resolve(
+            &kcl_pos,
+            |res| &res.completion,
+            || {
+                let (program, prog_scope, _) = compile_db(&db);
+                completion_response(&snapshot, &text, &program, &kcl_pos, &prog_scope, None)
+            },
+        ));
+    }
+    let (program, prog_scope, _) = compile_with_db(&snapshot, &file)?;
+    Ok(completion_response(
+        &snapshot,
+        &text,
         &program,
         &kcl_pos,
         &prog_scope,
-    );
-    Ok(res)
+        completion_trigger_character,
+    ))
+}
+
+/// Called when a `completionItem/resolve` request was received, which attaches the
+/// documentation to the completion item by the definition referenced in its data.
+pub(crate) fn handle_completion_resolve(
+    snapshot: LanguageServerSnapshot,
+    item: lsp_types::CompletionItem,
+    sender: Sender<Task>,
+) -> anyhow::Result<lsp_types::CompletionItem> {
+    let data: CompletionItemData = match item
+        .data
+        .clone()
+        .and_then(|data| serde_json::from_value(data).ok())
+    {
+        Some(data) => data,
+        None => return Ok(item),
+    };
+    log_message(format!("handle_completion_resolve {}", item.label), &sender)?;
+    if !is_analyzed(&snapshot, &data.file, &sender)? {
+        return Ok(item);
+    }
+    let (program, prog_scope, _) = compile_with_db(&snapshot, &data.file)?;
+    let markdown = completion_markdown_supported(&snapshot.client_capabilities);
+    Ok(resolve_completion_item(
+        item,
+        &data,
+        &program,
+        &prog_scope,
+        markdown,
+    ))
+}
+
+/// Computes the completion at the position with the trigger character, if any.
+fn completion_response(
+    snapshot: &LanguageServerSnapshot,
+    text: &str,
+    program: &Program,
+    kcl_pos: &KCLPos,
+    prog_scope: &ProgramScope,
+    completion_trigger_character: Option<char>,
+) -> Option<lsp_types::CompletionResponse> {
+    match completion_trigger_character {
+        Some(_) => None,
+        None => completion_type_annotation(
+            text,
+            program,
+            kcl_pos,
+            prog_scope,
+            snippet_supported(&snapshot.client_capabilities),
+        )
+        .or_else(|| {
+            if snapshot.config.suggest_schema_configs
+                && snippet_supported(&snapshot.client_capabilities)
+            {
+                completion_schema_config(text, program, kcl_pos, prog_scope)
+            } else {
+                None
+            }
+        }),
+    }
+    .or_else(|| {
+        let budget = CompletionBudget::new(
+            snapshot
+                .config
+                .completion_budget_ms
+                .map(Duration::from_millis),
+        );
+        let schema_insertion = SchemaInsertion::new(
+            snapshot.config.schema_completion_style,
+            snippet_supported(&snapshot.client_capabilities),
+            text,
+            kcl_pos,
+        );
+        completion_with_budget(
+            completion_trigger_character,
+            program,
+            kcl_pos,
+            prog_scope,
+            &budget,
+            &schema_insertion,
+        )
+    })
+    .or_else(|| {
+        if snapshot.config.suggest_existing_keys && completion_trigger_character.is_none() {
+            completion_existing_keys(
+                program,
+                kcl_pos,
+                snippet_supported(&snapshot.client_capabilities),
+            )
+        } else {
+            None
+        }
+    })
 }
 
 /// Called when a `Completion` request was received.
@@ -115,14 +352,12 @@ pub(crate) fn handle_hover(
     sender: Sender<Task>,
 ) -> anyhow::Result<Option<lsp_types::Hover>> {
     let file = file_path_from_url(&params.text_document_position_params.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(None);
+    }
 
-    let (program, prog_scope, _) = parse_param_and_compile(
-        Param {
-            file: file.to_string(),
-        },
-        Some(snapshot.vfs),
-    )?;
-    let kcl_pos = kcl_pos(&file, params.text_document_position_params.position);
+    let text = load_file_code_from_vfs(&file, snapshot.vfs.clone())?;
+    let kcl_pos = kcl_token_pos(&file, params.text_document_position_params.position, &text);
     log_message(
         format!(
             "handle_hover {:?}",
@@ -131,7 +366,55 @@ pub(crate) fn handle_hover(
         &sender,
     )?;
 
-    let res = hover::hover(&program, &kcl_pos, &prog_scope);
+    let db = db_with_cache(&snapshot, &file, &text)?;
+    let infos = db.resolve(
+        &kcl_pos,
+        |res| &res.hover,
+        || {
+            let (program, prog_scope, _) = compile_db(&db);
+            hover::hover_infos(&program, &kcl_pos, &prog_scope)
+        },
+    );
+    // The reference target is resolved only for the subtypes and the reference count.
+    let reference_target =
+        if snapshot.config.hover_show_subtypes || snapshot.config.hover_reference_count {
+            db.resolve(
+                &kcl_pos,
+                |res| &res.reference_target,
+                || {
+                    let (program, prog_scope, _) = compile_db(&db);
+                    hover::reference_target(&program, &kcl_pos, &prog_scope)
+                },
+            )
+        } else {
+            None
+        };
+    let markdown = hover::markdown_supported(&snapshot.client_capabilities);
+    let range = hover::hover_range(&infos, &text, &kcl_pos);
+    let mut res = hover::infos_to_hover(infos.clone(), markdown)
+        .map(|hover| lsp_types::Hover { range, ..hover });
+    // The subtypes are scanned from the compiled programs without compiling the others.
+    if snapshot.config.hover_show_subtypes {
+        let schema = infos
+            .first()
+            .filter(|info| info.kind == Some(ScopeObjectKind::Definition))
+            .and(reference_target.as_ref());
+        if let Some((name, _)) = schema {
+            let subtypes =
+                hover::direct_subtypes(name, snapshot.db.read().values().map(|db| &db.prog));
+            if !subtypes.is_empty() {
+                res = res.map(|hover| hover::with_subtypes(hover, &subtypes));
+            }
+        }
+    }
+    // The references are counted from the cached word index without refreshing it, which
+    // keeps the hover fast.
+    if snapshot.config.hover_reference_count {
+        if let Some((name, decl)) = &reference_target {
+            let count = hover::count_references(name, decl, &snapshot.word_index_map.read());
+            res = res.map(|hover| hover::with_reference_count(hover, count));
+        }
+    }
     Ok(res)
 }
 
@@ -142,13 +425,14 @@ pub(crate) fn handle_document_symbol(
     sender: Sender<Task>,
 ) -> anyhow::Result<Option<lsp_types::DocumentSymbolResponse>> {
     let file = file_path_from_url(&params.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(None);
+    }
+    if !snapshot.config.includes_symbols_of(&file) {
+        return Ok(None);
+    }
 
-    let (program, prog_scope, _) = parse_param_and_compile(
-        Param {
-            file: file.to_string(),
-        },
-        Some(snapshot.vfs),
-    )?;
+    let (program, prog_scope, _) = compile_with_db(&snapshot, &file)?;
 
     let res = document_symbol(&file, &program, &prog_scope);
     if res.is_none() {
@@ -156,3 +440,378 @@ pub(crate) fn handle_document_symbol(
     }
     Ok(res)
 }
+
+/// Called when a `FoldingRange` request was received.
+pub(crate) fn handle_folding_range(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::FoldingRangeParams,
+    _sender: Sender<Task>,
+) -> anyhow::Result<Option<Vec<lsp_types::FoldingRange>>> {
+    let file = file_path_from_url(&params.text_document.uri)?;
+    let source = load_file_code_from_vfs(&file, snapshot.vfs)?;
+    Ok(Some(folding_range(&file, source)?))
+}
+
+/// Called when a `Moniker` request was received.
+pub(crate) fn handle_moniker(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::MonikerParams,
+    sender: Sender<Task>,
+) -> anyhow::Result<Option<Vec<lsp_types::Moniker>>> {
+    let file = file_path_from_url(&params.text_document_position_params.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(None);
+    }
+
+    let text = load_file_code_from_vfs(&file, snapshot.vfs.clone())?;
+    let (program, prog_scope, _) = compile_with_db(&snapshot, &file)?;
+    let kcl_pos = kcl_token_pos(&file, params.text_document_position_params.position, &text);
+    let res = moniker(&program, &kcl_pos, &prog_scope);
+    if res.is_none() {
+        log_message("Moniker not found".to_string(), &sender)?;
+    }
+    Ok(res)
+}
+
+/// Called when a `Formatting` request was received.
+pub(crate) fn handle_formatting(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::DocumentFormattingParams,
+    _sender: Sender<Task>,
+) -> anyhow::Result<Option<Vec<lsp_types::TextEdit>>> {
+    let file = file_path_from_url(&params.text_document.uri)?;
+    let src = load_file_code_from_vfs(&file, snapshot.vfs)?;
+    format(file, src)
+}
+
+/// Called when a `CodeActionRequest` request was received.
+pub(crate) fn handle_code_action(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::CodeActionParams,
+    sender: Sender<Task>,
+) -> anyhow::Result<Option<lsp_types::CodeActionResponse>> {
+    let file = file_path_from_url(&params.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(None);
+    }
+
+    let (program, prog_scope, diags) = compile_with_db(&snapshot, &file)?;
+    let src = load_file_code_from_vfs(&file, snapshot.vfs.clone())?;
+    let mut actions = quick_fix(
+        &program,
+        &prog_scope,
+        &file,
+        &params.text_document.uri,
+        params.range,
+    );
+    if snapshot.config.lint_indentation {
+        actions.extend(fix_mixed_indentation(
+            &params.context.diagnostics,
+            &src,
+            &params.text_document.uri,
+        ));
+    }
+    // The `source.fixAll` action is offered unless the client asks for the other kinds,
+    // and is skipped if the file can not be formatted, e.g., with syntax errors.
+    let fix_all_requested = params.context.only.as_ref().map_or(true, |only| {
+        let fix_all = CodeActionKind::SOURCE_FIX_ALL;
+        only.iter().any(|kind| {
+            fix_all == *kind || fix_all.as_str().starts_with(&format!("{}.", kind.as_str()))
+        })
+    });
+    if fix_all_requested {
+        if let Ok(Some(action)) =
+            fix_all_action(&program, &diags, &file, &params.text_document.uri, &src)
+        {
+            actions.push(CodeActionOrCommand::CodeAction(action));
+        }
+    }
+    if actions.is_empty() {
+        log_message("Code action not found".to_string(), &sender)?;
+        return Ok(None);
+    }
+    Ok(Some(actions))
+}
+
+/// Called when a `ExecuteCommand` request was received.
+pub(crate) fn handle_execute_command(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::ExecuteCommandParams,
+    sender: Sender<Task>,
+) -> anyhow::Result<Option<serde_json::Value>> {
+    log_message(
+        format!("handle_execute_command {}", params.command),
+        &sender,
+    )?;
+    execute_command(
+        snapshot,
+        &params.command,
+        params.arguments,
+        params.work_done_progress_params.work_done_token,
+        &sender,
+    )
+}
+
+/// Called when a `WillRenameFiles` request was received.
+pub(crate) fn handle_will_rename_files(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::RenameFilesParams,
+    sender: Sender<Task>,
+) -> anyhow::Result<Option<lsp_types::WorkspaceEdit>> {
+    let res = rename_packages(
+        &params.files,
+        &snapshot.config.kcl_extensions,
+        snapshot.vfs.clone(),
+    )?;
+    if res.is_none() {
+        log_message("No imports to be renamed".to_string(), &sender)?;
+    }
+    Ok(res.map(|edit| versioned_edit(&snapshot, edit)))
+}
+
+/// Called when a `PrepareRename` request was received.
+pub(crate) fn handle_prepare_rename(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::TextDocumentPositionParams,
+    sender: Sender<Task>,
+) -> anyhow::Result<Option<lsp_types::PrepareRenameResponse>> {
+    let file = file_path_from_url(&params.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(None);
+    }
+
+    let text = load_file_code_from_vfs(&file, snapshot.vfs.clone())?;
+    let (program, prog_scope, _) = compile_with_db(&snapshot, &file)?;
+    let kcl_pos = kcl_token_pos(&file, params.position, &text);
+    let res = prepare_rename(&program, &kcl_pos, &prog_scope)?;
+    if res.is_none() {
+        log_message("No symbol to be renamed".to_string(), &sender)?;
+    }
+    Ok(res)
+}
+
+/// Called when a `Rename` request was received.
+pub(crate) fn handle_rename(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::RenameParams,
+    sender: Sender<Task>,
+) -> anyhow::Result<Option<lsp_types::WorkspaceEdit>> {
+    let file = file_path_from_url(&params.text_document_position.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(None);
+    }
+
+    let text = load_file_code_from_vfs(&file, snapshot.vfs.clone())?;
+    let (program, prog_scope, _) = compile_with_db(&snapshot, &file)?;
+    let kcl_pos = kcl_token_pos(&file, params.text_document_position.position, &text);
+    snapshot
+        .word_index_generations
+        .refresh(&snapshot.word_index_map, snapshot.vfs.clone());
+    let res = rename_symbol(
+        &program,
+        &kcl_pos,
+        &prog_scope,
+        &params.new_name,
+        &snapshot.word_index_map.read(),
+        snapshot.vfs.clone(),
+    )?;
+    if res.is_none() {
+        log_message("No symbol to be renamed".to_string(), &sender)?;
+    }
+    Ok(res.map(|edit| versioned_edit(&snapshot, edit)))
+}
+
+/// Returns the workspace edit with the versioned document changes if the client supports
+/// them, otherwise the edit with the changes map.
+fn versioned_edit(
+    snapshot: &LanguageServerSnapshot,
+    edit: lsp_types::WorkspaceEdit,
+) -> lsp_types::WorkspaceEdit {
+    if document_changes_supported(&snapshot.client_capabilities) {
+        with_document_changes(edit, &snapshot.document_versions.read())
+    } else {
+        edit
+    }
+}
+
+/// Called when a `WorkspaceSymbol` request was received.
+pub(crate) fn handle_workspace_symbol(
+    snapshot: LanguageServerSnapshot,
+    params: lsp_types::WorkspaceSymbolParams,
+    sender: Sender<Task>,
+) -> anyhow::Result<Option<Vec<lsp_types::SymbolInformation>>> {
+    // The compiled files and the KCL files in the workspace folders are the entries, and
+    // the files covered by the programs of the former entries are skipped.
+    let mut entries: Vec<String> = snapshot.db.read().keys().cloned().collect();
+    let folders: Vec<Url> = snapshot.word_index_map.read().keys().cloned().collect();
+    for folder in folders {
+        let path = file_path_from_url(&folder)?;
+        entries.extend(get_kcl_files_with_extensions(
+            Path::new(&path),
+            &snapshot.config.kcl_extensions,
+        )?);
+    }
+    let mut covered = HashSet::new();
+    // The programs of the files in the same package are the same, so the symbols are
+    // deduplicated by the locations. The symbol found in the imported package is preferred
+    // for its package path, e.g., `frontend` rather than `__main__` of the entry itself.
+    let mut symbols: IndexMap<(String, Url, lsp_types::Position), lsp_types::SymbolInformation> =
+        IndexMap::new();
+    for entry in entries {
+        if !covered.insert(entry.clone()) {
+            continue;
+        }
+        let db = match load_file_code_from_vfs(&entry, snapshot.vfs.clone())
+            .and_then(|text| db_with_cache(&snapshot, &entry, &text))
+        {
+            Ok(db) => db,
+            Err(err) => {
+                log_message(format!("failed to compile {}: {}", entry, err), &sender)?;
+                continue;
+            }
+        };
+        covered.extend(db.deps.iter().cloned());
+        let entry_symbols = db.symbols(|| {
+            let (_, prog_scope, _) = compile_db(&db);
+            workspace_symbols(&prog_scope)
+        });
+        for symbol in entry_symbols {
+            let included = file_path_from_url(&symbol.location.uri)
+                .map_or(true, |file| snapshot.config.includes_symbols_of(&file));
+            if !included || !matches_query(symbol, &params.query) {
+                continue;
+            }
+            let key = (
+                symbol.name.clone(),
+                symbol.location.uri.clone(),
+                symbol.location.range.start,
+            );
+            match symbols.get(&key) {
+                Some(found) if found.container_name.as_deref() != Some(MAIN_PKG) => {}
+                _ => {
+                    symbols.insert(key, symbol.clone());
+                }
+            }
+        }
+    }
+    let res: Vec<lsp_types::SymbolInformation> = symbols.into_values().collect();
+    if res.is_empty() {
+        log_message("Workspace symbol not found".to_string(), &sender)?;
+    }
+    Ok(Some(res))
+}
+
+/// Called when a `textDocument/diagnostic` request was received.
+pub(crate) fn handle_document_diagnostic(
+    snapshot: LanguageServerSnapshot,
+    params: DocumentDiagnosticParams,
+    sender: Sender<Task>,
+) -> anyhow::Result<FullDocumentDiagnosticReport> {
+    if !snapshot.config.pull_diagnostics() {
+        return Err(anyhow::anyhow!(
+            "The diagnostics are pushed by the server according to the diagnostics_mode"
+        ));
+    }
+    let file = file_path_from_url(&params.text_document.uri)?;
+    if !is_analyzed(&snapshot, &file, &sender)? {
+        return Ok(FullDocumentDiagnosticReport {
+            kind: "full".to_string(),
+            items: vec![],
+        });
+    }
+    let source = load_file_code_from_vfs(&file, snapshot.vfs.clone())?;
+    // The diagnostics are computed from the cached db of the file, so that they agree with
+    // the other requests on the file and the file is not compiled again.
+    let db = db_with_cache(&snapshot, &file, &source)?;
+    let (_, _, diags) = compile_db(&db);
+    let sourcemap = snapshot
+        .config
+        .sourcemap_diagnostics
+        .then(|| SourceMap::parse(&file, &source));
+    let mut report = document_diagnostic(
+        &file,
+        &source,
+        diags.iter(),
+        &snapshot.config.severity_overrides,
+        sourcemap.as_ref(),
+    );
+    if snapshot.config.lint_indentation {
+        report
+            .items
+            .extend(drop_suppressed(indentation_diagnostics(&source), &source));
+    }
+    Ok(report)
+}
+
+/// Whether the file is analyzed by the requests, i.e., it is a KCL file by the
+/// `kcl_extensions` of the config and its size doesn't exceed the `max_file_size`. The
+/// requests on the other files are answered with the empty results, except the formatting.
+fn is_analyzed(
+    snapshot: &LanguageServerSnapshot,
+    file: &str,
+    sender: &Sender<Task>,
+) -> anyhow::Result<bool> {
+    if !snapshot.config.is_kcl_file(file) {
+        log_message(
+            format!("Skip analyzing {} which is not a KCL file", file),
+            sender,
+        )?;
+        return Ok(false);
+    }
+    let size = load_file_code_from_vfs(file, snapshot.vfs.clone())?.len();
+    if snapshot.config.exceeds_max_file_size(size) {
+        log_message(
+            format!(
+                "Skip analyzing {} whose size {} bytes exceeds the max file size {} bytes",
+                file, size, snapshot.config.max_file_size
+            ),
+            sender,
+        )?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Returns the db of the file, whose resolutions at the positions are computed on demand by
+/// the goto definition, the hover and the completion, and shared by the later requests at
+/// the same positions until the db is replaced, e.g., for a click following a hover. The
+/// file without a db is compiled on the first request and the db is cached.
+fn db_with_cache(
+    snapshot: &LanguageServerSnapshot,
+    file: &str,
+    text: &str,
+) -> anyhow::Result<AnalysisDatabase> {
+    if let Some(db) = snapshot.db.read().get(file) {
+        return Ok(db.clone());
+    }
+    let compiled = parse_param(
+        Param {
+            file: file.to_string(),
+        },
+        Some(snapshot.vfs.clone()),
+    )?;
+    // The db compiled with the contents changed meanwhile is not cached, which is checked
+    // with the dbs locked, so that the db cached before the change is invalidated by it.
+    let mut db = snapshot.db.write();
+    if load_file_code_from_vfs(file, snapshot.vfs.clone())? != text {
+        return Ok(compiled);
+    }
+    Ok(db.entry(file.to_string()).or_insert(compiled).clone())
+}
+
+/// Compiles the file, reusing the analysis db of the file if it has been compiled, e.g.,
+/// warmed up on startup.
+fn compile_with_db(
+    snapshot: &LanguageServerSnapshot,
+    file: &str,
+) -> anyhow::Result<(Program, ProgramScope, IndexSet<Diagnostic>)> {
+    if let Some(db) = snapshot.db.read().get(file) {
+        return Ok(compile_db(db));
+    }
+    parse_param_and_compile(
+        Param {
+            file: file.to_string(),
+        },
+        Some(snapshot.vfs.clone()),
+    )
+}
@@ -0,0 +1,89 @@
+//! The source maps of the KCL files generated from the templates, which relate the
+//! diagnostics in a generated file to their locations in the source template, e.g.,
+//!
+//! ```kcl
+//! # sourcemap: templates/app.k.tmpl:10
+//! name = 1
+//! ```
+//!
+//! maps the line following the directive to the line 10 of the template, and the next lines
+//! to the next lines of the template until the next directive. The relative path of the
+//! template is relative to the directory of the generated file.
+
+use std::path::Path;
+
+use kclvm_error::Position as KCLPos;
+use lsp_types::{Location, Position, Range, Url};
+
+/// The directive of the source map comment.
+const SOURCEMAP: &str = "sourcemap:";
+
+/// A directive of the source map, which maps the lines following it to the template.
+#[derive(Debug, Clone)]
+struct SourceMapEntry {
+    /// The one-based line of the directive in the generated file.
+    line: u64,
+    /// The template.
+    source: Url,
+    /// The one-based line of the template mapped from the line following the directive.
+    source_line: u64,
+}
+
+/// The source map directives of a generated file ordered by their lines.
+#[derive(Debug, Default)]
+pub(crate) struct SourceMap {
+    entries: Vec<SourceMapEntry>,
+}
+
+impl SourceMap {
+    /// Parses the source map comments in the source of the file, which occupy whole lines.
+    /// The directives whose templates can't be located are skipped.
+    pub(crate) fn parse(file: &str, source: &str) -> Self {
+        let dir = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+        let mut entries = vec![];
+        for (line, text) in source.lines().enumerate() {
+            let directive = match text.trim_start().strip_prefix('#') {
+                Some(comment) => comment.trim_start(),
+                None => continue,
+            };
+            let target = match directive.strip_prefix(SOURCEMAP) {
+                Some(target) => target.trim(),
+                None => continue,
+            };
+            let (path, source_line) = match target.rsplit_once(':') {
+                Some((path, source_line)) => match source_line.parse::<u64>() {
+                    Ok(source_line) => (path, source_line),
+                    Err(_) => (target, 1),
+                },
+                None => (target, 1),
+            };
+            if let Ok(source) = Url::from_file_path(dir.join(path)) {
+                entries.push(SourceMapEntry {
+                    line: line as u64 + 1,
+                    source,
+                    source_line,
+                });
+            }
+        }
+        Self { entries }
+    }
+
+    /// Maps the position in the generated file to the location in the template by the last
+    /// directive before the position, with the column unchanged.
+    pub(crate) fn map(&self, pos: &KCLPos) -> Option<Location> {
+        let entry = self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| entry.line < pos.line)?;
+        let line = entry.source_line + (pos.line - entry.line - 1);
+        let position = Position::new(
+            line.saturating_sub(1) as u32,
+            pos.column.unwrap_or(0) as u32,
+        );
+        Some(Location {
+            uri: entry.source.clone(),
+            range: Range::new(position, position),
+        })
+    }
+}
@@ -1,18 +1,50 @@
-use crate::config::Config;
+use crate::analysis::Analysis;
+use crate::config::{Config, LogFormat};
+use crate::db::AnalysisDatabase;
+use crate::from_lsp::file_path_from_url;
+use crate::indentation::indentation_diagnostics;
+use crate::sourcemap::SourceMap;
+use crate::suppression::drop_suppressed;
 use crate::to_lsp::{kcl_diag_to_lsp_diags, url};
-use crate::util::{get_file_name, parse_param_and_compile, to_json, Param};
+use crate::util::{
+    build_word_index, get_file_name, load_file_code_from_vfs, mod_file_path, parse_param,
+    parse_param_and_compile, to_json, Param,
+};
+use crate::watcher::{spawn_watcher, FolderWatcher};
+use crate::word_index::{word_index_drift, WordIndexGenerations};
 use crossbeam_channel::{select, unbounded, Receiver, Sender};
-use lsp_server::{ReqQueue, Response};
+use indexmap::IndexSet;
+use kclvm_error::Diagnostic as KCLDiagnostic;
+use lsp_server::{ReqQueue, RequestId, Response};
 use lsp_types::{
     notification::{Notification, PublishDiagnostics},
-    Diagnostic, PublishDiagnosticsParams,
+    request::ApplyWorkspaceEdit,
+    ApplyWorkspaceEditParams, ApplyWorkspaceEditResponse, Diagnostic, DidChangeWatchedFilesParams,
+    FileEvent, Location, PublishDiagnosticsParams, Url,
 };
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use ra_ap_vfs::Vfs;
-use std::{sync::Arc, time::Instant};
+use serde::Serialize;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 pub(crate) type RequestHandler = fn(&mut LanguageServerState, lsp_server::Response);
 
+/// The interval of the verification of the word index if it is enabled.
+const VERIFY_WORD_INDEX_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The max number of the diverged words logged by the verification of the word index.
+const MAX_LOGGED_DRIFT_WORDS: usize = 10;
+
+/// The duration of the requests above which the time it took us to reply is logged.
+pub(crate) const SLOW_REQUEST_THRESHOLD: Duration = Duration::from_millis(100);
+
 /// A `Task` is something that is send from async tasks to the entry point for processing. This
 /// enables synchronizing resources like the connection with the client.
 #[allow(unused)]
@@ -20,12 +52,28 @@ pub(crate) type RequestHandler = fn(&mut LanguageServerState, lsp_server::Respon
 pub(crate) enum Task {
     Response(Response),
     Notify(lsp_server::Notification),
+    /// The files in the workspace folders watched by the server are changed.
+    ChangeWatchedFiles(Vec<FileEvent>),
+    /// The workspace edit produced by a command, which is applied by the client with the
+    /// `workspace/applyEdit` request.
+    ApplyEdit(ApplyWorkspaceEditParams),
+    /// The word index of the workspace folder is built.
+    WordIndex(Url, HashMap<String, Vec<Location>>),
+    /// The warmup entry is compiled into the db.
+    WarmUp(String, AnalysisDatabase),
 }
 
 #[derive(Debug)]
 pub(crate) enum Event {
     Task(Task),
     Lsp(lsp_server::Message),
+    /// The deadline of a request in the thread pool is reached.
+    Timeout,
+    /// No event is received for the configured idle time while some files are pending to
+    /// be recompiled.
+    Idle,
+    /// The interval of the verification of the word index is reached.
+    VerifyWordIndex,
 }
 
 /// State for the language server
@@ -36,8 +84,16 @@ pub(crate) struct LanguageServerState {
     /// The request queue keeps track of all incoming and outgoing requests.
     pub(crate) request_queue: lsp_server::ReqQueue<(String, Instant), RequestHandler>,
 
+    /// The ids of the requests sent to the client which are not responded yet. The
+    /// outgoing queue panics on completing an unknown id, so the responses are checked
+    /// against them first.
+    pub(crate) pending_outgoing_requests: HashSet<RequestId>,
+
     /// The configuration passed by the client
-    pub _config: Config,
+    pub config: Config,
+
+    /// The capabilities of the client
+    pub client_capabilities: lsp_types::ClientCapabilities,
 
     /// Thread pool for async execution
     pub thread_pool: threadpool::ThreadPool,
@@ -53,6 +109,35 @@ pub(crate) struct LanguageServerState {
 
     /// True if the client requested that we shut down
     pub shutdown_requested: bool,
+
+    /// The word index of each workspace folder, which maps a word to all of its locations
+    pub word_index_map: Arc<RwLock<HashMap<Url, HashMap<String, Vec<Location>>>>>,
+
+    /// The generations of the word index of each workspace folder
+    pub word_index_generations: WordIndexGenerations,
+
+    /// The compile results of the files which are reused by the requests
+    pub analysis: Analysis,
+
+    /// The diagnostics last published for each file, which are not published again
+    /// until they are changed
+    pub published_diagnostics: Arc<Mutex<HashMap<Url, Vec<Diagnostic>>>>,
+
+    /// The versions of the documents opened in the client, which are updated by the
+    /// changes of the documents
+    pub document_versions: Arc<RwLock<HashMap<Url, i32>>>,
+
+    /// The deadlines of the requests handled in the thread pool if the request timeout is
+    /// configured, which are removed once the requests are responded
+    pub request_deadlines: HashMap<RequestId, Instant>,
+
+    /// The files whose dbs are invalidated by the changed watched files, which are
+    /// recompiled once the event loop is idle if the idle flush is configured
+    pub pending_recompiles: IndexSet<String>,
+
+    /// The last time the word index was verified against the files if the verification
+    /// is enabled
+    pub word_index_verified: Instant,
 }
 
 /// A snapshot of the state of the language server
@@ -60,30 +145,191 @@ pub(crate) struct LanguageServerState {
 pub(crate) struct LanguageServerSnapshot {
     /// The virtual filesystem that holds all the file contents
     pub vfs: Arc<RwLock<Vfs>>,
+    /// The configuration passed by the client
+    pub config: Config,
+    /// The capabilities of the client
+    pub client_capabilities: lsp_types::ClientCapabilities,
+    /// The word index of each workspace folder
+    pub word_index_map: Arc<RwLock<HashMap<Url, HashMap<String, Vec<Location>>>>>,
+    /// The generations of the word index of each workspace folder
+    pub word_index_generations: WordIndexGenerations,
+    /// The compile results of the files
+    pub db: Arc<RwLock<HashMap<String, AnalysisDatabase>>>,
+    /// The diagnostics last published for each file
+    pub published_diagnostics: Arc<Mutex<HashMap<Url, Vec<Diagnostic>>>>,
+    /// The versions of the documents opened in the client
+    pub document_versions: Arc<RwLock<HashMap<Url, i32>>>,
 }
 
 #[allow(unused)]
 impl LanguageServerState {
-    pub fn new(sender: Sender<lsp_server::Message>, config: Config) -> Self {
+    pub fn new(
+        sender: Sender<lsp_server::Message>,
+        config: Config,
+        client_capabilities: lsp_types::ClientCapabilities,
+    ) -> Self {
         let (task_sender, task_receiver) = unbounded::<Task>();
+        // The pool is not used by the single-threaded server, so it is kept minimal.
+        let thread_pool = if config.single_threaded {
+            threadpool::ThreadPool::new(1)
+        } else {
+            threadpool::ThreadPool::default()
+        };
         LanguageServerState {
             sender,
             request_queue: ReqQueue::default(),
-            _config: config,
+            pending_outgoing_requests: HashSet::new(),
+            config,
+            client_capabilities,
             vfs: Arc::new(RwLock::new(Default::default())),
-            thread_pool: threadpool::ThreadPool::default(),
+            thread_pool,
             task_sender,
             task_receiver,
             shutdown_requested: false,
+            word_index_map: Arc::new(RwLock::new(HashMap::new())),
+            word_index_generations: WordIndexGenerations::default(),
+            analysis: Analysis::default(),
+            published_diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            document_versions: Arc::new(RwLock::new(HashMap::new())),
+            request_deadlines: HashMap::new(),
+            pending_recompiles: IndexSet::new(),
+            word_index_verified: Instant::now(),
+        }
+    }
+
+    /// Builds the word index of the workspace folders in the thread pool, or inline if the
+    /// server is single-threaded. The folders which fail to be indexed are logged and skipped.
+    pub fn build_word_index_map(&self, workspace_folders: &[Url]) {
+        let workspace_folders = workspace_folders.to_vec();
+        let extensions = self.config.kcl_extensions.clone();
+        let sender = self.task_sender.clone();
+        let build = move || {
+            for folder in workspace_folders {
+                let index = file_path_from_url(&folder)
+                    .and_then(|path| build_word_index(path, &extensions));
+                match index {
+                    Ok(index) => {
+                        let _ = sender.send(Task::WordIndex(folder, index));
+                    }
+                    Err(err) => {
+                        let _ = log_message(
+                            format!("failed to build the word index of {}: {}", folder, err),
+                            &sender,
+                        );
+                    }
+                }
+            }
+        };
+        if self.config.single_threaded {
+            build();
+        } else {
+            self.thread_pool.execute(build);
+        }
+    }
+
+    /// Increases the generation of the word index of the folders containing the changed
+    /// file, so that the stale index is refreshed before it is used.
+    pub(crate) fn mark_word_index_changed(&self, file: &str) {
+        if self.config.is_kcl_file(file) {
+            // Release the lock of the word index map before locking the generations, which
+            // is locked in the reverse order by the in-progress builds.
+            let folders: Vec<Url> = self.word_index_map.read().keys().cloned().collect();
+            self.word_index_generations
+                .mark_changed(file, folders.iter());
+        }
+    }
+
+    /// Compiles the warmup entries in the config into the analysis dbs in the thread pool,
+    /// or inline if the server is single-threaded. The relative entries are joined with the
+    /// first workspace folder. The entries which fail to be compiled are logged and skipped.
+    pub fn warm_up(&self, workspace_folders: &[Url]) {
+        let root = workspace_folders
+            .first()
+            .and_then(|folder| file_path_from_url(folder).ok());
+        let entries = self.config.warmup_entries.clone();
+        let vfs = self.vfs.clone();
+        let sender = self.task_sender.clone();
+        let warm_up = move || {
+            for entry in entries {
+                let file = match &root {
+                    Some(root) if Path::new(&entry).is_relative() => {
+                        Path::new(root).join(&entry).to_string_lossy().to_string()
+                    }
+                    _ => entry,
+                };
+                match parse_param(Param { file: file.clone() }, Some(vfs.clone())) {
+                    Ok(db) => {
+                        let _ = sender.send(Task::WarmUp(file, db));
+                    }
+                    Err(err) => {
+                        let _ =
+                            log_message(format!("failed to warm up {}: {}", file, err), &sender);
+                    }
+                }
+            }
+        };
+        if self.config.single_threaded {
+            warm_up();
+        } else {
+            self.thread_pool.execute(warm_up);
+        }
+    }
+
+    /// Watches the workspace folders for the created, changed and deleted files if the
+    /// client can't watch them.
+    pub fn watch_folders(&self, workspace_folders: &[Url]) {
+        if self.client_watches_files() {
+            return;
         }
+        let folders = workspace_folders
+            .iter()
+            .filter_map(|folder| file_path_from_url(folder).ok().map(PathBuf::from))
+            .collect();
+        // The zero interval would poll the folders in a busy loop.
+        let poll_interval = Duration::from_millis(self.config.watcher_poll_interval_ms.max(1));
+        let watcher = FolderWatcher::new(folders, &self.config.kcl_extensions, poll_interval);
+        spawn_watcher(watcher, self.task_sender.clone());
     }
 
     /// Blocks until a new event is received from one of the many channels the language server
     /// listens to. Returns the first event that is received.
+    /// The `Timeout` event is received once the earliest deadline of the requests is reached,
+    /// the `Idle` event once no event is received for the idle time of the idle flush, and
+    /// the `VerifyWordIndex` event once the interval of the verification is reached.
     fn next_event(&self, receiver: &Receiver<lsp_server::Message>) -> Option<Event> {
-        select! {
-            recv(receiver) -> msg => msg.ok().map(Event::Lsp),
-            recv(self.task_receiver) -> task => Some(Event::Task(task.unwrap()))
+        let request_deadline = self.request_deadlines.values().min().copied();
+        let idle_deadline = match self.config.idle_flush_ms {
+            Some(idle) if !self.pending_recompiles.is_empty() => {
+                Some(Instant::now() + Duration::from_millis(idle))
+            }
+            _ => None,
+        };
+        let verify_deadline = self
+            .config
+            .verify_word_index
+            .then(|| self.word_index_verified + VERIFY_WORD_INDEX_INTERVAL);
+        let deadline = [request_deadline, idle_deadline, verify_deadline]
+            .into_iter()
+            .flatten()
+            .min();
+        match deadline {
+            Some(deadline) => select! {
+                recv(receiver) -> msg => msg.ok().map(Event::Lsp),
+                recv(self.task_receiver) -> task => Some(Event::Task(task.unwrap())),
+                default(deadline.saturating_duration_since(Instant::now())) => {
+                    if request_deadline == Some(deadline) {
+                        Some(Event::Timeout)
+                    } else if idle_deadline == Some(deadline) {
+                        Some(Event::Idle)
+                    } else {
+                        Some(Event::VerifyWordIndex)
+                    }
+                }
+            },
+            None => select! {
+                recv(receiver) -> msg => msg.ok().map(Event::Lsp),
+                recv(self.task_receiver) -> task => Some(Event::Task(task.unwrap()))
+            },
         }
     }
 
@@ -101,7 +347,7 @@ impl LanguageServerState {
     }
 
     /// Handles an event from one of the many sources that the language server subscribes to.
-    fn handle_event(&mut self, event: Event) -> anyhow::Result<()> {
+    pub(crate) fn handle_event(&mut self, event: Event) -> anyhow::Result<()> {
         let start_time = Instant::now();
         // 1. Process the incoming event
         match event {
@@ -109,70 +355,271 @@ impl LanguageServerState {
             Event::Lsp(msg) => match msg {
                 lsp_server::Message::Request(req) => self.on_request(req, start_time)?,
                 lsp_server::Message::Notification(not) => self.on_notification(not)?,
-                // lsp_server::Message::Response(resp) => self.complete_request(resp),
-                _ => {}
+                lsp_server::Message::Response(resp) => self.complete_request(resp),
             },
+            // Nothing is changed, so the diagnostics are not handled.
+            Event::Timeout => return self.expire_requests(),
+            Event::Idle => {
+                self.flush_pending_recompiles();
+                return Ok(());
+            }
+            Event::VerifyWordIndex => {
+                self.verify_word_index();
+                return Ok(());
+            }
         };
+        self.expire_requests()?;
 
-        // 2. Process changes
-        // Todo: recompile and store result in db. Handle request and push diagnostis with db
-        // let state_changed: bool = self.process_vfs_changes();
-
-        // 3. Handle Diagnostics
-        let mut snapshot = self.snapshot();
+        // 2. Handle Diagnostics. The dbs depending on the changed files are invalidated by
+        // the notifications, and recompiled lazily by the requests or on idle.
+        let snapshot = self.snapshot();
         let task_sender = self.task_sender.clone();
-        // Spawn the diagnostics in the threadpool
-        self.thread_pool.execute(move || {
-            let _result = handle_diagnostics(snapshot, task_sender);
-        });
+        if self.config.synchronous_diagnostics() {
+            // The diagnostics are computed and sent before the next event is handled.
+            run_diagnostics_task(
+                move || handle_diagnostics(snapshot, task_sender),
+                &self.task_sender,
+            );
+            while let Ok(task) = self.task_receiver.try_recv() {
+                self.handle_task(task)?;
+            }
+        } else {
+            self.spawn_diagnostics_task(move || handle_diagnostics(snapshot, task_sender));
+        }
 
         Ok(())
     }
 
-    /// Processes any and all changes that have been applied to the virtual filesystem. Generates
-    /// an `AnalysisChange` and applies it if there are changes. True is returned if things changed,
-    /// otherwise false.
-    pub fn process_vfs_changes(&mut self) -> bool {
-        // Get all the changes since the last time we processed
-        let changed_files = {
-            let mut vfs = self.vfs.write();
-            vfs.take_changes()
+    /// Spawns the diagnostics task in the thread pool, or runs it inline if the server is
+    /// single-threaded. The errors and panics of the task are logged to the client, so that
+    /// a failed task does not stop the later diagnostics.
+    pub(crate) fn spawn_diagnostics_task<F>(&self, task: F)
+    where
+        F: FnOnce() -> anyhow::Result<()> + Send + 'static,
+    {
+        let sender = self.task_sender.clone();
+        let run = move || run_diagnostics_task(task, &sender);
+        if self.config.single_threaded {
+            run();
+        } else {
+            self.thread_pool.execute(run);
+        }
+    }
+
+    /// Recompiles the pending files into the analysis dbs in the thread pool, or inline if
+    /// the server is single-threaded. The dbs compiled by the requests in the meantime are
+    /// kept, and the deleted files are skipped.
+    pub(crate) fn flush_pending_recompiles(&mut self) {
+        let files: Vec<String> = self.pending_recompiles.drain(..).collect();
+        let vfs = self.vfs.clone();
+        let db = self.analysis.db.clone();
+        let sender = self.task_sender.clone();
+        let flush = move || {
+            for file in files {
+                if !Path::new(&file).exists() {
+                    continue;
+                }
+                match parse_param(Param { file: file.clone() }, Some(vfs.clone())) {
+                    Ok(compiled) => {
+                        db.write().entry(file).or_insert(compiled);
+                    }
+                    Err(err) => {
+                        let _ =
+                            log_message(format!("failed to recompile {}: {}", file, err), &sender);
+                    }
+                }
+            }
         };
-        if changed_files.is_empty() {
-            return false;
+        if self.config.single_threaded {
+            flush();
+        } else {
+            self.thread_pool.execute(flush);
         }
-        self.log_message("process_vfs_changes".to_string());
+    }
 
-        // Construct an AnalysisChange to apply to the analysis
-        let vfs = self.vfs.read();
-        for file in changed_files {
-            // todo: recompile and record context
+    /// Verifies the word index of the workspace folders against the index rebuilt from the
+    /// files in the thread pool, or inline if the server is single-threaded, and logs the
+    /// words whose locations diverge. The stale index is refreshed before the verification,
+    /// while the files changed during the verification may be reported transiently.
+    pub(crate) fn verify_word_index(&mut self) {
+        self.word_index_verified = Instant::now();
+        let word_index_map = self.word_index_map.clone();
+        let generations = self.word_index_generations.clone();
+        let vfs = self.vfs.clone();
+        let extensions = self.config.kcl_extensions.clone();
+        let sender = self.task_sender.clone();
+        let verify = move || {
+            generations.refresh(&word_index_map, vfs.clone());
+            let folders: Vec<Url> = word_index_map.read().keys().cloned().collect();
+            for folder in folders {
+                // The index is cloned to release the lock while the index is rebuilt.
+                let index = match word_index_map.read().get(&folder) {
+                    Some(index) => index.clone(),
+                    None => continue,
+                };
+                let message = match word_index_drift(&folder, &index, vfs.clone(), &extensions) {
+                    Ok(words) if words.is_empty() => continue,
+                    Ok(words) => format!(
+                        "the word index of {} diverges from the files at {} word(s): {}",
+                        folder,
+                        words.len(),
+                        words
+                            .iter()
+                            .take(MAX_LOGGED_DRIFT_WORDS)
+                            .cloned()
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ),
+                    Err(err) => format!("failed to verify the word index of {}: {}", folder, err),
+                };
+                let _ = sender.send(Task::Notify(lsp_server::Notification::new(
+                    lsp_types::notification::LogMessage::METHOD.to_string(),
+                    lsp_types::LogMessageParams {
+                        typ: lsp_types::MessageType::WARNING,
+                        message,
+                    },
+                )));
+            }
+        };
+        if self.config.single_threaded {
+            verify();
+        } else {
+            self.thread_pool.execute(verify);
         }
-        true
     }
 
     /// Handles a task sent by another async task
     #[allow(clippy::unnecessary_wraps)]
-    fn handle_task(&mut self, task: Task) -> anyhow::Result<()> {
+    pub(crate) fn handle_task(&mut self, task: Task) -> anyhow::Result<()> {
         match task {
             Task::Notify(notification) => {
-                self.send(notification.into());
+                // The messages logged by the background operations are formatted here,
+                // where the configuration is available.
+                if notification.method == lsp_types::notification::LogMessage::METHOD
+                    && self.config.log_format == LogFormat::Json
+                {
+                    let params: lsp_types::LogMessageParams =
+                        serde_json::from_value(notification.params)?;
+                    let message = self.format_log_message(params.typ, params.message, None, None);
+                    self.send_log_message(params.typ, message);
+                } else {
+                    self.send(notification.into());
+                }
             }
             Task::Response(response) => self.respond(response)?,
+            Task::ChangeWatchedFiles(changes) => {
+                self.on_did_change_watched_files(DidChangeWatchedFilesParams { changes })?
+            }
+            Task::ApplyEdit(params) => {
+                self.send_request::<ApplyWorkspaceEdit>(params, handle_apply_edit_response)?
+            }
+            Task::WordIndex(folder, index) => {
+                self.word_index_map.write().insert(folder, index);
+                // The index is built from the files on the disk, so the documents opened
+                // during the build are refreshed from the vfs before the index is used.
+                let opened: Vec<Url> = self.document_versions.read().keys().cloned().collect();
+                for uri in opened {
+                    if let Ok(file) = file_path_from_url(&uri) {
+                        self.mark_word_index_changed(&file);
+                    }
+                }
+            }
+            Task::WarmUp(file, db) => {
+                // The db compiled before the documents of its files are opened may be stale,
+                // which is compiled again by the requests on demand.
+                let opened = db.deps.iter().any(|dep| {
+                    Url::from_file_path(dep)
+                        .map(|uri| self.document_versions.read().contains_key(&uri))
+                        .unwrap_or(false)
+                });
+                if !opened {
+                    self.analysis.db.write().entry(file).or_insert(db);
+                }
+            }
         }
         Ok(())
     }
 
     /// Sends a response to the client. This method logs the time it took us to reply
-    /// to a request from the client.
+    /// to a request from the client if it exceeds the `SLOW_REQUEST_THRESHOLD`.
     pub(super) fn respond(&mut self, response: lsp_server::Response) -> anyhow::Result<()> {
-        if let Some((_method, start)) = self.request_queue.incoming.complete(response.id.clone()) {
+        self.request_deadlines.remove(&response.id);
+        if let Some((method, start)) = self.request_queue.incoming.complete(response.id.clone()) {
             let duration = start.elapsed();
             self.send(response.into())?;
+            if duration < SLOW_REQUEST_THRESHOLD {
+                return Ok(());
+            }
+            let message = self.format_log_message(
+                lsp_types::MessageType::INFO,
+                format!("{} finished in {}ms", method, duration.as_millis()),
+                Some(&method),
+                Some(duration),
+            );
+            self.send_log_message(lsp_types::MessageType::INFO, message);
         }
         Ok(())
     }
 
+    /// Sets the deadline of the request handled in the thread pool if the request timeout is
+    /// configured.
+    pub(crate) fn register_deadline(&mut self, id: &RequestId) {
+        if let Some(timeout) = self.config.request_timeout_ms {
+            self.request_deadlines
+                .insert(id.clone(), Instant::now() + Duration::from_millis(timeout));
+        }
+    }
+
+    /// Fails the requests whose deadlines are reached with `RequestFailed`. Their tasks are
+    /// abandoned rather than interrupted, and the late responses of the tasks are dropped
+    /// because the requests are completed.
+    pub(crate) fn expire_requests(&mut self) -> anyhow::Result<()> {
+        let now = Instant::now();
+        let expired: Vec<RequestId> = self
+            .request_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            self.respond(Response::new_err(
+                id,
+                lsp_server::ErrorCode::RequestFailed as i32,
+                format!(
+                    "request timed out after {}ms",
+                    self.config.request_timeout_ms.unwrap_or_default()
+                ),
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Handles a response to a request that was sent from the server to the client. The
+    /// responses with the unknown or the already completed ids are logged and dropped.
+    fn complete_request(&mut self, response: lsp_server::Response) {
+        if !self.pending_outgoing_requests.remove(&response.id) {
+            self.log_message(format!("unknown response {:?}", response));
+            return;
+        }
+        let handler = self.request_queue.outgoing.complete(response.id.clone());
+        handler(self, response)
+    }
+
+    /// Sends a request to the client and registers the handler which is called when the
+    /// client responds.
+    pub(crate) fn send_request<R: lsp_types::request::Request>(
+        &mut self,
+        params: R::Params,
+        handler: RequestHandler,
+    ) -> anyhow::Result<()> {
+        let request = self
+            .request_queue
+            .outgoing
+            .register(R::METHOD.to_string(), params, handler);
+        self.pending_outgoing_requests.insert(request.id.clone());
+        self.send(request.into())
+    }
+
     /// Sends a message to the client
     pub(crate) fn send(&mut self, message: lsp_server::Message) -> anyhow::Result<()> {
         self.sender.send(message)?;
@@ -196,21 +643,113 @@ impl LanguageServerState {
     pub fn snapshot(&self) -> LanguageServerSnapshot {
         LanguageServerSnapshot {
             vfs: self.vfs.clone(),
+            config: self.config.clone(),
+            client_capabilities: self.client_capabilities.clone(),
+            word_index_map: self.word_index_map.clone(),
+            word_index_generations: self.word_index_generations.clone(),
+            db: self.analysis.db.clone(),
+            published_diagnostics: self.published_diagnostics.clone(),
+            document_versions: self.document_versions.clone(),
+        }
+    }
+
+    pub fn show_message(&mut self, typ: lsp_types::MessageType, message: String) {
+        let not = lsp_server::Notification::new(
+            lsp_types::notification::ShowMessage::METHOD.to_string(),
+            lsp_types::ShowMessageParams { typ, message },
+        );
+        self.send(not.into());
+    }
+
+    /// Warns the client about the invalid fields of the config, which fall back to their
+    /// defaults.
+    pub fn warn_invalid_config(&mut self, errors: &[String]) {
+        for error in errors {
+            self.show_message(
+                lsp_types::MessageType::WARNING,
+                format!(
+                    "Invalid initialization options, {}, the default is used",
+                    error
+                ),
+            );
         }
     }
 
     pub fn log_message(&mut self, message: String) {
         let typ = lsp_types::MessageType::INFO;
+        let message = self.format_log_message(typ, message, None, None);
+        self.send_log_message(typ, message);
+    }
+
+    fn send_log_message(&mut self, typ: lsp_types::MessageType, message: String) {
         let not = lsp_server::Notification::new(
             lsp_types::notification::LogMessage::METHOD.to_string(),
             lsp_types::LogMessageParams { typ, message },
         );
         self.send(not.into());
     }
+
+    /// Formats the log message according to the configured log format.
+    fn format_log_message(
+        &self,
+        typ: lsp_types::MessageType,
+        message: String,
+        method: Option<&str>,
+        duration: Option<Duration>,
+    ) -> String {
+        match self.config.log_format {
+            LogFormat::Text => message,
+            LogFormat::Json => {
+                let record = LogRecord {
+                    timestamp: chrono::Local::now().to_rfc3339(),
+                    level: log_level(typ),
+                    method: method.map(|method| method.to_string()),
+                    duration_ms: duration.map(|duration| duration.as_millis()),
+                    message,
+                };
+                serde_json::to_string(&record).unwrap_or_else(|_| record.message)
+            }
+        }
+    }
+}
+
+/// A structured log record emitted in the JSON log format.
+#[derive(Debug, Serialize)]
+struct LogRecord {
+    timestamp: String,
+    level: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u128>,
+    message: String,
+}
+
+fn log_level(typ: lsp_types::MessageType) -> &'static str {
+    match typ {
+        lsp_types::MessageType::ERROR => "error",
+        lsp_types::MessageType::WARNING => "warning",
+        lsp_types::MessageType::INFO => "info",
+        _ => "log",
+    }
+}
+
+/// Runs the diagnostics task in the current thread and logs its error or panic to the
+/// client, so that a failed task does not take down the thread.
+pub(crate) fn run_diagnostics_task<F>(task: F, sender: &Sender<Task>)
+where
+    F: FnOnce() -> anyhow::Result<()>,
+{
+    let message = match panic::catch_unwind(AssertUnwindSafe(task)) {
+        Ok(Ok(())) => return,
+        Ok(Err(err)) => format!("diagnostics task failed: {}", err),
+        Err(panic) => format!("diagnostics task panicked: {}", panic_message(&*panic)),
+    };
+    let _ = log_message(message, sender);
 }
 
 // todo: `handle_diagnostics` only gets diag from db and converts them to lsp diagnostics.
-fn handle_diagnostics(
+pub(crate) fn handle_diagnostics(
     snapshot: LanguageServerSnapshot,
     sender: Sender<Task>,
 ) -> anyhow::Result<()> {
@@ -218,6 +757,10 @@ fn handle_diagnostics(
         let mut vfs = snapshot.vfs.write();
         vfs.take_changes()
     };
+    // The client pulls the diagnostics by the `textDocument/diagnostic` requests.
+    if snapshot.config.pull_diagnostics() {
+        return Ok(());
+    }
     for file in changed_files {
         let (filename, uri) = {
             let vfs = snapshot.vfs.read();
@@ -225,7 +768,15 @@ fn handle_diagnostics(
             let uri = url(&snapshot, file.file_id)?;
             (filename, uri)
         };
-        let (_, _, diags) = parse_param_and_compile(
+        if !snapshot.config.is_kcl_file(&filename)
+            || (file.exists()
+                && snapshot
+                    .config
+                    .exceeds_max_file_size(snapshot.vfs.read().file_contents(file.file_id).len()))
+        {
+            continue;
+        }
+        let (program, _, diags) = parse_param_and_compile(
             Param {
                 file: filename.clone(),
             },
@@ -233,22 +784,140 @@ fn handle_diagnostics(
         )
         .unwrap();
 
-        let diagnostics = diags
-            .iter()
-            .flat_map(|diag| kcl_diag_to_lsp_diags(diag, filename.as_str()))
-            .collect::<Vec<Diagnostic>>();
-        sender.send(Task::Notify(lsp_server::Notification {
-            method: PublishDiagnostics::METHOD.to_owned(),
-            params: to_json(PublishDiagnosticsParams {
-                uri,
-                diagnostics,
-                version: None,
-            })?,
-        }))?;
+        // Besides the changed file, the diagnostics are published for the files in its
+        // import closure, which are opened or allowed by the config.
+        let mut files = vec![(filename.clone(), uri)];
+        for dep in program.pkgs.values().flatten().map(|m| &m.filename) {
+            if files.iter().any(|(file, _)| file == dep) {
+                continue;
+            }
+            if !snapshot.config.publish_diagnostics_for_dependencies && !is_opened(&snapshot, dep) {
+                continue;
+            }
+            if let Ok(uri) = Url::from_file_path(dep) {
+                files.push((dep.clone(), uri));
+            }
+        }
+        // The parse error of the malformed kcl.mod file is published on the kcl.mod file,
+        // and cleared once the kcl.mod file is fixed.
+        if let Some(mod_file) = mod_file_path(&filename) {
+            if let Ok(uri) = Url::from_file_path(&mod_file) {
+                files.push((mod_file, uri));
+            }
+        }
+
+        for (file, uri) in files {
+            let source = load_file_code_from_vfs(&file, snapshot.vfs.clone()).unwrap_or_default();
+            let diagnostics = file_diagnostics(&snapshot.config, &diags, &file, &source);
+            // The unchanged diagnostics are not published again to avoid the flicker.
+            {
+                let mut published = snapshot.published_diagnostics.lock();
+                if published.get(&uri) == Some(&diagnostics) {
+                    continue;
+                }
+                published.insert(uri.clone(), diagnostics.clone());
+            }
+            sender.send(Task::Notify(lsp_server::Notification {
+                method: PublishDiagnostics::METHOD.to_owned(),
+                params: to_json(PublishDiagnosticsParams {
+                    uri,
+                    diagnostics,
+                    version: None,
+                })?,
+            }))?;
+        }
+    }
+    Ok(())
+}
+
+/// Converts the diagnostics of the compilation to the diagnostics of the file in its import
+/// closure, with the indentation lint applied if enabled and the suppressed ones dropped.
+pub(crate) fn file_diagnostics(
+    config: &Config,
+    diags: &IndexSet<KCLDiagnostic>,
+    file: &str,
+    source: &str,
+) -> Vec<Diagnostic> {
+    let sourcemap = config
+        .sourcemap_diagnostics
+        .then(|| SourceMap::parse(file, source));
+    let mut diagnostics = diags
+        .iter()
+        .flat_map(|diag| {
+            kcl_diag_to_lsp_diags(diag, file, &config.severity_overrides, sourcemap.as_ref())
+        })
+        .collect::<Vec<Diagnostic>>();
+    if config.lint_indentation && config.is_kcl_file(file) {
+        diagnostics.extend(indentation_diagnostics(source));
+    }
+    drop_suppressed(diagnostics, source)
+}
+
+/// Returns the message of the panic payload, which is a `&str` or a `String` in most cases.
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
     }
+}
+
+/// Whether the file is opened in the client, i.e., it is opened and not closed yet. The
+/// vfs also holds the files which are not opened, e.g., the changed files on the disk.
+fn is_opened(snapshot: &LanguageServerSnapshot, file: &str) -> bool {
+    match Url::from_file_path(file) {
+        Ok(uri) => snapshot.document_versions.read().contains_key(&uri),
+        Err(_) => false,
+    }
+}
+
+/// Sends the `$/progress` notification of the work done progress with the token.
+pub(crate) fn send_work_done_progress(
+    token: &lsp_types::ProgressToken,
+    progress: lsp_types::WorkDoneProgress,
+    sender: &Sender<Task>,
+) -> anyhow::Result<()> {
+    sender.send(Task::Notify(lsp_server::Notification::new(
+        lsp_types::notification::Progress::METHOD.to_string(),
+        lsp_types::ProgressParams {
+            token: token.clone(),
+            value: lsp_types::ProgressParamsValue::WorkDone(progress),
+        },
+    )))?;
     Ok(())
 }
 
+/// Handles the response of the `workspace/applyEdit` request. The command producing the
+/// edit has completed before the client applies it, so the edit rejected by the client or
+/// failed to be applied is logged and shown to the user.
+pub(crate) fn handle_apply_edit_response(
+    state: &mut LanguageServerState,
+    response: lsp_server::Response,
+) {
+    let lsp_server::Response { error, result, .. } = response;
+    let failure = match error {
+        Some(err) => Some(err.message),
+        None => match result
+            .and_then(|value| serde_json::from_value::<ApplyWorkspaceEditResponse>(value).ok())
+        {
+            Some(response) if response.applied => None,
+            Some(response) => Some(
+                response
+                    .failure_reason
+                    .unwrap_or_else(|| "rejected by the client".to_string()),
+            ),
+            None => Some("invalid response of the client".to_string()),
+        },
+    };
+    if let Some(failure) = failure {
+        let message = format!("Failed to apply the workspace edit: {}", failure);
+        state.log_message(message.clone());
+        state.show_message(lsp_types::MessageType::ERROR, message);
+    }
+}
+
 pub(crate) fn log_message(message: String, sender: &Sender<Task>) -> anyhow::Result<()> {
     let typ = lsp_types::MessageType::INFO;
     sender.send(Task::Notify(lsp_server::Notification::new(
@@ -0,0 +1,70 @@
+//! Line-level suppression of the diagnostics by the comments in the source, e.g.,
+//!
+//! ```kcl
+//! # kcl-disable-next-line TypeError
+//! p = Person {name = 1}
+//! ```
+//!
+//! drops the `TypeError` diagnostics on the next line. Several codes are separated by
+//! commas or spaces, and all the diagnostics on the next line are dropped without codes.
+
+use std::collections::HashMap;
+
+use lsp_types::{Diagnostic, NumberOrString};
+
+/// The directive of the suppression comment.
+const DISABLE_NEXT_LINE: &str = "kcl-disable-next-line";
+
+/// The suppressed diagnostic codes keyed by the zero-based lines. An empty list
+/// suppresses all the diagnostics on the line.
+#[derive(Debug, Default)]
+pub(crate) struct Suppressions {
+    lines: HashMap<u32, Vec<String>>,
+}
+
+impl Suppressions {
+    /// Parses the suppression comments in the source, which occupy whole lines.
+    pub(crate) fn parse(source: &str) -> Self {
+        let mut lines = HashMap::new();
+        for (line, text) in source.lines().enumerate() {
+            let directive = match text.trim_start().strip_prefix('#') {
+                Some(comment) => comment.trim_start(),
+                None => continue,
+            };
+            if let Some(codes) = directive.strip_prefix(DISABLE_NEXT_LINE) {
+                if !codes.is_empty() && !codes.starts_with(char::is_whitespace) {
+                    continue;
+                }
+                let codes = codes
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|code| !code.is_empty())
+                    .map(|code| code.to_string())
+                    .collect();
+                lines.insert(line as u32 + 1, codes);
+            }
+        }
+        Self { lines }
+    }
+
+    /// Whether the diagnostic is suppressed by a comment on its previous line.
+    pub(crate) fn is_suppressed(&self, diagnostic: &Diagnostic) -> bool {
+        match self.lines.get(&diagnostic.range.start.line) {
+            Some(codes) if codes.is_empty() => true,
+            Some(codes) => match &diagnostic.code {
+                Some(NumberOrString::String(code)) => codes.contains(code),
+                Some(NumberOrString::Number(code)) => codes.contains(&code.to_string()),
+                None => false,
+            },
+            None => false,
+        }
+    }
+}
+
+/// Drops the diagnostics suppressed by the comments in the source of their file.
+pub(crate) fn drop_suppressed(diagnostics: Vec<Diagnostic>, source: &str) -> Vec<Diagnostic> {
+    let suppressions = Suppressions::parse(source);
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| !suppressions.is_suppressed(diagnostic))
+        .collect()
+}
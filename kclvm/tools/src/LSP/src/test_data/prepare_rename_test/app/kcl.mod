@@ -0,0 +1,7 @@
+[package]
+name = "app"
+edition = "0.0.1"
+version = "0.0.1"
+
+[dependencies]
+ext = "0.0.1"
@@ -1,30 +1,92 @@
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::io::BufReader;
+use std::net::TcpListener;
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crossbeam_channel::{Receiver, Sender};
 use indexmap::IndexSet;
 use kclvm_ast::ast::Program;
+use kclvm_config::modfile::KCL_MOD_FILE;
 use kclvm_error::Diagnostic;
 use kclvm_error::Position as KCLPos;
+use kclvm_parser::{load_program, LoadProgramOptions, ParseSession};
 use kclvm_sema::builtin::MATH_FUNCTION_NAMES;
 use kclvm_sema::builtin::STRING_MEMBER_FUNCTIONS;
-use kclvm_sema::resolver::scope::ProgramScope;
+use kclvm_sema::resolver::resolve_program;
+use kclvm_sema::resolver::scope::{ProgramScope, ScopeObjectKind};
 use lsp_types::request::GotoTypeDefinitionResponse;
+use lsp_types::CompletionItem;
 use lsp_types::CompletionResponse;
+use lsp_types::DiagnosticSeverity;
 use lsp_types::DocumentSymbol;
 use lsp_types::DocumentSymbolResponse;
+use lsp_types::FoldingRangeKind;
+use lsp_types::InsertTextFormat;
+use lsp_types::Location;
 use lsp_types::MarkedString;
 use lsp_types::SymbolKind;
 use lsp_types::Url;
 use lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+use parking_lot::RwLock;
+use ra_ap_vfs::Vfs;
 
-use crate::document_symbol::document_symbol;
-use crate::from_lsp::file_path_from_url;
-use crate::hover::hover;
+use crate::commands::{
+    execute_command, KCL_BUILTIN_DOCUMENT_COMMAND, KCL_DUMP_AST_COMMAND,
+    KCL_EFFECTIVE_CONFIG_COMMAND, KCL_EXPLAIN_COMMAND, KCL_EXPORT_WORD_INDEX_COMMAND,
+    KCL_FIX_ALL_COMMAND, KCL_GENERATE_EXAMPLE_COMMAND, KCL_IMPORT_GRAPH_COMMAND,
+    KCL_MEMORY_USAGE_COMMAND, KCL_VALIDATE_COMMAND, KCL_WORKSPACE_DIAGNOSTICS_COMMAND,
+};
+use crate::config::{
+    Config, DiagnosticsMode, LogFormat, SchemaCompletionStyle, DEFAULT_MAX_FILE_SIZE,
+};
+use crate::db::MAX_RESOLVED_POSITIONS;
+use crate::dispatcher::RequestDispatcher;
+use crate::document_diagnostic::{
+    pull_diagnostics_supported, DocumentDiagnosticParams, DocumentDiagnosticRequest,
+    FullDocumentDiagnosticReport,
+};
+use crate::document_symbol::{document_symbol, document_symbols};
+use crate::find_refs::{find_refs, references};
+use crate::fix_all::{fix_all, fix_all_action};
+use crate::folding_range::folding_range;
+use crate::formatting::{format, format_source};
+use crate::from_lsp::{file_path_from_url, forget_file_path, kcl_token_pos};
+use crate::hover::{hover, hover_info, with_subtypes, HoverInfo};
+use crate::import_graph::{ImportEdge, ImportGraph};
+use crate::indentation::{indentation_diagnostics, MIXED_INDENTATION_CODE};
+use crate::moniker::moniker;
+use crate::quick_fix::{fix_mixed_indentation, quick_fix};
+use crate::rename::{prepare_rename, rename_packages, rename_symbol};
+use crate::request::{
+    handle_completion, handle_completion_resolve, handle_document_diagnostic,
+    handle_document_symbol, handle_goto_definition, handle_hover, handle_reference, handle_rename,
+    handle_workspace_symbol,
+};
+use crate::state::{
+    file_diagnostics, handle_apply_edit_response, handle_diagnostics, run_diagnostics_task, Event,
+    LanguageServerSnapshot, LanguageServerState, Task, SLOW_REQUEST_THRESHOLD,
+};
+use crate::suppression::{drop_suppressed, Suppressions};
+use crate::to_lsp::{kcl_diag_to_lsp_diags, DiagnosticData};
+use crate::transport::Transport;
+use crate::watcher::{FolderWatcher, MAX_POLL_BACKOFF};
+use crate::workspace_symbol::{matches_query, workspace_symbols};
 use crate::{
-    completion::{completion, into_completion_items},
+    completion::{
+        completion, completion_schema_config, completion_with_budget, into_completion_items,
+        snippet_supported, CompletionBudget, SchemaInsertion,
+    },
     goto_def::goto_definition,
-    util::{apply_document_changes, parse_param_and_compile, Param},
+    run_server,
+    util::{
+        apply_document_changes, build_word_index, build_word_index_for_file_content, compile_db,
+        get_kcl_files_with_extensions, load_file_code_from_vfs, parse_param,
+        parse_param_and_compile, valid_prefix, Param,
+    },
 };
 
 fn compile_test_file(testfile: &str) -> (String, Program, ProgramScope, IndexSet<Diagnostic>) {
@@ -39,6 +101,25 @@ fn compile_test_file(testfile: &str) -> (String, Program, ProgramScope, IndexSet
     (file, program, prog_scope, diags)
 }
 
+fn build_test_state(
+    config: Config,
+    client_capabilities: serde_json::Value,
+) -> (LanguageServerState, Receiver<lsp_server::Message>) {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    let client_capabilities = serde_json::from_value(client_capabilities).unwrap();
+    let state = LanguageServerState::new(sender, config, client_capabilities);
+    (state, receiver)
+}
+
+/// Handles the tasks sent by the background operations of the state, e.g., building the
+/// word index, once the thread pool finishes them.
+fn handle_background_tasks(state: &mut LanguageServerState) {
+    state.thread_pool.join();
+    while let Ok(task) = state.task_receiver.try_recv() {
+        state.handle_task(task).unwrap();
+    }
+}
+
 fn compare_goto_res(res: Option<GotoTypeDefinitionResponse>, pos: (&String, u32, u32, u32, u32)) {
     match res.unwrap() {
         lsp_types::GotoDefinitionResponse::Scalar(loc) => {
@@ -87,6 +168,7 @@ fn diagnostics_test() {
             "Cannot find the module abc from {}/src/test_data/abc",
             path.to_str().unwrap()
         ),
+        "Can not change the value of 'version', because it was declared immutable",
         "expected str, got int(1)",
         "Module 'abc' imported but unused",
     ];
@@ -96,6 +178,165 @@ fn diagnostics_test() {
     }
 }
 
+#[test]
+fn circular_import_diagnostics_test() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/circular_import_test")
+        .canonicalize()
+        .unwrap();
+    let (_, _, diags) = parse_param_and_compile(
+        Param {
+            file: root.join("main.k").to_str().unwrap().to_string(),
+        },
+        None,
+    )
+    .unwrap();
+    let cycle_diags: Vec<&Diagnostic> = diags
+        .iter()
+        .filter(|diag| {
+            diag.code
+                == Some(kclvm_error::DiagnosticId::Error(
+                    kclvm_error::ErrorKind::CircularImport,
+                ))
+        })
+        .collect();
+    assert_eq!(cycle_diags.len(), 1);
+
+    // Both import statements of the cycle are reported, each related to the other one.
+    let a_file = root.join("a/a.k").to_str().unwrap().to_string();
+    let b_file = root.join("b/b.k").to_str().unwrap().to_string();
+    for (file, other) in [(&a_file, &b_file), (&b_file, &a_file)] {
+        let lsp_diags = kcl_diag_to_lsp_diags(cycle_diags[0], file, &HashMap::new(), None);
+        assert_eq!(lsp_diags.len(), 1);
+        let diagnostic = &lsp_diags[0];
+        assert_eq!(
+            diagnostic.code,
+            Some(lsp_types::NumberOrString::String(
+                "CircularImport".to_string()
+            ))
+        );
+        assert_eq!(diagnostic.range.start, Position::new(0, 0));
+        assert!(
+            diagnostic.message.contains("a -> b -> a")
+                || diagnostic.message.contains("b -> a -> b")
+        );
+        let related = diagnostic.related_information.as_ref().unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].location.uri, Url::from_file_path(other).unwrap());
+        assert_eq!(related[0].location.range.start, Position::new(0, 0));
+    }
+}
+
+#[test]
+fn diagnostics_related_information_test() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mut test_file = path.clone();
+    test_file.push("src/test_data/diagnostics.k");
+    let file = test_file.to_str().unwrap();
+
+    let (_, _, diags) = parse_param_and_compile(
+        Param {
+            file: file.to_string(),
+        },
+        None,
+    )
+    .unwrap();
+    let diag = diags
+        .iter()
+        .find(|diag| {
+            diag.code
+                == Some(kclvm_error::DiagnosticId::Error(
+                    kclvm_error::ErrorKind::ImmutableError,
+                ))
+        })
+        .unwrap();
+    let lsp_diags = kcl_diag_to_lsp_diags(diag, file, &HashMap::new(), None);
+    let related = lsp_diags[0].related_information.as_ref().unwrap();
+    assert_eq!(related.len(), 1);
+    // The related information points at the declaration token `version` in line 7
+    assert_eq!(related[0].location.uri, Url::from_file_path(file).unwrap());
+    assert_eq!(
+        related[0].location.range,
+        Range::new(Position::new(6, 0), Position::new(6, 7))
+    );
+    assert_eq!(
+        related[0].message,
+        "The variable 'version' is declared here"
+    );
+}
+
+#[test]
+fn sourcemap_diagnostics_test() {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/sourcemap_test");
+    let file = folder.join("generated.k").to_str().unwrap().to_string();
+    let source = std::fs::read_to_string(&file).unwrap();
+    let (_, _, diags) = parse_param_and_compile(Param { file: file.clone() }, None).unwrap();
+    let diagnostics = |sourcemap_diagnostics: bool| {
+        let config = Config {
+            sourcemap_diagnostics,
+            ..Default::default()
+        };
+        file_diagnostics(&config, &diags, &file, &source)
+    };
+
+    // The type error in the generated file is related to the mapped line of the template.
+    let diagnostic = diagnostics(true)
+        .into_iter()
+        .find(|diagnostic| diagnostic.message == "expected str, got int(1)")
+        .unwrap();
+    assert_eq!(diagnostic.range.start.line, 1);
+    let related = diagnostic.related_information.unwrap();
+    let mapped = related.last().unwrap();
+    assert_eq!(mapped.message, "Generated from here");
+    assert_eq!(
+        mapped.location.uri,
+        Url::from_file_path(folder.join("templates/app.k.tmpl")).unwrap()
+    );
+    assert_eq!(mapped.location.range.start.line, 2);
+    assert_eq!(
+        mapped.location.range.start.character,
+        diagnostic.range.start.character
+    );
+
+    // The source map is ignored by default.
+    assert!(diagnostics(false)
+        .iter()
+        .all(|diagnostic| diagnostic.related_information.is_none()));
+}
+
+#[test]
+fn valid_prefix_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/valid_prefix_test/main.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let db = parse_param(Param { file: file.clone() }, None).unwrap();
+    // test goto the schema attr above the syntax error: name = server.name
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 7,
+        column: Some(15),
+    };
+
+    // The syntax error is reported, and the symbols above it are navigable.
+    let (program, prog_scope, diags) = compile_db(&db);
+    assert!(diags.iter().any(|diag| diag.code
+        == Some(kclvm_error::DiagnosticId::Error(
+            kclvm_error::ErrorKind::InvalidSyntax
+        ))));
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 1, 4, 1, 8));
+
+    // The valid prefix of the file, which is analyzed if the resolver fails on the
+    // recovered statements, keeps the statements before the line of the syntax error.
+    let mut prefix = valid_prefix(&db.prog, &db.diags);
+    assert_eq!(prefix.pkgs["__main__"][0].body.len(), 3);
+    let prog_scope = resolve_program(&mut prefix);
+    let res = goto_definition(&prefix, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 1, 4, 1, 8));
+}
+
 #[test]
 fn goto_import_pkg_test() {
     let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -159,6 +400,145 @@ fn goto_import_file_test() {
     }
 }
 
+#[test]
+fn goto_spread_attr_def_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_spread_test/spread.k");
+
+    // The attr merged from the spread base: n = config.name
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 15,
+        column: Some(12),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 5, 4, 5, 8));
+
+    // The attr overriding the spread base: p = config.port
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 16,
+        column: Some(12),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 10, 4, 10, 8));
+
+    // The attr of the schema spread into the dict: a = merged.age
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 17,
+        column: Some(12),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 2, 4, 2, 7));
+}
+
+#[test]
+fn goto_reexport_def_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_reexport_test/main.k");
+    let server_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/goto_reexport_test/pkg/base/server.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // The schema re-exported by `Server = base.Server` in pkg: s = pkg.Server {}
+    let pos = KCLPos {
+        filename: file,
+        line: 3,
+        column: Some(9),
+    };
+    match goto_definition(&program, &pos, &prog_scope).unwrap() {
+        lsp_types::GotoDefinitionResponse::Scalar(loc) => {
+            assert_eq!(file_path_from_url(&loc.uri).unwrap(), server_file);
+            assert_eq!(loc.range.start, Position::new(0, 0));
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn goto_union_member_const_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_union_const_test/main.k");
+    let mut expected_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    expected_path.push("src/test_data/goto_union_const_test/consts/env.k");
+    let expected_path = expected_path.to_str().unwrap().to_string();
+
+    // The constant in the config value of the union-typed attr: env = consts.PROD
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 7,
+        column: Some(18),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&expected_path, 0, 0, 0, 4));
+
+    // The constant in the default value of the union-typed attr: env: "prod" | "dev" = consts.DEV
+    let pos = KCLPos {
+        filename: file,
+        line: 4,
+        column: Some(34),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&expected_path, 1, 0, 1, 3));
+}
+
+#[test]
+fn goto_import_member_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_import_member_test/main.k");
+    let server_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/goto_import_member_test/models/server.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let app_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/goto_import_member_test/models/app.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // The module declares several schemas, so navigate to the file: import .models.server
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 1,
+        column: Some(15),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&server_file, 0, 0, 0, 0));
+
+    // The package declares several schemas, so navigate to its files: import .models
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 2,
+        column: Some(10),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    match res.unwrap() {
+        lsp_types::GotoDefinitionResponse::Array(arr) => assert_eq!(arr.len(), 2),
+        _ => unreachable!("test error"),
+    }
+
+    // The module declares the only schema, so navigate to the schema: import .models.app
+    let pos = KCLPos {
+        filename: file,
+        line: 3,
+        column: Some(15),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    match res.unwrap() {
+        lsp_types::GotoDefinitionResponse::Scalar(loc) => {
+            assert_eq!(file_path_from_url(&loc.uri).unwrap(), app_file);
+            assert_eq!(loc.range.start, Position::new(0, 0));
+            assert_ne!(loc.range.end, loc.range.start);
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
 #[test]
 fn goto_pkg_prefix_def_test() {
     let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -261,6 +641,31 @@ fn goto_schema_attr_def_test() {
     );
 }
 
+#[test]
+fn goto_interpolation_attr_def_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_interpolation_test/main.k");
+
+    // test goto schema attr definition in the interpolation: a = "${server.name}"
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 7,
+        column: Some(15),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 1, 4, 1, 8));
+
+    // test goto schema attr definition of the element in the interpolation, which is not
+    // the variable `name` with the same name: b = "${servers[0].name}"
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 8,
+        column: Some(19),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 1, 4, 1, 8));
+}
+
 #[test]
 fn goto_schema_attr_def_test1() {
     let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -284,6 +689,21 @@ fn goto_schema_attr_def_test1() {
     );
 }
 
+#[test]
+fn goto_protocol_attr_def_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_protocol_def_test/protocol.k");
+
+    // test goto protocol attr definition, goto data in: x: str = data
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 5,
+        column: Some(14),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 1, 4, 1, 8));
+}
+
 #[test]
 fn test_goto_identifier_names() {
     let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -367,6 +787,29 @@ fn goto_assign_type_test() {
     compare_goto_res(res, (&file, 33, 0, 37, 0));
 }
 
+#[test]
+fn goto_ty_annotation_element_def_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_ty_annotation_test/ty_annotation.k");
+
+    // The element types in the list and dict type annotations of the schema args, the
+    // schema attrs, the lambda args and the lambda return type resolve to `Sidecar`.
+    for (line, column) in [(4, 31), (5, 27), (6, 25), (8, 27), (8, 40)] {
+        let pos = KCLPos {
+            filename: file.clone(),
+            line,
+            column: Some(column),
+        };
+        match goto_definition(&program, &pos, &prog_scope).unwrap() {
+            lsp_types::GotoDefinitionResponse::Scalar(loc) => {
+                assert_eq!(file_path_from_url(&loc.uri).unwrap(), file);
+                assert_eq!(loc.range.start, Position::new(0, 0));
+            }
+            _ => unreachable!("test error"),
+        }
+    }
+}
+
 #[test]
 fn goto_schema_attr_ty_def_test() {
     // test goto schema attr type definition: p1: pkg.Person
@@ -601,6 +1044,148 @@ fn test_apply_document_changes() {
     // assert_eq!(text, "ațc\ncb");
 }
 
+#[test]
+fn crlf_hover_goto_test() {
+    let lf_file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/crlf_test/main.k");
+    let folder = env::temp_dir().join(format!("kcl_crlf_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    let crlf_file = folder.canonicalize().unwrap().join("main.k");
+    let lf_text = std::fs::read_to_string(&lf_file).unwrap();
+    std::fs::write(&crlf_file, lf_text.replace('\n', "\r\n")).unwrap();
+
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let position =
+        |file: &PathBuf, line: u32, character: u32| lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: Url::from_file_path(file).unwrap(),
+            },
+            position: Position::new(line, character),
+        };
+    let hover = |file: &PathBuf, line: u32, character: u32| {
+        let (sender, _) = crossbeam_channel::unbounded();
+        handle_hover(
+            state.snapshot(),
+            lsp_types::HoverParams {
+                text_document_position_params: position(file, line, character),
+                work_done_progress_params: Default::default(),
+            },
+            sender,
+        )
+        .unwrap()
+    };
+    let goto_range = |file: &PathBuf, line: u32, character: u32| {
+        let (sender, _) = crossbeam_channel::unbounded();
+        let res = handle_goto_definition(
+            state.snapshot(),
+            lsp_types::GotoDefinitionParams {
+                text_document_position_params: position(file, line, character),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+            sender,
+        )
+        .unwrap();
+        match res.unwrap() {
+            lsp_types::GotoDefinitionResponse::Scalar(loc) => loc.range,
+            _ => unreachable!("test error"),
+        }
+    };
+
+    // The schema `Person`, the attr `name` in the config and the attr `name` of `p.name`
+    // resolve the same in the CRLF file as in the LF file, including at the end of a line.
+    for (line, character) in [(5, 6), (6, 6), (9, 6), (9, 10)] {
+        assert!(hover(&lf_file, line, character).is_some());
+        assert_eq!(
+            hover(&crlf_file, line, character),
+            hover(&lf_file, line, character)
+        );
+        assert_eq!(
+            goto_range(&crlf_file, line, character),
+            goto_range(&lf_file, line, character)
+        );
+    }
+
+    // The character beyond the end of a CRLF line is clamped before the `\r\n`.
+    let mut text = "a = 1\r\nb = 2\r\n".to_string();
+    apply_document_changes(
+        &mut text,
+        vec![TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(0, 100), Position::new(0, 100))),
+            range_length: None,
+            text: " # one".to_string(),
+        }],
+    );
+    assert_eq!(text, "a = 1 # one\r\nb = 2\r\n");
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn completion_schema_delimiters_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/dot/completion.k");
+    let text = std::fs::read_to_string(&file).unwrap();
+    // p3 = subpkg.<cursor>  # complete user module definition
+    let pos = KCLPos {
+        filename: file.to_owned(),
+        line: 16,
+        column: Some(12),
+    };
+    let complete = |insertion: SchemaInsertion| match completion_with_budget(
+        Some('.'),
+        &program,
+        &pos,
+        &prog_scope,
+        &CompletionBudget::new(None),
+        &insertion,
+    )
+    .unwrap()
+    {
+        CompletionResponse::Array(mut items) => items.remove(0),
+        CompletionResponse::List(_) => unreachable!("test error"),
+    };
+    let insertion = SchemaInsertion::new(SchemaCompletionStyle::Config, true, &text, &pos);
+    assert_eq!(insertion.next_char, Some('#'));
+
+    // The braces are closed with the cursor inside them.
+    let item = complete(insertion);
+    assert_eq!(item.label, "Person1");
+    assert_eq!(item.insert_text, Some("Person1 {$0}".to_string()));
+    assert_eq!(item.insert_text_format, Some(InsertTextFormat::SNIPPET));
+    assert_eq!(item.command, None);
+
+    // The delimiter already following the cursor is not inserted again.
+    let following = SchemaInsertion::new(
+        SchemaCompletionStyle::Call,
+        true,
+        "p3 = subpkg. ()\n",
+        &KCLPos {
+            filename: file.to_owned(),
+            line: 1,
+            column: Some(12),
+        },
+    );
+    assert_eq!(following.next_char, Some('('));
+    let item = complete(following);
+    assert_eq!(item.insert_text, None);
+    assert_eq!(item.insert_text_format, None);
+
+    // Only the name is inserted without the snippet support.
+    let item = complete(SchemaInsertion::new(
+        SchemaCompletionStyle::Call,
+        false,
+        &text,
+        &pos,
+    ));
+    assert_eq!(
+        item,
+        CompletionItem {
+            label: "Person1".to_string(),
+            ..Default::default()
+        }
+    );
+}
+
 #[test]
 fn completion_test() {
     let (file, program, prog_scope, _) =
@@ -663,10 +1248,19 @@ fn completion_test() {
     };
 
     let got = completion(Some('.'), &program, &pos, &prog_scope).unwrap();
-    items.insert("Person1".to_string());
-    let expect: CompletionResponse = into_completion_items(&items).into();
+    let expect: CompletionResponse = vec![CompletionItem {
+        label: "Person1".to_string(),
+        insert_text: Some("Person1($0)".to_string()),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        command: Some(lsp_types::Command {
+            title: "Trigger Parameter Hints".to_string(),
+            command: "editor.action.triggerParameterHints".to_string(),
+            arguments: None,
+        }),
+        ..Default::default()
+    }]
+    .into();
     assert_eq!(got, expect);
-    items.clear();
 
     let pos = KCLPos {
         filename: file.to_owned(),
@@ -697,31 +1291,312 @@ fn completion_test() {
 }
 
 #[test]
-fn schema_doc_hover_test() {
-    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-
+fn completion_in_string_test() {
     let (file, program, prog_scope, _) =
-        compile_test_file("src/test_data/goto_def_test/goto_def.k");
-
-    let mut expected_path = path;
-    expected_path.push("src/test_data/goto_def_test/pkg/schema_def.k");
+        compile_test_file("src/test_data/completion_test/in_string/completion.k");
 
-    // test hover of schema doc: p = pkg.Person
+    // No code completion inside the plain string: "hello p.name"
     let pos = KCLPos {
         filename: file.clone(),
-        line: 4,
-        column: Some(11),
+        line: 5,
+        column: Some(15),
     };
-    let got = hover(&program, &pos, &prog_scope).unwrap();
-    match got.contents {
-        lsp_types::HoverContents::Array(vec) => {
-            if let MarkedString::String(s) = vec[0].clone() {
-                assert_eq!(s, "Person");
-            }
-            if let MarkedString::String(s) = vec[1].clone() {
-                assert_eq!(s, "hover doc test");
-            }
-        }
+    assert!(completion(Some('.'), &program, &pos, &prog_scope).is_none());
+    assert!(completion(None, &program, &pos, &prog_scope).is_none());
+
+    // The interpolation is completed: "hi ${p.name}"
+    let pos = KCLPos {
+        filename: file,
+        line: 6,
+        column: Some(19),
+    };
+    let got = completion(Some('.'), &program, &pos, &prog_scope).unwrap();
+    let mut items = IndexSet::new();
+    items.insert("name".to_string());
+    let expect: CompletionResponse = into_completion_items(&items).into();
+    assert_eq!(got, expect);
+}
+
+#[test]
+fn completion_in_assert_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/assert/completion.k");
+
+    // The condition is completed: assert p.name == "alice"
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 5,
+        column: Some(9),
+    };
+    let got = completion(Some('.'), &program, &pos, &prog_scope).unwrap();
+    let mut items = IndexSet::new();
+    items.insert("name".to_string());
+    let expect: CompletionResponse = into_completion_items(&items).into();
+    assert_eq!(got, expect);
+
+    // No code completion inside the message: "the name of p.name"
+    let pos = KCLPos {
+        filename: file,
+        line: 5,
+        column: Some(41),
+    };
+    assert!(completion(Some('.'), &program, &pos, &prog_scope).is_none());
+    assert!(completion(None, &program, &pos, &prog_scope).is_none());
+}
+
+#[test]
+fn completion_schema_config_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/schema_config/completion.k");
+    let text = std::fs::read_to_string(&file).unwrap();
+
+    // The configs of the schemas are scaffolded at the empty assignment: s = <cursor>
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 12,
+        column: Some(4),
+    };
+    let got = match completion_schema_config(&text, &program, &pos, &prog_scope).unwrap() {
+        CompletionResponse::Array(items) => items,
+        CompletionResponse::List(_) => unreachable!("test error"),
+    };
+    let snippets: Vec<(String, String)> = got
+        .into_iter()
+        .map(|item| {
+            assert_eq!(item.insert_text_format, Some(InsertTextFormat::SNIPPET));
+            (item.label, item.insert_text.unwrap())
+        })
+        .collect();
+    assert_eq!(
+        snippets,
+        vec![
+            (
+                "Server config".to_string(),
+                "Server {\n    name = ${1:\"\"}\n    port = ${2:0}\n}".to_string()
+            ),
+            ("Base config".to_string(), "Base {$0}".to_string()),
+        ]
+    );
+
+    // Not at the value of the assignment: s<cursor> =
+    let pos = KCLPos {
+        filename: file,
+        line: 12,
+        column: Some(1),
+    };
+    assert!(completion_schema_config(&text, &program, &pos, &prog_scope).is_none());
+
+    // The snippets are offered only if the client supports them
+    let capabilities: lsp_types::ClientCapabilities = serde_json::from_value(serde_json::json!({
+        "textDocument": {"completion": {"completionItem": {"snippetSupport": true}}}
+    }))
+    .unwrap();
+    assert!(snippet_supported(&capabilities));
+    assert!(!snippet_supported(&Default::default()));
+}
+
+#[test]
+fn completion_call_args_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/call_args/completion.k");
+    let labels = |line: u64, column: u64| -> Vec<String> {
+        let pos = KCLPos {
+            filename: file.clone(),
+            line,
+            column: Some(column),
+        };
+        match completion(Some('('), &program, &pos, &prog_scope).unwrap() {
+            CompletionResponse::Array(items) => {
+                for item in &items {
+                    assert_eq!(item.insert_text_format, Some(InsertTextFormat::SNIPPET));
+                }
+                items.into_iter().map(|item| item.label).collect()
+            }
+            CompletionResponse::List(_) => unreachable!("test error"),
+        }
+    };
+
+    // The parameters of the schema are completed right after the `(`: Server(<cursor>)
+    assert_eq!(labels(4, 11), vec!["name=", "port="]);
+
+    // The parameters passed by keywords are excluded: Server(name="a", <cursor>)
+    assert_eq!(labels(5, 21), vec!["port="]);
+
+    // No completion on the callee: Ser<cursor>ver()
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 4,
+        column: Some(7),
+    };
+    assert!(completion(Some('('), &program, &pos, &prog_scope).is_none());
+
+    // The arguments are inserted as the plain text without the client snippet support
+    let pos = KCLPos {
+        filename: file,
+        line: 4,
+        column: Some(11),
+    };
+    let got = completion_with_budget(
+        Some('('),
+        &program,
+        &pos,
+        &prog_scope,
+        &CompletionBudget::new(None),
+        &SchemaInsertion {
+            snippet: false,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            assert_eq!(arr[0].insert_text, Some("name=".to_string()));
+            assert_eq!(
+                arr[0].insert_text_format,
+                Some(InsertTextFormat::PLAIN_TEXT)
+            );
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_file_path_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/file_path/completion.k");
+    let labels = |line: u64, column: u64| -> Vec<String> {
+        let pos = KCLPos {
+            filename: file.clone(),
+            line,
+            column: Some(column),
+        };
+        match completion(None, &program, &pos, &prog_scope).unwrap() {
+            CompletionResponse::Array(items) => items.into_iter().map(|item| item.label).collect(),
+            CompletionResponse::List(_) => unreachable!("test error"),
+        }
+    };
+
+    // test completion for the sibling files of the current file: file.read("<cursor>")
+    assert_eq!(labels(3, 15), vec!["completion.k", "conf/", "data.yaml"]);
+
+    // test completion for the files in the typed directory: yaml.dump_to_file({a = 1}, "conf/<cursor>")
+    assert_eq!(labels(4, 33), vec!["app.yaml"]);
+
+    // test completion for the raw string after the whitespace: file.read( r"conf/<cursor>")
+    assert_eq!(labels(5, 22), vec!["app.yaml"]);
+
+    // test completion for the long string: file.read("""conf/<cursor>""")
+    assert_eq!(labels(6, 22), vec!["app.yaml"]);
+}
+
+#[test]
+fn completion_dot_at_eof_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/dot_eof/completion.k");
+
+    // test completion for schema attr after the dot at the end of the file: p.
+    let pos = KCLPos {
+        filename: file,
+        line: 10,
+        column: Some(2),
+    };
+    let got = completion(Some('.'), &program, &pos, &prog_scope).unwrap();
+    let mut items = IndexSet::new();
+    items.insert("name".to_string());
+    items.insert("age".to_string());
+    let expect: CompletionResponse = into_completion_items(&items).into();
+    assert_eq!(got, expect);
+}
+
+#[test]
+fn malformed_mod_file_test() {
+    let (file, program, prog_scope, diags) =
+        compile_test_file("src/test_data/broken_mod_test/main.k");
+
+    // The parse error is reported on the kcl.mod file only
+    let mod_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/broken_mod_test/kcl.mod")
+        .canonicalize()
+        .unwrap();
+    let mod_file_diags: Vec<&Diagnostic> = diags
+        .iter()
+        .filter(|diag| diag.messages[0].pos.filename.ends_with(KCL_MOD_FILE))
+        .collect();
+    assert_eq!(mod_file_diags.len(), 1);
+    let message = &mod_file_diags[0].messages[0];
+    assert_eq!(PathBuf::from(&message.pos.filename), mod_file);
+    assert!(message.message.starts_with("Failed to parse kcl.mod"));
+
+    // The file is analyzed alone and the completion still works
+    let pos = KCLPos {
+        filename: file,
+        line: 6,
+        column: Some(2),
+    };
+    let got = completion(Some('.'), &program, &pos, &prog_scope).unwrap();
+    let mut items = IndexSet::new();
+    items.insert("name".to_string());
+    items.insert("age".to_string());
+    let expect: CompletionResponse = into_completion_items(&items).into();
+    assert_eq!(got, expect);
+}
+
+#[test]
+fn folding_range_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/folding_range_test/main.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let source = std::fs::read_to_string(&file).unwrap();
+    let ranges = folding_range(&file, source).unwrap();
+    let lines_of_kind = |kind: Option<FoldingRangeKind>| -> Vec<(u32, u32)> {
+        ranges
+            .iter()
+            .filter(|range| range.kind == kind)
+            .map(|range| (range.start_line, range.end_line))
+            .collect()
+    };
+    assert_eq!(lines_of_kind(Some(FoldingRangeKind::Imports)), vec![(0, 1)]);
+    // The region markers with or without the space after `#` are recognized
+    assert_eq!(
+        lines_of_kind(Some(FoldingRangeKind::Region)),
+        vec![(3, 12), (14, 16)]
+    );
+    // The config of `s` is folded as a block
+    assert!(lines_of_kind(None).contains(&(8, 11)));
+}
+
+#[test]
+fn schema_doc_hover_test() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_def_test/goto_def.k");
+
+    let mut expected_path = path;
+    expected_path.push("src/test_data/goto_def_test/pkg/schema_def.k");
+
+    // test hover of schema doc: p = pkg.Person
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 4,
+        column: Some(11),
+    };
+    let got = hover(&program, &pos, &prog_scope, false).unwrap();
+    assert_eq!(
+        got.range,
+        Some(Range::new(Position::new(3, 8), Position::new(3, 14)))
+    );
+    match got.contents {
+        lsp_types::HoverContents::Array(vec) => {
+            if let MarkedString::String(s) = vec[0].clone() {
+                assert_eq!(s, "Person");
+            }
+            if let MarkedString::String(s) = vec[1].clone() {
+                assert_eq!(s, "hover doc test");
+            }
+        }
         _ => unreachable!("test error"),
     }
     let pos = KCLPos {
@@ -729,7 +1604,11 @@ fn schema_doc_hover_test() {
         line: 5,
         column: Some(7),
     };
-    let got = hover(&program, &pos, &prog_scope).unwrap();
+    let got = hover(&program, &pos, &prog_scope, false).unwrap();
+    assert_eq!(
+        got.range,
+        Some(Range::new(Position::new(4, 4), Position::new(4, 8)))
+    );
     match got.contents {
         lsp_types::HoverContents::Scalar(marked_string) => {
             if let MarkedString::String(s) = marked_string {
@@ -740,124 +1619,4987 @@ fn schema_doc_hover_test() {
     }
 }
 
-#[allow(deprecated)]
-fn build_document_symbol(
-    name: &str,
-    kind: SymbolKind,
-    range: ((u32, u32), (u32, u32)),
-    child: Option<Vec<DocumentSymbol>>,
-    detail: Option<String>,
-) -> DocumentSymbol {
-    let range: Range = Range {
-        start: Position {
-            line: range.0 .0,
-            character: range.0 .1,
-        },
-        end: Position {
-            line: range.1 .0,
-            character: range.1 .1,
-        },
+#[test]
+fn schema_doc_markdown_hover_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_def_test/goto_def.k");
+
+    // test markdown hover of schema doc: p = pkg.Person
+    let pos = KCLPos {
+        filename: file,
+        line: 4,
+        column: Some(11),
     };
-    DocumentSymbol {
-        name: name.to_string(),
-        detail,
-        kind,
-        tags: None,
-        deprecated: None,
-        range,
-        selection_range: range,
-        children: child,
+    let got = hover(&program, &pos, &prog_scope, true).unwrap();
+    match got.contents {
+        lsp_types::HoverContents::Markup(markup) => {
+            assert_eq!(markup.kind, lsp_types::MarkupKind::Markdown);
+            assert_eq!(
+                markup.value,
+                "```kcl\nPerson\n```\n\n---\n\nhover doc test\n\n---\n\n- `name: str`\n- `age: int`"
+            );
+        }
+        _ => unreachable!("test error"),
     }
 }
 
 #[test]
-fn document_symbol_test() {
-    let (file, program, prog_scope, _) = compile_test_file("src/test_data/document_symbol.k");
+fn schema_hover_info_test() {
+    let (file, _, _, _) = compile_test_file("src/test_data/goto_def_test/goto_def.k");
 
-    let res = document_symbol(file.as_str(), &program, &prog_scope).unwrap();
-    let mut expect = vec![];
-    expect.push(build_document_symbol(
-        "p",
-        SymbolKind::VARIABLE,
-        ((3, 0), (3, 1)),
-        None,
-        Some("Person4".to_string()),
-    ));
-    expect.push(build_document_symbol(
-        "Person4",
-        SymbolKind::STRUCT,
-        ((0, 7), (1, 13)),
-        Some(vec![build_document_symbol(
-            "name",
-            SymbolKind::PROPERTY,
-            ((1, 4), (1, 8)),
-            None,
-            Some("str".to_string()),
-        )]),
-        Some("schema".to_string()),
-    ));
-    let expect = DocumentSymbolResponse::Nested(expect);
-    assert_eq!(res, expect)
+    // test the structured hover of schema: p = pkg.Person
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 4,
+        column: Some(11),
+    };
+    assert_eq!(
+        hover_info(&file, pos).unwrap(),
+        Some(HoverInfo {
+            name: Some("Person".to_string()),
+            kind: Some(ScopeObjectKind::Definition),
+            ty: "Person".to_string(),
+            signature: "Person".to_string(),
+            doc: Some("hover doc test".to_string()),
+            attrs: vec!["name: str".to_string(), "age: int".to_string()],
+        })
+    );
 }
 
 #[test]
-fn file_path_from_url_test() {
-    if cfg!(windows) {
-        let url =
-            Url::parse("file:///c%3A/Users/abc/Desktop/%E4%B8%AD%E6%96%87/ab%20c/abc.k").unwrap();
-        let path = file_path_from_url(&url).unwrap();
-        assert_eq!(path, "c:\\Users\\abc\\Desktop\\中文\\ab c\\abc.k");
-    } else {
-        let url = Url::parse("file:///Users/abc/Desktop/%E4%B8%AD%E6%96%87/ab%20c/abc.k").unwrap();
-        let path = file_path_from_url(&url).unwrap();
-        assert_eq!(path, "/Users/abc/Desktop/中文/ab c/abc.k");
-    }
+fn parse_param_missing_file_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/goto_def_test/missing.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    // The load error is returned rather than panicking the thread of the job.
+    let err = parse_param(Param { file: file.clone() }, None).unwrap_err();
+    assert!(err.to_string().contains("Cannot find the kcl file"));
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 1,
+        column: Some(0),
+    };
+    assert!(hover_info(&file, pos).is_err());
 }
 
 #[test]
-fn goto_import_external_file_test() {
-    let path = PathBuf::from(".")
-        .join("src")
-        .join("test_data")
-        .join("goto_import_def_test")
-        .join("main.k")
-        .canonicalize()
-        .unwrap()
-        .display()
-        .to_string();
+fn hover_nested_type_truncated_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/hover_test/nested_type.k");
 
-    let _ = Command::new("kpm")
-        .arg("metadata")
-        .arg("--update")
-        .current_dir(
+    // test hover of the schema with a deeply nested attribute type: n = Nested {}
+    let pos = KCLPos {
+        filename: file,
+        line: 4,
+        column: Some(6),
+    };
+    let start = std::time::Instant::now();
+    let got = hover(&program, &pos, &prog_scope, true).unwrap();
+    assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    match got.contents {
+        lsp_types::HoverContents::Markup(markup) => {
+            let attr = markup
+                .value
+                .lines()
+                .find(|line| line.starts_with("- `data: "))
+                .unwrap();
+            assert!(attr.ends_with("...`"));
+            assert!(attr.len() < 300);
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn hover_type_alias_test() {
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/hover_test/type_alias.k");
+
+    // test hover of the variable annotated with a type alias: n: Name = "alice"
+    let pos = KCLPos {
+        filename: file,
+        line: 3,
+        column: Some(0),
+    };
+    let got = hover(&program, &pos, &prog_scope, false).unwrap();
+    match got.contents {
+        lsp_types::HoverContents::Scalar(MarkedString::String(s)) => {
+            assert_eq!(s, "Name (= str)");
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn hover_subscript_attr_test() {
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/hover_test/subscript.k");
+
+    // test hover of the attr accessed on the list element: servers[0].image, which is not
+    // the variable `image` with the same name
+    let pos = KCLPos {
+        filename: file,
+        line: 6,
+        column: Some(19),
+    };
+    let got = hover(&program, &pos, &prog_scope, false).unwrap();
+    match got.contents {
+        lsp_types::HoverContents::Scalar(MarkedString::String(s)) => {
+            assert_eq!(s, "str");
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn hover_ambiguous_name_test() {
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/hover_test/ambiguous.k");
+
+    // test hover of the schema attr `Name` which shadows the schema `Name`: greeting: str = Name
+    let pos = KCLPos {
+        filename: file,
+        line: 6,
+        column: Some(20),
+    };
+    let got = hover(&program, &pos, &prog_scope, true).unwrap();
+    match got.contents {
+        lsp_types::HoverContents::Markup(markup) => {
+            assert_eq!(
+                markup.value,
+                "```kcl\nstr\n```\n\n---\n\n```kcl\nName\n```\n\n---\n\n- `value: str`"
+            );
+        }
+        _ => unreachable!("test error"),
+    }
+
+    let got = hover(&program, &pos, &prog_scope, false).unwrap();
+    match got.contents {
+        lsp_types::HoverContents::Array(vec) => {
+            assert_eq!(
+                vec,
+                vec![
+                    MarkedString::String("str".to_string()),
+                    MarkedString::String("Name".to_string()),
+                ]
+            );
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn hover_expr_ty_test() {
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/hover_test/expr.k");
+
+    // test hover of the binary expr on the operator: c = a + b
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 3,
+        column: Some(6),
+    };
+    let got = hover(&program, &pos, &prog_scope, false).unwrap();
+    match got.contents {
+        lsp_types::HoverContents::Scalar(MarkedString::String(s)) => {
+            assert_eq!(s, "float");
+        }
+        _ => unreachable!("test error"),
+    }
+
+    // test hover of the nested binary expr: d = a * 2 - 1
+    let pos = KCLPos {
+        filename: file,
+        line: 4,
+        column: Some(10),
+    };
+    let got = hover(&program, &pos, &prog_scope, false).unwrap();
+    match got.contents {
+        lsp_types::HoverContents::Scalar(MarkedString::String(s)) => {
+            assert_eq!(s, "int");
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn hover_range_utf16_test() {
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/hover_test/utf16.k");
+
+    // The range of `a` after the emoji, which is one char and two UTF-16 code units, in:
+    // b = "😀" + str(a)
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 2,
+        column: Some(14),
+    };
+    let got = hover(&program, &pos, &prog_scope, false).unwrap();
+    assert_eq!(
+        got.range,
+        Some(Range::new(Position::new(1, 15), Position::new(1, 16)))
+    );
+
+    // No range for the type of the expr on the operator `+`.
+    let pos = KCLPos {
+        filename: file,
+        line: 2,
+        column: Some(8),
+    };
+    let got = hover(&program, &pos, &prog_scope, false).unwrap();
+    assert_eq!(got.range, None);
+}
+
+#[test]
+fn hover_attr_decl_test() {
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/hover_test/attr_decl.k");
+    let hover_docs = |line: u64, column: u64| -> Vec<MarkedString> {
+        let pos = KCLPos {
+            filename: file.clone(),
+            line,
+            column: Some(column),
+        };
+        match hover(&program, &pos, &prog_scope, false).unwrap().contents {
+            lsp_types::HoverContents::Array(docs) => docs,
+            _ => unreachable!("test error"),
+        }
+    };
+    let optional_port = vec![
+        MarkedString::String("port?: int = 8080".to_string()),
+        MarkedString::String("The attribute is optional, the default value is `8080`".to_string()),
+    ];
+
+    // test hover of the default value: port?: int = 8080
+    assert_eq!(hover_docs(3, 18), optional_port);
+
+    // test hover of the optional mark: port?: int = 8080
+    assert_eq!(hover_docs(3, 8), optional_port);
+
+    // test hover of the default value of a required attr: host: str = "localhost"
+    assert_eq!(
+        hover_docs(4, 20),
+        vec![
+            MarkedString::String("host: str = \"localhost\"".to_string()),
+            MarkedString::String(
+                "The attribute is required, the default value is `\"localhost\"`".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn workspace_symbol_qualified_name_test() {
+    let (file, _, prog_scope, _) = compile_test_file("src/test_data/workspace_symbol_test/main.k");
+    let mut expected_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    expected_path.push("src/test_data/workspace_symbol_test/frontend/server.k");
+
+    let query = |query: &str| -> Vec<lsp_types::SymbolInformation> {
+        workspace_symbols(&prog_scope)
+            .into_iter()
+            .filter(|symbol| matches_query(symbol, query))
+            .collect()
+    };
+    let res = query("frontend.Server");
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].name, "Server");
+    assert_eq!(res[0].kind, SymbolKind::STRUCT);
+    assert_eq!(res[0].container_name, Some("frontend".to_string()));
+    assert_eq!(res[0].location.uri.path(), expected_path.to_str().unwrap());
+
+    // the bare name matches the schemas in both packages
+    let res = query("Server");
+    let mut containers: Vec<String> = res
+        .iter()
+        .filter(|symbol| symbol.name == "Server")
+        .filter_map(|symbol| symbol.container_name.clone())
+        .collect();
+    containers.sort();
+    assert_eq!(
+        containers,
+        vec!["__main__".to_string(), "frontend".to_string()]
+    );
+    assert!(res
+        .iter()
+        .any(|symbol| symbol.location.uri.path() == file.as_str()));
+}
+
+#[test]
+fn workspace_symbol_of_workspace_folders_test() {
+    let folder =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/workspace_symbol_test");
+    let mut expected_path = folder.clone();
+    expected_path.push("frontend/server.k");
+    let (mut state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    state.build_word_index_map(&[Url::from_file_path(&folder).unwrap()]);
+    handle_background_tasks(&mut state);
+    // Nothing is compiled before the query.
+    state.analysis.db.write().clear();
+
+    let query = |state: &LanguageServerState, query: &str| -> Vec<lsp_types::SymbolInformation> {
+        let (sender, _) = crossbeam_channel::unbounded();
+        handle_workspace_symbol(
+            state.snapshot(),
+            lsp_types::WorkspaceSymbolParams {
+                query: query.to_string(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+            sender,
+        )
+        .unwrap()
+        .unwrap()
+    };
+    let res = query(&state, "frontend.Server");
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].name, "Server");
+    assert_eq!(res[0].container_name, Some("frontend".to_string()));
+    assert_eq!(res[0].location.uri.path(), expected_path.to_str().unwrap());
+
+    // The compiled dbs are cached for the later queries.
+    assert!(!state.analysis.db.read().is_empty());
+    let mut containers: Vec<String> = query(&state, "Server")
+        .into_iter()
+        .filter_map(|symbol| symbol.container_name)
+        .collect();
+    containers.sort();
+    assert_eq!(
+        containers,
+        vec!["__main__".to_string(), "frontend".to_string()]
+    );
+}
+
+#[allow(deprecated)]
+fn build_document_symbol(
+    name: &str,
+    kind: SymbolKind,
+    range: ((u32, u32), (u32, u32)),
+    child: Option<Vec<DocumentSymbol>>,
+    detail: Option<String>,
+) -> DocumentSymbol {
+    let range: Range = Range {
+        start: Position {
+            line: range.0 .0,
+            character: range.0 .1,
+        },
+        end: Position {
+            line: range.1 .0,
+            character: range.1 .1,
+        },
+    };
+    DocumentSymbol {
+        name: name.to_string(),
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children: child,
+    }
+}
+
+#[test]
+fn include_test_symbols_test() {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/test_symbols_test");
+    let file = folder.join("main.k").to_str().unwrap().to_string();
+    let test_file = folder.join("main_test.k");
+
+    // Returns the workspace symbols and whether the test file has document symbols.
+    let symbols = |include_test_symbols: bool| -> (Vec<String>, bool) {
+        let config = Config {
+            include_test_symbols,
+            ..Default::default()
+        };
+        let (state, _) = build_test_state(config, serde_json::json!({}));
+        state.analysis.set_db(
+            file.clone(),
+            parse_param(Param { file: file.clone() }, None).unwrap(),
+        );
+        let (sender, _) = crossbeam_channel::unbounded();
+        let mut names: Vec<String> = handle_workspace_symbol(
+            state.snapshot(),
+            lsp_types::WorkspaceSymbolParams {
+                query: "server".to_string(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+            sender.clone(),
+        )
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .map(|symbol| symbol.name)
+        .collect();
+        names.sort();
+        let document_symbols = handle_document_symbol(
+            state.snapshot(),
+            lsp_types::DocumentSymbolParams {
+                text_document: lsp_types::TextDocumentIdentifier {
+                    uri: Url::from_file_path(&test_file).unwrap(),
+                },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+            sender,
+        )
+        .unwrap();
+        (names, document_symbols.is_some())
+    };
+
+    assert_eq!(
+        symbols(true),
+        (
+            vec![
+                "Server".to_string(),
+                "server".to_string(),
+                "test_server".to_string()
+            ],
+            true
+        )
+    );
+    assert_eq!(
+        symbols(false),
+        (vec!["Server".to_string(), "server".to_string()], false)
+    );
+}
+
+#[test]
+fn document_symbol_test() {
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/document_symbol.k");
+
+    let res = document_symbol(file.as_str(), &program, &prog_scope).unwrap();
+    let mut expect = vec![];
+    expect.push(build_document_symbol(
+        "p",
+        SymbolKind::VARIABLE,
+        ((3, 0), (3, 1)),
+        None,
+        Some("Person4".to_string()),
+    ));
+    expect.push(build_document_symbol(
+        "Person4",
+        SymbolKind::STRUCT,
+        ((0, 7), (1, 13)),
+        Some(vec![build_document_symbol(
+            "name",
+            SymbolKind::PROPERTY,
+            ((1, 4), (1, 8)),
+            None,
+            Some("str".to_string()),
+        )]),
+        Some("schema".to_string()),
+    ));
+    let expect = DocumentSymbolResponse::Nested(expect);
+    assert_eq!(res, expect)
+}
+
+#[test]
+fn document_symbols_api_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/document_symbol.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let symbols = match document_symbols(&file).unwrap().unwrap() {
+        DocumentSymbolResponse::Nested(symbols) => symbols,
+        DocumentSymbolResponse::Flat(_) => unreachable!("test error"),
+    };
+    let schema = symbols
+        .iter()
+        .find(|symbol| symbol.name == "Person4")
+        .unwrap();
+    assert_eq!(schema.kind, SymbolKind::STRUCT);
+    assert_eq!(schema.children.as_ref().unwrap()[0].name, "name");
+}
+
+#[test]
+fn document_symbol_data_test() {
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/document_symbol_data.k");
+
+    let res = document_symbol(file.as_str(), &program, &prog_scope).unwrap();
+    let symbols = match res {
+        DocumentSymbolResponse::Nested(symbols) => symbols,
+        DocumentSymbolResponse::Flat(_) => unreachable!("test error"),
+    };
+    assert_eq!(symbols.len(), 1);
+    assert_eq!(symbols[0].name, "config");
+    let keys = symbols[0].children.as_ref().unwrap();
+    let names: Vec<&str> = keys.iter().map(|key| key.name.as_str()).collect();
+    assert_eq!(names, vec!["name", "labels", "ports"]);
+    assert_eq!(keys[0].kind, SymbolKind::KEY);
+    assert_eq!(
+        keys[0].selection_range,
+        Range::new(Position::new(1, 4), Position::new(1, 8))
+    );
+    assert_eq!(keys[1].kind, SymbolKind::OBJECT);
+    assert_eq!(keys[1].children.as_ref().unwrap()[0].name, "env");
+    // The dict elements of the list are named by their indexes
+    assert_eq!(keys[2].kind, SymbolKind::ARRAY);
+    let element = &keys[2].children.as_ref().unwrap()[0];
+    assert_eq!(element.name, "[0]");
+    assert_eq!(element.children.as_ref().unwrap()[0].name, "port");
+}
+
+#[test]
+fn document_symbol_nested_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/document_symbol_nested.k");
+
+    let res = document_symbol(file.as_str(), &program, &prog_scope).unwrap();
+    let symbols = match res {
+        DocumentSymbolResponse::Nested(symbols) => symbols,
+        DocumentSymbolResponse::Flat(_) => unreachable!("test error"),
+    };
+    let names = |symbols: &Vec<DocumentSymbol>| -> Vec<String> {
+        symbols.iter().map(|symbol| symbol.name.clone()).collect()
+    };
+    let find = |symbols: &Vec<DocumentSymbol>, name: &str| -> DocumentSymbol {
+        symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .unwrap()
+            .clone()
+    };
+
+    // The local variables of the lambda are nested in the variable, and the nested lambda
+    // has its own children.
+    let make = find(&symbols, "make");
+    assert_eq!(
+        names(make.children.as_ref().unwrap()),
+        vec!["prefix", "join"]
+    );
+    let join = find(make.children.as_ref().unwrap(), "join");
+    assert_eq!(names(join.children.as_ref().unwrap()), vec!["sep"]);
+    assert_eq!(
+        join.children.as_ref().unwrap()[0].selection_range,
+        Range::new(Position::new(10, 8), Position::new(10, 11))
+    );
+
+    // The local variables of the lambda attr are nested in the attr of the schema.
+    let server = find(&symbols, "Server");
+    let address = find(server.children.as_ref().unwrap(), "address");
+    assert_eq!(names(address.children.as_ref().unwrap()), vec!["sep"]);
+    assert!(find(server.children.as_ref().unwrap(), "host")
+        .children
+        .is_none());
+}
+
+#[test]
+fn file_path_from_url_test() {
+    if cfg!(windows) {
+        let url =
+            Url::parse("file:///c%3A/Users/abc/Desktop/%E4%B8%AD%E6%96%87/ab%20c/abc.k").unwrap();
+        let path = file_path_from_url(&url).unwrap();
+        assert_eq!(path, "C:\\Users\\abc\\Desktop\\中文\\ab c\\abc.k");
+
+        // The drive letters in the different cases are converted to the same path
+        let upper = Url::parse("file:///C:/Users/abc/abc.k").unwrap();
+        let lower = Url::parse("file:///c%3A/Users/abc/abc.k").unwrap();
+        assert_eq!(file_path_from_url(&upper).unwrap(), "C:\\Users\\abc\\abc.k");
+        assert_eq!(
+            file_path_from_url(&upper).unwrap(),
+            file_path_from_url(&lower).unwrap()
+        );
+
+        // The url with a host is converted to the UNC path
+        let url = Url::parse("file://server/share/ab%20c/abc.k").unwrap();
+        let path = file_path_from_url(&url).unwrap();
+        assert_eq!(path, "\\\\server\\share\\ab c\\abc.k");
+    } else {
+        let url = Url::parse("file:///Users/abc/Desktop/%E4%B8%AD%E6%96%87/ab%20c/abc.k").unwrap();
+        let path = file_path_from_url(&url).unwrap();
+        assert_eq!(path, "/Users/abc/Desktop/中文/ab c/abc.k");
+
+        // The localhost is the same as no host
+        let url = Url::parse("file://localhost/Users/abc/abc.k").unwrap();
+        let path = file_path_from_url(&url).unwrap();
+        assert_eq!(path, "/Users/abc/abc.k");
+    }
+}
+
+#[test]
+fn goto_import_external_file_test() {
+    let path = PathBuf::from(".")
+        .join("src")
+        .join("test_data")
+        .join("goto_import_def_test")
+        .join("main.k")
+        .canonicalize()
+        .unwrap()
+        .display()
+        .to_string();
+
+    let _ = Command::new("kpm")
+        .arg("metadata")
+        .arg("--update")
+        .current_dir(
             PathBuf::from(".")
                 .join("src")
                 .join("test_data")
                 .join("goto_import_def_test")
                 .canonicalize()
                 .unwrap()
-                .display()
-                .to_string(),
+                .display()
+                .to_string(),
+        )
+        .output()
+        .unwrap();
+
+    let (program, prog_scope, diags) = parse_param_and_compile(
+        Param {
+            file: path.to_string(),
+        },
+        None,
+    )
+    .unwrap();
+
+    assert_eq!(diags.len(), 0);
+
+    // test goto import file: import .pkg.schema_def
+    let pos = KCLPos {
+        filename: path.to_string(),
+        line: 1,
+        column: Some(15),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    assert!(res.is_some());
+}
+
+#[test]
+fn moniker_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_def_test/goto_def.k");
+
+    // test moniker of schema: p = pkg.Person
+    let pos = KCLPos {
+        filename: file,
+        line: 4,
+        column: Some(11),
+    };
+    let res = moniker(&program, &pos, &prog_scope).unwrap();
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].scheme, "kcl");
+    assert_eq!(res[0].identifier, "pkg.Person");
+    assert_eq!(res[0].kind, Some(lsp_types::MonikerKind::Import));
+}
+
+#[test]
+fn format_source_test() {
+    let src = "a=1\nb   =2\nc  =  [1,2 ,3]\n";
+    let got = format_source(src).unwrap();
+    assert_eq!(got, "a = 1\nb = 2\nc = [1, 2, 3]\n");
+}
+
+#[test]
+fn formatting_test() {
+    let src = "a=1\n";
+    let got = format("test.k".to_string(), src.to_string())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        got,
+        vec![lsp_types::TextEdit {
+            range: Range::new(Position::new(0, 0), Position::new(u32::MAX, u32::MAX)),
+            new_text: "a = 1\n".to_string(),
+        }]
+    );
+
+    // The formatted source has no edits
+    let got = format("test.k".to_string(), "a = 1\n".to_string())
+        .unwrap()
+        .unwrap();
+    assert!(got.is_empty());
+}
+
+#[test]
+fn completion_budget_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/budget/completion.k");
+
+    // test completion for the 200 attrs of the schema in the config: large = Large { <cursor> }
+    let pos = KCLPos {
+        filename: file,
+        line: 204,
+        column: Some(4),
+    };
+    let budget = CompletionBudget::new(Some(Duration::from_secs(60)));
+    match completion_with_budget(
+        None,
+        &program,
+        &pos,
+        &prog_scope,
+        &budget,
+        &SchemaInsertion::default(),
+    )
+    .unwrap()
+    {
+        CompletionResponse::Array(arr) => assert_eq!(arr.len(), 200),
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+
+    // The partial list is incomplete once the budget is exceeded
+    let budget = CompletionBudget::new(Some(Duration::ZERO));
+    match completion_with_budget(
+        None,
+        &program,
+        &pos,
+        &prog_scope,
+        &budget,
+        &SchemaInsertion::default(),
+    )
+    .unwrap()
+    {
+        CompletionResponse::List(list) => {
+            assert!(list.is_incomplete);
+            assert!(list.items.len() < 200);
+        }
+        CompletionResponse::Array(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_attr_separator_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/assign/completion.k");
+
+    // test completion for schema attr in the config using `:`
+    let pos = KCLPos {
+        filename: file.to_owned(),
+        line: 7,
+        column: Some(4),
+    };
+    let got = completion(None, &program, &pos, &prog_scope).unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            let insert_texts: Vec<String> = arr
+                .iter()
+                .map(|item| item.insert_text.clone().unwrap())
+                .collect();
+            assert_eq!(insert_texts, vec!["age: $0"]);
+            assert!(arr
+                .iter()
+                .all(|item| item.insert_text_format == Some(lsp_types::InsertTextFormat::SNIPPET)));
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+
+    // test completion for schema attr in the config using `=`
+    let pos = KCLPos {
+        filename: file,
+        line: 12,
+        column: Some(4),
+    };
+    let got = completion(None, &program, &pos, &prog_scope).unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            let insert_texts: Vec<String> = arr
+                .iter()
+                .map(|item| item.insert_text.clone().unwrap())
+                .collect();
+            assert_eq!(insert_texts, vec!["age = $0"]);
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+
+    // The separator is inserted as the plain text without the snippet support
+    let insertion = SchemaInsertion {
+        snippet: false,
+        ..Default::default()
+    };
+    let got = completion_with_budget(
+        None,
+        &program,
+        &pos,
+        &prog_scope,
+        &CompletionBudget::new(None),
+        &insertion,
+    )
+    .unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            assert_eq!(arr[0].insert_text, Some("age = ".to_string()));
+            assert_eq!(
+                arr[0].insert_text_format,
+                Some(lsp_types::InsertTextFormat::PLAIN_TEXT)
+            );
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_existing_keys_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/completion_test/existing_keys/completion.k");
+    let params = lsp_types::CompletionParams {
+        text_document_position: lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: Url::from_file_path(&file).unwrap(),
+            },
+            position: Position::new(7, 4),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    };
+
+    // The keys of the other dicts are not offered by default
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let (sender, _) = crossbeam_channel::unbounded();
+    let got = handle_completion(state.snapshot(), params.clone(), sender).unwrap();
+    assert!(got.is_none());
+
+    let config = Config {
+        suggest_existing_keys: true,
+        ..Default::default()
+    };
+    let (state, _) = build_test_state(
+        config.clone(),
+        serde_json::json!({
+            "textDocument": { "completion": { "completionItem": { "snippetSupport": true } } }
+        }),
+    );
+    let (sender, _) = crossbeam_channel::unbounded();
+    let got = handle_completion(state.snapshot(), params.clone(), sender).unwrap();
+    match got.unwrap() {
+        CompletionResponse::Array(arr) => {
+            // The key `app` already set in the dict is excluded
+            let labels: Vec<String> = arr.iter().map(|item| item.label.clone()).collect();
+            assert_eq!(labels, vec!["tier", "owner-team"]);
+            let insert_texts: Vec<String> = arr
+                .iter()
+                .map(|item| item.insert_text.clone().unwrap())
+                .collect();
+            assert_eq!(insert_texts, vec!["tier = $0", "\"owner-team\" = $0"]);
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+
+    // The keys are inserted as the plain text without the client snippet support
+    let (state, _) = build_test_state(config, serde_json::json!({}));
+    let (sender, _) = crossbeam_channel::unbounded();
+    let got = handle_completion(state.snapshot(), params, sender).unwrap();
+    match got.unwrap() {
+        CompletionResponse::Array(arr) => {
+            let insert_texts: Vec<String> = arr
+                .iter()
+                .map(|item| item.insert_text.clone().unwrap())
+                .collect();
+            assert_eq!(insert_texts, vec!["tier = ", "\"owner-team\" = "]);
+            assert!(arr
+                .iter()
+                .all(|item| item.insert_text_format == Some(InsertTextFormat::PLAIN_TEXT)));
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_resolve_markdown_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/completion_test/resolve/completion.k");
+    let params = lsp_types::CompletionParams {
+        text_document_position: lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: Url::from_file_path(&file).unwrap(),
+            },
+            position: Position::new(13, 4),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    };
+    let (state, _) = build_test_state(
+        Config::default(),
+        serde_json::json!({
+            "textDocument": {
+                "completion": {
+                    "completionItem": { "documentationFormat": ["markdown", "plaintext"] }
+                }
+            }
+        }),
+    );
+    let (sender, _) = crossbeam_channel::unbounded();
+    let item = match handle_completion(state.snapshot(), params, sender.clone()).unwrap() {
+        Some(CompletionResponse::Array(arr)) => {
+            arr.into_iter().find(|item| item.label == "port").unwrap()
+        }
+        _ => unreachable!("test error"),
+    };
+    // The documentation is attached lazily by the resolve request
+    assert!(item.documentation.is_none());
+
+    let item = handle_completion_resolve(state.snapshot(), item, sender).unwrap();
+    match item.documentation {
+        Some(lsp_types::Documentation::MarkupContent(content)) => {
+            assert_eq!(content.kind, lsp_types::MarkupKind::Markdown);
+            assert_eq!(
+                content.value,
+                "```kcl\nport: int\n```\n\n---\n\nThe port to listen on."
+            );
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_type_annotation_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/completion_test/type_annotation/completion.k");
+    let params = lsp_types::CompletionParams {
+        text_document_position: lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: Url::from_file_path(&file).unwrap(),
+            },
+            position: Position::new(8, 3),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    };
+
+    let (state, _) = build_test_state(
+        Config::default(),
+        serde_json::json!({
+            "textDocument": { "completion": { "completionItem": { "snippetSupport": true } } }
+        }),
+    );
+    let (sender, _) = crossbeam_channel::unbounded();
+    let got = handle_completion(state.snapshot(), params.clone(), sender).unwrap();
+    match got.unwrap() {
+        CompletionResponse::Array(arr) => {
+            // The mixins and the variables are not types
+            let labels: Vec<String> = arr.iter().map(|item| item.label.clone()).collect();
+            assert_eq!(
+                labels[..7],
+                ["str", "int", "float", "bool", "any", "[T]", "{str:T}"]
+            );
+            let mut user_types = labels[7..].to_vec();
+            user_types.sort();
+            assert_eq!(user_types, vec!["Name", "Person"]);
+            let list_item = arr.iter().find(|item| item.label == "[T]").unwrap();
+            assert_eq!(list_item.insert_text, Some("[${1:T}]".to_string()));
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+
+    // The placeholders are inserted as the plain text without the client snippet support
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let (sender, _) = crossbeam_channel::unbounded();
+    let got = handle_completion(state.snapshot(), params, sender).unwrap();
+    match got.unwrap() {
+        CompletionResponse::Array(arr) => {
+            let dict_item = arr.iter().find(|item| item.label == "{str:T}").unwrap();
+            assert_eq!(dict_item.insert_text, Some("{str:T}".to_string()));
+            assert_eq!(
+                dict_item.insert_text_format,
+                Some(InsertTextFormat::PLAIN_TEXT)
+            );
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_qualified_type_annotation_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/completion_test/qualified/completion.k");
+    let params = lsp_types::CompletionParams {
+        text_document_position: lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: Url::from_file_path(&file).unwrap(),
+            },
+            position: Position::new(5, 3),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    };
+
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let (sender, _) = crossbeam_channel::unbounded();
+    let arr = match handle_completion(state.snapshot(), params, sender).unwrap() {
+        Some(CompletionResponse::Array(arr)) => arr,
+        _ => unreachable!("test error"),
+    };
+    let item = |label: &str| arr.iter().find(|item| item.label == label).unwrap();
+    let import_b = Some(vec![lsp_types::TextEdit {
+        range: Range::new(Position::new(1, 0), Position::new(1, 0)),
+        new_text: "import b\n".to_string(),
+    }]);
+
+    // The ambiguous `Server` of the packages `a` and `b` are offered separately with the
+    // qualified names, and the import of `b` not imported by the file is added.
+    assert!(!arr.iter().any(|item| item.label == "Server"));
+    let server_a = item("a.Server");
+    assert_eq!(server_a.insert_text, Some("a.Server".to_string()));
+    assert_eq!(server_a.detail, Some("a".to_string()));
+    assert_eq!(server_a.additional_text_edits, None);
+    let server_b = item("b.Server");
+    assert_eq!(server_b.insert_text, Some("b.Server".to_string()));
+    assert_eq!(server_b.detail, Some("b".to_string()));
+    assert_eq!(server_b.additional_text_edits, import_b);
+
+    // The unambiguous `Database` is labeled with its name and inserted qualified.
+    let database = item("Database");
+    assert_eq!(database.insert_text, Some("b.Database".to_string()));
+    assert_eq!(database.additional_text_edits, import_b);
+}
+
+#[test]
+fn completion_hide_set_attrs_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/set_attrs/completion.k");
+
+    // test completion for schema attr excluding the attrs already set
+    let pos = KCLPos {
+        filename: file,
+        line: 9,
+        column: Some(4),
+    };
+    let got = completion(None, &program, &pos, &prog_scope).unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            let labels: Vec<String> = arr.iter().map(|item| item.label.clone()).collect();
+            // The dict attr `labels` can be set repeatedly
+            assert_eq!(labels, vec!["port", "labels"]);
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_preselect_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/preselect/completion.k");
+
+    // The first required attr not set yet is preselected, skipping the optional attrs and
+    // the attrs with default values.
+    let pos = KCLPos {
+        filename: file,
+        line: 9,
+        column: Some(4),
+    };
+    match completion(None, &program, &pos, &prog_scope).unwrap() {
+        CompletionResponse::Array(arr) => {
+            let labels: Vec<String> = arr.iter().map(|item| item.label.clone()).collect();
+            assert_eq!(labels, vec!["description", "replicas", "port"]);
+            let preselected: Vec<&String> = arr
+                .iter()
+                .filter(|item| item.preselect == Some(true))
+                .map(|item| &item.label)
+                .collect();
+            assert_eq!(preselected, vec!["port"]);
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn initialized_registration_test() {
+    use lsp_types::notification::{DidChangeWatchedFiles, Initialized, Notification};
+    use lsp_types::request::{RegisterCapability, Request, WorkspaceConfiguration};
+
+    let (mut state, receiver) = build_test_state(
+        Config::default(),
+        serde_json::json!({
+            "workspace": {
+                "didChangeWatchedFiles": { "dynamicRegistration": true },
+                "configuration": true
+            }
+        }),
+    );
+    state
+        .on_notification(lsp_server::Notification::new(
+            Initialized::METHOD.to_string(),
+            lsp_types::InitializedParams {},
+        ))
+        .unwrap();
+
+    let requests: Vec<lsp_server::Request> = receiver
+        .try_iter()
+        .filter_map(|msg| match msg {
+            lsp_server::Message::Request(req) => Some(req),
+            _ => None,
+        })
+        .collect();
+    let methods: Vec<&str> = requests.iter().map(|req| req.method.as_str()).collect();
+    assert_eq!(
+        methods,
+        vec![RegisterCapability::METHOD, WorkspaceConfiguration::METHOD]
+    );
+    let params: lsp_types::RegistrationParams =
+        serde_json::from_value(requests[0].params.clone()).unwrap();
+    assert_eq!(params.registrations.len(), 1);
+    assert_eq!(
+        params.registrations[0].method,
+        DidChangeWatchedFiles::METHOD
+    );
+
+    // No registration without the dynamic registration support of the client
+    let (mut state, receiver) = build_test_state(Config::default(), serde_json::json!({}));
+    state
+        .on_notification(lsp_server::Notification::new(
+            Initialized::METHOD.to_string(),
+            lsp_types::InitializedParams {},
+        ))
+        .unwrap();
+    assert!(receiver
+        .try_iter()
+        .all(|msg| !matches!(msg, lsp_server::Message::Request(_))));
+}
+
+#[test]
+fn kcl_extensions_requests_test() {
+    use lsp_types::notification::{DidSaveTextDocument, Initialized, Notification};
+
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/kcl_extensions_test/main.gen.kcl");
+    let params = lsp_types::HoverParams {
+        text_document_position_params: lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: Url::from_file_path(&file).unwrap(),
+            },
+            position: Position::new(0, 0),
+        },
+        work_done_progress_params: Default::default(),
+    };
+    let config = Config {
+        kcl_extensions: vec![".k".to_string(), ".gen.kcl".to_string()],
+        ..Default::default()
+    };
+
+    // The file which is not a KCL file by the extensions is not analyzed
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let (sender, _) = crossbeam_channel::unbounded();
+    assert!(handle_hover(state.snapshot(), params.clone(), sender)
+        .unwrap()
+        .is_none());
+    let (state, _) = build_test_state(config.clone(), serde_json::json!({}));
+    let (sender, _) = crossbeam_channel::unbounded();
+    assert!(handle_hover(state.snapshot(), params, sender)
+        .unwrap()
+        .is_some());
+
+    // The saves of the files are registered with the document selector of the extensions
+    let (mut state, receiver) = build_test_state(
+        config,
+        serde_json::json!({
+            "textDocument": { "synchronization": { "dynamicRegistration": true } }
+        }),
+    );
+    state
+        .on_notification(lsp_server::Notification::new(
+            Initialized::METHOD.to_string(),
+            lsp_types::InitializedParams {},
+        ))
+        .unwrap();
+    let registrations: Vec<lsp_types::Registration> = receiver
+        .try_iter()
+        .filter_map(|msg| match msg {
+            lsp_server::Message::Request(req) => {
+                serde_json::from_value::<lsp_types::RegistrationParams>(req.params).ok()
+            }
+            _ => None,
+        })
+        .flat_map(|params| params.registrations)
+        .collect();
+    assert_eq!(registrations.len(), 1);
+    assert_eq!(registrations[0].method, DidSaveTextDocument::METHOD);
+    let options: lsp_types::TextDocumentSaveRegistrationOptions =
+        serde_json::from_value(registrations[0].register_options.clone().unwrap()).unwrap();
+    let patterns: Vec<String> = options
+        .text_document_registration_options
+        .document_selector
+        .unwrap()
+        .into_iter()
+        .filter_map(|filter| filter.pattern)
+        .collect();
+    assert_eq!(patterns, vec!["**/*.k", "**/*.gen.kcl"]);
+}
+
+#[test]
+fn workspace_configuration_merge_test() {
+    use lsp_types::notification::{Initialized, Notification, ShowMessage};
+    use lsp_types::request::{Request, WorkspaceConfiguration};
+
+    let config = Config {
+        kcl_extensions: vec![".k".to_string(), ".gen.kcl".to_string()],
+        max_file_size: 10,
+        ..Default::default()
+    };
+    let (mut state, receiver) = build_test_state(
+        config,
+        serde_json::json!({ "workspace": { "configuration": true } }),
+    );
+    state
+        .on_notification(lsp_server::Notification::new(
+            Initialized::METHOD.to_string(),
+            lsp_types::InitializedParams {},
+        ))
+        .unwrap();
+    let request = receiver
+        .try_iter()
+        .find_map(|msg| match msg {
+            lsp_server::Message::Request(req) if req.method == WorkspaceConfiguration::METHOD => {
+                Some(req)
+            }
+            _ => None,
+        })
+        .unwrap();
+    state
+        .handle_event(Event::Lsp(lsp_server::Message::Response(
+            lsp_server::Response::new_ok(
+                request.id,
+                serde_json::json!([{ "lint_indentation": true, "max_file_size": "big" }]),
+            ),
+        )))
+        .unwrap();
+
+    // The pulled fields are merged, while the absent and the invalid fields are kept
+    assert!(state.config.lint_indentation);
+    assert_eq!(state.config.max_file_size, 10);
+    assert_eq!(state.config.kcl_extensions, vec![".k", ".gen.kcl"]);
+    let shown: Vec<lsp_types::ShowMessageParams> = receiver
+        .try_iter()
+        .filter_map(|msg| match msg {
+            lsp_server::Message::Notification(not) if not.method == ShowMessage::METHOD => {
+                serde_json::from_value(not.params).ok()
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(shown.len(), 1);
+    assert_eq!(shown[0].typ, lsp_types::MessageType::WARNING);
+    assert!(shown[0].message.contains("invalid `max_file_size`"));
+}
+
+#[test]
+fn build_word_index_with_kcl_extensions_test() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("src/test_data/kcl_extensions_test");
+    let path = path.to_str().unwrap().to_string();
+
+    // Only `.k` files are indexed by default.
+    let index = build_word_index(path.clone(), &Config::default().kcl_extensions).unwrap();
+    assert_eq!(index.get("a").unwrap().len(), 1);
+    assert!(index.get("b").is_none());
+
+    let config = Config {
+        kcl_extensions: vec![".k".to_string(), ".gen.kcl".to_string()],
+        ..Default::default()
+    };
+    assert!(config.is_kcl_file("main.gen.kcl"));
+    let index = build_word_index(path, &config.kcl_extensions).unwrap();
+    assert_eq!(index.get("a").unwrap().len(), 2);
+    let locations = index.get("b").unwrap();
+    assert_eq!(locations.len(), 1);
+    assert!(locations[0].uri.path().ends_with("main.gen.kcl"));
+    assert_eq!(
+        locations[0].range,
+        Range::new(Position::new(0, 0), Position::new(0, 1))
+    );
+}
+
+#[test]
+fn build_word_index_utf16_test() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("src/test_data/word_index_test/cjk");
+    let path = path.to_str().unwrap().to_string();
+
+    let index = build_word_index(path, &Config::default().kcl_extensions).unwrap();
+    // The CJK chars take one UTF-16 code unit and the emoji takes two
+    let ranges: Vec<Range> = index
+        .get("name")
+        .unwrap()
+        .iter()
+        .map(|location| location.range)
+        .collect();
+    assert_eq!(
+        ranges,
+        vec![
+            Range::new(Position::new(0, 0), Position::new(0, 4)),
+            Range::new(Position::new(1, 19), Position::new(1, 23)),
+        ]
+    );
+    assert_eq!(
+        index.get("中文描述").unwrap()[0].range,
+        Range::new(Position::new(1, 8), Position::new(1, 12))
+    );
+}
+
+#[test]
+fn find_refs_schema_attr_test() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("src/test_data/find_refs_test");
+    let folder = path.to_str().unwrap().to_string();
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/find_refs_test/main.k");
+    let url = Url::from_file_path(&file).unwrap();
+
+    let mut word_index_map = HashMap::new();
+    word_index_map.insert(
+        Url::from_file_path(&folder).unwrap(),
+        build_word_index(folder, &Config::default().kcl_extensions).unwrap(),
+    );
+
+    // test find refs of the attr `name` of `Person` in: p1 = p.name
+    let pos = KCLPos {
+        filename: file,
+        line: 15,
+        column: Some(8),
+    };
+    let got = find_refs(&program, &pos, &prog_scope, &word_index_map, None, true).unwrap();
+    let expect: Vec<Location> = vec![(4, 4, 8), (11, 4, 8), (14, 7, 11)]
+        .into_iter()
+        .map(|(line, start, end)| Location {
+            uri: url.clone(),
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+        })
+        .collect();
+    assert_eq!(got, expect);
+}
+
+#[test]
+fn find_refs_stable_order_test() {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_test");
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/find_refs_test/main.k");
+    let url = Url::from_file_path(&file).unwrap();
+    let index = build_word_index(
+        folder.to_str().unwrap().to_string(),
+        &Config::default().kcl_extensions,
+    )
+    .unwrap();
+    let expect = |locs: Vec<(u32, u32, u32)>| -> Vec<Location> {
+        locs.into_iter()
+            .map(|(line, start, end)| Location {
+                uri: url.clone(),
+                range: Range::new(Position::new(line, start), Position::new(line, end)),
+            })
+            .collect()
+    };
+
+    // The same occurrences are indexed in the overlapped folders, whose iteration order
+    // differs between the maps, and the refs are sorted without the duplicates.
+    for _ in 0..10 {
+        let mut word_index_map = HashMap::new();
+        word_index_map.insert(Url::from_file_path(&folder).unwrap(), index.clone());
+        word_index_map.insert(
+            Url::from_file_path(folder.join("nested")).unwrap(),
+            index.clone(),
+        );
+        // The attr `name` of `Person` in: p1 = p.name
+        let pos = KCLPos {
+            filename: file.clone(),
+            line: 15,
+            column: Some(8),
+        };
+        let got = find_refs(&program, &pos, &prog_scope, &word_index_map, None, true).unwrap();
+        assert_eq!(got, expect(vec![(4, 4, 8), (11, 4, 8), (14, 7, 11)]));
+        // The schema `Person` in: p = Person {
+        let pos = KCLPos {
+            filename: file.clone(),
+            line: 11,
+            column: Some(5),
+        };
+        let got = find_refs(&program, &pos, &prog_scope, &word_index_map, None, true).unwrap();
+        assert_eq!(got, expect(vec![(3, 7, 13), (10, 4, 10)]));
+    }
+}
+
+#[test]
+fn find_refs_exclude_declaration_test() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("src/test_data/find_refs_test");
+    let folder = path.to_str().unwrap().to_string();
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/find_refs_test/main.k");
+    let url = Url::from_file_path(&file).unwrap();
+
+    let mut word_index_map = HashMap::new();
+    word_index_map.insert(
+        Url::from_file_path(&folder).unwrap(),
+        build_word_index(folder, &Config::default().kcl_extensions).unwrap(),
+    );
+
+    // The declaration `name: str` of `Person` is excluded, and the occurrences of `name`
+    // in the comment and the string are not references.
+    let pos = KCLPos {
+        filename: file,
+        line: 15,
+        column: Some(8),
+    };
+    let got = find_refs(&program, &pos, &prog_scope, &word_index_map, None, false).unwrap();
+    let expect: Vec<Location> = vec![(11, 4, 8), (14, 7, 11)]
+        .into_iter()
+        .map(|(line, start, end)| Location {
+            uri: url.clone(),
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+        })
+        .collect();
+    assert_eq!(got, expect);
+}
+
+#[test]
+fn find_refs_unsaved_def_test() {
+    let path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_unsaved_test");
+    let folder = path.to_str().unwrap().to_string();
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/find_refs_unsaved_test/main.k");
+    let url = Url::from_file_path(&file).unwrap();
+
+    // The file declaring `a` is opened with the unsaved changes, which move the declaration.
+    let base_path = path.join("pkg/base.k");
+    let base_url = Url::from_file_path(&base_path).unwrap();
+    let base_code = "# unsaved\n\na = 1\nc = a\n";
+    let vfs: Arc<RwLock<Vfs>> = Arc::new(RwLock::new(Default::default()));
+    vfs.write().set_file_contents(
+        ra_ap_vfs::AbsPathBuf::try_from(base_path).unwrap().into(),
+        Some(base_code.as_bytes().to_vec()),
+    );
+    let mut index = build_word_index(folder.clone(), &Config::default().kcl_extensions).unwrap();
+    for locations in index.values_mut() {
+        locations.retain(|loc| loc.uri != base_url);
+    }
+    for (word, locations) in build_word_index_for_file_content(base_code.to_string(), &base_url) {
+        index.entry(word).or_default().extend(locations);
+    }
+    let mut word_index_map = HashMap::new();
+    word_index_map.insert(Url::from_file_path(&folder).unwrap(), index);
+
+    // test find refs of the variable `a` in: b = pkg.a
+    let pos = KCLPos {
+        filename: file,
+        line: 3,
+        column: Some(8),
+    };
+    let mut got = find_refs(
+        &program,
+        &pos,
+        &prog_scope,
+        &word_index_map,
+        Some(vfs),
+        true,
+    )
+    .unwrap();
+    got.sort_by_key(|loc| (loc.uri.to_string(), loc.range.start));
+    let mut expect: Vec<Location> = vec![
+        (url, 2, 8, 9),
+        (base_url.clone(), 2, 0, 1),
+        (base_url, 3, 4, 5),
+    ]
+    .into_iter()
+    .map(|(uri, line, start, end)| Location {
+        uri,
+        range: Range::new(Position::new(line, start), Position::new(line, end)),
+    })
+    .collect();
+    expect.sort_by_key(|loc| (loc.uri.to_string(), loc.range.start));
+    assert_eq!(got, expect);
+}
+
+#[test]
+fn references_api_test() {
+    let (file, ..) = compile_test_file("src/test_data/find_refs_test/main.k");
+    let url = Url::from_file_path(&file).unwrap();
+
+    // test find refs of the variable `p` in: p1 = p.name, and the public API builds the
+    // word index of the directory of the file by itself.
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 15,
+        column: Some(5),
+    };
+    let got = references(&file, pos.clone(), true).unwrap();
+    let expect: Vec<Location> = vec![(10, 0, 1), (14, 5, 6)]
+        .into_iter()
+        .map(|(line, start, end)| Location {
+            uri: url.clone(),
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+        })
+        .collect();
+    assert_eq!(got, expect);
+
+    let got = references(&file, pos, false).unwrap();
+    assert_eq!(got, expect[1..].to_vec());
+}
+
+#[test]
+fn goto_definition_cache_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/find_refs_test/main.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let params = lsp_types::GotoDefinitionParams {
+        text_document_position_params: lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: Url::from_file_path(&file).unwrap(),
+            },
+            position: Position::new(14, 5),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let set_db = || {
+        state.analysis.set_db(
+            file.clone(),
+            parse_param(Param { file: file.clone() }, None).unwrap(),
+        )
+    };
+    let cache_hits = || state.analysis.db.read()[&file].resolution_cache_hits();
+    let goto = || {
+        let (sender, _) = crossbeam_channel::unbounded();
+        handle_goto_definition(state.snapshot(), params.clone(), sender)
+            .unwrap()
+            .unwrap()
+    };
+
+    set_db();
+    let first = goto();
+    assert_eq!(cache_hits(), 0);
+    // The second identical goto is served from the cache.
+    let second = goto();
+    assert_eq!(cache_hits(), 1);
+    assert_eq!(first, second);
+    compare_goto_res(Some(second), (&file, 10, 0, 10, 1));
+
+    // The cache is dropped with the replaced db.
+    set_db();
+    goto();
+    assert_eq!(cache_hits(), 0);
+}
+
+#[test]
+fn shared_resolution_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/find_refs_test/main.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let position = lsp_types::TextDocumentPositionParams {
+        text_document: lsp_types::TextDocumentIdentifier {
+            uri: Url::from_file_path(&file).unwrap(),
+        },
+        position: Position::new(14, 5),
+    };
+    // The file without a db is compiled by the first request and the db is cached.
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    assert!(state.analysis.db.read().is_empty());
+    let (sender, _receiver) = crossbeam_channel::unbounded();
+    let hover = || {
+        handle_hover(
+            state.snapshot(),
+            lsp_types::HoverParams {
+                text_document_position_params: position.clone(),
+                work_done_progress_params: Default::default(),
+            },
+            sender.clone(),
+        )
+        .unwrap()
+    };
+
+    assert!(hover().is_some());
+    let definition = handle_goto_definition(
+        state.snapshot(),
+        lsp_types::GotoDefinitionParams {
+            text_document_position_params: position.clone(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        },
+        sender.clone(),
+    )
+    .unwrap();
+    compare_goto_res(definition, (&file, 10, 0, 10, 1));
+
+    // Only the kinds requested at the position are resolved.
+    let db = state.analysis.db.read()[&file].clone();
+    assert_eq!(db.resolved_positions(), 1);
+    let text = std::fs::read_to_string(&file).unwrap();
+    let resolution = db
+        .resolution(&kcl_token_pos(&file, position.position, &text))
+        .unwrap();
+    assert!(resolution.hover.get().is_some());
+    assert!(resolution.definition.get().is_some());
+    assert!(resolution.reference_target.get().is_none());
+    assert!(resolution.completion.get().is_none());
+    assert_eq!(db.resolution_cache_hits(), 0);
+
+    // The later hover at the same position is served from the cache.
+    assert!(hover().is_some());
+    assert_eq!(db.resolution_cache_hits(), 1);
+}
+
+#[test]
+fn resolution_cache_bound_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/find_refs_test/main.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let db = parse_param(Param { file: file.clone() }, None).unwrap();
+    let pos = |line: u64| KCLPos {
+        filename: file.clone(),
+        line,
+        column: Some(0),
+    };
+    for line in 0..=MAX_RESOLVED_POSITIONS as u64 {
+        db.resolve(&pos(line), |res| &res.definition, || None);
+    }
+    // The least recently requested position is dropped.
+    assert_eq!(db.resolved_positions(), MAX_RESOLVED_POSITIONS);
+    assert!(db.resolution(&pos(0)).is_none());
+    assert!(db.resolution(&pos(1)).is_some());
+
+    // The requested position is kept as the most recent one.
+    db.resolve(&pos(1), |res| &res.definition, || None);
+    db.resolve(&pos(0), |res| &res.definition, || None);
+    assert!(db.resolution(&pos(1)).is_some());
+    assert!(db.resolution(&pos(2)).is_none());
+}
+
+#[test]
+fn clear_resolutions_on_workspace_configuration_test() {
+    use lsp_types::notification::{Initialized, Notification};
+    use lsp_types::request::{Request, WorkspaceConfiguration};
+
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/find_refs_test/main.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let config = Config {
+        single_threaded: true,
+        ..Default::default()
+    };
+    let (mut state, receiver) = build_test_state(
+        config,
+        serde_json::json!({ "workspace": { "configuration": true } }),
+    );
+    let (sender, _) = crossbeam_channel::unbounded();
+    handle_hover(
+        state.snapshot(),
+        lsp_types::HoverParams {
+            text_document_position_params: lsp_types::TextDocumentPositionParams {
+                text_document: lsp_types::TextDocumentIdentifier {
+                    uri: Url::from_file_path(&file).unwrap(),
+                },
+                position: Position::new(14, 5),
+            },
+            work_done_progress_params: Default::default(),
+        },
+        sender,
+    )
+    .unwrap();
+    let db = state.analysis.db.read()[&file].clone();
+    assert_eq!(db.resolved_positions(), 1);
+
+    state
+        .on_notification(lsp_server::Notification::new(
+            Initialized::METHOD.to_string(),
+            lsp_types::InitializedParams {},
+        ))
+        .unwrap();
+    let request = receiver
+        .try_iter()
+        .find_map(|msg| match msg {
+            lsp_server::Message::Request(req) if req.method == WorkspaceConfiguration::METHOD => {
+                Some(req)
+            }
+            _ => None,
+        })
+        .unwrap();
+    state
+        .handle_event(Event::Lsp(lsp_server::Message::Response(
+            lsp_server::Response::new_ok(
+                request.id,
+                serde_json::json!([{ "schema_completion_style": "config" }]),
+            ),
+        )))
+        .unwrap();
+    // The resolutions built with the old config are dropped.
+    assert_eq!(db.resolved_positions(), 0);
+}
+
+#[test]
+fn find_refs_progress_test() {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_test");
+    let uri = Url::from_file_path(folder.join("main.k")).unwrap();
+    let (mut state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    state.build_word_index_map(&[Url::from_file_path(&folder).unwrap()]);
+    handle_background_tasks(&mut state);
+
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    let got = handle_reference(
+        state.snapshot(),
+        lsp_types::ReferenceParams {
+            text_document_position: lsp_types::TextDocumentPositionParams {
+                text_document: lsp_types::TextDocumentIdentifier { uri },
+                position: Position::new(14, 5),
+            },
+            work_done_progress_params: lsp_types::WorkDoneProgressParams {
+                work_done_token: Some(lsp_types::NumberOrString::String("refs".to_string())),
+            },
+            partial_result_params: Default::default(),
+            context: lsp_types::ReferenceContext {
+                include_declaration: true,
+            },
+        },
+        sender,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(got.len(), 2);
+
+    // The candidates of `p` are the two references and the word in the comment.
+    let progress: Vec<lsp_types::WorkDoneProgress> = receiver
+        .try_iter()
+        .filter_map(|task| match task {
+            Task::Notify(notification) if notification.method == "$/progress" => {
+                let params: lsp_types::ProgressParams =
+                    serde_json::from_value(notification.params).unwrap();
+                assert_eq!(
+                    params.token,
+                    lsp_types::NumberOrString::String("refs".to_string())
+                );
+                match params.value {
+                    lsp_types::ProgressParamsValue::WorkDone(progress) => Some(progress),
+                }
+            }
+            _ => None,
+        })
+        .collect();
+    assert!(matches!(
+        progress.first(),
+        Some(lsp_types::WorkDoneProgress::Begin(_))
+    ));
+    assert!(matches!(
+        progress.last(),
+        Some(lsp_types::WorkDoneProgress::End(_))
+    ));
+    let percentages: Vec<u32> = progress
+        .iter()
+        .filter_map(|progress| match progress {
+            lsp_types::WorkDoneProgress::Report(report) => report.percentage,
+            _ => None,
+        })
+        .collect();
+    assert_eq!(percentages, vec![33, 66, 100]);
+}
+
+#[test]
+fn invalid_config_test() {
+    use lsp_types::notification::{Notification, ShowMessage};
+
+    // The wrong-typed field falls back to the default, and the valid fields are kept.
+    let (config, errors) = Config::from_initialization_options(serde_json::json!({
+        "max_file_size": "large",
+        "lint_indentation": true,
+    }));
+    assert_eq!(config.max_file_size, DEFAULT_MAX_FILE_SIZE);
+    assert!(config.lint_indentation);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("max_file_size"));
+
+    // The unknown fields, e.g., the misspelled ones, are reported too.
+    let (config, errors) = Config::from_initialization_options(serde_json::json!({
+        "maxFileSize": 1,
+        "lint_indentation": true,
+    }));
+    assert_eq!(config.max_file_size, DEFAULT_MAX_FILE_SIZE);
+    assert!(config.lint_indentation);
+    assert_eq!(errors, vec!["unknown `maxFileSize`".to_string()]);
+    let mut config = Config::default();
+    let errors = config.merge(serde_json::json!({ "lintIndentation": true }));
+    assert!(!config.lint_indentation);
+    assert_eq!(errors, vec!["unknown `lintIndentation`".to_string()]);
+
+    // The options which are not an object fall back to the default config.
+    let (config, errors) = Config::from_initialization_options(serde_json::json!([1]));
+    assert!(!config.lint_indentation);
+    assert_eq!(errors.len(), 1);
+
+    let (config, errors) = Config::from_initialization_options(serde_json::json!({
+        "max_file_size": "large",
+    }));
+    let (mut state, receiver) = build_test_state(config, serde_json::json!({}));
+    state.warn_invalid_config(&errors);
+    let messages: Vec<lsp_types::ShowMessageParams> = receiver
+        .try_iter()
+        .filter_map(|msg| match msg {
+            lsp_server::Message::Notification(not) if not.method == ShowMessage::METHOD => {
+                serde_json::from_value(not.params).ok()
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].typ, lsp_types::MessageType::WARNING);
+    assert!(messages[0].message.contains("max_file_size"));
+}
+
+#[test]
+fn skip_analysis_for_large_file_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification, ShowMessage};
+
+    let config = Config {
+        max_file_size: 8,
+        ..Default::default()
+    };
+    let (mut state, receiver) = build_test_state(config, serde_json::json!({}));
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("src/test_data/large_file_test/large.k");
+    let uri = Url::from_file_path(&path).unwrap();
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri,
+                    language_id: "KCL".to_string(),
+                    version: 0,
+                    text: "a = 1\nb = 2\n".to_string(),
+                },
+            },
+        ))
+        .unwrap();
+
+    let messages: Vec<lsp_types::ShowMessageParams> = receiver
+        .try_iter()
+        .filter_map(|msg| match msg {
+            lsp_server::Message::Notification(not) if not.method == ShowMessage::METHOD => {
+                serde_json::from_value(not.params).ok()
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].typ, lsp_types::MessageType::INFO);
+
+    // The diagnostics of the large file are not published.
+    let (task_sender, task_receiver) = crossbeam_channel::unbounded();
+    handle_diagnostics(state.snapshot(), task_sender).unwrap();
+    assert!(task_receiver.try_iter().all(|task| match task {
+        Task::Notify(not) => not.method != lsp_types::notification::PublishDiagnostics::METHOD,
+        _ => true,
+    }));
+}
+
+#[test]
+fn skip_requests_for_large_file_test() {
+    let file =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/kcl_extensions_test/main.k");
+    let position = lsp_types::TextDocumentPositionParams {
+        text_document: lsp_types::TextDocumentIdentifier {
+            uri: Url::from_file_path(&file).unwrap(),
+        },
+        position: Position::new(0, 0),
+    };
+    let hover = |state: &LanguageServerState| {
+        let (sender, _) = crossbeam_channel::unbounded();
+        handle_hover(
+            state.snapshot(),
+            lsp_types::HoverParams {
+                text_document_position_params: position.clone(),
+                work_done_progress_params: Default::default(),
+            },
+            sender,
+        )
+        .unwrap()
+    };
+
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    assert!(hover(&state).is_some());
+
+    // `a = 1` exceeds the max file size of 4 bytes
+    let config = Config {
+        max_file_size: 4,
+        ..Default::default()
+    };
+    let (state, _) = build_test_state(config, serde_json::json!({}));
+    assert!(hover(&state).is_none());
+    let (sender, _) = crossbeam_channel::unbounded();
+    let completion = handle_completion(
+        state.snapshot(),
+        lsp_types::CompletionParams {
+            text_document_position: lsp_types::TextDocumentPositionParams {
+                position: Position::new(0, 1),
+                ..position.clone()
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        },
+        sender,
+    )
+    .unwrap();
+    assert!(completion.is_none());
+}
+
+#[test]
+fn pull_diagnostics_mode_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification, PublishDiagnostics};
+
+    let mut config = Config::default();
+    config.resolve_diagnostics_mode(pull_diagnostics_supported(&serde_json::json!({
+        "capabilities": {"textDocument": {"diagnostic": {"dynamicRegistration": false}}}
+    })));
+    assert_eq!(config.diagnostics_mode, DiagnosticsMode::Pull);
+    let mut push_config = Config::default();
+    push_config.resolve_diagnostics_mode(pull_diagnostics_supported(&serde_json::json!({
+        "capabilities": {}
+    })));
+    assert_eq!(push_config.diagnostics_mode, DiagnosticsMode::Push);
+
+    let (mut state, _) = build_test_state(config, serde_json::json!({}));
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("src/test_data/diagnostic_data_test/type_error.k");
+    let uri = Url::from_file_path(&path).unwrap();
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "KCL".to_string(),
+                    version: 0,
+                    text: std::fs::read_to_string(&path).unwrap(),
+                },
+            },
+        ))
+        .unwrap();
+
+    // The diagnostics are not pushed when the client pulls them.
+    let (task_sender, task_receiver) = crossbeam_channel::unbounded();
+    handle_diagnostics(state.snapshot(), task_sender.clone()).unwrap();
+    assert!(task_receiver.try_iter().all(|task| match task {
+        Task::Notify(not) => not.method != PublishDiagnostics::METHOD,
+        _ => true,
+    }));
+
+    let report = handle_document_diagnostic(
+        state.snapshot(),
+        DocumentDiagnosticParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri },
+            identifier: None,
+            previous_result_id: None,
+        },
+        task_sender,
+    )
+    .unwrap();
+    assert_eq!(report.kind, "full");
+    assert!(!report.items.is_empty());
+    // The pulled diagnostics are computed from the db cached for the later requests.
+    assert!(state
+        .analysis
+        .db
+        .read()
+        .contains_key(path.to_str().unwrap()));
+}
+
+#[test]
+fn pull_diagnostics_mode_with_workspace_configuration_test() {
+    use lsp_types::notification::{Initialized, Notification};
+    use lsp_types::request::{Request, WorkspaceConfiguration};
+
+    let mut config = Config {
+        single_threaded: true,
+        ..Default::default()
+    };
+    config.resolve_diagnostics_mode(pull_diagnostics_supported(&serde_json::json!({
+        "capabilities": {"textDocument": {"diagnostic": {"dynamicRegistration": false}}}
+    })));
+    let (mut state, receiver) = build_test_state(
+        config,
+        serde_json::json!({ "workspace": { "configuration": true } }),
+    );
+    state
+        .on_notification(lsp_server::Notification::new(
+            Initialized::METHOD.to_string(),
+            lsp_types::InitializedParams {},
+        ))
+        .unwrap();
+    let request = receiver
+        .try_iter()
+        .find_map(|msg| match msg {
+            lsp_server::Message::Request(req) if req.method == WorkspaceConfiguration::METHOD => {
+                Some(req)
+            }
+            _ => None,
+        })
+        .unwrap();
+    // The client settings include the unresolved `auto` mode.
+    state
+        .handle_event(Event::Lsp(lsp_server::Message::Response(
+            lsp_server::Response::new_ok(
+                request.id,
+                serde_json::json!([{ "diagnostics_mode": "auto", "lint_indentation": true }]),
+            ),
+        )))
+        .unwrap();
+    assert!(state.config.lint_indentation);
+    assert_eq!(state.config.diagnostics_mode, DiagnosticsMode::Pull);
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("src/test_data/diagnostic_data_test/type_error.k");
+    state
+        .handle_event(Event::Lsp(lsp_server::Message::Request(
+            lsp_server::Request::new(
+                1.into(),
+                DocumentDiagnosticRequest::METHOD.to_string(),
+                DocumentDiagnosticParams {
+                    text_document: lsp_types::TextDocumentIdentifier {
+                        uri: Url::from_file_path(&path).unwrap(),
+                    },
+                    identifier: None,
+                    previous_result_id: None,
+                },
+            ),
+        )))
+        .unwrap();
+    let response = receiver
+        .try_iter()
+        .find_map(|msg| match msg {
+            lsp_server::Message::Response(response) => Some(response),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(response.id, 1.into());
+    assert!(response.error.is_none());
+    let report: FullDocumentDiagnosticReport =
+        serde_json::from_value(response.result.unwrap()).unwrap();
+    assert!(!report.items.is_empty());
+}
+
+#[test]
+fn json_log_format_test() {
+    use lsp_types::notification::{LogMessage, Notification};
+
+    let config = Config {
+        log_format: LogFormat::Json,
+        ..Default::default()
+    };
+    let (mut state, receiver) = build_test_state(config, serde_json::json!({}));
+    let log_records = |receiver: &Receiver<lsp_server::Message>| -> Vec<serde_json::Value> {
+        receiver
+            .try_iter()
+            .filter_map(|msg| match msg {
+                lsp_server::Message::Notification(not) if not.method == LogMessage::METHOD => {
+                    let params: lsp_types::LogMessageParams =
+                        serde_json::from_value(not.params).unwrap();
+                    Some(serde_json::from_str(&params.message).unwrap())
+                }
+                _ => None,
+            })
+            .collect()
+    };
+
+    state.log_message("hello".to_string());
+    let records = log_records(&receiver);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["level"], "info");
+    assert_eq!(records[0]["message"], "hello");
+    assert!(records[0]["timestamp"].is_string());
+
+    // The fast requests are not logged.
+    let request = lsp_server::Request::new(
+        lsp_server::RequestId::from(1),
+        "textDocument/hover".to_string(),
+        serde_json::Value::Null,
+    );
+    state.register_request(&request, std::time::Instant::now());
+    state
+        .respond(lsp_server::Response::new_ok(request.id, ()))
+        .unwrap();
+    assert!(log_records(&receiver).is_empty());
+
+    // The method and duration are recorded when responding to a slow request.
+    let request = lsp_server::Request::new(
+        lsp_server::RequestId::from(2),
+        "textDocument/hover".to_string(),
+        serde_json::Value::Null,
+    );
+    let start = std::time::Instant::now()
+        .checked_sub(SLOW_REQUEST_THRESHOLD)
+        .unwrap();
+    state.register_request(&request, start);
+    state
+        .respond(lsp_server::Response::new_ok(request.id, ()))
+        .unwrap();
+    let records = log_records(&receiver);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["method"], "textDocument/hover");
+    assert!(records[0]["duration_ms"].is_number());
+}
+
+#[test]
+fn quick_fix_convert_separator_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/quick_fix_test/separator.k");
+    let uri = Url::from_file_path(&file).unwrap();
+
+    // test converting `:` to `=` in: name: "alice"
+    let range = Range::new(Position::new(5, 6), Position::new(5, 6));
+    let actions = quick_fix(&program, &prog_scope, &file, &uri, range);
+    assert_eq!(actions.len(), 1);
+    match &actions[0] {
+        lsp_types::CodeActionOrCommand::CodeAction(action) => {
+            assert_eq!(action.title, "Convert `:` to `=`");
+            assert_eq!(
+                action.kind,
+                Some(lsp_types::CodeActionKind::REFACTOR_REWRITE)
+            );
+            let changes = action.edit.as_ref().unwrap().changes.as_ref().unwrap();
+            assert_eq!(
+                changes.get(&uri).unwrap(),
+                &vec![lsp_types::TextEdit {
+                    range: Range::new(Position::new(5, 8), Position::new(5, 10)),
+                    new_text: " = ".to_string(),
+                }]
+            );
+        }
+        _ => unreachable!("test error"),
+    }
+
+    // test converting `=` to `:` in: age = 1
+    let range = Range::new(Position::new(6, 4), Position::new(6, 4));
+    let actions = quick_fix(&program, &prog_scope, &file, &uri, range);
+    match &actions[0] {
+        lsp_types::CodeActionOrCommand::CodeAction(action) => {
+            assert_eq!(action.title, "Convert `=` to `:`");
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn fix_all_test() {
+    let (file, program, _, diags) = compile_test_file("src/test_data/fix_all_test/main.k");
+    let uri = Url::from_file_path(&file).unwrap();
+    let src = std::fs::read_to_string(&file).unwrap();
+
+    // test removing the unused `base64` and the reimported `math`, sorting the imports and
+    // formatting the file in one edit
+    let edit = fix_all(&program, &diags, &file, &uri, &src)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        edit.changes.unwrap().get(&uri).unwrap(),
+        &vec![lsp_types::TextEdit {
+            range: Range::new(Position::new(0, 0), Position::new(u32::MAX, u32::MAX)),
+            new_text:
+                "import math\nimport regex\na = math.log(10)\nb = regex.match(\"a\", \"b\")\n"
+                    .to_string(),
+        }]
+    );
+    let action = fix_all_action(&program, &diags, &file, &uri, &src)
+        .unwrap()
+        .unwrap();
+    assert_eq!(action.kind, Some(lsp_types::CodeActionKind::SOURCE_FIX_ALL));
+}
+
+#[test]
+fn fix_all_apply_edit_rejected_test() {
+    use lsp_types::notification::{Notification, ShowMessage};
+    use lsp_types::request::{ApplyWorkspaceEdit, Request};
+
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/fix_all_test/main.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let (mut state, receiver) = build_test_state(Config::default(), serde_json::json!({}));
+    let (sender, tasks) = crossbeam_channel::unbounded();
+
+    // The edit is applied by the client rather than returned
+    let res = execute_command(
+        state.snapshot(),
+        KCL_FIX_ALL_COMMAND,
+        vec![serde_json::json!({ "file": file, "apply": true })],
+        None,
+        &sender,
+    )
+    .unwrap();
+    assert!(res.is_none());
+    state
+        .handle_event(Event::Task(tasks.try_recv().unwrap()))
+        .unwrap();
+    let request = receiver
+        .try_iter()
+        .find_map(|msg| match msg {
+            lsp_server::Message::Request(req) => Some(req),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(request.method, ApplyWorkspaceEdit::METHOD);
+
+    // The rejected edit is shown to the user
+    state
+        .handle_event(Event::Lsp(lsp_server::Message::Response(
+            lsp_server::Response::new_ok(
+                request.id,
+                lsp_types::ApplyWorkspaceEditResponse {
+                    applied: false,
+                    failure_reason: Some("the file is modified".to_string()),
+                    failed_change: None,
+                },
+            ),
+        )))
+        .unwrap();
+    let shown: Vec<lsp_types::ShowMessageParams> = receiver
+        .try_iter()
+        .filter_map(|msg| match msg {
+            lsp_server::Message::Notification(not) if not.method == ShowMessage::METHOD => {
+                serde_json::from_value(not.params).ok()
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(shown.len(), 1);
+    assert_eq!(shown[0].typ, lsp_types::MessageType::ERROR);
+    assert_eq!(
+        shown[0].message,
+        "Failed to apply the workspace edit: the file is modified"
+    );
+}
+
+#[test]
+fn unknown_response_test() {
+    use lsp_types::notification::{LogMessage, Notification, ShowMessage};
+    use lsp_types::request::ApplyWorkspaceEdit;
+
+    let (mut state, receiver) = build_test_state(Config::default(), serde_json::json!({}));
+    state
+        .send_request::<ApplyWorkspaceEdit>(
+            lsp_types::ApplyWorkspaceEditParams {
+                label: None,
+                edit: lsp_types::WorkspaceEdit::default(),
+            },
+            handle_apply_edit_response,
+        )
+        .unwrap();
+    let id = receiver
+        .try_iter()
+        .find_map(|msg| match msg {
+            lsp_server::Message::Request(req) => Some(req.id),
+            _ => None,
+        })
+        .unwrap();
+    let rejected = |id: lsp_server::RequestId| {
+        Event::Lsp(lsp_server::Message::Response(lsp_server::Response::new_ok(
+            id,
+            lsp_types::ApplyWorkspaceEditResponse {
+                applied: false,
+                failure_reason: None,
+                failed_change: None,
+            },
+        )))
+    };
+
+    // The duplicate response and the response of an unknown request are dropped
+    state.handle_event(rejected(id.clone())).unwrap();
+    state.handle_event(rejected(id)).unwrap();
+    state
+        .handle_event(rejected(lsp_server::RequestId::from(1024)))
+        .unwrap();
+    let notifications: Vec<lsp_server::Notification> = receiver
+        .try_iter()
+        .filter_map(|msg| match msg {
+            lsp_server::Message::Notification(not) => Some(not),
+            _ => None,
+        })
+        .collect();
+    let shown = notifications
+        .iter()
+        .filter(|not| not.method == ShowMessage::METHOD)
+        .count();
+    assert_eq!(shown, 1);
+    let unknown = notifications
+        .iter()
+        .filter(|not| not.method == LogMessage::METHOD)
+        .filter_map(|not| {
+            serde_json::from_value::<lsp_types::LogMessageParams>(not.params.clone()).ok()
+        })
+        .filter(|params| params.message.starts_with("unknown response"))
+        .count();
+    assert_eq!(unknown, 2);
+}
+
+#[test]
+fn quick_fix_add_missing_attr_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/quick_fix_test/missing_attr.k");
+    let uri = Url::from_file_path(&file).unwrap();
+    let edits = |line: u32| -> Vec<(String, lsp_types::TextEdit)> {
+        let range = Range::new(Position::new(line, 4), Position::new(line, 4));
+        quick_fix(&program, &prog_scope, &file, &uri, range)
+            .into_iter()
+            .filter_map(|action| match action {
+                lsp_types::CodeActionOrCommand::CodeAction(action)
+                    if action.kind == Some(lsp_types::CodeActionKind::QUICKFIX) =>
+                {
+                    let mut changes = action.edit.unwrap().changes.unwrap();
+                    Some((action.title, changes.remove(&uri).unwrap().remove(0)))
+                }
+                _ => None,
+            })
+            .collect()
+    };
+
+    // The optional attr and the attr with the default value are not required, and the
+    // missing attr is inserted before the closing brace in its own line.
+    assert_eq!(
+        edits(7),
+        vec![(
+            "Add the missing attribute `port`".to_string(),
+            lsp_types::TextEdit {
+                range: Range::new(Position::new(8, 0), Position::new(8, 0)),
+                new_text: "    port = 0\n".to_string(),
+            }
+        )]
+    );
+
+    // The missing attrs are inserted inline in the empty config: t = Server {}
+    let got: Vec<lsp_types::TextEdit> = edits(9).into_iter().map(|(_, edit)| edit).collect();
+    assert_eq!(
+        got,
+        vec![
+            lsp_types::TextEdit {
+                range: Range::new(Position::new(9, 12), Position::new(9, 12)),
+                new_text: "name = \"\"".to_string(),
+            },
+            lsp_types::TextEdit {
+                range: Range::new(Position::new(9, 12), Position::new(9, 12)),
+                new_text: "port = 0".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn fix_mixed_indentation_test() {
+    let file = "src/test_data/quick_fix_test/mixed_indentation.k";
+    let uri = Url::from_file_path(std::fs::canonicalize(file).unwrap()).unwrap();
+    let src = std::fs::read_to_string(file).unwrap();
+
+    // The line indented with a tab and a space, and the line indented with a tab while the
+    // other lines are indented with spaces.
+    let diags = indentation_diagnostics(&src);
+    assert_eq!(
+        diags.iter().map(|diag| diag.range).collect::<Vec<Range>>(),
+        vec![
+            Range::new(Position::new(2, 0), Position::new(2, 2)),
+            Range::new(Position::new(3, 0), Position::new(3, 1)),
+        ]
+    );
+    for diag in &diags {
+        assert_eq!(diag.severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diag.code,
+            Some(lsp_types::NumberOrString::String(
+                MIXED_INDENTATION_CODE.to_string()
+            ))
+        );
+    }
+
+    // The tabs are converted to the spaces up to the next tab stop.
+    let edits: Vec<lsp_types::TextEdit> = fix_mixed_indentation(&diags, &src, &uri)
+        .into_iter()
+        .map(|action| match action {
+            lsp_types::CodeActionOrCommand::CodeAction(action) => {
+                assert_eq!(action.kind, Some(lsp_types::CodeActionKind::QUICKFIX));
+                action
+                    .edit
+                    .unwrap()
+                    .changes
+                    .unwrap()
+                    .remove(&uri)
+                    .unwrap()
+                    .remove(0)
+            }
+            _ => unreachable!("test error"),
+        })
+        .collect();
+    assert_eq!(
+        edits,
+        vec![
+            lsp_types::TextEdit {
+                range: Range::new(Position::new(2, 0), Position::new(2, 2)),
+                new_text: "     ".to_string(),
+            },
+            lsp_types::TextEdit {
+                range: Range::new(Position::new(3, 0), Position::new(3, 1)),
+                new_text: "    ".to_string(),
+            },
+        ]
+    );
+
+    // The file indented with tabs only is not mixed.
+    assert!(indentation_diagnostics("schema A:\n\ta: int\n").is_empty());
+}
+
+#[test]
+fn goto_inferred_schema_attr_def_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_inferred_test/main.k");
+
+    // The types of `x` and `y` are inferred from the values without annotations, and the
+    // attr `port` resolves to the declaration in `Server`.
+    for (line, column) in [(8, 6), (10, 6)] {
+        let pos = KCLPos {
+            filename: file.clone(),
+            line,
+            column: Some(column),
+        };
+        let res = goto_definition(&program, &pos, &prog_scope);
+        compare_goto_res(res, (&file, 1, 4, 1, 8));
+    }
+}
+
+#[test]
+fn goto_package_sibling_def_test() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    // main.k is not listed in kcl.yaml, and `Person` is defined in its sibling file.
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_package_def_test/main.k");
+
+    let mut expected_path = path;
+    expected_path.push("src/test_data/goto_package_def_test/schema_def.k");
+
+    // test goto schema definition: p = Person
+    let pos = KCLPos {
+        filename: file,
+        line: 1,
+        column: Some(5),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(
+        res,
+        (&expected_path.to_str().unwrap().to_string(), 0, 7, 0, 13),
+    );
+}
+
+#[test]
+fn validate_command_test() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/validate_test");
+    let schema_file = path.join("schema.k").to_str().unwrap().to_string();
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+
+    // The conforming data has no violations.
+    let data_file = path.join("valid.json").to_str().unwrap().to_string();
+    let res = execute_command(
+        state.snapshot(),
+        KCL_VALIDATE_COMMAND,
+        vec![serde_json::json!({
+            "data_file": data_file,
+            "schema_file": schema_file,
+            "schema_name": "User",
+        })],
+        None,
+        &state.task_sender,
+    )
+    .unwrap()
+    .unwrap();
+    let diags: Vec<lsp_types::Diagnostic> = serde_json::from_value(res).unwrap();
+    assert!(diags.is_empty());
+
+    // The non-conforming data reports the violation as a diagnostic.
+    let data_file = path.join("invalid.json").to_str().unwrap().to_string();
+    let res = execute_command(
+        state.snapshot(),
+        KCL_VALIDATE_COMMAND,
+        vec![serde_json::json!({
+            "data_file": data_file,
+            "schema_file": schema_file,
+        })],
+        None,
+        &state.task_sender,
+    )
+    .unwrap()
+    .unwrap();
+    let diags: Vec<lsp_types::Diagnostic> = serde_json::from_value(res).unwrap();
+    assert_eq!(diags.len(), 1);
+    assert_eq!(
+        diags[0].severity,
+        Some(lsp_types::DiagnosticSeverity::ERROR)
+    );
+}
+
+#[test]
+fn token_pos_utf16_test() {
+    // `a` after the emoji is at the UTF-16 column 11 and the char 10: b = "😀" + a
+    let text = "a = 1\nb = \"😀\" + a\n";
+    let pos = kcl_token_pos("main.k", Position::new(1, 11), text);
+    assert_eq!(pos.line, 2);
+    assert_eq!(pos.column, Some(10));
+    // The cursor at the end of `a` prefers `a` on the left.
+    let pos = kcl_token_pos("main.k", Position::new(1, 12), text);
+    assert_eq!(pos.column, Some(10));
+}
+
+#[test]
+fn token_boundary_resolution_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_def_test/goto_def.k");
+    let text = std::fs::read_to_string(&file).unwrap();
+
+    // p = pkg.Person {
+    // The cursor at the end of `Person` or on the whitespace after it resolves `Person`
+    let inner = kcl_token_pos(&file, Position::new(3, 10), &text);
+    for character in [13, 14] {
+        let pos = kcl_token_pos(&file, Position::new(3, character), &text);
+        assert_eq!(
+            goto_definition(&program, &pos, &prog_scope),
+            goto_definition(&program, &inner, &prog_scope)
+        );
+        assert_eq!(
+            hover(&program, &pos, &prog_scope, false),
+            hover(&program, &inner, &prog_scope, false)
+        );
+    }
+
+    // The cursor between `pkg` and `.` prefers `pkg` on the left
+    let inner = kcl_token_pos(&file, Position::new(3, 5), &text);
+    let pos = kcl_token_pos(&file, Position::new(3, 7), &text);
+    assert_eq!(pos.column, Some(6));
+    assert_eq!(
+        goto_definition(&program, &pos, &prog_scope),
+        goto_definition(&program, &inner, &prog_scope)
+    );
+
+    // The cursor at the start of a line is on the first token
+    let pos = kcl_token_pos(&file, Position::new(3, 0), &text);
+    assert_eq!(pos.column, Some(0));
+
+    // The cursor after `=` is not moved because `=` is not a part of an identifier
+    let pos = kcl_token_pos(&file, Position::new(3, 3), &text);
+    assert_eq!(pos.column, Some(3));
+}
+
+#[test]
+fn transport_from_args_test() {
+    let args = |args: &[&str]| {
+        Transport::from_args(args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    };
+    assert_eq!(args(&[]).unwrap(), Transport::Stdio);
+    assert_eq!(args(&["--stdio"]).unwrap(), Transport::Stdio);
+    assert_eq!(
+        args(&["--socket", "8080"]).unwrap(),
+        Transport::Socket(8080)
+    );
+    assert_eq!(args(&["--socket=8080"]).unwrap(), Transport::Socket(8080));
+    assert_eq!(
+        args(&["--pipe", "/tmp/kcl.sock"]).unwrap(),
+        Transport::Pipe("/tmp/kcl.sock".to_string())
+    );
+    assert!(args(&["--socket"]).is_err());
+    assert!(args(&["--socket", "port"]).is_err());
+    assert!(args(&["--unknown"]).is_err());
+}
+
+#[test]
+fn socket_transport_test() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || run_server(&Transport::Socket(port)));
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    let mut wait_response = |id: i32| loop {
+        match lsp_server::Message::read(&mut reader).unwrap() {
+            Some(lsp_server::Message::Response(resp))
+                if resp.id == lsp_server::RequestId::from(id) =>
+            {
+                break resp
+            }
+            Some(_) => continue,
+            None => panic!("The connection is closed before the response {}", id),
+        }
+    };
+
+    lsp_server::Message::Request(lsp_server::Request::new(
+        1.into(),
+        "initialize".to_string(),
+        lsp_types::InitializeParams::default(),
+    ))
+    .write(&mut writer)
+    .unwrap();
+    let resp = wait_response(1);
+    let result: lsp_types::InitializeResult = serde_json::from_value(resp.result.unwrap()).unwrap();
+    assert_eq!(result.server_info.unwrap().name, "kcl-language-server");
+
+    lsp_server::Message::Notification(lsp_server::Notification::new(
+        "initialized".to_string(),
+        lsp_types::InitializedParams {},
+    ))
+    .write(&mut writer)
+    .unwrap();
+    lsp_server::Message::Request(lsp_server::Request::new(
+        2.into(),
+        "shutdown".to_string(),
+        (),
+    ))
+    .write(&mut writer)
+    .unwrap();
+    assert!(wait_response(2).error.is_none());
+    lsp_server::Message::Notification(lsp_server::Notification::new("exit".to_string(), ()))
+        .write(&mut writer)
+        .unwrap();
+
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn watcher_globs_test() {
+    use lsp_types::notification::{Initialized, Notification};
+    use lsp_types::request::{RegisterCapability, Request};
+
+    // Returns the globs in the file watch registration sent on initialized.
+    let registered_globs = |config: Config| -> Vec<String> {
+        let (mut state, receiver) = build_test_state(
+            config,
+            serde_json::json!({
+                "workspace": {"didChangeWatchedFiles": {"dynamicRegistration": true}}
+            }),
+        );
+        state
+            .on_notification(lsp_server::Notification::new(
+                Initialized::METHOD.to_string(),
+                lsp_types::InitializedParams {},
+            ))
+            .unwrap();
+        let params = receiver
+            .try_iter()
+            .find_map(|msg| match msg {
+                lsp_server::Message::Request(req) if req.method == RegisterCapability::METHOD => {
+                    serde_json::from_value::<lsp_types::RegistrationParams>(req.params).ok()
+                }
+                _ => None,
+            })
+            .unwrap();
+        let options: lsp_types::DidChangeWatchedFilesRegistrationOptions =
+            serde_json::from_value(params.registrations[0].register_options.clone().unwrap())
+                .unwrap();
+        options
+            .watchers
+            .into_iter()
+            .map(|watcher| watcher.glob_pattern)
+            .collect()
+    };
+
+    assert_eq!(
+        registered_globs(Config::default()),
+        vec!["**/*.k", "**/kcl.mod"]
+    );
+    let config = Config {
+        watcher_globs: vec![
+            "**/*.k".to_string(),
+            "**/kcl.mod".to_string(),
+            "**/*.yaml".to_string(),
+        ],
+        ..Default::default()
+    };
+    assert_eq!(
+        registered_globs(config),
+        vec!["**/*.k", "**/kcl.mod", "**/*.yaml"]
+    );
+}
+
+#[test]
+fn notification_after_shutdown_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Exit, Notification};
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/diagnostics.k");
+    let (mut state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    state
+        .on_request(
+            lsp_server::Request::new(1.into(), "shutdown".to_string(), ()),
+            std::time::Instant::now(),
+        )
+        .unwrap();
+    assert!(state.shutdown_requested);
+
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    sender
+        .send(lsp_server::Message::Notification(
+            lsp_server::Notification::new(
+                DidOpenTextDocument::METHOD.to_string(),
+                lsp_types::DidOpenTextDocumentParams {
+                    text_document: lsp_types::TextDocumentItem {
+                        uri: Url::from_file_path(&path).unwrap(),
+                        language_id: "KCL".to_string(),
+                        version: 0,
+                        text: "a = 1".to_string(),
+                    },
+                },
+            ),
+        ))
+        .unwrap();
+    sender
+        .send(lsp_server::Message::Notification(
+            lsp_server::Notification::new(Exit::METHOD.to_string(), ()),
+        ))
+        .unwrap();
+    let vfs = state.vfs.clone();
+    // The loop exits on the `exit` notification after the dropped notification.
+    state.run(receiver).unwrap();
+    assert!(vfs.read().iter().next().is_none());
+}
+
+#[test]
+fn completion_index_signature_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/index_signature/completion.k");
+
+    let pos = KCLPos {
+        filename: file,
+        line: 7,
+        column: Some(4),
+    };
+    let got = completion(None, &program, &pos, &prog_scope).unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            let labels: Vec<String> = arr.iter().map(|item| item.label.clone()).collect();
+            assert_eq!(labels, vec!["app", "[str]: str"]);
+            assert_eq!(arr[1].insert_text, Some("${1:key}: $0".to_string()));
+            assert_eq!(arr[1].insert_text_format, Some(InsertTextFormat::SNIPPET));
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+
+    // The key placeholder is inserted as the plain text without the client snippet support
+    let got = completion_with_budget(
+        None,
+        &program,
+        &pos,
+        &prog_scope,
+        &CompletionBudget::new(None),
+        &SchemaInsertion {
+            snippet: false,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            assert_eq!(arr[1].insert_text, Some("key: ".to_string()));
+            assert_eq!(
+                arr[1].insert_text_format,
+                Some(InsertTextFormat::PLAIN_TEXT)
+            );
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_typed_value_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/typed_value/completion.k");
+
+    let pos = KCLPos {
+        filename: file,
+        line: 11,
+        column: Some(14),
+    };
+    let got = completion(None, &program, &pos, &prog_scope).unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            let labels: Vec<String> = arr.iter().map(|item| item.label.clone()).collect();
+            assert!(labels.contains(&"s1".to_string()));
+            assert!(labels.contains(&"s2".to_string()));
+            assert!(!labels.contains(&"n".to_string()));
+            assert!(!labels.contains(&"app".to_string()));
+            assert_eq!(arr[0].detail, Some("Server".to_string()));
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_schema_self_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/schema_self/completion.k");
+
+    // test completion in the default value of the attr: fullName: str = f
+    let pos = KCLPos {
+        filename: file,
+        line: 7,
+        column: Some(21),
+    };
+    let got = completion(None, &program, &pos, &prog_scope).unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            let labels: Vec<String> = arr.iter().map(|item| item.label.clone()).collect();
+            assert_eq!(labels, vec!["firstName", "lastName", "fullName", "id"]);
+            assert_eq!(arr[0].detail, Some("str".to_string()));
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn completion_check_block_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/completion_test/check_block/completion.k");
+
+    let pos = KCLPos {
+        filename: file,
+        line: 7,
+        column: Some(9),
+    };
+    let got = completion(None, &program, &pos, &prog_scope).unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            let labels: Vec<String> = arr.iter().map(|item| item.label.clone()).collect();
+            for label in ["all", "any", "isunique", "regex.match", "name"] {
+                assert!(labels.contains(&label.to_string()), "{} not found", label);
+            }
+            // The validation builtins are sorted before the schema attrs.
+            assert_eq!(arr[0].sort_text, Some("0all".to_string()));
+            assert_eq!(arr.last().unwrap().label, "name");
+            assert_eq!(arr[0].insert_text_format, Some(InsertTextFormat::SNIPPET));
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+
+    // The placeholders are inserted as the plain text without the client snippet support
+    let got = completion_with_budget(
+        None,
+        &program,
+        &pos,
+        &prog_scope,
+        &CompletionBudget::new(None),
+        &SchemaInsertion {
+            snippet: false,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    match got {
+        CompletionResponse::Array(arr) => {
+            assert_eq!(
+                arr[0].insert_text,
+                Some("all item in items {\n\t\n}".to_string())
+            );
+            let isunique = arr.iter().find(|item| item.label == "isunique").unwrap();
+            assert_eq!(isunique.insert_text, Some("isunique()".to_string()));
+            assert!(arr
+                .iter()
+                .filter(|item| item.insert_text.is_some())
+                .all(|item| item.insert_text_format == Some(InsertTextFormat::PLAIN_TEXT)));
+        }
+        CompletionResponse::List(_) => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn synchronous_diagnostics_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification, PublishDiagnostics};
+
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/diagnostics.k");
+    let uri = Url::from_file_path(&file).unwrap();
+    let config = Config {
+        synchronous_diagnostics: true,
+        diagnostics_mode: DiagnosticsMode::Push,
+        ..Default::default()
+    };
+    let (mut state, receiver) = build_test_state(config, serde_json::json!({}));
+    state
+        .handle_event(Event::Lsp(lsp_server::Message::Notification(
+            lsp_server::Notification::new(
+                DidOpenTextDocument::METHOD.to_string(),
+                lsp_types::DidOpenTextDocumentParams {
+                    text_document: lsp_types::TextDocumentItem {
+                        uri: uri.clone(),
+                        language_id: "KCL".to_string(),
+                        version: 0,
+                        text: std::fs::read_to_string(&file).unwrap(),
+                    },
+                },
+            ),
+        )))
+        .unwrap();
+
+    // The diagnostics are sent once the event is handled without waiting for the thread pool.
+    let published = receiver
+        .try_iter()
+        .find_map(|msg| match msg {
+            lsp_server::Message::Notification(not) if not.method == PublishDiagnostics::METHOD => {
+                serde_json::from_value::<lsp_types::PublishDiagnosticsParams>(not.params).ok()
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(published.uri, uri);
+    assert!(!published.diagnostics.is_empty());
+}
+
+#[test]
+fn single_threaded_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification, PublishDiagnostics};
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data");
+    let config = Config {
+        single_threaded: true,
+        diagnostics_mode: DiagnosticsMode::Push,
+        ..Default::default()
+    };
+    let (mut state, receiver) = build_test_state(config, serde_json::json!({}));
+
+    // The request is answered once the event is handled without the thread pool.
+    let uri = Url::from_file_path(path.join("find_refs_test/main.k")).unwrap();
+    state
+        .handle_event(Event::Lsp(lsp_server::Message::Request(
+            lsp_server::Request::new(
+                1.into(),
+                "textDocument/hover".to_string(),
+                lsp_types::HoverParams {
+                    text_document_position_params: lsp_types::TextDocumentPositionParams {
+                        text_document: lsp_types::TextDocumentIdentifier { uri },
+                        position: Position::new(14, 5),
+                    },
+                    work_done_progress_params: Default::default(),
+                },
+            ),
+        )))
+        .unwrap();
+    let response = receiver
+        .try_iter()
+        .find_map(|msg| match msg {
+            lsp_server::Message::Response(response) => Some(response),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(response.id, 1.into());
+    assert!(response.error.is_none());
+    assert!(!response.result.unwrap().is_null());
+
+    // The diagnostics are published inline as well.
+    let file = path.join("diagnostics.k");
+    let uri = Url::from_file_path(&file).unwrap();
+    state
+        .handle_event(Event::Lsp(lsp_server::Message::Notification(
+            lsp_server::Notification::new(
+                DidOpenTextDocument::METHOD.to_string(),
+                lsp_types::DidOpenTextDocumentParams {
+                    text_document: lsp_types::TextDocumentItem {
+                        uri: uri.clone(),
+                        language_id: "KCL".to_string(),
+                        version: 0,
+                        text: std::fs::read_to_string(&file).unwrap(),
+                    },
+                },
+            ),
+        )))
+        .unwrap();
+    assert!(receiver.try_iter().any(|msg| match msg {
+        lsp_server::Message::Notification(not) => not.method == PublishDiagnostics::METHOD,
+        _ => false,
+    }));
+    assert_eq!(state.thread_pool.queued_count(), 0);
+    assert_eq!(state.thread_pool.active_count(), 0);
+}
+
+#[test]
+fn severity_overrides_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification, PublishDiagnostics};
+
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/diagnostics.k");
+    let uri = Url::from_file_path(&file).unwrap();
+    // Returns the published severity of the unused import warning.
+    let unused_import_severity = |severity_overrides: HashMap<String, DiagnosticSeverity>| {
+        let config = Config {
+            synchronous_diagnostics: true,
+            diagnostics_mode: DiagnosticsMode::Push,
+            severity_overrides,
+            ..Default::default()
+        };
+        let (mut state, receiver) = build_test_state(config, serde_json::json!({}));
+        state
+            .handle_event(Event::Lsp(lsp_server::Message::Notification(
+                lsp_server::Notification::new(
+                    DidOpenTextDocument::METHOD.to_string(),
+                    lsp_types::DidOpenTextDocumentParams {
+                        text_document: lsp_types::TextDocumentItem {
+                            uri: uri.clone(),
+                            language_id: "KCL".to_string(),
+                            version: 0,
+                            text: std::fs::read_to_string(&file).unwrap(),
+                        },
+                    },
+                ),
+            )))
+            .unwrap();
+        let published = receiver
+            .try_iter()
+            .find_map(|msg| match msg {
+                lsp_server::Message::Notification(not)
+                    if not.method == PublishDiagnostics::METHOD =>
+                {
+                    serde_json::from_value::<lsp_types::PublishDiagnosticsParams>(not.params).ok()
+                }
+                _ => None,
+            })
+            .unwrap();
+        published
+            .diagnostics
+            .iter()
+            .find(|diag| diag.message == "Module 'abc' imported but unused")
+            .unwrap()
+            .severity
+    };
+
+    assert_eq!(
+        unused_import_severity(HashMap::new()),
+        Some(DiagnosticSeverity::WARNING)
+    );
+    assert_eq!(
+        unused_import_severity(HashMap::from([(
+            "UnusedImportWarning".to_string(),
+            DiagnosticSeverity::ERROR
+        )])),
+        Some(DiagnosticSeverity::ERROR)
+    );
+}
+
+#[test]
+fn duplicate_did_open_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification};
+
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/diagnostics.k");
+    let (mut state, _receiver) = build_test_state(Config::default(), serde_json::json!({}));
+    let did_open = |text: &str| {
+        lsp_server::Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri: Url::from_file_path(&file).unwrap(),
+                    language_id: "KCL".to_string(),
+                    version: 0,
+                    text: text.to_string(),
+                },
+            },
+        )
+    };
+    state.on_notification(did_open("a = 1\n")).unwrap();
+    state.on_notification(did_open("a = 1\n")).unwrap();
+    // The second identical didOpen does not change the file, so the file is compiled once.
+    assert_eq!(state.vfs.write().take_changes().len(), 1);
+
+    // The didOpen with the different contents still changes the file.
+    state.on_notification(did_open("a = 2\n")).unwrap();
+    assert_eq!(state.vfs.write().take_changes().len(), 1);
+}
+
+#[test]
+fn reuse_unaffected_db_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification};
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data");
+    let file = path.join("goto_def_test/goto_def.k");
+    let file = file.to_str().unwrap().to_string();
+    let changed = path.join("hover_test/nested_type.k");
+    let (mut state, _receiver) = build_test_state(
+        Config {
+            warmup_entries: vec![file.clone(), changed.to_str().unwrap().to_string()],
+            ..Default::default()
+        },
+        serde_json::json!({}),
+    );
+    state.warm_up(&[]);
+    handle_background_tasks(&mut state);
+    assert_eq!(state.analysis.db.read().len(), 2);
+    // The imported file invalidates the db
+    let dep = path.join("goto_def_test/pkg/schema_def.k");
+    assert!(!state.analysis.db.read()[&file]
+        .is_valid(&HashSet::from([dep.to_str().unwrap().to_string()])));
+
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri: Url::from_file_path(&changed).unwrap(),
+                    language_id: "KCL".to_string(),
+                    version: 0,
+                    text: "n = 1\n".to_string(),
+                },
+            },
+        ))
+        .unwrap();
+    // The db of the unrelated file is reused, and only the changed one is dropped.
+    let db = state.analysis.db.read();
+    assert_eq!(db.len(), 1);
+    assert!(db.contains_key(&file));
+}
+
+#[test]
+fn warmup_entries_test() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/goto_def_test");
+    let root = Url::from_file_path(&path).unwrap();
+    let file = path.join("goto_def.k").to_str().unwrap().to_string();
+
+    let (mut state, _receiver) = build_test_state(
+        Config {
+            warmup_entries: vec!["goto_def.k".to_string()],
+            ..Default::default()
+        },
+        serde_json::json!({}),
+    );
+    assert!(state.analysis.db.read().is_empty());
+    // The relative entry is joined with the workspace folder
+    state.warm_up(&[root]);
+    handle_background_tasks(&mut state);
+    let snapshot = state.snapshot();
+    let db = snapshot.db.read();
+    let db = db.get(&file).unwrap();
+    assert!(db.prog.pkgs.values().flatten().any(|m| m.filename == file));
+}
+
+#[test]
+fn warm_up_in_background_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification};
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/goto_def_test");
+    let root = Url::from_file_path(&path).unwrap();
+    let file = path.join("goto_def.k");
+    let (mut state, _receiver) = build_test_state(
+        Config {
+            warmup_entries: vec!["goto_def.k".to_string()],
+            ..Default::default()
+        },
+        serde_json::json!({}),
+    );
+    state.build_word_index_map(&[root.clone()]);
+    state.warm_up(&[root.clone()]);
+    state.thread_pool.join();
+    // The results are stored once the tasks are handled by the main loop
+    assert!(state.word_index_map.read().is_empty());
+    assert!(state.analysis.db.read().is_empty());
+
+    // The document opened meanwhile is changed since the results are built
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri: Url::from_file_path(&file).unwrap(),
+                    language_id: "KCL".to_string(),
+                    version: 0,
+                    text: "a = 1\n".to_string(),
+                },
+            },
+        ))
+        .unwrap();
+    handle_background_tasks(&mut state);
+    assert!(state.word_index_map.read().contains_key(&root));
+    assert!(!state.word_index_generations.folders.lock()[&root].is_current());
+    assert!(state.analysis.db.read().is_empty());
+}
+
+#[test]
+fn goto_builtin_def_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_builtin_def_test/builtin.k");
+
+    // a = len([1, 2])
+    let pos = KCLPos {
+        filename: file,
+        line: 1,
+        column: Some(5),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    let location = match res {
+        Some(lsp_types::GotoDefinitionResponse::Scalar(location)) => location,
+        _ => unreachable!("test error"),
+    };
+    assert_eq!(location.uri.as_str(), "kcl-builtin:///len.k");
+
+    // The synthetic document contains the signature and the doc of the builtin
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let res = execute_command(
+        state.snapshot(),
+        KCL_BUILTIN_DOCUMENT_COMMAND,
+        vec![serde_json::json!(location.uri)],
+        None,
+        &state.task_sender,
+    )
+    .unwrap()
+    .unwrap();
+    let doc: String = serde_json::from_value(res).unwrap();
+    let signature = doc.lines().next().unwrap();
+    assert!(signature.starts_with("len(inval: "));
+    assert!(signature.ends_with(") -> int"));
+    assert_eq!(location.range.end.character as usize, signature.len());
+    assert!(doc.contains("Return the length of a value."));
+}
+
+#[test]
+fn publish_diagnostics_for_dependencies_test() {
+    let path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/dependency_diagnostics_test");
+    let main_file = path.join("main.k");
+    let dep_file = path.join("dep/dep.k");
+
+    let published_uris = |publish_diagnostics_for_dependencies: bool| {
+        let config = Config {
+            publish_diagnostics_for_dependencies,
+            ..Default::default()
+        };
+        let (state, _) = build_test_state(config, serde_json::json!({}));
+        // Open the main file only
+        state.vfs.write().set_file_contents(
+            ra_ap_vfs::AbsPathBuf::try_from(main_file.clone())
+                .unwrap()
+                .into(),
+            Some(std::fs::read(&main_file).unwrap()),
+        );
+        let (task_sender, task_receiver) = crossbeam_channel::unbounded();
+        handle_diagnostics(state.snapshot(), task_sender).unwrap();
+        task_receiver
+            .try_iter()
+            .filter_map(|task| match task {
+                Task::Notify(not)
+                    if not.method == lsp_types::notification::PublishDiagnostics::METHOD =>
+                {
+                    serde_json::from_value::<lsp_types::PublishDiagnosticsParams>(not.params).ok()
+                }
+                _ => None,
+            })
+            .map(|params| (params.uri, params.diagnostics.len()))
+            .collect::<Vec<(Url, usize)>>()
+    };
+
+    let main_uri = Url::from_file_path(&main_file).unwrap();
+    let dep_uri = Url::from_file_path(&dep_file).unwrap();
+    let uris = published_uris(false);
+    assert_eq!(
+        uris.iter()
+            .map(|(uri, _)| uri.clone())
+            .collect::<Vec<Url>>(),
+        vec![main_uri.clone()]
+    );
+
+    let uris = published_uris(true);
+    assert_eq!(
+        uris.iter()
+            .map(|(uri, _)| uri.clone())
+            .collect::<Vec<Url>>(),
+        vec![main_uri, dep_uri]
+    );
+    // The type error in the dependency is reported in its own file
+    assert!(uris[1].1 > 0);
+}
+
+#[test]
+fn publish_diagnostics_for_opened_dependencies_test() {
+    use lsp_types::notification::{DidCloseTextDocument, DidOpenTextDocument, Notification};
+
+    let path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/dependency_diagnostics_test");
+    let main_file = path.join("main.k");
+    let dep_file = path.join("dep/dep.k");
+    let main_uri = Url::from_file_path(&main_file).unwrap();
+    let dep_uri = Url::from_file_path(&dep_file).unwrap();
+    let (mut state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    // Returns the uris published after the main file is changed to the contents.
+    let publish = |state: &LanguageServerState, contents: String| {
+        state.vfs.write().set_file_contents(
+            ra_ap_vfs::AbsPathBuf::try_from(main_file.clone())
+                .unwrap()
+                .into(),
+            Some(contents.into_bytes()),
+        );
+        let (task_sender, task_receiver) = crossbeam_channel::unbounded();
+        handle_diagnostics(state.snapshot(), task_sender).unwrap();
+        task_receiver
+            .try_iter()
+            .filter_map(|task| match task {
+                Task::Notify(not)
+                    if not.method == lsp_types::notification::PublishDiagnostics::METHOD =>
+                {
+                    serde_json::from_value::<lsp_types::PublishDiagnosticsParams>(not.params).ok()
+                }
+                _ => None,
+            })
+            .map(|params| params.uri)
+            .collect::<Vec<Url>>()
+    };
+    let main_code = std::fs::read_to_string(&main_file).unwrap();
+
+    // The dependency held by the vfs, e.g., changed on the disk, is not opened
+    state.vfs.write().set_file_contents(
+        ra_ap_vfs::AbsPathBuf::try_from(dep_file.clone())
+            .unwrap()
+            .into(),
+        Some(std::fs::read(&dep_file).unwrap()),
+    );
+    state.vfs.write().take_changes();
+    assert_eq!(publish(&state, main_code.clone()), vec![main_uri.clone()]);
+
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri: dep_uri.clone(),
+                    language_id: "KCL".to_string(),
+                    version: 0,
+                    text: std::fs::read_to_string(&dep_file).unwrap(),
+                },
+            },
+        ))
+        .unwrap();
+    state.vfs.write().take_changes();
+    assert_eq!(
+        publish(&state, format!("{}\n", main_code)),
+        vec![dep_uri.clone()]
+    );
+
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidCloseTextDocument::METHOD.to_string(),
+            lsp_types::DidCloseTextDocumentParams {
+                text_document: lsp_types::TextDocumentIdentifier { uri: dep_uri },
+            },
+        ))
+        .unwrap();
+    assert!(state.document_versions.read().is_empty());
+}
+
+#[test]
+fn publish_changed_diagnostics_only_test() {
+    let path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/dependency_diagnostics_test");
+    let main_file = path.join("main.k");
+    let dep_file = path.join("dep/dep.k");
+    let config = Config {
+        publish_diagnostics_for_dependencies: true,
+        ..Default::default()
+    };
+    let (state, _) = build_test_state(config, serde_json::json!({}));
+    // Returns the uris published after the main file is changed to the contents.
+    let publish = |contents: String| {
+        state.vfs.write().set_file_contents(
+            ra_ap_vfs::AbsPathBuf::try_from(main_file.clone())
+                .unwrap()
+                .into(),
+            Some(contents.into_bytes()),
+        );
+        let (task_sender, task_receiver) = crossbeam_channel::unbounded();
+        handle_diagnostics(state.snapshot(), task_sender).unwrap();
+        task_receiver
+            .try_iter()
+            .filter_map(|task| match task {
+                Task::Notify(not)
+                    if not.method == lsp_types::notification::PublishDiagnostics::METHOD =>
+                {
+                    serde_json::from_value::<lsp_types::PublishDiagnosticsParams>(not.params).ok()
+                }
+                _ => None,
+            })
+            .map(|params| params.uri)
+            .collect::<Vec<Url>>()
+    };
+
+    let main_uri = Url::from_file_path(&main_file).unwrap();
+    let dep_uri = Url::from_file_path(&dep_file).unwrap();
+    let contents = std::fs::read_to_string(&main_file).unwrap();
+    assert_eq!(publish(contents.clone()), vec![main_uri.clone(), dep_uri]);
+    // The diagnostics of the dependency are unchanged and not published again.
+    assert_eq!(
+        publish(format!("{}c: int = \"1\"\n", contents)),
+        vec![main_uri]
+    );
+    // Nothing is published if no diagnostics are changed by the edit.
+    assert!(publish(format!("{}c: int = \"1\"\n# comment\n", contents)).is_empty());
+}
+
+#[test]
+fn rename_package_test() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/rename_pkg_test")
+        .canonicalize()
+        .unwrap();
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let renames = vec![lsp_types::FileRename {
+        old_uri: Url::from_file_path(path.join("pkg")).unwrap().to_string(),
+        new_uri: Url::from_file_path(path.join("base")).unwrap().to_string(),
+    }];
+    let edit = rename_packages(&renames, &state.config.kcl_extensions, state.vfs.clone())
+        .unwrap()
+        .unwrap();
+    let changes = edit.changes.unwrap();
+    let edits = |file: &str| -> Vec<(Range, String)> {
+        changes
+            .get(&Url::from_file_path(path.join(file)).unwrap())
+            .map(|edits| {
+                edits
+                    .iter()
+                    .map(|edit| (edit.range, edit.new_text.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    // The imports of the package and its nested package are rewritten
+    assert_eq!(
+        edits("main.k"),
+        vec![
+            (
+                Range::new(Position::new(0, 7), Position::new(0, 10)),
+                "base".to_string()
+            ),
+            (
+                Range::new(Position::new(1, 7), Position::new(1, 14)),
+                "base.sub".to_string()
+            ),
+        ]
+    );
+    // The relative import is kept relative
+    assert_eq!(
+        edits("other/other.k"),
+        vec![(
+            Range::new(Position::new(0, 7), Position::new(0, 16)),
+            "..base.sub".to_string()
+        )]
+    );
+    // The relative import inside the renamed package is still valid
+    assert!(edits("pkg/pkg.k").is_empty());
+}
+
+#[test]
+fn goto_lambda_param_def_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_lambda_def_test/lambda.k");
+
+    // test goto lambda param: z = x + y
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 2,
+        column: Some(8),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 0, 11, 0, 12));
+
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 2,
+        column: Some(12),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 0, 19, 0, 20));
+
+    // test goto local variable in the lambda body: z
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 3,
+        column: Some(4),
+    };
+    let res = goto_definition(&program, &pos, &prog_scope);
+    compare_goto_res(res, (&file, 1, 4, 1, 5));
+}
+
+#[test]
+fn goto_schema_self_attr_def_test() {
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/goto_schema_self_test/main.k");
+    let pos = |line: u64, column: u64| KCLPos {
+        filename: file.clone(),
+        line,
+        column: Some(column),
+    };
+    let hover_ty = |pos: &KCLPos| match hover(&program, pos, &prog_scope, false).unwrap().contents {
+        lsp_types::HoverContents::Scalar(MarkedString::String(s)) => s,
+        _ => unreachable!("test error"),
+    };
+
+    // test goto and hover of the sibling attr in the interpolation of the default value:
+    // address: str = "${host}:${port}"
+    let res = goto_definition(&program, &pos(4, 23), &prog_scope);
+    compare_goto_res(res, (&file, 1, 4, 1, 8));
+    assert_eq!(hover_ty(&pos(4, 23)), "str");
+
+    // test goto and hover of the attr referenced in the lambda attr of the schema:
+    // "http://" + address + path
+    let res = goto_definition(&program, &pos(6, 21), &prog_scope);
+    compare_goto_res(res, (&file, 3, 4, 3, 11));
+    assert_eq!(hover_ty(&pos(6, 21)), "str");
+
+    // test goto and hover of the attr referenced in the check block: port > 0
+    let res = goto_definition(&program, &pos(10, 9), &prog_scope);
+    compare_goto_res(res, (&file, 2, 4, 2, 8));
+    assert_eq!(hover_ty(&pos(10, 9)), "int");
+}
+
+#[test]
+fn diagnostics_task_panic_recovery_test() {
+    use lsp_types::notification::{LogMessage, Notification, PublishDiagnostics};
+
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    state.spawn_diagnostics_task(|| panic!("injected panic"));
+    state.thread_pool.join();
+
+    // The diagnostics are still published after the panicked task
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/goto_lambda_def_test/lambda.k");
+    state.vfs.write().set_file_contents(
+        ra_ap_vfs::AbsPathBuf::try_from(path.clone())
+            .unwrap()
+            .into(),
+        Some(std::fs::read(&path).unwrap()),
+    );
+    let snapshot = state.snapshot();
+    let task_sender = state.task_sender.clone();
+    state.spawn_diagnostics_task(move || handle_diagnostics(snapshot, task_sender));
+    state.thread_pool.join();
+
+    let notifications: Vec<lsp_server::Notification> = state
+        .task_receiver
+        .try_iter()
+        .filter_map(|task| match task {
+            Task::Notify(not) => Some(not),
+            _ => None,
+        })
+        .collect();
+    assert!(notifications.iter().any(|not| {
+        not.method == LogMessage::METHOD
+            && serde_json::from_value::<lsp_types::LogMessageParams>(not.params.clone())
+                .unwrap()
+                .message
+                .contains("diagnostics task panicked: injected panic")
+    }));
+    assert!(notifications
+        .iter()
+        .any(|not| not.method == PublishDiagnostics::METHOD));
+}
+
+#[test]
+fn synchronous_diagnostics_task_panic_recovery_test() {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    // The panic of the task run inline is caught and logged rather than unwinding the caller.
+    run_diagnostics_task(|| panic!("injected panic"), &sender);
+    match receiver.try_recv() {
+        Ok(Task::Notify(not)) => {
+            let params: lsp_types::LogMessageParams = serde_json::from_value(not.params).unwrap();
+            assert!(params
+                .message
+                .contains("diagnostics task panicked: injected panic"));
+        }
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn explain_command_test() {
+    // The diagnostic code is the name of the error kind
+    let diag = Diagnostic::new_with_code(
+        kclvm_error::Level::Error,
+        "Immutable variable 'a' is modified during compiling",
+        None,
+        KCLPos {
+            filename: "main.k".to_string(),
+            line: 2,
+            column: Some(0),
+        },
+        Some(kclvm_error::DiagnosticId::Error(
+            kclvm_error::ErrorKind::ImmutableError,
+        )),
+    );
+    let lsp_diags = kcl_diag_to_lsp_diags(&diag, "main.k", &HashMap::new(), None);
+    let code = match &lsp_diags[0].code {
+        Some(lsp_types::NumberOrString::String(code)) => code.clone(),
+        _ => unreachable!("test error"),
+    };
+    assert_eq!(code, "ImmutableError");
+
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let res = execute_command(
+        state.snapshot(),
+        KCL_EXPLAIN_COMMAND,
+        vec![serde_json::json!(code)],
+        None,
+        &state.task_sender,
+    )
+    .unwrap()
+    .unwrap();
+    let explanation: String = serde_json::from_value(res).unwrap();
+    assert!(explanation.starts_with("# ImmutableError"));
+    assert!(explanation.contains("```kcl"));
+
+    assert!(execute_command(
+        state.snapshot(),
+        KCL_EXPLAIN_COMMAND,
+        vec![serde_json::json!("UnknownCode")],
+        None,
+        &state.task_sender,
+    )
+    .is_err());
+}
+
+#[test]
+fn prepare_rename_external_symbol_test() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/prepare_rename_test");
+    let file = root
+        .join("app")
+        .join("main.k")
+        .canonicalize()
+        .unwrap()
+        .display()
+        .to_string();
+    // The external package is mapped to its local path like the package cache of kpm.
+    let mut opts = LoadProgramOptions::default();
+    opts.package_maps.insert(
+        "ext".to_string(),
+        root.join("ext")
+            .canonicalize()
+            .unwrap()
+            .display()
+            .to_string(),
+    );
+    let mut program =
+        load_program(Arc::new(ParseSession::default()), &[&file], Some(opts)).unwrap();
+    let prog_scope = resolve_program(&mut program);
+
+    // The schema `Server` of the external package is read-only: a = ext.Server {port = 80}
+    let pos = KCLPos {
+        filename: file.clone(),
+        line: 3,
+        column: Some(10),
+    };
+    let err = prepare_rename(&program, &pos, &prog_scope).unwrap_err();
+    assert!(err.to_string().contains("outside the workspace"));
+
+    // The variable defined in the workspace can be renamed: b = a
+    let pos = KCLPos {
+        filename: file,
+        line: 4,
+        column: Some(4),
+    };
+    let res = prepare_rename(&program, &pos, &prog_scope).unwrap();
+    assert_eq!(
+        res,
+        Some(lsp_types::PrepareRenameResponse::RangeWithPlaceholder {
+            range: Range::new(Position::new(3, 4), Position::new(3, 5)),
+            placeholder: "a".to_string(),
+        })
+    );
+}
+
+#[test]
+fn rename_symbol_test() {
+    let path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_unsaved_test");
+    let folder = path.to_str().unwrap().to_string();
+    let (file, program, prog_scope, _) =
+        compile_test_file("src/test_data/find_refs_unsaved_test/main.k");
+    let url = Url::from_file_path(&file).unwrap();
+    let base_url = Url::from_file_path(path.join("pkg/base.k")).unwrap();
+    let mut word_index_map = HashMap::new();
+    word_index_map.insert(
+        Url::from_file_path(&folder).unwrap(),
+        build_word_index(folder, &Config::default().kcl_extensions).unwrap(),
+    );
+    let rename_to = |line: u64, column: u64, new_name: &str| {
+        let pos = KCLPos {
+            filename: file.clone(),
+            line,
+            column: Some(column),
+        };
+        rename_symbol(
+            &program,
+            &pos,
+            &prog_scope,
+            new_name,
+            &word_index_map,
+            Arc::new(RwLock::new(Vfs::default())),
+        )
+    };
+    let rename = |line: u64, column: u64| rename_to(line, column, "c").unwrap();
+    let edit = |uri: &Url, line: u32, start: u32, end: u32| {
+        (
+            uri.clone(),
+            vec![lsp_types::TextEdit {
+                range: Range::new(Position::new(line, start), Position::new(line, end)),
+                new_text: "c".to_string(),
+            }],
+        )
+    };
+
+    // The variable `a` of the package is renamed in both files: b = pkg.a
+    let got = rename(3, 8).unwrap();
+    assert!(got.document_changes.is_none());
+    assert_eq!(
+        got.changes.unwrap(),
+        HashMap::from([edit(&url, 2, 8, 9), edit(&base_url, 0, 0, 1)])
+    );
+
+    // The variable `b` is renamed in the main file only.
+    let got = rename(3, 0).unwrap();
+    assert_eq!(got.changes.unwrap(), HashMap::from([edit(&url, 2, 0, 1)]));
+
+    // There is no symbol on the blank line.
+    assert!(rename(2, 0).is_none());
+
+    // The keywords and the invalid identifiers are rejected.
+    for new_name in ["schema", "True", "1a", "a-b", ""] {
+        let err = rename_to(3, 8, new_name).unwrap_err();
+        assert!(err.to_string().contains("not a valid identifier"));
+    }
+}
+
+#[test]
+fn rename_external_symbol_test() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/prepare_rename_test");
+    let file = root
+        .join("app")
+        .join("main.k")
+        .canonicalize()
+        .unwrap()
+        .display()
+        .to_string();
+    let mut opts = LoadProgramOptions::default();
+    opts.package_maps.insert(
+        "ext".to_string(),
+        root.join("ext")
+            .canonicalize()
+            .unwrap()
+            .display()
+            .to_string(),
+    );
+    let mut program =
+        load_program(Arc::new(ParseSession::default()), &[&file], Some(opts)).unwrap();
+    let prog_scope = resolve_program(&mut program);
+
+    // The schema `Server` of the external package is not renamed: a = ext.Server {port = 80}
+    let pos = KCLPos {
+        filename: file,
+        line: 3,
+        column: Some(10),
+    };
+    let err = rename_symbol(
+        &program,
+        &pos,
+        &prog_scope,
+        "Service",
+        &HashMap::new(),
+        Arc::new(RwLock::new(Vfs::default())),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("outside the workspace"));
+}
+
+#[test]
+fn prepare_rename_placeholder_test() {
+    let (file, program, prog_scope, _) = compile_test_file("src/test_data/crlf_test/main.k");
+    let text = std::fs::read_to_string(&file).unwrap();
+
+    // The placeholder is the name under the cursor, e.g., the schema `Person` of
+    // `p = Person {` and the attr `name` of `n = p.name`.
+    for (position, expected) in [
+        (Position::new(5, 6), "Person"),
+        (Position::new(9, 7), "name"),
+    ] {
+        let pos = kcl_token_pos(&file, position, &text);
+        match prepare_rename(&program, &pos, &prog_scope).unwrap() {
+            Some(lsp_types::PrepareRenameResponse::RangeWithPlaceholder { range, placeholder }) => {
+                assert_eq!(placeholder, expected);
+                let line = text.lines().nth(range.start.line as usize).unwrap();
+                assert_eq!(
+                    &line[range.start.character as usize..range.end.character as usize],
+                    expected
+                );
+            }
+            _ => unreachable!("test error"),
+        }
+    }
+}
+
+#[test]
+fn suppression_comment_test() {
+    let (file, _, _, diags) = compile_test_file("src/test_data/suppression_test/suppression.k");
+    let diagnostics: Vec<lsp_types::Diagnostic> = diags
+        .iter()
+        .flat_map(|diag| kcl_diag_to_lsp_diags(diag, &file, &HashMap::new(), None))
+        .collect();
+    assert!(diagnostics.iter().any(|diag| diag.range.start.line == 4));
+
+    // The type error on the line after the comment is suppressed, the other one remains
+    let source = std::fs::read_to_string(&file).unwrap();
+    let diagnostics = drop_suppressed(diagnostics, &source);
+    assert!(!diagnostics.is_empty());
+    assert!(diagnostics.iter().all(|diag| diag.range.start.line == 5));
+
+    // The comment suppressing the other codes keeps the diagnostics
+    let suppressions = Suppressions::parse("# kcl-disable-next-line NameError, KeyError\n");
+    assert!(diagnostics
+        .iter()
+        .map(|diag| lsp_types::Diagnostic {
+            range: Range::new(Position::new(1, 0), Position::new(1, 0)),
+            ..diag.clone()
+        })
+        .all(|diag| !suppressions.is_suppressed(&diag)));
+}
+
+#[test]
+fn diagnostic_data_test() {
+    let (file, _, _, diags) = compile_test_file("src/test_data/diagnostic_data_test/type_error.k");
+    let diag = diags
+        .iter()
+        .flat_map(|diag| kcl_diag_to_lsp_diags(diag, &file, &HashMap::new(), None))
+        .find(|diag| diag.code == Some(lsp_types::NumberOrString::String("TypeError".to_string())))
+        .unwrap();
+    let data: DiagnosticData = serde_json::from_value(diag.data.unwrap()).unwrap();
+    assert_eq!(data.code, Some("TypeError".to_string()));
+    assert_eq!(data.expected_type, Some("str".to_string()));
+    assert_eq!(data.got_type, Some("int(1)".to_string()));
+}
+
+#[test]
+fn word_index_generation_test() {
+    use lsp_types::notification::{DidChangeTextDocument, DidOpenTextDocument, Notification};
+
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_test");
+    let folder_url = Url::from_file_path(&folder).unwrap();
+    let file = folder.join("main.k");
+    let uri = Url::from_file_path(&file).unwrap();
+    let text = std::fs::read_to_string(&file).unwrap();
+
+    let (mut state, _receiver) = build_test_state(Config::default(), serde_json::json!({}));
+    state.build_word_index_map(&[folder_url.clone()]);
+    handle_background_tasks(&mut state);
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "KCL".to_string(),
+                    version: 0,
+                    text: text.clone(),
+                },
+            },
+        ))
+        .unwrap();
+    // Opening the file with the same content as the disk does not change the generation.
+    assert!(state
+        .word_index_generations
+        .folders
+        .lock()
+        .get(&folder_url)
+        .is_none());
+
+    // Add a new reference of the attr `name` of `Person` at the end of the file.
+    let new_line = text.lines().count() as u32;
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidChangeTextDocument::METHOD.to_string(),
+            lsp_types::DidChangeTextDocumentParams {
+                text_document: lsp_types::VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 1,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: format!("{}p2 = p.name\n", text),
+                }],
+            },
+        ))
+        .unwrap();
+    assert!(!state.word_index_generations.folders.lock()[&folder_url].is_current());
+
+    let (sender, _) = crossbeam_channel::unbounded();
+    let refs = handle_reference(
+        state.snapshot(),
+        lsp_types::ReferenceParams {
+            text_document_position: lsp_types::TextDocumentPositionParams {
+                text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+                position: Position::new(14, 8),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: lsp_types::ReferenceContext {
+                include_declaration: true,
+            },
+        },
+        sender,
+    )
+    .unwrap()
+    .unwrap();
+    assert!(refs.contains(&Location {
+        uri,
+        range: Range::new(Position::new(new_line, 7), Position::new(new_line, 11)),
+    }));
+    assert!(state.word_index_generations.folders.lock()[&folder_url].is_current());
+}
+
+#[test]
+fn word_index_did_save_test() {
+    use lsp_types::notification::{DidOpenTextDocument, DidSaveTextDocument, Notification};
+
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_test");
+    let folder_url = Url::from_file_path(&folder).unwrap();
+    let file = folder.join("main.k");
+    let uri = Url::from_file_path(&file).unwrap();
+    let text = std::fs::read_to_string(&file).unwrap();
+
+    let (mut state, _receiver) = build_test_state(Config::default(), serde_json::json!({}));
+    state.build_word_index_map(&[folder_url.clone()]);
+    handle_background_tasks(&mut state);
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "KCL".to_string(),
+                    version: 0,
+                    text: text.clone(),
+                },
+            },
+        ))
+        .unwrap();
+    // The file is formatted by an external formatter on save.
+    let saved = format!("formatted = 1\n{}", text);
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidSaveTextDocument::METHOD.to_string(),
+            lsp_types::DidSaveTextDocumentParams {
+                text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+                text: Some(saved.clone()),
+            },
+        ))
+        .unwrap();
+    assert!(!state.word_index_generations.folders.lock()[&folder_url].is_current());
+
+    let snapshot = state.snapshot();
+    snapshot
+        .word_index_generations
+        .refresh(&snapshot.word_index_map, snapshot.vfs.clone());
+    let word_index_map = snapshot.word_index_map.read();
+    let index = &word_index_map[&folder_url];
+    assert_eq!(
+        index["formatted"],
+        vec![Location {
+            uri: uri.clone(),
+            range: Range::new(Position::new(0, 0), Position::new(0, 9)),
+        }]
+    );
+    // The words after the inserted line are shifted.
+    assert!(index["Person"]
+        .iter()
+        .any(|loc| loc.uri == uri && loc.range.start == Position::new(4, 7)));
+}
+
+#[test]
+fn word_index_concurrent_refresh_test() {
+    use lsp_types::notification::{DidChangeTextDocument, Notification};
+
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_test");
+    let folder_url = Url::from_file_path(&folder).unwrap();
+    let file = folder.join("main.k");
+    let uri = Url::from_file_path(&file).unwrap();
+    let text = std::fs::read_to_string(&file).unwrap();
+
+    let (mut state, _receiver) = build_test_state(Config::default(), serde_json::json!({}));
+    state.build_word_index_map(&[folder_url.clone()]);
+    handle_background_tasks(&mut state);
+    state
+        .on_notification(lsp_server::Notification::new(
+            DidChangeTextDocument::METHOD.to_string(),
+            lsp_types::DidChangeTextDocumentParams {
+                text_document: lsp_types::VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 1,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: format!("{}p2 = p.name\n", text),
+                }],
+            },
+        ))
+        .unwrap();
+
+    // Issue two find references concurrently on the stale index.
+    let handles: Vec<_> = (0..2)
+        .map(|_| {
+            let snapshot = state.snapshot();
+            let uri = uri.clone();
+            std::thread::spawn(move || {
+                let (sender, _) = crossbeam_channel::unbounded();
+                handle_reference(
+                    snapshot,
+                    lsp_types::ReferenceParams {
+                        text_document_position: lsp_types::TextDocumentPositionParams {
+                            text_document: lsp_types::TextDocumentIdentifier { uri },
+                            position: Position::new(14, 8),
+                        },
+                        work_done_progress_params: Default::default(),
+                        partial_result_params: Default::default(),
+                        context: lsp_types::ReferenceContext {
+                            include_declaration: true,
+                        },
+                    },
+                    sender,
+                )
+                .unwrap()
+                .unwrap()
+            })
+        })
+        .collect();
+    let results: Vec<Vec<Location>> = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    assert_eq!(results[0], results[1]);
+
+    // Only one build of the index runs for the folder.
+    let generations = state.word_index_generations.folders.lock();
+    let generation = &generations[&folder_url];
+    assert!(generation.is_current());
+    assert_eq!(generation.building, None);
+    assert_eq!(generation.builds, 1);
+}
+
+#[test]
+fn generate_example_command_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/generate_example_test/main.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let res = execute_command(
+        state.snapshot(),
+        KCL_GENERATE_EXAMPLE_COMMAND,
+        vec![serde_json::json!({"file": file, "schema": "Server"})],
+        None,
+        &state.task_sender,
+    )
+    .unwrap()
+    .unwrap();
+    let got: String = serde_json::from_value(res).unwrap();
+    // The optional attrs and the attrs with default values are omitted
+    assert_eq!(
+        got,
+        "Server {\n    id = 0\n    name = \"\"\n    labels = {}\n    backend = Backend {\n        host = \"\"\n    }\n}"
+    );
+
+    assert!(execute_command(
+        state.snapshot(),
+        KCL_GENERATE_EXAMPLE_COMMAND,
+        vec![serde_json::json!({"file": file, "schema": "Unknown"})],
+        None,
+        &state.task_sender,
+    )
+    .is_err());
+}
+
+#[test]
+fn memory_usage_command_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification};
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data");
+    let file = path.join("goto_def_test/goto_def.k");
+    let folder = path.join("find_refs_test");
+    let (mut state, _receiver) = build_test_state(
+        Config {
+            warmup_entries: vec![file.to_str().unwrap().to_string()],
+            ..Default::default()
+        },
+        serde_json::json!({}),
+    );
+    state.warm_up(&[]);
+    state.build_word_index_map(&[Url::from_file_path(&folder).unwrap()]);
+    handle_background_tasks(&mut state);
+    for opened in [folder.join("main.k"), path.join("diagnostics.k")] {
+        state
+            .on_notification(lsp_server::Notification::new(
+                DidOpenTextDocument::METHOD.to_string(),
+                lsp_types::DidOpenTextDocumentParams {
+                    text_document: lsp_types::TextDocumentItem {
+                        uri: Url::from_file_path(&opened).unwrap(),
+                        language_id: "KCL".to_string(),
+                        version: 0,
+                        text: std::fs::read_to_string(&opened).unwrap(),
+                    },
+                },
+            ))
+            .unwrap();
+    }
+
+    let res = execute_command(
+        state.snapshot(),
+        KCL_MEMORY_USAGE_COMMAND,
+        vec![],
+        None,
+        &state.task_sender,
+    )
+    .unwrap()
+    .unwrap();
+    let words = state.word_index_map.read()[&Url::from_file_path(&folder).unwrap()].len();
+    assert_eq!(res["dbEntries"], 1);
+    assert_eq!(res["openedFiles"], 2);
+    assert_eq!(res["wordIndexEntries"], words);
+    assert!(res["wordIndexLocations"].as_u64().unwrap() >= words as u64);
+}
+
+#[test]
+fn export_word_index_command_test() {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_test");
+    let folder_url = Url::from_file_path(&folder).unwrap();
+    let uri = Url::from_file_path(folder.join("main.k")).unwrap();
+    let (mut state, _receiver) = build_test_state(Config::default(), serde_json::json!({}));
+    state.build_word_index_map(&[folder_url.clone()]);
+    handle_background_tasks(&mut state);
+    let export = |arguments: serde_json::Value| {
+        execute_command(
+            state.snapshot(),
+            KCL_EXPORT_WORD_INDEX_COMMAND,
+            vec![arguments],
+            None,
+            &state.task_sender,
         )
-        .output()
+    };
+
+    // The occurrences of a known symbol are exported.
+    let res = export(serde_json::json!({ "folder": folder_url }))
+        .unwrap()
         .unwrap();
+    let words = state.word_index_map.read()[&folder_url].len();
+    assert_eq!(res["total"], words);
+    assert!(res["nextOffset"].is_null());
+    let locations: Vec<Location> = serde_json::from_value(res["words"]["Person"].clone()).unwrap();
+    assert!(locations.contains(&Location {
+        uri,
+        range: Range::new(Position::new(3, 7), Position::new(3, 13)),
+    }));
 
-    let (program, prog_scope, diags) = parse_param_and_compile(
-        Param {
-            file: path.to_string(),
+    // The index is exported page by page in the order of the words.
+    let first = export(serde_json::json!({ "folder": folder_url, "limit": 1 }))
+        .unwrap()
+        .unwrap();
+    assert_eq!(first["words"].as_object().unwrap().len(), 1);
+    assert_eq!(first["nextOffset"], 1);
+    let second = export(serde_json::json!({ "folder": folder_url, "offset": 1, "limit": 1 }))
+        .unwrap()
+        .unwrap();
+    assert!(
+        first["words"].as_object().unwrap().keys().next()
+            < second["words"].as_object().unwrap().keys().next()
+    );
+
+    // The folder which is not indexed fails.
+    let other = Url::from_file_path(folder.parent().unwrap()).unwrap();
+    assert!(export(serde_json::json!({ "folder": other })).is_err());
+}
+
+#[test]
+fn dump_ast_command_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/goto_ty_annotation_test/ty_annotation.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let dump = |args: serde_json::Value| -> String {
+        let res = execute_command(
+            state.snapshot(),
+            KCL_DUMP_AST_COMMAND,
+            vec![args],
+            None,
+            &state.task_sender,
+        )
+        .unwrap()
+        .unwrap();
+        serde_json::from_value(res).unwrap()
+    };
+
+    // The first statement is the schema `Sidecar`
+    let got = dump(serde_json::json!({"file": file, "path": "/body/0/node"}));
+    let node: serde_json::Value = serde_json::from_str(&got).unwrap();
+    assert_eq!(node["Schema"]["name"]["node"], "Sidecar");
+
+    // The output is bounded by the max size
+    let got = dump(serde_json::json!({"file": file, "max_size": 16}));
+    assert!(got.ends_with("bytes in total)"));
+    assert!(got.len() < 64);
+
+    assert!(execute_command(
+        state.snapshot(),
+        KCL_DUMP_AST_COMMAND,
+        vec![serde_json::json!({"file": file, "path": "/body/100"})],
+        None,
+        &state.task_sender,
+    )
+    .is_err());
+}
+
+/// The hover handler which runs longer than the request timeout.
+/// Releases the hover handled by `slow_hover_handler`, which blocks until it is released.
+static SLOW_HOVER_RELEASE: once_cell::sync::Lazy<(Sender<()>, Receiver<()>)> =
+    once_cell::sync::Lazy::new(|| crossbeam_channel::bounded(0));
+
+fn slow_hover_handler(
+    _snapshot: LanguageServerSnapshot,
+    _params: lsp_types::HoverParams,
+    _sender: Sender<Task>,
+) -> anyhow::Result<Option<lsp_types::Hover>> {
+    SLOW_HOVER_RELEASE.1.recv()?;
+    Ok(None)
+}
+
+#[test]
+fn request_timeout_test() {
+    let config = Config {
+        request_timeout_ms: Some(0),
+        ..Default::default()
+    };
+    let (mut state, receiver) = build_test_state(config, serde_json::json!({}));
+    let request = lsp_server::Request::new(
+        1.into(),
+        "textDocument/hover".to_string(),
+        lsp_types::HoverParams {
+            text_document_position_params: lsp_types::TextDocumentPositionParams {
+                text_document: lsp_types::TextDocumentIdentifier {
+                    uri: Url::parse("file:///main.k").unwrap(),
+                },
+                position: Position::new(0, 0),
+            },
+            work_done_progress_params: Default::default(),
+        },
+    );
+    state.register_request(&request, Instant::now());
+    RequestDispatcher::new(&mut state, request)
+        .on::<lsp_types::request::HoverRequest>(slow_hover_handler)
+        .unwrap()
+        .finish();
+
+    // The request fails once the deadline is reached, which is immediate with the zero
+    // timeout, while the handler is still blocked
+    state.expire_requests().unwrap();
+    let responses: Vec<lsp_server::Response> = receiver
+        .try_iter()
+        .filter_map(|msg| match msg {
+            lsp_server::Message::Response(response) => Some(response),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].id, 1.into());
+    assert_eq!(
+        responses[0].error.as_ref().unwrap().code,
+        lsp_server::ErrorCode::RequestFailed as i32
+    );
+    assert!(state.request_deadlines.is_empty());
+
+    // The late response of the abandoned task is dropped
+    SLOW_HOVER_RELEASE.0.send(()).unwrap();
+    let task = state.task_receiver.recv().unwrap();
+    state.handle_event(Event::Task(task)).unwrap();
+    assert!(!receiver
+        .try_iter()
+        .any(|msg| matches!(msg, lsp_server::Message::Response(_))));
+}
+
+#[test]
+fn watch_folder_new_file_test() {
+    let folder = env::temp_dir().join(format!("kcl_watch_folder_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    let folder = folder.canonicalize().unwrap();
+    std::fs::write(folder.join("main.k"), "a = 1\n").unwrap();
+    let mut watcher = FolderWatcher::new(
+        vec![folder.clone()],
+        &Config::default().kcl_extensions,
+        Duration::from_millis(Config::default().watcher_poll_interval_ms),
+    );
+
+    // The existing files are not reported, and the new file is reported as created.
+    assert!(watcher.poll().is_empty());
+    let new_file = folder.join("new.k");
+    std::fs::write(&new_file, "b = 1\n").unwrap();
+    let events = watcher.poll();
+    assert_eq!(
+        events,
+        vec![lsp_types::FileEvent {
+            uri: Url::from_file_path(&new_file).unwrap(),
+            typ: lsp_types::FileChangeType::CREATED,
+        }]
+    );
+
+    // The new file is loaded into the vfs and analyzable before it is opened.
+    let (mut state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    state
+        .handle_event(Event::Task(Task::ChangeWatchedFiles(events)))
+        .unwrap();
+    let path = ra_ap_vfs::AbsPathBuf::try_from(new_file.clone()).unwrap();
+    assert!(state.vfs.read().file_id(&path.into()).is_some());
+    let file = new_file.to_str().unwrap().to_string();
+    let (_, prog_scope, diags) =
+        parse_param_and_compile(Param { file }, Some(state.vfs.clone())).unwrap();
+    assert!(diags.is_empty());
+    assert!(prog_scope.scope_map["__main__"]
+        .borrow()
+        .elems
+        .contains_key("b"));
+
+    std::fs::remove_file(&new_file).unwrap();
+    let events = watcher.poll();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].typ, lsp_types::FileChangeType::DELETED);
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn watcher_poll_backoff_test() {
+    let folder = env::temp_dir().join(format!("kcl_watch_backoff_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    let folder = folder.canonicalize().unwrap();
+    std::fs::write(folder.join("main.k"), "a = 1\n").unwrap();
+    let interval = Duration::from_millis(10);
+    let mut watcher = FolderWatcher::new(
+        vec![folder.clone()],
+        &Config::default().kcl_extensions,
+        interval,
+    );
+    assert_eq!(watcher.interval(), interval);
+
+    // The interval is doubled by the polls without the events up to the max backoff.
+    assert!(watcher.poll().is_empty());
+    assert_eq!(watcher.interval(), interval * 2);
+    for _ in 0..MAX_POLL_BACKOFF {
+        assert!(watcher.poll().is_empty());
+    }
+    assert_eq!(watcher.interval(), interval * MAX_POLL_BACKOFF);
+
+    // The interval is reset once any file is changed.
+    std::fs::write(folder.join("new.k"), "b = 1\n").unwrap();
+    assert_eq!(watcher.poll().len(), 1);
+    assert_eq!(watcher.interval(), interval);
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn watched_file_change_of_opened_document_test() {
+    let folder = env::temp_dir().join(format!("kcl_watch_opened_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    let file = folder.canonicalize().unwrap().join("main.k");
+    std::fs::write(&file, "a = 1\n").unwrap();
+    let uri = Url::from_file_path(&file).unwrap();
+
+    let (mut state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    state
+        .on_notification(lsp_server::Notification::new(
+            "textDocument/didOpen".to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "KCL".to_string(),
+                    version: 1,
+                    text: "a = 1\nb = 2\n".to_string(),
+                },
+            },
+        ))
+        .unwrap();
+
+    // The file of the opened document is changed on the disk, e.g., by a git checkout
+    std::fs::write(&file, "c = 3\n").unwrap();
+    state
+        .handle_event(Event::Task(Task::ChangeWatchedFiles(vec![
+            lsp_types::FileEvent {
+                uri: uri.clone(),
+                typ: lsp_types::FileChangeType::CHANGED,
+            },
+        ])))
+        .unwrap();
+
+    // The incremental change is still applied to the contents of the opened document
+    state
+        .on_notification(lsp_server::Notification::new(
+            "textDocument/didChange".to_string(),
+            lsp_types::DidChangeTextDocumentParams {
+                text_document: lsp_types::VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 2,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: Some(Range::new(Position::new(1, 4), Position::new(1, 5))),
+                    range_length: None,
+                    text: "4".to_string(),
+                }],
+            },
+        ))
+        .unwrap();
+    let path = file.to_str().unwrap();
+    assert_eq!(
+        load_file_code_from_vfs(path, state.vfs.clone()).unwrap(),
+        "a = 1\nb = 4\n"
+    );
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn skip_invalid_watched_files_test() {
+    let folder = env::temp_dir().join(format!("kcl_watch_invalid_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    let folder = folder.canonicalize().unwrap();
+    let file = folder.join("main.k");
+    std::fs::write(&file, "a = 1\n").unwrap();
+    // The file not encoded in UTF-8 is skipped by the word index.
+    std::fs::write(folder.join("invalid.k"), [0xff, 0xfe, 0x00]).unwrap();
+    let index = build_word_index(
+        folder.to_str().unwrap().to_string(),
+        &Config::default().kcl_extensions,
+    )
+    .unwrap();
+    assert!(index.contains_key("a"));
+
+    // The change of the invalid uri is skipped, and the later changes are still applied.
+    let (mut state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    state
+        .handle_event(Event::Task(Task::ChangeWatchedFiles(vec![
+            lsp_types::FileEvent {
+                uri: Url::parse("untitled:Untitled-1").unwrap(),
+                typ: lsp_types::FileChangeType::CREATED,
+            },
+            lsp_types::FileEvent {
+                uri: Url::from_file_path(&file).unwrap(),
+                typ: lsp_types::FileChangeType::CREATED,
+            },
+        ])))
+        .unwrap();
+    let path: ra_ap_vfs::VfsPath = ra_ap_vfs::AbsPathBuf::try_from(file).unwrap().into();
+    assert!(state.vfs.read().file_id(&path).is_some());
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn goto_def_through_symlinks_test() {
+    let folder = env::temp_dir().join(format!("kcl_symlink_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(folder.join("app")).unwrap();
+    std::fs::create_dir_all(folder.join("shared/pkg")).unwrap();
+    let folder = folder.canonicalize().unwrap();
+    std::fs::write(
+        folder.join("app/main.k"),
+        "import pkg\n\ns = pkg.Server {}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        folder.join("shared/pkg/server.k"),
+        "schema Server:\n    name?: str\n",
+    )
+    .unwrap();
+    // The package directory is symlinked into the app, and the app is opened via a link.
+    std::os::unix::fs::symlink(folder.join("shared/pkg"), folder.join("app/pkg")).unwrap();
+    std::os::unix::fs::symlink(folder.join("app"), folder.join("link")).unwrap();
+
+    let uri = Url::from_file_path(folder.join("link/main.k")).unwrap();
+    assert_eq!(
+        file_path_from_url(&uri).unwrap(),
+        folder.join("app/main.k").to_str().unwrap()
+    );
+    let params = lsp_types::GotoDefinitionParams {
+        text_document_position_params: lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri },
+            position: Position::new(2, 9),
         },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let (sender, _) = crossbeam_channel::unbounded();
+    let res = handle_goto_definition(state.snapshot(), params, sender).unwrap();
+    // The schema is located by the real path of the symlinked package.
+    match res.unwrap() {
+        lsp_types::GotoDefinitionResponse::Scalar(loc) => {
+            assert_eq!(
+                loc.uri,
+                Url::from_file_path(folder.join("shared/pkg/server.k")).unwrap()
+            );
+            assert_eq!(loc.range.start.line, 0);
+        }
+        _ => unreachable!("test error"),
+    }
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn kcl_files_symlink_loop_test() {
+    let folder = env::temp_dir().join(format!("kcl_symlink_loop_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(folder.join("pkg")).unwrap();
+    std::fs::create_dir_all(folder.join(".git")).unwrap();
+    std::fs::create_dir_all(folder.join("node_modules")).unwrap();
+    std::fs::write(folder.join("main.k"), "a = 1\n").unwrap();
+    std::fs::write(folder.join("pkg/base.k"), "b = 1\n").unwrap();
+    std::fs::write(folder.join(".git/hidden.k"), "c = 1\n").unwrap();
+    std::fs::write(folder.join("node_modules/module.k"), "d = 1\n").unwrap();
+    // The link to the parent folder makes a cycle if the symlinks are followed.
+    std::os::unix::fs::symlink(&folder, folder.join("pkg/loop")).unwrap();
+
+    let files = get_kcl_files_with_extensions(&folder, &Config::default().kcl_extensions).unwrap();
+    assert_eq!(
+        files,
+        vec![
+            folder.join("main.k").to_str().unwrap().to_string(),
+            folder.join("pkg/base.k").to_str().unwrap().to_string(),
+        ]
+    );
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn file_path_from_url_cache_test() {
+    let folder = env::temp_dir().join(format!("kcl_file_path_cache_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    let folder = folder.canonicalize().unwrap();
+    std::fs::write(folder.join("a.k"), "a = 1\n").unwrap();
+    std::fs::write(folder.join("b.k"), "b = 1\n").unwrap();
+    std::os::unix::fs::symlink(folder.join("a.k"), folder.join("main.k")).unwrap();
+    let uri = Url::from_file_path(folder.join("main.k")).unwrap();
+    let a = folder.join("a.k").to_str().unwrap().to_string();
+    let b = folder.join("b.k").to_str().unwrap().to_string();
+    assert_eq!(file_path_from_url(&uri).unwrap(), a);
+
+    // The canonicalized path is cached until the file is created or deleted again.
+    std::fs::remove_file(folder.join("main.k")).unwrap();
+    std::os::unix::fs::symlink(folder.join("b.k"), folder.join("main.k")).unwrap();
+    assert_eq!(file_path_from_url(&uri).unwrap(), a);
+    forget_file_path(&uri);
+    assert_eq!(file_path_from_url(&uri).unwrap(), b);
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn verify_word_index_test() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_test");
+    let folder = Url::from_file_path(&path).unwrap();
+    let config = Config {
+        verify_word_index: true,
+        single_threaded: true,
+        ..Default::default()
+    };
+    let (mut state, _) = build_test_state(config, serde_json::json!({}));
+    state.build_word_index_map(&[folder.clone()]);
+    handle_background_tasks(&mut state);
+    let warnings = |state: &LanguageServerState| -> Vec<String> {
+        state
+            .task_receiver
+            .try_iter()
+            .filter_map(|task| match task {
+                Task::Notify(not) => {
+                    serde_json::from_value::<lsp_types::LogMessageParams>(not.params).ok()
+                }
+                _ => None,
+            })
+            .filter(|params| params.typ == lsp_types::MessageType::WARNING)
+            .map(|params| params.message)
+            .collect()
+    };
+
+    // The index built from the files is consistent.
+    state.handle_event(Event::VerifyWordIndex).unwrap();
+    assert!(warnings(&state).is_empty());
+
+    // The drifted index is reported with the diverged words.
+    state
+        .word_index_map
+        .write()
+        .get_mut(&folder)
+        .unwrap()
+        .insert(
+            "drifted".to_string(),
+            vec![Location {
+                uri: Url::from_file_path(path.join("main.k")).unwrap(),
+                range: Range::new(Position::new(0, 0), Position::new(0, 7)),
+            }],
+        );
+    state.handle_event(Event::VerifyWordIndex).unwrap();
+    assert_eq!(
+        warnings(&state),
+        vec![format!(
+            "the word index of {} diverges from the files at 1 word(s): drifted",
+            folder
+        )]
+    );
+}
+
+#[test]
+fn idle_flush_test() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/document_symbol.k");
+    let file = path.to_str().unwrap().to_string();
+    let config = Config {
+        idle_flush_ms: Some(0),
+        single_threaded: true,
+        ..Default::default()
+    };
+    let (mut state, _) = build_test_state(config, serde_json::json!({}));
+    state.analysis.set_db(
+        file.clone(),
+        parse_param(Param { file: file.clone() }, None).unwrap(),
+    );
+
+    // The db invalidated by the changed watched file is pending to be recompiled.
+    let events = vec![lsp_types::FileEvent {
+        uri: Url::from_file_path(&path).unwrap(),
+        typ: lsp_types::FileChangeType::CHANGED,
+    }];
+    state
+        .handle_event(Event::Task(Task::ChangeWatchedFiles(events)))
+        .unwrap();
+    assert!(!state.analysis.db.read().contains_key(&file));
+    assert!(state.pending_recompiles.contains(&file));
+
+    // The db is refreshed once the event loop is idle.
+    state.handle_event(Event::Idle).unwrap();
+    assert!(state.pending_recompiles.is_empty());
+    assert!(state.analysis.db.read().contains_key(&file));
+}
+
+#[test]
+fn effective_config_command_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/effective_config_test/main.k")
+        .canonicalize()
+        .unwrap()
+        .display()
+        .to_string();
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let effective_config = |variable: &str| {
+        execute_command(
+            state.snapshot(),
+            KCL_EFFECTIVE_CONFIG_COMMAND,
+            vec![serde_json::json!({"file": file, "variable": variable})],
+            None,
+            &state.task_sender,
+        )
+    };
+
+    // The layers of the config are merged, and the other variables are not returned.
+    let res = effective_config("server").unwrap().unwrap();
+    assert_eq!(
+        res,
+        serde_json::json!({
+            "name": "app",
+            "replicas": 3,
+            "labels": {"env": "prod", "tier": "web"},
+        })
+    );
+
+    // The variable not in the output is an error.
+    assert!(effective_config("missing").is_err());
+}
+
+#[test]
+fn import_graph_command_test() {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/import_graph_test")
+        .canonicalize()
+        .unwrap();
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let res = execute_command(
+        state.snapshot(),
+        KCL_IMPORT_GRAPH_COMMAND,
+        vec![serde_json::json!({"folders": [Url::from_file_path(&folder).unwrap()]})],
         None,
+        &state.task_sender,
     )
+    .unwrap()
     .unwrap();
+    let got: ImportGraph = serde_json::from_value(res).unwrap();
 
-    assert_eq!(diags.len(), 0);
+    let edge = |file: &str, from: &str, to: &str| ImportEdge {
+        file: Url::from_file_path(folder.join(file)).unwrap(),
+        from: from.to_string(),
+        to: to.to_string(),
+    };
+    let mut expected = vec![
+        edge("a/a.k", "a", "b"),
+        edge("b/b.k", "b", "a"),
+        edge("main.k", "__main__", "a"),
+        edge("main.k", "__main__", "c"),
+    ];
+    expected.sort();
+    assert_eq!(got.edges, expected);
+    assert_eq!(got.cycles, vec![vec!["a".to_string(), "b".to_string()]]);
+}
 
-    // test goto import file: import .pkg.schema_def
-    let pos = KCLPos {
-        filename: path.to_string(),
-        line: 1,
-        column: Some(15),
+#[test]
+fn workspace_diagnostics_command_test() {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/workspace_diagnostics_test")
+        .canonicalize()
+        .unwrap();
+    let (state, _) = build_test_state(Config::default(), serde_json::json!({}));
+    let (task_sender, task_receiver) = crossbeam_channel::unbounded();
+    let res = execute_command(
+        state.snapshot(),
+        KCL_WORKSPACE_DIAGNOSTICS_COMMAND,
+        vec![serde_json::json!({"folders": [Url::from_file_path(&folder).unwrap()]})],
+        Some(lsp_types::NumberOrString::String("workspace".to_string())),
+        &task_sender,
+    )
+    .unwrap()
+    .unwrap();
+    let got: HashMap<Url, Vec<lsp_types::Diagnostic>> = serde_json::from_value(res).unwrap();
+
+    // The errors of the entries, the imported package and the standalone file are all
+    // collected, and the file without errors is omitted.
+    let mut files: Vec<String> = got
+        .keys()
+        .map(|uri| file_path_from_url(uri).unwrap())
+        .collect();
+    files.sort();
+    let expected: Vec<String> = ["main.k", "pkg/pkg.k", "standalone/other.k"]
+        .iter()
+        .map(|file| folder.join(file).to_str().unwrap().to_string())
+        .collect();
+    assert_eq!(files, expected);
+    for diagnostics in got.values() {
+        assert!(diagnostics
+            .iter()
+            .any(|diag| diag.severity == Some(lsp_types::DiagnosticSeverity::ERROR)));
+    }
+
+    // The progress begins and ends with the work done token.
+    let progress: Vec<lsp_types::WorkDoneProgress> = task_receiver
+        .try_iter()
+        .filter_map(|task| match task {
+            Task::Notify(not) => {
+                match serde_json::from_value::<lsp_types::ProgressParams>(not.params)
+                    .ok()?
+                    .value
+                {
+                    lsp_types::ProgressParamsValue::WorkDone(progress) => Some(progress),
+                }
+            }
+            _ => None,
+        })
+        .collect();
+    assert!(matches!(
+        progress.first(),
+        Some(lsp_types::WorkDoneProgress::Begin(_))
+    ));
+    assert!(matches!(
+        progress.last(),
+        Some(lsp_types::WorkDoneProgress::End(_))
+    ));
+}
+
+#[test]
+fn hover_subtypes_test() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/hover_test/subtypes.k")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let hover = |config: Config, position: Position| -> Vec<MarkedString> {
+        let (state, _) = build_test_state(config, serde_json::json!({}));
+        state.analysis.set_db(
+            file.clone(),
+            parse_param(Param { file: file.clone() }, None).unwrap(),
+        );
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let res = handle_hover(
+            state.snapshot(),
+            lsp_types::HoverParams {
+                text_document_position_params: lsp_types::TextDocumentPositionParams {
+                    text_document: lsp_types::TextDocumentIdentifier {
+                        uri: Url::from_file_path(&file).unwrap(),
+                    },
+                    position,
+                },
+                work_done_progress_params: Default::default(),
+            },
+            sender,
+        )
+        .unwrap()
+        .unwrap();
+        match res.contents {
+            lsp_types::HoverContents::Scalar(doc) => vec![doc],
+            lsp_types::HoverContents::Array(docs) => docs,
+            _ => unreachable!("test error"),
+        }
     };
-    let res = goto_definition(&program, &pos, &prog_scope);
-    assert!(res.is_some());
+    let enabled = Config {
+        hover_show_subtypes: true,
+        ..Default::default()
+    };
+    let is_subtypes =
+        |doc: &MarkedString| matches!(doc, MarkedString::String(s) if s.starts_with("Subtypes"));
+
+    // The direct subtypes of `Base` exclude `Canary` inheriting from `Prod`.
+    let got = hover(enabled.clone(), Position::new(0, 8));
+    assert_eq!(
+        got.last(),
+        Some(&MarkedString::String("Subtypes: Dev, Prod".to_string()))
+    );
+    let got = hover(enabled.clone(), Position::new(6, 8));
+    assert_eq!(
+        got.last(),
+        Some(&MarkedString::String("Subtypes: Canary".to_string()))
+    );
+
+    // No subtypes of `Canary`, and no subtypes by default.
+    assert!(!hover(enabled, Position::new(9, 8)).iter().any(is_subtypes));
+    assert!(!hover(Config::default(), Position::new(0, 8))
+        .iter()
+        .any(is_subtypes));
+
+    // The list is bounded.
+    let subtypes: Vec<String> = (0..12).map(|i| format!("Sub{:02}", i)).collect();
+    let bounded = with_subtypes(
+        lsp_types::Hover {
+            contents: lsp_types::HoverContents::Scalar(MarkedString::String("Base".to_string())),
+            range: None,
+        },
+        &subtypes,
+    );
+    match bounded.contents {
+        lsp_types::HoverContents::Array(docs) => assert_eq!(
+            docs[1],
+            MarkedString::String(format!("Subtypes: {}, +2 more", subtypes[..10].join(", ")))
+        ),
+        _ => unreachable!("test error"),
+    }
+}
+
+#[test]
+fn hover_reference_count_test() {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_data/find_refs_test");
+    let uri = Url::from_file_path(folder.join("main.k")).unwrap();
+    let hover = |config: Config, position: Position| -> Vec<MarkedString> {
+        let (mut state, _) = build_test_state(config, serde_json::json!({}));
+        state.build_word_index_map(&[Url::from_file_path(&folder).unwrap()]);
+        handle_background_tasks(&mut state);
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let res = handle_hover(
+            state.snapshot(),
+            lsp_types::HoverParams {
+                text_document_position_params: lsp_types::TextDocumentPositionParams {
+                    text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+                    position,
+                },
+                work_done_progress_params: Default::default(),
+            },
+            sender,
+        )
+        .unwrap()
+        .unwrap();
+        match res.contents {
+            lsp_types::HoverContents::Scalar(doc) => vec![doc],
+            lsp_types::HoverContents::Array(docs) => docs,
+            _ => unreachable!("test error"),
+        }
+    };
+    let enabled = Config {
+        hover_reference_count: true,
+        ..Default::default()
+    };
+
+    // The schema `Person` is referenced once besides its declaration.
+    for position in [Position::new(3, 8), Position::new(10, 6)] {
+        let got = hover(enabled.clone(), position);
+        assert_eq!(
+            got.last(),
+            Some(&MarkedString::String("1 reference".to_string()))
+        );
+    }
+
+    // The variable `p` is not counted.
+    let got = hover(enabled, Position::new(14, 5));
+    assert!(!got
+        .iter()
+        .any(|doc| matches!(doc, MarkedString::String(s) if s.ends_with("reference"))));
+
+    // The count is omitted by default.
+    let got = hover(Config::default(), Position::new(3, 8));
+    assert!(!got
+        .iter()
+        .any(|doc| matches!(doc, MarkedString::String(s) if s.ends_with("reference"))));
+}
+
+#[test]
+fn rename_document_changes_test() {
+    use lsp_types::notification::{DidOpenTextDocument, Notification};
+
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test_data/find_refs_unsaved_test")
+        .canonicalize()
+        .unwrap();
+    let main = Url::from_file_path(folder.join("main.k")).unwrap();
+    let base = Url::from_file_path(folder.join("pkg/base.k")).unwrap();
+    let rename = |client_capabilities: serde_json::Value| -> lsp_types::WorkspaceEdit {
+        let (mut state, _) = build_test_state(Config::default(), client_capabilities);
+        state.build_word_index_map(&[Url::from_file_path(&folder).unwrap()]);
+        handle_background_tasks(&mut state);
+        state
+            .on_notification(lsp_server::Notification::new(
+                DidOpenTextDocument::METHOD.to_string(),
+                lsp_types::DidOpenTextDocumentParams {
+                    text_document: lsp_types::TextDocumentItem {
+                        uri: main.clone(),
+                        language_id: "KCL".to_string(),
+                        version: 7,
+                        text: std::fs::read_to_string(folder.join("main.k")).unwrap(),
+                    },
+                },
+            ))
+            .unwrap();
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        handle_rename(
+            state.snapshot(),
+            lsp_types::RenameParams {
+                text_document_position: lsp_types::TextDocumentPositionParams {
+                    text_document: lsp_types::TextDocumentIdentifier { uri: main.clone() },
+                    position: Position::new(2, 8),
+                },
+                new_name: "c".to_string(),
+                work_done_progress_params: Default::default(),
+            },
+            sender,
+        )
+        .unwrap()
+        .unwrap()
+    };
+
+    // The opened document is versioned, and the file on the disk has a null version.
+    let got = rename(serde_json::json!({
+        "workspace": {"workspaceEdit": {"documentChanges": true}}
+    }));
+    assert!(got.changes.is_none());
+    match got.document_changes {
+        Some(lsp_types::DocumentChanges::Edits(edits)) => {
+            let documents: Vec<(Url, Option<i32>)> = edits
+                .iter()
+                .map(|edit| (edit.text_document.uri.clone(), edit.text_document.version))
+                .collect();
+            assert_eq!(
+                documents,
+                vec![(main.clone(), Some(7)), (base.clone(), None)]
+            );
+            for edit in &edits {
+                assert_eq!(edit.edits.len(), 1);
+            }
+        }
+        _ => unreachable!("test error"),
+    }
+
+    // The changes map is returned to the client without the document changes.
+    let got = rename(serde_json::json!({}));
+    assert!(got.document_changes.is_none());
+    let changes = got.changes.unwrap();
+    assert!(changes.contains_key(&main) && changes.contains_key(&base));
 }
@@ -1,12 +1,16 @@
 use kclvm_error::Diagnostic as KCLDiagnostic;
+use kclvm_error::DiagnosticId;
 use kclvm_error::Level;
 use kclvm_error::Message;
 use kclvm_error::Position as KCLPos;
 use lsp_types::*;
 use ra_ap_vfs::FileId;
+use serde::{Deserialize, Serialize};
 
+use crate::sourcemap::SourceMap;
 use crate::state::LanguageServerSnapshot;
 use std::{
+    collections::HashMap,
     path::{Component, Path, Prefix},
     str::FromStr,
 };
@@ -20,8 +24,104 @@ pub fn lsp_pos(pos: &KCLPos) -> Position {
     }
 }
 
+/// The structured context of the KCL diagnostic, which is serialized into the `data` of
+/// the LSP diagnostic, so that the quick fixes can be computed without parsing the message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DiagnosticData {
+    /// The name of the error or warning kind, e.g., `TypeError`.
+    pub code: Option<String>,
+    /// The note of the message, e.g., a suggestion to fix the error.
+    pub note: Option<String>,
+    /// The expected type of the type error, e.g., `str` in `expected str, got int(1)`.
+    pub expected_type: Option<String>,
+    /// The actual type of the type error, e.g., `int(1)` in `expected str, got int(1)`.
+    pub got_type: Option<String>,
+    /// The locations of the other messages of the diagnostic, e.g., the declaration of the
+    /// variable whose type is expected.
+    pub related_locations: Vec<Location>,
+}
+
+impl DiagnosticData {
+    fn new(diag: &KCLDiagnostic, msg: &Message) -> Self {
+        let code = kcl_diag_code(diag).map(|code| match code {
+            NumberOrString::String(code) => code,
+            NumberOrString::Number(code) => code.to_string(),
+        });
+        let (expected_type, got_type) = match msg
+            .message
+            .strip_prefix("expected ")
+            .and_then(|types| types.split_once(", got "))
+        {
+            Some((expected, got)) if code.as_deref() == Some("TypeError") => {
+                (Some(expected.to_string()), Some(got.to_string()))
+            }
+            _ => (None, None),
+        };
+        let related_locations = related_information(diag, msg)
+            .into_iter()
+            .map(|info| info.location)
+            .collect();
+        DiagnosticData {
+            code,
+            note: msg.note.clone(),
+            expected_type,
+            got_type,
+            related_locations,
+        }
+    }
+}
+
+/// Returns the other messages of the diagnostic as the related information of the message,
+/// e.g., the declaration of the variable modified in an `ImmutableError`.
+fn related_information(diag: &KCLDiagnostic, msg: &Message) -> Vec<DiagnosticRelatedInformation> {
+    diag.messages
+        .iter()
+        .filter(|other| *other != msg)
+        .filter_map(|other| {
+            Some(DiagnosticRelatedInformation {
+                location: Location {
+                    uri: Url::from_file_path(&other.pos.filename).ok()?,
+                    range: related_range(other),
+                },
+                message: other.message.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the range of the related message. The message of a declaration, e.g., `The
+/// variable 'a' is declared here`, is positioned at the start of the declared name, and
+/// the range covers the name token, so that the navigation lands on the declaration.
+fn related_range(msg: &Message) -> Range {
+    let start = lsp_pos(&msg.pos);
+    let end = match declared_name(&msg.message) {
+        Some(name) => Position {
+            line: start.line,
+            character: start.character + name.encode_utf16().count() as u32,
+        },
+        None => start,
+    };
+    Range::new(start, end)
+}
+
+/// Returns the name in the message of a declaration, e.g., `a` in `The variable 'a' is
+/// declared here firstly`.
+fn declared_name(message: &str) -> Option<&str> {
+    let (name, rest) = message
+        .strip_prefix("The variable '")
+        .and_then(|message| message.split_once('\''))?;
+    rest.starts_with(" is declared here").then_some(name)
+}
+
 /// Convert KCL Message to LSP Diagnostic
-fn kcl_msg_to_lsp_diags(msg: &Message, severity: DiagnosticSeverity) -> Diagnostic {
+fn kcl_msg_to_lsp_diags(
+    msg: &Message,
+    severity: DiagnosticSeverity,
+    code: Option<NumberOrString>,
+    related_information: Vec<DiagnosticRelatedInformation>,
+    data: DiagnosticData,
+) -> Diagnostic {
     let kcl_pos = msg.pos.clone();
     let start_position = lsp_pos(&kcl_pos);
     let end_position = lsp_pos(&kcl_pos);
@@ -29,13 +129,17 @@ fn kcl_msg_to_lsp_diags(msg: &Message, severity: DiagnosticSeverity) -> Diagnost
     Diagnostic {
         range: Range::new(start_position, end_position),
         severity: Some(severity),
-        code: None,
+        code,
         code_description: None,
         source: None,
         message: msg.message.clone(),
-        related_information: None,
+        related_information: if related_information.is_empty() {
+            None
+        } else {
+            Some(related_information)
+        },
         tags: None,
-        data: None,
+        data: serde_json::to_value(data).ok(),
     }
 }
 
@@ -49,14 +153,54 @@ fn kcl_err_level_to_severity(level: Level) -> DiagnosticSeverity {
 
 /// Convert KCL Diagnostic to LSP Diagnostics.
 /// Because the diagnostic of KCL contains multiple messages, and each messages corresponds to a diagnostic of LSP, the return value is a vec
-pub fn kcl_diag_to_lsp_diags(diag: &KCLDiagnostic, file_name: &str) -> Vec<Diagnostic> {
+/// The severity of the diagnostic is overridden by `severity_overrides` keyed by the code,
+/// e.g., `UnusedImportWarning`. If the source map of the generated file is given, the
+/// locations in the source template are related to the diagnostics.
+pub fn kcl_diag_to_lsp_diags(
+    diag: &KCLDiagnostic,
+    file_name: &str,
+    severity_overrides: &HashMap<String, DiagnosticSeverity>,
+    sourcemap: Option<&SourceMap>,
+) -> Vec<Diagnostic> {
+    let code = kcl_diag_code(diag);
+    let severity = match &code {
+        Some(NumberOrString::String(code)) => severity_overrides.get(code).copied(),
+        _ => None,
+    }
+    .unwrap_or_else(|| kcl_err_level_to_severity(diag.level));
     diag.messages
         .iter()
         .filter(|msg| msg.pos.filename == file_name)
-        .map(|msg| kcl_msg_to_lsp_diags(msg, kcl_err_level_to_severity(diag.level)))
+        .map(|msg| {
+            let mut related = related_information(diag, msg);
+            related.extend(sourcemap.and_then(|sourcemap| sourcemap.map(&msg.pos)).map(
+                |location| DiagnosticRelatedInformation {
+                    location,
+                    message: "Generated from here".to_string(),
+                },
+            ));
+            kcl_msg_to_lsp_diags(
+                msg,
+                severity,
+                code.clone(),
+                related,
+                DiagnosticData::new(diag, msg),
+            )
+        })
         .collect()
 }
 
+/// Returns the code of the KCL diagnostic, which is the name of the error or warning
+/// kind, e.g., `ImmutableError`. The code can be explained by the `kcl.explain` command.
+fn kcl_diag_code(diag: &KCLDiagnostic) -> Option<NumberOrString> {
+    diag.code.as_ref().map(|code| {
+        NumberOrString::String(match code {
+            DiagnosticId::Error(kind) => kind.name(),
+            DiagnosticId::Warning(kind) => kind.name(),
+        })
+    })
+}
+
 /// Returns the `Url` associated with the specified `FileId`.
 pub(crate) fn url(snapshot: &LanguageServerSnapshot, file_id: FileId) -> anyhow::Result<Url> {
     let vfs = snapshot.vfs.read();
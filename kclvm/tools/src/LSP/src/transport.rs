@@ -0,0 +1,152 @@
+//! The transports between the language server and the client, selected by the
+//! command line arguments of the `kcl-language-server` executable:
+//! + `--stdio`: communicate over the stdin and stdout, which is the default transport.
+//! + `--socket PORT`: connect to the tcp socket on the port the client listens to.
+//! + `--pipe NAME`: connect to the named pipe (the unix domain socket on unix) of the client.
+
+use std::io::{self, BufReader, Read, Write};
+use std::thread::{self, JoinHandle};
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+use lsp_server::{Connection, IoThreads, Message};
+
+/// The transport selected by the command line arguments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Transport {
+    #[default]
+    Stdio,
+    Socket(u16),
+    Pipe(String),
+}
+
+impl Transport {
+    /// Parses the transport from the command line arguments without the executable
+    /// name. Both `--socket PORT` and `--socket=PORT` forms are supported, and the
+    /// last transport argument wins.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> anyhow::Result<Self> {
+        let mut transport = Transport::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut next_value = |flag: &str| {
+                value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow::anyhow!("Missing the value of the argument {}", flag))
+            };
+            transport = match flag.as_str() {
+                "--stdio" => Transport::Stdio,
+                "--socket" => {
+                    let port = next_value(&flag)?;
+                    Transport::Socket(
+                        port.parse()
+                            .map_err(|_| anyhow::anyhow!("Invalid socket port {}", port))?,
+                    )
+                }
+                "--pipe" => Transport::Pipe(next_value(&flag)?),
+                _ => return Err(anyhow::anyhow!("Unknown argument {}", flag)),
+            };
+        }
+        Ok(transport)
+    }
+
+    /// Creates the connection to the client over the transport. The returned io threads
+    /// should be joined after the connection is closed.
+    pub(crate) fn connect(&self) -> anyhow::Result<(Connection, TransportThreads)> {
+        match self {
+            Transport::Stdio => {
+                let (connection, io_threads) = Connection::stdio();
+                Ok((connection, TransportThreads::Lsp(io_threads)))
+            }
+            Transport::Socket(port) => {
+                let (connection, io_threads) = Connection::connect(("127.0.0.1", *port))?;
+                Ok((connection, TransportThreads::Lsp(io_threads)))
+            }
+            Transport::Pipe(name) => {
+                let (reader, writer) = open_pipe(name)?;
+                Ok(stream_connection(reader, writer))
+            }
+        }
+    }
+}
+
+/// The io threads of the transport.
+pub(crate) enum TransportThreads {
+    Lsp(IoThreads),
+    Stream {
+        reader: JoinHandle<io::Result<()>>,
+        writer: JoinHandle<io::Result<()>>,
+    },
+}
+
+impl TransportThreads {
+    pub(crate) fn join(self) -> anyhow::Result<()> {
+        match self {
+            TransportThreads::Lsp(io_threads) => io_threads.join()?,
+            TransportThreads::Stream { reader, writer } => {
+                for thread in [reader, writer] {
+                    thread
+                        .join()
+                        .map_err(|_| anyhow::anyhow!("The transport thread panicked"))??;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn open_pipe(
+    name: &str,
+) -> io::Result<(
+    std::os::unix::net::UnixStream,
+    std::os::unix::net::UnixStream,
+)> {
+    let stream = std::os::unix::net::UnixStream::connect(name)?;
+    Ok((stream.try_clone()?, stream))
+}
+
+#[cfg(windows)]
+fn open_pipe(name: &str) -> io::Result<(std::fs::File, std::fs::File)> {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(name)?;
+    Ok((file.try_clone()?, file))
+}
+
+/// Creates the connection over the reader and writer of a stream, which is similar to
+/// the socket connection of `lsp_server`.
+fn stream_connection<R, W>(reader: R, mut writer: W) -> (Connection, TransportThreads)
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    let (reader_sender, reader_receiver): (Sender<Message>, Receiver<Message>) = bounded(0);
+    let reader = thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        while let Some(msg) = Message::read(&mut reader)? {
+            let is_exit = matches!(&msg, Message::Notification(n) if n.method == "exit");
+            if reader_sender.send(msg).is_err() || is_exit {
+                break;
+            }
+        }
+        Ok(())
+    });
+    let (writer_sender, writer_receiver): (Sender<Message>, Receiver<Message>) = bounded(0);
+    let writer = thread::spawn(move || {
+        writer_receiver
+            .into_iter()
+            .try_for_each(|msg| msg.write(&mut writer))
+    });
+    (
+        Connection {
+            sender: writer_sender,
+            receiver: reader_receiver,
+        },
+        TransportThreads::Stream { reader, writer },
+    )
+}
@@ -1,5 +1,7 @@
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::{fs, sync::Arc};
 
@@ -8,20 +10,23 @@ use kclvm_ast::ast::{ConfigEntry, Expr, Identifier, Node, NodeRef, PosTuple, Pro
 use kclvm_ast::pos::ContainsPos;
 use kclvm_ast::MAIN_PKG;
 use kclvm_compiler::pkgpath_without_prefix;
-use kclvm_config::modfile::KCL_FILE_EXTENSION;
+use kclvm_config::modfile::{get_pkg_root, try_load_mod_file, KCL_FILE_EXTENSION, KCL_MOD_FILE};
 use kclvm_driver::kpm_metadata::fetch_metadata;
 use kclvm_driver::{get_kcl_files, lookup_compile_unit};
-use kclvm_error::Diagnostic;
 use kclvm_error::Position as KCLPos;
+use kclvm_error::{Diagnostic, DiagnosticId, ErrorKind, Level};
 use kclvm_parser::{load_program, ParseSession};
 use kclvm_sema::resolver::scope::Scope;
 use kclvm_sema::resolver::{resolve_program, scope::ProgramScope};
 use kclvm_utils::pkgpath::rm_external_pkg_name;
-use lsp_types::Url;
+use lsp_types::{Location, Position, Range, Url};
 use parking_lot::{RwLock, RwLockReadGuard};
 use ra_ap_vfs::{FileId, Vfs};
 use serde::{de::DeserializeOwned, Serialize};
+use walkdir::WalkDir;
 
+use crate::db::AnalysisDatabase;
+use crate::find_ref::{line_to_words, read_file};
 use crate::from_lsp;
 
 #[allow(unused)]
@@ -58,11 +63,36 @@ pub(crate) struct Param {
     pub file: String,
 }
 
+/// Compile the compile unit of the file. If the file is not in the compile unit, e.g., the
+/// `kcl.yaml` only lists some files of the package, the files of the package where the
+/// file is located are compiled together, so that the definitions in the sibling files
+/// are visible.
 pub(crate) fn parse_param_and_compile(
     param: Param,
     vfs: Option<Arc<RwLock<Vfs>>>,
 ) -> anyhow::Result<(Program, ProgramScope, IndexSet<Diagnostic>)> {
-    let (files, opt) = lookup_compile_unit(&param.file, true);
+    Ok(compile_db(&parse_param(param, vfs)?))
+}
+
+/// Loads the program of the compile unit where the file is located without resolving it.
+pub(crate) fn parse_param(
+    param: Param,
+    vfs: Option<Arc<RwLock<Vfs>>>,
+) -> anyhow::Result<AnalysisDatabase> {
+    // The malformed kcl.mod file breaks the resolution of the modules of the package, then
+    // the file is analyzed alone, and the parse error is reported on the kcl.mod file.
+    let mod_file_diag = check_mod_file(&param.file);
+    let (mut files, opt) = match mod_file_diag {
+        Some(_) => (vec![param.file.clone()], None),
+        None => lookup_compile_unit(&param.file, true),
+    };
+    if !files.iter().any(|f| Path::new(f) == Path::new(&param.file)) {
+        for file in get_package_files(&param.file) {
+            if !files.iter().any(|f| Path::new(f) == Path::new(&file)) {
+                files.push(file);
+            }
+        }
+    }
     let files: Vec<&str> = files.iter().map(|s| s.as_str()).collect();
     let mut opt = opt.unwrap_or_default();
     opt.load_plugins = true;
@@ -73,11 +103,99 @@ pub(crate) fn parse_param_and_compile(
         opt.k_code_list.append(&mut k_code_list);
     }
     let sess = Arc::new(ParseSession::default());
-    let mut program = load_program(sess.clone(), &files, Some(opt)).unwrap();
-    let prog_scope = resolve_program(&mut program);
-    sess.append_diagnostic(prog_scope.handler.diagnostics.clone());
-    let diags = sess.1.borrow().diagnostics.clone();
-    Ok((program, prog_scope, diags))
+    let prog = load_program(sess.clone(), &files, Some(opt)).map_err(|err| anyhow::anyhow!(err))?;
+    let mut diags = sess.1.borrow().diagnostics.clone();
+    diags.extend(mod_file_diag);
+    Ok(AnalysisDatabase::new(prog, diags))
+}
+
+/// Returns the path of the kcl.mod file of the package where the file is located, if any.
+pub(crate) fn mod_file_path(file: &str) -> Option<String> {
+    let root = get_pkg_root(file)?;
+    let path = Path::new(&root).join(KCL_MOD_FILE);
+    path.is_file().then(|| path.to_string_lossy().to_string())
+}
+
+/// Parses the kcl.mod file of the package where the file is located, and returns the
+/// diagnostic of the parse error on the kcl.mod file if it is malformed.
+pub(crate) fn check_mod_file(file: &str) -> Option<Diagnostic> {
+    let mod_file = mod_file_path(file)?;
+    let root = Path::new(&mod_file).parent()?.to_string_lossy().to_string();
+    let err = try_load_mod_file(&root).err()?;
+    let (line, column) = err.line_col().unwrap_or_default();
+    Some(Diagnostic::new_with_code(
+        Level::Error,
+        &format!("Failed to parse {}: {}", KCL_MOD_FILE, err),
+        None,
+        KCLPos {
+            filename: mod_file,
+            line: line as u64 + 1,
+            column: Some(column as u64),
+        },
+        Some(DiagnosticId::Error(ErrorKind::InvalidSyntax)),
+    ))
+}
+
+/// Resolves the program loaded in the db. The program is cloned, so that the db can be
+/// compiled again by other requests.
+pub(crate) fn compile_db(db: &AnalysisDatabase) -> (Program, ProgramScope, IndexSet<Diagnostic>) {
+    let resolve = |mut program: Program| {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            let prog_scope = resolve_program(&mut program);
+            (program, prog_scope)
+        }))
+        .ok()
+    };
+    let (program, prog_scope) = resolve(db.prog.clone())
+        // The resolver may fail on the statements recovered from a syntax error, then the
+        // valid prefixes of the files are analyzed, so that the features still work above
+        // the error, and the syntax error itself is reported in the diagnostics of the db.
+        .or_else(|| resolve(valid_prefix(&db.prog, &db.diags)))
+        // The resolver fails on the valid prefixes too, then the modules without statements
+        // are resolved, and only the diagnostics of the db are reported.
+        .unwrap_or_else(|| {
+            let mut program = db.prog.clone();
+            for module in program.pkgs.values_mut().flatten() {
+                module.body.clear();
+            }
+            let prog_scope = resolve_program(&mut program);
+            (program, prog_scope)
+        });
+    let mut diags = db.diags.clone();
+    diags.extend(prog_scope.handler.diagnostics.clone());
+    (program, prog_scope, diags)
+}
+
+/// Returns the program whose modules with the syntax errors keep only the statements
+/// ending before the lines of their first syntax errors, i.e., the valid prefixes of the
+/// files.
+pub(crate) fn valid_prefix(program: &Program, diags: &IndexSet<Diagnostic>) -> Program {
+    let mut program = program.clone();
+    for module in program.pkgs.values_mut().flatten() {
+        let first_error_line = diags
+            .iter()
+            .filter(|diag| diag.code == Some(DiagnosticId::Error(ErrorKind::InvalidSyntax)))
+            .filter_map(|diag| diag.messages.first())
+            .filter(|message| message.pos.filename == module.filename)
+            .map(|message| message.pos.line)
+            .min();
+        if let Some(line) = first_error_line {
+            module.body.retain(|stmt| stmt.end_line < line);
+        }
+    }
+    program
+}
+
+/// Get the files of the package where the file is located, including the file itself.
+fn get_package_files(file: &str) -> Vec<String> {
+    let mut files = Path::new(file)
+        .parent()
+        .and_then(|dir| get_kcl_files(dir, false).ok())
+        .unwrap_or_default();
+    if !files.iter().any(|f| Path::new(f) == Path::new(file)) {
+        files.push(file.to_string());
+    }
+    files
 }
 
 /// Update text with TextDocumentContentChangeEvent param
@@ -98,7 +216,19 @@ pub(crate) fn apply_document_changes(
     }
 }
 
-fn load_files_code_from_vfs(files: &[&str], vfs: Arc<RwLock<Vfs>>) -> anyhow::Result<Vec<String>> {
+/// Load the code of the file from vfs if it is opened, otherwise from the file system.
+pub(crate) fn load_file_code_from_vfs(file: &str, vfs: Arc<RwLock<Vfs>>) -> anyhow::Result<String> {
+    load_files_code_from_vfs(&[file], vfs)?
+        .pop()
+        .ok_or_else(|| anyhow::anyhow!("can't load code of file: {}", file))
+}
+
+/// Load the code of the files from vfs if they are opened, otherwise from the file system,
+/// in the order of the files.
+pub(crate) fn load_files_code_from_vfs(
+    files: &[&str],
+    vfs: Arc<RwLock<Vfs>>,
+) -> anyhow::Result<Vec<String>> {
     let mut res = vec![];
     let vfs = &mut vfs.read();
     for file in files {
@@ -284,6 +414,9 @@ pub(crate) fn inner_most_expr_in_stmt(
                 pos,
                 schema_def
             );
+            if let Some(args) = &schema_stmt.args {
+                walk_if_contains_with_new_expr!(args, pos, schema_def, Expr::Arguments);
+            }
             if let Some(parent_id) = &schema_stmt.parent_name {
                 walk_if_contains_with_new_expr!(parent_id, pos, schema_def, Expr::Identifier);
             }
@@ -307,6 +440,11 @@ pub(crate) fn inner_most_expr_in_stmt(
             (None, schema_def)
         }
         Stmt::SchemaAttr(schema_attr_expr) => {
+            walk_if_contains!(
+                build_identifier_from_string(&schema_attr_expr.name),
+                pos,
+                schema_def
+            );
             if schema_attr_expr.ty.contains_pos(pos) {
                 return (
                     build_identifier_from_ty_string(&schema_attr_expr.ty, pos),
@@ -458,6 +596,11 @@ pub(crate) fn inner_most_expr(
             if let Some(args) = &lambda_expr.args {
                 walk_if_contains_with_new_expr!(args, pos, schema_def, Expr::Arguments);
             }
+            if let Some(ty) = &lambda_expr.return_ty {
+                if ty.contains_pos(pos) {
+                    return (build_identifier_from_ty_string(ty, pos), schema_def);
+                }
+            }
             for stmt in &lambda_expr.body {
                 if stmt.contains_pos(pos) {
                     return inner_most_expr_in_stmt(&stmt.node, pos, schema_def);
@@ -486,6 +629,12 @@ pub(crate) fn inner_most_expr(
             for default in &argument.defaults {
                 walk_option_if_contains!(default, pos, schema_def);
             }
+            // Resolve the element types of the list, dict and union type annotations.
+            for ty in argument.ty_list.iter().flatten() {
+                if ty.contains_pos(pos) {
+                    return (build_identifier_from_ty_string(ty, pos), schema_def);
+                }
+            }
             for ty in argument.type_annotation_list.iter().flatten() {
                 if ty.contains_pos(pos) {
                     return (Some(build_identifier_from_string(ty)), schema_def);
@@ -694,3 +843,102 @@ pub(crate) fn get_pkg_scope(
         .borrow()
         .clone()
 }
+
+/// Scan the KCL files under the path and build a word index which maps each word
+/// to all of its locations. Only the files with one of the `extensions` are indexed, and
+/// the unreadable files, e.g., the files not encoded in UTF-8, are skipped.
+pub(crate) fn build_word_index(
+    path: String,
+    extensions: &[String],
+) -> anyhow::Result<HashMap<String, Vec<Location>>> {
+    let mut index: HashMap<String, Vec<Location>> = HashMap::new();
+    for file in get_kcl_files_with_extensions(Path::new(&path), extensions)? {
+        if let Ok(url) = Url::from_file_path(&file) {
+            let text = match read_file(&file) {
+                Ok(text) => text,
+                Err(err) => {
+                    log::warn!("skip indexing the file {}: {}", file, err);
+                    continue;
+                }
+            };
+            for (word, locations) in build_word_index_for_file_content(text, &url) {
+                index.entry(word).or_insert_with(Vec::new).extend(locations);
+            }
+        }
+    }
+    Ok(index)
+}
+
+/// Build the word index of the file content. The columns of the words are in UTF-16 code
+/// units as the LSP positions.
+pub(crate) fn build_word_index_for_file_content(
+    content: String,
+    url: &Url,
+) -> HashMap<String, Vec<Location>> {
+    let mut index: HashMap<String, Vec<Location>> = HashMap::new();
+    for (li, line) in content.lines().enumerate() {
+        let columns = utf16_columns(line);
+        for word in line_to_words(line.to_string()) {
+            let location = Location {
+                uri: url.clone(),
+                range: Range {
+                    start: Position::new(li as u32, columns[word.startpos as usize]),
+                    end: Position::new(li as u32, columns[word.endpos as usize]),
+                },
+            };
+            index
+                .entry(word.word)
+                .or_insert_with(Vec::new)
+                .push(location);
+        }
+    }
+    index
+}
+
+/// Returns the UTF-16 column of each char index of the line, including the index past the
+/// last char, so that the char ranges of the words can be converted into the LSP ranges.
+pub(crate) fn utf16_columns(line: &str) -> Vec<u32> {
+    let mut columns = Vec::with_capacity(line.len() + 1);
+    let mut column = 0;
+    for ch in line.chars() {
+        columns.push(column);
+        column += ch.len_utf16() as u32;
+    }
+    columns.push(column);
+    columns
+}
+
+/// The directories which are skipped by the walk of the workspace files besides the hidden
+/// ones, e.g., the packages installed by npm for the editor extensions.
+const SKIPPED_DIRS: [&str; 1] = ["node_modules"];
+
+/// Get all the files under the path recursively whose names end with one of the `extensions`.
+/// The symlinks are not followed, so that a symlink cycle does not recurse without bound, and
+/// the hidden directories and the `SKIPPED_DIRS` are skipped.
+pub(crate) fn get_kcl_files_with_extensions(
+    path: &Path,
+    extensions: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let mut files = vec![];
+    let walker = WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || !(name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()))
+        });
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let file = entry.path().to_string_lossy().to_string();
+        if extensions.iter().any(|ext| file.ends_with(ext.as_str())) {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
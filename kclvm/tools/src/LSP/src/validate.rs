@@ -0,0 +1,95 @@
+//! Validate a data file (json or yaml) against a KCL schema, and report the
+//! violations as diagnostics of the data file.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use kclvm_error::{Diagnostic as KCLDiagnostic, Level, Position as KCLPos};
+use kclvm_runtime::PanicInfo;
+use kclvm_tools::util::loader::LoaderKind;
+use kclvm_tools::vet::validator::{validate as vet_validate, ValidateOption};
+use lsp_types::Diagnostic;
+use serde::Deserialize;
+
+use crate::to_lsp::kcl_diag_to_lsp_diags;
+
+/// The attribute name which the data is assigned to in the validation.
+const VALIDATED_ATTRIBUTE_NAME: &str = "value";
+
+/// The arguments of the `kcl.validate` command.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ValidateArgs {
+    /// The path of the data file to be validated.
+    pub data_file: String,
+    /// The path of the KCL file which defines the schema.
+    pub schema_file: String,
+    /// The name of the schema, default is the first schema in the KCL file.
+    #[serde(default)]
+    pub schema_name: Option<String>,
+}
+
+/// Validates the data file against the schema and returns the violations as the
+/// diagnostics of the data file. An empty result means the data conforms to the schema.
+pub(crate) fn validate(args: ValidateArgs) -> anyhow::Result<Vec<Diagnostic>> {
+    let kind = match Path::new(&args.data_file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+    {
+        Some("json") => LoaderKind::JSON,
+        Some("yaml") | Some("yml") => LoaderKind::YAML,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported data file {}, expected a json or yaml file",
+                args.data_file
+            ))
+        }
+    };
+    let option = ValidateOption::new(
+        args.schema_name,
+        VALIDATED_ATTRIBUTE_NAME.to_string(),
+        args.data_file.clone(),
+        kind,
+        Some(args.schema_file),
+        None,
+    );
+    match vet_validate(option) {
+        Ok(_) => Ok(vec![]),
+        Err(err) => {
+            let diag = validation_error_to_diag(&err, &args.data_file);
+            Ok(kcl_diag_to_lsp_diags(
+                &diag,
+                &args.data_file,
+                &HashMap::new(),
+                None,
+            ))
+        }
+    }
+}
+
+/// Converts the validation error into a diagnostic located in the data file. The
+/// position of the config in the data file is used if the error contains it, otherwise
+/// the diagnostic is located at the beginning of the data file.
+fn validation_error_to_diag(err: &str, data_file: &str) -> KCLDiagnostic {
+    let info = PanicInfo::from_string(err);
+    let (line, column) = if info.kcl_config_meta_file == data_file {
+        (info.kcl_config_meta_line, info.kcl_config_meta_col)
+    } else if info.kcl_file == data_file {
+        (info.kcl_line, info.kcl_col)
+    } else {
+        (1, 0)
+    };
+    let message = [info.kcl_arg_msg, info.kcl_config_meta_arg_msg, info.message]
+        .into_iter()
+        .filter(|msg| !msg.is_empty())
+        .collect::<Vec<String>>()
+        .join(": ");
+    KCLDiagnostic::new(
+        Level::Error,
+        &message,
+        KCLPos {
+            filename: data_file.to_string(),
+            line: line.max(1) as u64,
+            column: Some(column.max(0) as u64),
+        },
+    )
+}
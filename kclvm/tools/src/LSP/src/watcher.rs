@@ -0,0 +1,116 @@
+//! The watcher of the workspace folders on the server side, for the clients which can't
+//! watch the files, i.e., without the dynamic registration of the
+//! `workspace/didChangeWatchedFiles` notification. The KCL files and the `kcl.mod` files in
+//! the folders are polled periodically, and the created, changed and deleted files are
+//! handled as the watched file events, so that the new files are analyzable before they
+//! are opened. The polling backs off while the files are unchanged, which bounds the cost
+//! of stating the files of the large workspaces.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crossbeam_channel::Sender;
+use kclvm_config::modfile::KCL_MOD_FILE;
+use lsp_types::{FileChangeType, FileEvent, Url};
+
+use crate::state::Task;
+use crate::util::get_kcl_files_with_extensions;
+
+/// The max multiple of the poll interval which the polling backs off to while the files
+/// are unchanged.
+pub(crate) const MAX_POLL_BACKOFF: u32 = 8;
+
+/// The modification times of the watched files in the workspace folders.
+pub(crate) struct FolderWatcher {
+    folders: Vec<PathBuf>,
+    /// The suffixes of the watched files, i.e., the KCL file extensions and `kcl.mod`.
+    suffixes: Vec<String>,
+    mtimes: HashMap<String, SystemTime>,
+    /// The configured interval of polling the folders.
+    poll_interval: Duration,
+    /// The multiple of the poll interval before the next poll, which is doubled by the
+    /// polls without the events up to `MAX_POLL_BACKOFF`, and reset by any event.
+    backoff: u32,
+}
+
+impl FolderWatcher {
+    /// Creates the watcher of the folders, whose existing files are not reported as created.
+    pub(crate) fn new(
+        folders: Vec<PathBuf>,
+        kcl_extensions: &[String],
+        poll_interval: Duration,
+    ) -> Self {
+        let mut suffixes = kcl_extensions.to_vec();
+        suffixes.push(KCL_MOD_FILE.to_string());
+        let mut watcher = FolderWatcher {
+            folders,
+            suffixes,
+            mtimes: HashMap::new(),
+            poll_interval,
+            backoff: 1,
+        };
+        watcher.poll();
+        watcher.backoff = 1;
+        watcher
+    }
+
+    /// Returns the interval before the next poll.
+    pub(crate) fn interval(&self) -> Duration {
+        self.poll_interval * self.backoff
+    }
+
+    /// Returns the events of the files created, changed and deleted since the last poll.
+    pub(crate) fn poll(&mut self) -> Vec<FileEvent> {
+        let mut mtimes = HashMap::new();
+        for folder in &self.folders {
+            for file in get_kcl_files_with_extensions(folder, &self.suffixes).unwrap_or_default() {
+                let mtime = Path::new(&file)
+                    .metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                mtimes.insert(file, mtime);
+            }
+        }
+        let mut events = vec![];
+        for (file, mtime) in &mtimes {
+            let typ = match self.mtimes.get(file) {
+                None => FileChangeType::CREATED,
+                Some(last) if last != mtime => FileChangeType::CHANGED,
+                Some(_) => continue,
+            };
+            events.extend(file_event(file, typ));
+        }
+        for file in self.mtimes.keys() {
+            if !mtimes.contains_key(file) {
+                events.extend(file_event(file, FileChangeType::DELETED));
+            }
+        }
+        self.mtimes = mtimes;
+        self.backoff = if events.is_empty() {
+            (self.backoff * 2).min(MAX_POLL_BACKOFF)
+        } else {
+            1
+        };
+        events
+    }
+}
+
+/// Polls the workspace folders in a background thread and sends the events as the tasks,
+/// until the language server stops receiving the tasks.
+pub(crate) fn spawn_watcher(mut watcher: FolderWatcher, sender: Sender<Task>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(watcher.interval());
+        let events = watcher.poll();
+        if !events.is_empty() && sender.send(Task::ChangeWatchedFiles(events)).is_err() {
+            break;
+        }
+    });
+}
+
+fn file_event(file: &str, typ: FileChangeType) -> Option<FileEvent> {
+    Some(FileEvent {
+        uri: Url::from_file_path(file).ok()?,
+        typ,
+    })
+}
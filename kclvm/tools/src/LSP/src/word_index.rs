@@ -0,0 +1,199 @@
+//! The generations of the word index of the workspace folders. The generation of a folder
+//! is increased on any change of the files in the folder, and the word index built with an
+//! older generation is stale. The stale index is refreshed with the changed files only
+//! before it is used, e.g., by the find references.
+//! The incrementally updated index can be verified against the index rebuilt from the
+//! files, which reveals the drift caused by the bugs of the incremental updates.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use lsp_types::{Location, Url};
+use parking_lot::{Condvar, Mutex, RwLock};
+use ra_ap_vfs::Vfs;
+
+use crate::from_lsp::file_path_from_url;
+use crate::util::{
+    build_word_index_for_file_content, get_kcl_files_with_extensions, load_file_code_from_vfs,
+};
+
+/// The generation of the files in a workspace folder and the generation of its word index.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct FolderGeneration {
+    /// The generation of the files in the folder.
+    pub generation: u64,
+    /// The generation of the files which the word index of the folder was built with.
+    pub indexed: u64,
+    /// The generation of the files which the in-progress build of the word index is
+    /// built with, if any.
+    pub building: Option<u64>,
+    /// The number of the builds of the word index started for the folder.
+    pub builds: u64,
+    /// The files changed since the word index was built.
+    changed_files: HashSet<String>,
+}
+
+impl FolderGeneration {
+    /// Whether the word index of the folder is built with the current files.
+    pub(crate) fn is_current(&self) -> bool {
+        self.indexed == self.generation
+    }
+}
+
+/// The generations of the workspace folders keyed by the folder urls, shared by the
+/// language server state and its snapshots to track the in-progress builds.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct WordIndexGenerations {
+    pub(crate) folders: Arc<Mutex<HashMap<Url, FolderGeneration>>>,
+    /// Notified when an in-progress build is finished or cancelled.
+    built: Arc<Condvar>,
+}
+
+impl WordIndexGenerations {
+    /// Increases the generation of the folders which contain the changed file.
+    pub(crate) fn mark_changed<'a>(&self, file: &str, folders: impl Iterator<Item = &'a Url>) {
+        let mut generations = self.folders.lock();
+        for folder in folders {
+            let contains_file = file_path_from_url(folder).map_or(false, |folder_path| {
+                Path::new(file).starts_with(folder_path)
+            });
+            if contains_file {
+                let generation = generations.entry(folder.clone()).or_default();
+                generation.generation += 1;
+                generation.changed_files.insert(file.to_string());
+            }
+        }
+    }
+
+    /// Rebuilds the word index of the changed files in the stale folders with the file
+    /// contents in the vfs, so that the indexes are built with the current generations.
+    pub(crate) fn refresh(
+        &self,
+        word_index_map: &RwLock<HashMap<Url, HashMap<String, Vec<Location>>>>,
+        vfs: Arc<RwLock<Vfs>>,
+    ) {
+        let stale_folders: Vec<Url> = self
+            .folders
+            .lock()
+            .iter()
+            .filter(|(_, generation)| !generation.is_current())
+            .map(|(folder, _)| folder.clone())
+            .collect();
+        for folder in stale_folders {
+            self.refresh_folder(&folder, word_index_map, vfs.clone());
+        }
+    }
+
+    /// Rebuilds the word index of the folder. A build in progress with the current files
+    /// is awaited rather than duplicated, and a build with the stale files is replaced by
+    /// a new one, whose result is discarded if the files are changed again meanwhile.
+    fn refresh_folder(
+        &self,
+        folder: &Url,
+        word_index_map: &RwLock<HashMap<Url, HashMap<String, Vec<Location>>>>,
+        vfs: Arc<RwLock<Vfs>>,
+    ) {
+        let (generation, changed_files) = {
+            let mut generations = self.folders.lock();
+            loop {
+                let state = match generations.get_mut(folder) {
+                    Some(state) => state,
+                    None => return,
+                };
+                if state.is_current() {
+                    return;
+                }
+                match state.building {
+                    Some(building) if building == state.generation => {
+                        self.built.wait(&mut generations)
+                    }
+                    _ => {
+                        state.building = Some(state.generation);
+                        state.builds += 1;
+                        break (state.generation, state.changed_files.clone());
+                    }
+                }
+            }
+        };
+
+        // Build the index of the changed files without blocking the changes of the files.
+        let file_indexes: Vec<(Url, HashMap<String, Vec<Location>>)> = changed_files
+            .iter()
+            .filter_map(|file| {
+                let url = Url::from_file_path(file).ok()?;
+                // The deleted files are removed from the index only.
+                let index = load_file_code_from_vfs(file, vfs.clone())
+                    .map(|text| build_word_index_for_file_content(text, &url))
+                    .unwrap_or_default();
+                Some((url, index))
+            })
+            .collect();
+
+        let mut generations = self.folders.lock();
+        let state = match generations.get_mut(folder) {
+            Some(state) => state,
+            None => return,
+        };
+        // The build has been replaced by a newer one.
+        if state.building != Some(generation) {
+            return;
+        }
+        state.building = None;
+        // The files are changed during the build, and the next refresh rebuilds them.
+        if state.generation != generation {
+            self.built.notify_all();
+            return;
+        }
+        if let Some(index) = word_index_map.write().get_mut(folder) {
+            for (url, file_index) in file_indexes {
+                for locations in index.values_mut() {
+                    locations.retain(|loc| loc.uri != url);
+                }
+                index.retain(|_, locations| !locations.is_empty());
+                for (word, locations) in file_index {
+                    index.entry(word).or_default().extend(locations);
+                }
+            }
+        }
+        state.changed_files.clear();
+        state.indexed = generation;
+        self.built.notify_all();
+    }
+}
+
+/// Rebuilds the word index of the folder from the current files, i.e., the contents in the
+/// vfs or on the disk, and returns the words whose locations in the incrementally updated
+/// index differ from the rebuilt one, sorted by the words.
+pub(crate) fn word_index_drift(
+    folder: &Url,
+    index: &HashMap<String, Vec<Location>>,
+    vfs: Arc<RwLock<Vfs>>,
+    extensions: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let path = file_path_from_url(folder)?;
+    let mut rebuilt: HashMap<String, HashSet<Location>> = HashMap::new();
+    for file in get_kcl_files_with_extensions(Path::new(&path), extensions)? {
+        let url = match Url::from_file_path(&file) {
+            Ok(url) => url,
+            Err(_) => continue,
+        };
+        let text = load_file_code_from_vfs(&file, vfs.clone())?;
+        for (word, locations) in build_word_index_for_file_content(text, &url) {
+            rebuilt.entry(word).or_default().extend(locations);
+        }
+    }
+    let mut words: Vec<String> = index
+        .keys()
+        .chain(rebuilt.keys())
+        .filter(|word| {
+            let incremental: HashSet<&Location> = index.get(*word).into_iter().flatten().collect();
+            let fresh: HashSet<&Location> = rebuilt.get(*word).into_iter().flatten().collect();
+            incremental != fresh
+        })
+        .cloned()
+        .collect();
+    words.sort();
+    words.dedup();
+    Ok(words)
+}
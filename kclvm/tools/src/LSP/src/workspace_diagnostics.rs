@@ -0,0 +1,88 @@
+//! The diagnostics of all the files in the workspace folders, i.e., the
+//! `kcl.workspaceDiagnostics` command, which lists the errors of the files not opened in
+//! the client, e.g., before a commit.
+//!
+//! Every KCL file in the folders is compiled as an entry unless it has been covered by the
+//! import closure of a compiled entry, and the diagnostics of the files in the closure are
+//! collected in the same way as the published diagnostics.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use lsp_types::{Diagnostic, Url};
+use serde::Deserialize;
+
+use crate::from_lsp::file_path_from_url;
+use crate::state::{file_diagnostics, LanguageServerSnapshot};
+use crate::util::{
+    get_kcl_files_with_extensions, load_file_code_from_vfs, mod_file_path, parse_param_and_compile,
+    Param,
+};
+
+/// The arguments of the `kcl.workspaceDiagnostics` command.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct WorkspaceDiagnosticsArgs {
+    /// The folders to be checked, which are the workspace folders if omitted.
+    pub folders: Option<Vec<Url>>,
+}
+
+/// Compiles the KCL files in the folders and returns the diagnostics of each file in the
+/// folders which has any. The progress is reported with the number of the checked files
+/// and the total number of the files.
+pub(crate) fn workspace_diagnostics(
+    snapshot: &LanguageServerSnapshot,
+    folders: &[Url],
+    progress: &mut dyn FnMut(usize, usize),
+) -> anyhow::Result<HashMap<Url, Vec<Diagnostic>>> {
+    let roots = folders
+        .iter()
+        .map(|folder| file_path_from_url(folder).map(PathBuf::from))
+        .collect::<anyhow::Result<Vec<PathBuf>>>()?;
+    let mut files = vec![];
+    for root in &roots {
+        files.extend(get_kcl_files_with_extensions(
+            root,
+            &snapshot.config.kcl_extensions,
+        )?);
+    }
+    files.sort();
+    files.dedup();
+
+    let mut checked: HashSet<String> = HashSet::new();
+    let mut res = HashMap::new();
+    for (i, file) in files.iter().enumerate() {
+        if !checked.contains(file) {
+            let (program, _, diags) =
+                parse_param_and_compile(Param { file: file.clone() }, Some(snapshot.vfs.clone()))?;
+            let mut closure = vec![file.clone()];
+            closure.extend(
+                program
+                    .pkgs
+                    .values()
+                    .flatten()
+                    .map(|module| module.filename.clone()),
+            );
+            closure.extend(mod_file_path(file));
+            for dep in closure {
+                if !checked.insert(dep.clone()) || !in_roots(&dep, &roots) {
+                    continue;
+                }
+                let source =
+                    load_file_code_from_vfs(&dep, snapshot.vfs.clone()).unwrap_or_default();
+                let diagnostics = file_diagnostics(&snapshot.config, &diags, &dep, &source);
+                if diagnostics.is_empty() {
+                    continue;
+                }
+                if let Ok(uri) = Url::from_file_path(&dep) {
+                    res.insert(uri, diagnostics);
+                }
+            }
+        }
+        progress(i + 1, files.len());
+    }
+    Ok(res)
+}
+
+fn in_roots(file: &str, roots: &[PathBuf]) -> bool {
+    roots.iter().any(|root| Path::new(file).starts_with(root))
+}
@@ -0,0 +1,62 @@
+//! Workspace symbols of KCL, i.e., the top level definitions of the packages in the
+//! program, which are matched with the query by the bare names and the names qualified by
+//! the package paths, e.g., both `Server` and `frontend.Server`.
+
+use kclvm_ast::MAIN_PKG;
+use kclvm_compiler::pkgpath_without_prefix;
+use kclvm_sema::resolver::scope::{ProgramScope, ScopeKind, ScopeObjectKind};
+use lsp_types::{Location, Range, SymbolInformation, Url};
+
+use crate::document_symbol::scope_obj_kind_to_document_symbol_kind;
+use crate::to_lsp::lsp_pos;
+
+/// Returns the top level symbols of the packages in the program scope, which are matched
+/// with the queries by `matches_query`. The `container_name` of the symbols is the package
+/// path.
+#[allow(deprecated)]
+pub(crate) fn workspace_symbols(prog_scope: &ProgramScope) -> Vec<SymbolInformation> {
+    let mut symbols = vec![];
+    for (pkgpath, scope) in &prog_scope.scope_map {
+        let scope = scope.borrow();
+        if !matches!(scope.kind, ScopeKind::Package(_)) {
+            continue;
+        }
+        let pkgpath = pkgpath_without_prefix!(pkgpath);
+        for obj in scope.elems.values() {
+            let obj = obj.borrow();
+            if obj.kind == ScopeObjectKind::Module {
+                continue;
+            }
+            let uri = match Url::from_file_path(&obj.start.filename) {
+                Ok(uri) => uri,
+                Err(_) => continue,
+            };
+            symbols.push(SymbolInformation {
+                name: obj.name.clone(),
+                kind: scope_obj_kind_to_document_symbol_kind(obj.kind),
+                tags: None,
+                deprecated: None,
+                location: Location {
+                    uri,
+                    range: Range {
+                        start: lsp_pos(&obj.start),
+                        end: lsp_pos(&obj.end),
+                    },
+                },
+                container_name: Some(pkgpath.clone()),
+            });
+        }
+    }
+    symbols
+}
+
+/// Whether the symbol matches the query by the bare name or the name qualified by the
+/// package path, case-insensitively.
+pub(crate) fn matches_query(symbol: &SymbolInformation, query: &str) -> bool {
+    let query = query.to_lowercase();
+    let qualified_name = match symbol.container_name.as_deref() {
+        Some(pkgpath) if pkgpath != MAIN_PKG => format!("{}.{}", pkgpath, symbol.name),
+        _ => symbol.name.clone(),
+    };
+    symbol.name.to_lowercase().contains(&query) || qualified_name.to_lowercase().contains(&query)
+}